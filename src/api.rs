@@ -1,7 +1,19 @@
+mod normalize;
+
+use crate::config::{NetworkConfig, ProxyConfig, ProxyMode};
+use crate::doh::{DohProvider, DohResolver};
+use crate::events::UiEvent;
+use crate::health::EndpointHealth;
+use crate::inspector::InspectEvent;
+use crate::models::TokenUsage;
+use crate::providers::ApiTarget;
+use crate::resolver::{AppResolver, IpPreference};
+use crate::utils::Utf8Decoder;
 use futures_util::StreamExt;
 use log::{debug, error};
 use reqwest::Client;
 use serde_json::Value as JsonValue;
+use std::time::Duration;
 use tokio::sync::mpsc;
 
 #[derive(Debug)]
@@ -31,40 +43,187 @@ impl std::error::Error for ApiError {
     }
 }
 
+// 停滞提示时间的上限（秒）
+pub const MAX_STALL_TIMEOUT_SECS: u64 = 600;
+
+// 建立连接的超时
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// 两次读取之间的超时，只用于回收已断开的连接；比停滞提示的上限更长，
+// 流式回复中途停滞时总是先由停滞检测提示，由用户决定继续等待、重试或取消
+const READ_TIMEOUT: Duration = Duration::from_secs(MAX_STALL_TIMEOUT_SECS * 2);
+
+// 按代理和网络设置创建 HTTP 客户端；不设置整体超时，否则较长的流式回复会被中途截断
+pub fn build_client(proxy: &ProxyConfig, network: &NetworkConfig) -> Result<Client, String> {
+    let mut builder = Client::builder()
+        .pool_idle_timeout(Duration::from_secs(30))
+        .tcp_keepalive(Duration::from_secs(60))
+        .connect_timeout(CONNECT_TIMEOUT)
+        .read_timeout(READ_TIMEOUT);
+    let doh = if network.doh_enabled {
+        let url = network.doh_provider.url(&network.doh_url);
+        if url.is_empty() {
+            return Err("未填写 DoH 地址".to_string());
+        }
+        if network.doh_provider == DohProvider::Custom && !url.starts_with("https://") {
+            return Err("DoH 地址必须以 https:// 开头".to_string());
+        }
+        Some(DohResolver::new(url).map_err(|e| format!("创建 DoH 解析器失败: {}", e))?)
+    } else {
+        None
+    };
+    if doh.is_some() || network.ip_preference != IpPreference::Auto {
+        builder = builder.dns_resolver(std::sync::Arc::new(AppResolver {
+            doh,
+            preference: network.ip_preference,
+        }));
+    }
+
+    let bind = network.bind.trim();
+    if let Ok(ip) = bind.parse::<std::net::IpAddr>() {
+        builder = builder.local_address(ip);
+    } else if !bind.is_empty() {
+        #[cfg(target_os = "linux")]
+        {
+            builder = builder.interface(bind);
+        }
+        #[cfg(not(target_os = "linux"))]
+        return Err(format!(
+            "无效的本地地址: {}（按网卡名绑定仅支持 Linux）",
+            bind
+        ));
+    }
+    let builder = match proxy.mode {
+        // reqwest 默认读取 HTTP(S)_PROXY 等环境变量和系统代理设置
+        ProxyMode::System => builder,
+        ProxyMode::Direct => builder.no_proxy(),
+        ProxyMode::Manual => {
+            let mut manual = reqwest::Proxy::all(proxy.url.trim())
+                .map_err(|e| format!("代理地址无效: {}", e))?;
+            if !proxy.username.is_empty() {
+                manual = manual.basic_auth(&proxy.username, &proxy.password);
+            }
+            builder.proxy(manual)
+        }
+    };
+    builder
+        .build()
+        .map_err(|e| format!("创建 HTTP 客户端失败: {}", e))
+}
+
+// 测试能否连接到服务商端点；收到任何 HTTP 响应（包括 401、404）都说明网络可达
+pub async fn test_connection(client: &Client, endpoint: &str) -> Result<String, String> {
+    let started = std::time::Instant::now();
+    let request = client.get(endpoint).timeout(Duration::from_secs(30));
+    match request.send().await {
+        Ok(response) => Ok(format!(
+            "连接成功（HTTP {}，耗时 {} ms）",
+            response.status().as_u16(),
+            started.elapsed().as_millis()
+        )),
+        Err(e) => Err(format!("连接失败: {}", e)),
+    }
+}
+
+// 流式请求的重试设置；stall_timeout 为零时不检测停滞
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub enabled: bool,
+    pub max_retries: i32,
+    pub stall_timeout: Duration,
+}
+
+// 发送流式请求；每个片段只发送本次新增的内容，由接收方负责拼接
 pub async fn send_request(
     client: &Client,
-    api_endpoint: &str,
-    api_key: &str,
+    target: &ApiTarget,
     payload: &JsonValue,
-    retry_enabled: bool,
-    max_retries: i32,
-    tx: &mpsc::UnboundedSender<String>,
+    policy: RetryPolicy,
+    health: &EndpointHealth,
+    tx: &mpsc::UnboundedSender<UiEvent>,
 ) -> Result<(), ApiError> {
+    let RetryPolicy {
+        enabled: retry_enabled,
+        max_retries,
+        stall_timeout,
+    } = policy;
+    let provider = target.provider.provider();
+    let endpoint = target.url();
+    let quirks = normalize::quirks_for(endpoint);
+    debug!("响应格式: {}", quirks.name);
     let mut retry_count = 0;
     let mut incomplete_data = String::new();
+    let mut usage = TokenUsage::default();
 
     loop {
         debug!("发送API请求 (重试次数: {})", retry_count);
 
-        let response = client
-            .post(api_endpoint)
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            .json(payload)
-            .send()
-            .await
+        // 端点连续失败时所有请求统一退避
+        let backoff = health.backoff(endpoint);
+        if !backoff.is_zero() {
+            debug!("端点连续失败，等待 {:?} 后发送", backoff);
+            tokio::time::sleep(backoff).await;
+        }
+
+        let request = target
+            .request(client, payload)
+            .build()
             .map_err(ApiError::Other)?;
+        let _ = tx.send(UiEvent::Inspect(InspectEvent::request(&request)));
+        let response = match client.execute(request).await {
+            Ok(response) => response,
+            // 连接失败或收到响应之前超时才计入端点故障
+            Err(e) if e.is_timeout() || e.is_connect() => {
+                health.record_failure(endpoint);
+                if retry_enabled && retry_count < max_retries {
+                    retry_count += 1;
+                    debug!("连接失败，即将进行第 {} 次重试: {}", retry_count, e);
+                    let _ = tx.send(UiEvent::ClearNotices);
+                    let _ = tx.send(UiEvent::Notice(format!(
+                        "遇到连接超时，正在进行第 {} 次重试...",
+                        retry_count
+                    )));
+                    continue;
+                }
+                return Err(ApiError::Other(e));
+            }
+            Err(e) => return Err(ApiError::Other(e)),
+        };
+        let _ = tx.send(UiEvent::Inspect(InspectEvent::response(&response)));
+
+        if response.status().is_server_error() {
+            health.record_failure(endpoint);
+            if retry_enabled && retry_count < max_retries {
+                retry_count += 1;
+                debug!(
+                    "遇到 {} 错误，即将进行第 {} 次重试",
+                    response.status(),
+                    retry_count
+                );
+                let _ = tx.send(UiEvent::ClearNotices);
+                let _ = tx.send(UiEvent::Notice(format!(
+                    "遇到服务端错误 (HTTP {})，正在进行第 {} 次重试...",
+                    response.status().as_u16(),
+                    retry_count
+                )));
+                continue;
+            }
+            return Err(ApiError::HttpError(response));
+        }
+        if response.status().is_success() {
+            health.record_success(endpoint);
+        }
 
         if !response.status().is_success() {
             if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
                 if retry_enabled && retry_count < max_retries {
                     retry_count += 1;
                     debug!("遇到 429 错误，即将进行第 {} 次重试", retry_count);
-                    let _ = tx.send("__CLEAR_ERRORS__".to_string());
-                    let _ = tx.send(format!(
+                    let _ = tx.send(UiEvent::ClearNotices);
+                    let _ = tx.send(UiEvent::Notice(format!(
                         "遇到频率限制，正在进行第 {} 次重试...",
                         retry_count
-                    ));
+                    )));
                     continue;
                 }
                 return Err(ApiError::TooManyRequests(()));
@@ -73,110 +232,144 @@ pub async fn send_request(
         }
 
         let mut stream = response.bytes_stream();
+        let mut decoder = Utf8Decoder::default();
+        // 还没有收到换行的半行内容
+        let mut pending = String::new();
+        // 停滞检测：超过 stall_timeout 没有收到数据时通知界面，由用户决定继续等待、重试或取消
+        let mut idle = Duration::ZERO;
 
-        while let Some(chunk_result) = stream.next().await {
-            match chunk_result {
-                Ok(chunk) => {
-                    if let Ok(text) = String::from_utf8(chunk.to_vec()) {
-                        for line in text.lines() {
-                            incomplete_data.push_str(line);
-                            debug!("{}", incomplete_data);
-                            if incomplete_data.contains("data: ") {
-                                let index = incomplete_data.find("data: ").unwrap();
-                                let data = &incomplete_data[index + 6..];
-
-                                if data == "[DONE]" {
-                                    debug!("收到结束标记: [DONE]");
-                                    let _ = tx.send("__STREAM_DONE__".to_string());
-                                    return Ok(());
-                                }
-
-                                match serde_json::from_str::<JsonValue>(data) {
-                                    Ok(json) => {
-                                        incomplete_data.clear();
-
-                                        if let Some(error) = json.get("error") {
-                                            if retry_enabled && retry_count < max_retries {
-                                                retry_count += 1;
-                                                debug!(
-                                                    "遇到API错误，即将进行第 {} 次重试",
-                                                    retry_count
-                                                );
-                                                let _ = tx.send(format!(
-                                                    "遇到API错误，正在进行第 {} 次重试...",
-                                                    retry_count
-                                                ));
-                                                break;
-                                            } else {
-                                                let error_msg = if let Some(metadata) =
-                                                    error.get("metadata")
-                                                {
-                                                    if let Some(raw) = metadata.get("raw") {
-                                                        format!("API错误 (重试{}次后): {} - 详细信息: {}",
-                                                            retry_count,
-                                                            error["message"].as_str().unwrap_or("未知错误"),
-                                                            raw.as_str().unwrap_or(""))
-                                                    } else {
-                                                        format!(
-                                                            "API错误 (重试{}次后): {}",
-                                                            retry_count,
-                                                            error["message"]
-                                                                .as_str()
-                                                                .unwrap_or("未知错误")
-                                                        )
-                                                    }
-                                                } else {
-                                                    format!(
-                                                        "API错误 (重试{}次后): {}",
-                                                        retry_count,
-                                                        error["message"]
-                                                            .as_str()
-                                                            .unwrap_or("未知错误")
-                                                    )
-                                                };
-
-                                                error!("{}", error_msg);
-                                                let _ = tx.send(error_msg);
-                                                let _ = tx.send("__STREAM_DONE__".to_string());
-                                                return Ok(());
-                                            }
-                                        }
-
-                                        if let Some(content) =
-                                            json["choices"][0]["delta"]["content"].as_str()
-                                        {
-                                            if !content.is_empty() {
-                                                if retry_count > 0 {
-                                                    let _ = tx.send("__CLEAR_ERRORS__".to_string());
-                                                    retry_count = 0; // 重置重试计数
-                                                }
-                                                let _ = tx.send(content.to_string());
-                                            }
-                                        }
-                                    }
-                                    Err(_) => {
-                                        debug!("JSON解析失败（数据不完整）");
-                                        continue;
-                                    }
-                                }
-                            }
-                        }
+        'stream: loop {
+            let next = if stall_timeout.is_zero() {
+                stream.next().await
+            } else {
+                match tokio::time::timeout(stall_timeout, stream.next()).await {
+                    Ok(next) => next,
+                    Err(_) => {
+                        idle += stall_timeout;
+                        debug!("流式响应已停滞 {:?}", idle);
+                        let _ = tx.send(UiEvent::Stalled(idle.as_secs()));
+                        continue;
+                    }
+                }
+            };
+            if !idle.is_zero() {
+                idle = Duration::ZERO;
+                let _ = tx.send(UiEvent::Resumed);
+            }
+            let finished = next.is_none();
+            match next {
+                // 连接关闭时最后一行可能没有换行
+                None => {
+                    pending.push_str(&decoder.finish());
+                    if pending.is_empty() {
+                        break;
                     }
+                    pending.push('\n');
+                }
+                Some(Ok(chunk)) => {
+                    let text = decoder.push(&chunk);
+                    let _ = tx.send(UiEvent::Inspect(InspectEvent::Raw(text.clone())));
+                    pending.push_str(&text);
                 }
-                Err(e) => {
+                Some(Err(e)) => {
+                    // 已经收到响应，中途断开或超时不计入端点故障
                     error!("流式数据接收错误: {}", e);
                     if retry_enabled && retry_count < max_retries {
                         retry_count += 1;
                         debug!("遇到网络错误，即将进行第 {} 次重试", retry_count);
-                        let _ = tx.send(format!(
+                        let _ = tx.send(UiEvent::Notice(format!(
                             "遇到网络错误，正在进行第 {} 次重试...",
                             retry_count
-                        ));
+                        )));
                         break; // 跳出内层循环，返回到外层循环重新发送请求
                     }
-                    return Err(ApiError::Other(e.into()));
+                    return Err(ApiError::Other(e));
                 }
             }
+
+            while let Some(end) = pending.find('\n') {
+                let line: String = pending.drain(..=end).collect();
+                debug!("{}", line.trim_end());
+                let data =
+                    match normalize::classify_line(&line, quirks, !incomplete_data.is_empty()) {
+                        normalize::StreamLine::Skip => continue,
+                        normalize::StreamLine::Done => {
+                            debug!("收到结束标记: [DONE]");
+                            let _ = tx.send(UiEvent::Done);
+                            return Ok(());
+                        }
+                        normalize::StreamLine::Data(data) => data,
+                    };
+                incomplete_data.push_str(data);
+
+                let json = match serde_json::from_str::<JsonValue>(&incomplete_data) {
+                    Ok(json) => normalize::normalize_event(json, target.provider, quirks),
+                    Err(_) => {
+                        debug!("JSON解析失败（数据不完整）");
+                        continue;
+                    }
+                };
+                incomplete_data.clear();
+
+                if let Some(error) = json.get("error") {
+                    if retry_enabled && retry_count < max_retries {
+                        retry_count += 1;
+                        debug!("遇到API错误，即将进行第 {} 次重试", retry_count);
+                        let _ = tx.send(UiEvent::Notice(format!(
+                            "遇到API错误，正在进行第 {} 次重试...",
+                            retry_count
+                        )));
+                        break 'stream;
+                    }
+                    let message = error["message"].as_str().unwrap_or("未知错误");
+                    let error_msg = match error.pointer("/metadata/raw") {
+                        Some(raw) => format!(
+                            "API错误 (重试{}次后): {} - 详细信息: {}",
+                            retry_count,
+                            message,
+                            raw.as_str().unwrap_or("")
+                        ),
+                        None => format!("API错误 (重试{}次后): {}", retry_count, message),
+                    };
+
+                    error!("{}", error_msg);
+                    let _ = tx.send(UiEvent::Error(error_msg));
+                    let _ = tx.send(UiEvent::Done);
+                    return Ok(());
+                }
+
+                if let Some(reasoning) = provider.reasoning_delta(&json) {
+                    if !reasoning.is_empty() {
+                        let _ = tx.send(UiEvent::Reasoning(reasoning.to_string()));
+                    }
+                }
+
+                if let Some(event_usage) = provider.stream_usage(&json) {
+                    usage.merge(event_usage);
+                    let _ = tx.send(UiEvent::Usage(usage));
+                }
+
+                if let Some(content) = provider.stream_delta(&json) {
+                    if !content.is_empty() {
+                        if retry_count > 0 {
+                            let _ = tx.send(UiEvent::ClearNotices);
+                            retry_count = 0; // 重置重试计数
+                        }
+                        let _ = tx.send(UiEvent::Delta(content.to_string()));
+                    }
+                }
+
+                // 结束事件可能同时携带最后一段内容（如 Gemini）
+                if provider.is_stream_end(&json) {
+                    debug!("收到结束事件");
+                    let _ = tx.send(UiEvent::Done);
+                    return Ok(());
+                }
+            }
+
+            if finished {
+                break;
+            }
         }
 
         // 如果没有遇到错误并且正常完成，就退出循环
@@ -187,5 +380,43 @@ pub async fn send_request(
         continue;
     }
 
+    // 连接正常关闭但没有收到结束标记
+    debug!("流式连接已关闭");
+    let _ = tx.send(UiEvent::Done);
     Ok(())
 }
+
+// 发送非流式请求，返回第一条回复的文本内容
+pub async fn complete(
+    client: &Client,
+    target: &ApiTarget,
+    payload: &JsonValue,
+) -> Result<String, ApiError> {
+    let response = target
+        .request(client, payload)
+        .send()
+        .await
+        .map_err(ApiError::Other)?;
+
+    if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(ApiError::TooManyRequests(()));
+    }
+    if !response.status().is_success() {
+        return Err(ApiError::HttpError(response));
+    }
+
+    let json = response
+        .json::<JsonValue>()
+        .await
+        .map_err(ApiError::Other)?;
+    debug!("非流式响应JSON: {:?}", json);
+    let json =
+        normalize::normalize_response(json, target.provider, normalize::quirks_for(target.url()));
+    Ok(target
+        .provider
+        .provider()
+        .response_text(&json)
+        .unwrap_or_default()
+        .trim()
+        .to_string())
+}
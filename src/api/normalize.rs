@@ -0,0 +1,451 @@
+// 响应规整：不同的 OpenAI 兼容网关返回的流式片段略有差异（缺少 choices、错误格式不同、保活行不带 data: 前缀），
+// 在交给各服务商解析之前统一成标准的 chat-completions 格式
+use crate::providers::ProviderKind;
+use serde_json::{json, Value as JsonValue};
+
+// 网关的已知差异，按端点地址匹配
+pub struct Quirks {
+    pub name: &'static str,
+    // 端点地址中包含其中任一片段即视为该网关
+    hosts: &'static [&'static str],
+    // 每行一个不带 data: 前缀的 JSON（NDJSON），而不是 SSE
+    pub ndjson: bool,
+    // 没有 choices 时依次查找错误信息的位置（JSON 指针），在通用位置之前查找
+    error_pointers: &'static [&'static str],
+}
+
+const QUIRKS: &[Quirks] = &[
+    Quirks {
+        name: "Ollama",
+        hosts: &[":11434/api/"],
+        ndjson: true,
+        error_pointers: &["/error"],
+    },
+    Quirks {
+        name: "百度千帆",
+        hosts: &["baidubce.com"],
+        ndjson: false,
+        error_pointers: &["/error_msg"],
+    },
+    Quirks {
+        name: "Cloudflare Workers AI",
+        hosts: &["api.cloudflare.com"],
+        ndjson: false,
+        error_pointers: &["/errors/0/message"],
+    },
+    Quirks {
+        name: "MiniMax",
+        hosts: &["minimax.chat", "minimaxi.com"],
+        ndjson: false,
+        error_pointers: &["/base_resp/status_msg"],
+    },
+];
+
+const DEFAULT_QUIRKS: Quirks = Quirks {
+    name: "通用",
+    hosts: &[],
+    ndjson: false,
+    error_pointers: &[],
+};
+
+// 各网关通用的错误信息位置：{"error": "..."}、FastAPI 的 {"detail": "..."}
+const COMMON_ERROR_POINTERS: &[&str] = &["/error", "/detail"];
+// 顶层的 message/msg 也可能是正常的状态说明，只有同时带有错误码时才视为错误，如 {"code": 500, "message": "..."}
+const CODED_ERROR_POINTERS: &[&str] = &["/message", "/msg"];
+const ERROR_CODE_KEYS: &[&str] = &["code", "status", "status_code", "error_code"];
+
+pub fn quirks_for(endpoint: &str) -> &'static Quirks {
+    QUIRKS
+        .iter()
+        .find(|quirks| quirks.hosts.iter().any(|host| endpoint.contains(host)))
+        .unwrap_or(&DEFAULT_QUIRKS)
+}
+
+// 流中一行的含义
+#[derive(Debug, PartialEq)]
+pub enum StreamLine<'a> {
+    Data(&'a str),
+    Done,
+    Skip,
+}
+
+// 识别一行：SSE 注释、event/id/retry 字段和空行都是保活或元数据，跳过；data: 后面的空格可有可无
+//
+// continuing 表示上一段 JSON 还没有接收完整，此时不带前缀的行视为它的后续内容
+pub fn classify_line<'a>(line: &'a str, quirks: &Quirks, continuing: bool) -> StreamLine<'a> {
+    let line = line.trim_end_matches(['\r', '\n']);
+    if line.trim().is_empty() || line.starts_with(':') {
+        return StreamLine::Skip;
+    }
+    if let Some(data) = line.strip_prefix("data:") {
+        let data = data.trim_start();
+        return match data {
+            "[DONE]" => StreamLine::Done,
+            "" => StreamLine::Skip,
+            _ => StreamLine::Data(data),
+        };
+    }
+    if ["event:", "id:", "retry:"]
+        .iter()
+        .any(|field| line.starts_with(field))
+    {
+        return StreamLine::Skip;
+    }
+    if quirks.ndjson || continuing || line.trim_start().starts_with('{') {
+        return StreamLine::Data(line);
+    }
+    StreamLine::Skip
+}
+
+// 规整一个流式事件：错误统一为 {"error": {"message": ...}}，OpenAI 兼容格式的内容统一放在 choices[0].delta 中
+pub fn normalize_event(event: JsonValue, provider: ProviderKind, quirks: &Quirks) -> JsonValue {
+    let event = normalize_error(event, quirks);
+    if event.get("error").is_some() || !is_openai_format(provider) {
+        return event;
+    }
+    normalize_choices(event, "delta")
+}
+
+// 规整非流式响应，内容统一放在 choices[0].message 中
+pub fn normalize_response(
+    response: JsonValue,
+    provider: ProviderKind,
+    quirks: &Quirks,
+) -> JsonValue {
+    let response = normalize_error(response, quirks);
+    if response.get("error").is_some() || !is_openai_format(provider) {
+        return response;
+    }
+    normalize_choices(response, "message")
+}
+
+fn is_openai_format(provider: ProviderKind) -> bool {
+    matches!(provider, ProviderKind::OpenAi | ProviderKind::Azure)
+}
+
+fn normalize_error(mut event: JsonValue, quirks: &Quirks) -> JsonValue {
+    // 部分网关在正常的片段中也带有 "error": null
+    if event.get("error").is_some_and(JsonValue::is_null) {
+        if let Some(object) = event.as_object_mut() {
+            object.remove("error");
+        }
+    }
+    // 已是标准格式（带 message 的对象），保留 metadata 等附加信息
+    if event
+        .pointer("/error/message")
+        .is_some_and(JsonValue::is_string)
+    {
+        return event;
+    }
+    // 出错时部分网关（如 MiniMax）返回 "choices": null
+    let has_content = |key| event.get(key).is_some_and(|value| !value.is_null());
+    if has_content("choices") || has_content("candidates") {
+        return event;
+    }
+    let coded_pointers = if has_error_code(&event) {
+        CODED_ERROR_POINTERS
+    } else {
+        &[]
+    };
+    let message = quirks
+        .error_pointers
+        .iter()
+        .chain(COMMON_ERROR_POINTERS)
+        .chain(coded_pointers)
+        .find_map(|pointer| event.pointer(pointer).and_then(JsonValue::as_str))
+        .filter(|message| !message.is_empty())
+        .map(str::to_string);
+    // MiniMax 成功时也带有 base_resp，状态码为 0
+    let succeeded = event
+        .pointer("/base_resp/status_code")
+        .and_then(JsonValue::as_i64)
+        == Some(0);
+    match message {
+        Some(message) if !succeeded => {
+            event["error"] = json!({ "message": message });
+            event
+        }
+        _ => event,
+    }
+}
+
+// 是否带有表示失败的状态：非 0 且不在 2xx 之间的错误码、"error" 这样的状态文字或 "success": false
+fn has_error_code(event: &JsonValue) -> bool {
+    let is_error_code = |code: i64| code != 0 && !(200..300).contains(&code);
+    let failed = ERROR_CODE_KEYS
+        .iter()
+        .filter_map(|key| event.get(key))
+        .any(|value| match value {
+            JsonValue::Number(code) => code.as_i64().is_some_and(is_error_code),
+            JsonValue::String(status) => match status.parse::<i64>() {
+                Ok(code) => is_error_code(code),
+                Err(_) => matches!(
+                    status.to_lowercase().as_str(),
+                    "error" | "fail" | "failed" | "failure"
+                ),
+            },
+            _ => false,
+        });
+    failed || event.get("success").and_then(JsonValue::as_bool) == Some(false)
+}
+
+// 把缺少 choices 或 choices 形状不同的事件改写为标准格式；field 为 delta（流式）或 message（非流式）
+fn normalize_choices(mut event: JsonValue, field: &str) -> JsonValue {
+    match event.get_mut("choices") {
+        // choices 是单个对象而不是数组
+        Some(choices) if choices.is_object() => {
+            *choices = JsonValue::Array(vec![choices.take()]);
+        }
+        Some(_) => {}
+        // Ollama 原生接口：{"message": {"content": ...}, "done": false}
+        None => {
+            let Some(message) = event.get("message").filter(|message| message.is_object()) else {
+                return event;
+            };
+            let mut choice = json!({ field: message.clone() });
+            if let Some(thinking) = message.get("thinking") {
+                choice[field]["reasoning_content"] = thinking.clone();
+            }
+            if event.get("done").and_then(JsonValue::as_bool) == Some(true) {
+                choice["finish_reason"] = json!("stop");
+                event["usage"] = json!({
+                    "prompt_tokens": event["prompt_eval_count"].as_u64().unwrap_or(0),
+                    "completion_tokens": event["eval_count"].as_u64().unwrap_or(0),
+                });
+            }
+            event["choices"] = json!([choice]);
+            return event;
+        }
+    }
+
+    if let Some(choice) = event.pointer_mut("/choices/0") {
+        if choice.get(field).is_none() {
+            // 流式事件用 message 代替 delta，或反之
+            let other = if field == "delta" { "message" } else { "delta" };
+            if let Some(content) = choice.get(other).cloned() {
+                choice[field] = content;
+            } else if let Some(text) = choice.get("text").cloned() {
+                // 旧版 completions 格式的 text
+                choice[field] = json!({ "content": text });
+            }
+        }
+    }
+    event
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 按接收流时的方式逐行解析抓取的响应，返回拼接的内容、错误信息、结束原因和是否收到 [DONE]
+    #[derive(Default, Debug)]
+    struct Replay {
+        content: String,
+        errors: Vec<String>,
+        finish_reason: Option<String>,
+        usage: Option<JsonValue>,
+        done: bool,
+    }
+
+    fn replay(fixture: &str, endpoint: &str) -> Replay {
+        let quirks = quirks_for(endpoint);
+        let mut replay = Replay::default();
+        let mut incomplete = String::new();
+        for line in fixture.lines() {
+            let data = match classify_line(line, quirks, !incomplete.is_empty()) {
+                StreamLine::Skip => continue,
+                StreamLine::Done => {
+                    replay.done = true;
+                    break;
+                }
+                StreamLine::Data(data) => data,
+            };
+            incomplete.push_str(data);
+            let Ok(event) = serde_json::from_str::<JsonValue>(&incomplete) else {
+                continue;
+            };
+            incomplete.clear();
+            let event = normalize_event(event, ProviderKind::OpenAi, quirks);
+            if let Some(message) = event.pointer("/error/message").and_then(JsonValue::as_str) {
+                replay.errors.push(message.to_string());
+                continue;
+            }
+            let choice = &event["choices"][0];
+            if let Some(content) = choice["delta"]["content"].as_str() {
+                replay.content.push_str(content);
+            }
+            if let Some(reason) = choice["finish_reason"].as_str() {
+                replay.finish_reason = Some(reason.to_string());
+            }
+            if let Some(usage) = event.get("usage") {
+                replay.usage = Some(usage.clone());
+            }
+        }
+        replay
+    }
+
+    fn normalized_response(fixture: &str, endpoint: &str) -> JsonValue {
+        let response = serde_json::from_str(fixture).unwrap();
+        normalize_response(response, ProviderKind::OpenAi, quirks_for(endpoint))
+    }
+
+    const OLLAMA: &str = "http://localhost:11434/api/chat";
+    const QIANFAN: &str =
+        "https://aip.baidubce.com/rpc/2.0/ai_custom/v1/wenxinworkshop/chat/ernie-speed-128k";
+    const CLOUDFLARE: &str =
+        "https://api.cloudflare.com/client/v4/accounts/abc/ai/run/@cf/meta/llama-3-8b-instruct";
+    const MINIMAX: &str = "https://api.minimax.chat/v1/text/chatcompletion_v2";
+    const GENERIC: &str = "https://openrouter.ai/api/v1/chat/completions";
+
+    #[test]
+    fn quirks_match_endpoints() {
+        assert_eq!(quirks_for(OLLAMA).name, "Ollama");
+        assert_eq!(quirks_for(QIANFAN).name, "百度千帆");
+        assert_eq!(quirks_for(CLOUDFLARE).name, "Cloudflare Workers AI");
+        assert_eq!(quirks_for(MINIMAX).name, "MiniMax");
+        assert_eq!(quirks_for(GENERIC).name, "通用");
+        // Ollama 的 OpenAI 兼容接口 /v1/ 是标准格式
+        assert_eq!(
+            quirks_for("http://localhost:11434/v1/chat/completions").name,
+            "通用"
+        );
+    }
+
+    #[test]
+    fn ollama_ndjson_stream() {
+        let replay = replay(include_str!("fixtures/ollama_stream.ndjson"), OLLAMA);
+        assert_eq!(replay.content, "你好");
+        assert_eq!(replay.finish_reason.as_deref(), Some("stop"));
+        assert_eq!(
+            replay.usage,
+            Some(json!({ "prompt_tokens": 26, "completion_tokens": 2 }))
+        );
+        assert!(replay.errors.is_empty());
+    }
+
+    #[test]
+    fn ollama_error() {
+        let replay = replay(include_str!("fixtures/ollama_error.ndjson"), OLLAMA);
+        assert_eq!(
+            replay.errors,
+            ["model \"llama9\" not found, try pulling it first"]
+        );
+    }
+
+    #[test]
+    fn qianfan_choices_object() {
+        let replay = replay(include_str!("fixtures/qianfan_stream.sse"), QIANFAN);
+        assert_eq!(replay.content, "你好！");
+        assert_eq!(replay.finish_reason.as_deref(), Some("stop"));
+        assert!(replay.errors.is_empty());
+    }
+
+    #[test]
+    fn qianfan_error() {
+        let response = normalized_response(include_str!("fixtures/qianfan_error.json"), QIANFAN);
+        assert_eq!(
+            response["error"]["message"],
+            "Access token invalid or no longer valid"
+        );
+    }
+
+    #[test]
+    fn cloudflare_text_chunks() {
+        let replay = replay(include_str!("fixtures/cloudflare_stream.sse"), CLOUDFLARE);
+        assert_eq!(replay.content, "Hello world");
+        assert!(replay.done);
+    }
+
+    #[test]
+    fn cloudflare_error() {
+        let response =
+            normalized_response(include_str!("fixtures/cloudflare_error.json"), CLOUDFLARE);
+        assert!(response["error"]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Could not route"));
+    }
+
+    #[test]
+    fn minimax_success_with_base_resp() {
+        let replay = replay(include_str!("fixtures/minimax_stream.sse"), MINIMAX);
+        assert_eq!(replay.content, "你好！");
+        assert!(replay.errors.is_empty());
+    }
+
+    #[test]
+    fn minimax_error() {
+        let response = normalized_response(include_str!("fixtures/minimax_error.json"), MINIMAX);
+        assert!(response["error"]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("login fail"));
+    }
+
+    #[test]
+    fn generic_sse_stream() {
+        let replay = replay(include_str!("fixtures/generic_stream.sse"), GENERIC);
+        assert_eq!(replay.content, "Hello");
+        assert_eq!(replay.finish_reason.as_deref(), Some("stop"));
+        assert!(replay.errors.is_empty());
+        assert!(replay.done);
+    }
+
+    #[test]
+    fn generic_errors() {
+        let response = normalized_response(include_str!("fixtures/generic_error.json"), GENERIC);
+        assert_eq!(
+            response["error"]["message"],
+            "Upstream provider returned 502 Bad Gateway"
+        );
+        let response =
+            normalized_response(include_str!("fixtures/generic_coded_error.json"), GENERIC);
+        assert_eq!(response["error"]["message"], "internal server error");
+    }
+
+    #[test]
+    fn status_message_without_error_code_is_not_an_error() {
+        let response = normalized_response(include_str!("fixtures/generic_status.json"), GENERIC);
+        assert!(response.get("error").is_none());
+        let event = json!({ "code": 0, "message": "success" });
+        assert!(
+            normalize_event(event, ProviderKind::OpenAi, quirks_for(GENERIC))
+                .get("error")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn standard_error_is_kept() {
+        let event = json!({ "error": { "message": "rate limited", "code": 429 } });
+        let normalized = normalize_event(event.clone(), ProviderKind::OpenAi, quirks_for(GENERIC));
+        assert_eq!(normalized, event);
+    }
+
+    #[test]
+    fn classify_lines() {
+        let quirks = quirks_for(GENERIC);
+        assert_eq!(
+            classify_line(": keepalive\n", quirks, false),
+            StreamLine::Skip
+        );
+        assert_eq!(
+            classify_line("event: ping\r\n", quirks, false),
+            StreamLine::Skip
+        );
+        assert_eq!(classify_line("data:\n", quirks, false), StreamLine::Skip);
+        assert_eq!(
+            classify_line("data: [DONE]\n", quirks, false),
+            StreamLine::Done
+        );
+        assert_eq!(
+            classify_line("data:{\"a\":1}\n", quirks, false),
+            StreamLine::Data("{\"a\":1}")
+        );
+        assert_eq!(classify_line("partial\n", quirks, false), StreamLine::Skip);
+        assert_eq!(
+            classify_line("partial\n", quirks, true),
+            StreamLine::Data("partial")
+        );
+    }
+}
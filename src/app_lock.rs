@@ -0,0 +1,106 @@
+// 应用锁：启动时和空闲一段时间后要求输入密码，锁定期间不显示任何对话内容
+//
+// 配置中只保存加盐的 Argon2id 摘要，密码本身不落盘；导出的配置中摘要会被隐藏，
+// 即使摘要泄露，内存开销较大的 Argon2id 也让离线穷举的代价很高
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use log::error;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use uuid::Uuid;
+
+// 旧版本使用的 PBKDF2 迭代次数，只用于校验旧摘要
+const LEGACY_KEY_ITERATIONS: u32 = 20_000;
+const KEY_LEN: usize = 32;
+pub const MIN_PASSPHRASE_CHARS: usize = 8;
+
+// 摘要使用的密钥派生算法；旧版本的配置中没有这个字段，按 PBKDF2 处理
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LockKdf {
+    #[default]
+    Pbkdf2,
+    Argon2id,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LockConfig {
+    pub enabled: bool,
+    // 空闲多少分钟后自动锁定，0 表示只在启动时要求密码
+    pub idle_minutes: u64,
+    pub salt: String,
+    pub hash: String,
+    pub kdf: LockKdf,
+}
+
+impl Default for LockConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            idle_minutes: 10,
+            salt: String::new(),
+            hash: String::new(),
+            kdf: LockKdf::default(),
+        }
+    }
+}
+
+impl LockConfig {
+    pub fn has_passphrase(&self) -> bool {
+        !self.salt.is_empty() && !self.hash.is_empty()
+    }
+
+    // 是否需要在启动时锁定
+    pub fn is_active(&self) -> bool {
+        self.enabled && self.has_passphrase()
+    }
+
+    // 旧版本的摘要，解锁成功后应该用当前算法重新生成
+    pub fn needs_upgrade(&self) -> bool {
+        self.has_passphrase() && self.kdf != LockKdf::Argon2id
+    }
+
+    pub fn set_passphrase(&mut self, passphrase: &str) {
+        let salt = *Uuid::new_v4().as_bytes();
+        self.salt = BASE64.encode(salt);
+        self.kdf = LockKdf::Argon2id;
+        self.hash = BASE64.encode(derive_key(passphrase, &salt, self.kdf));
+    }
+
+    pub fn verify(&self, passphrase: &str) -> bool {
+        let (Ok(salt), Ok(expected)) = (BASE64.decode(&self.salt), BASE64.decode(&self.hash))
+        else {
+            return false;
+        };
+        let actual = derive_key(passphrase, &salt, self.kdf);
+        // 逐字节比较全部内容，耗时不随第一个不同的位置变化
+        expected.len() == actual.len()
+            && expected
+                .iter()
+                .zip(actual.iter())
+                .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], kdf: LockKdf) -> [u8; KEY_LEN] {
+    let mut key = [0u8; KEY_LEN];
+    match kdf {
+        LockKdf::Pbkdf2 => pbkdf2::pbkdf2_hmac::<Sha1>(
+            passphrase.as_bytes(),
+            salt,
+            LEGACY_KEY_ITERATIONS,
+            &mut key,
+        ),
+        // 默认参数为 19 MiB 内存、2 轮，派生一次约几十毫秒
+        LockKdf::Argon2id => {
+            if let Err(e) =
+                Argon2::default().hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            {
+                error!("派生应用锁密钥失败: {}", e);
+            }
+        }
+    }
+    key
+}
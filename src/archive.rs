@@ -0,0 +1,123 @@
+use chrono::{Datelike, Local, Timelike};
+use std::io::{self, Cursor, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{AesMode, CompressionMethod, DateTime, ZipWriter};
+
+// WinZip AES（AE-2，AES-256）加密的 ZIP 压缩包，系统解压工具、7-Zip 等都可以打开；也可以不加密
+pub struct ArchiveEntry {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+fn modified_time() -> DateTime {
+    let now = Local::now();
+    DateTime::from_date_and_time(
+        now.year().clamp(1980, 2107) as u16,
+        now.month() as u8,
+        now.day() as u8,
+        now.hour() as u8,
+        now.minute() as u8,
+        now.second() as u8,
+    )
+    .unwrap_or_default()
+}
+
+pub fn encrypted_zip(entries: &[ArchiveEntry], password: &str) -> io::Result<Vec<u8>> {
+    write_zip(entries, Some(password))
+}
+
+pub fn plain_zip(entries: &[ArchiveEntry]) -> io::Result<Vec<u8>> {
+    write_zip(entries, None)
+}
+
+fn write_zip(entries: &[ArchiveEntry], password: Option<&str>) -> io::Result<Vec<u8>> {
+    let mut options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .last_modified_time(modified_time());
+    if let Some(password) = password {
+        options = options.with_aes_encryption(AesMode::Aes256, password);
+    }
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for entry in entries {
+        writer.start_file(entry.name.as_str(), options)?;
+        writer.write_all(&entry.data)?;
+    }
+    Ok(writer.finish()?.into_inner())
+}
+
+// 在后台线程中压缩加密后写入文件
+pub async fn write_encrypted_zip(
+    path: &Path,
+    entries: Vec<ArchiveEntry>,
+    password: String,
+) -> io::Result<()> {
+    let bytes = tokio::task::spawn_blocking(move || encrypted_zip(&entries, &password))
+        .await
+        .map_err(io::Error::other)??;
+    tokio::fs::write(path, bytes).await
+}
+
+pub async fn write_plain_zip(path: &Path, entries: Vec<ArchiveEntry>) -> io::Result<()> {
+    let bytes = tokio::task::spawn_blocking(move || plain_zip(&entries))
+        .await
+        .map_err(io::Error::other)??;
+    tokio::fs::write(path, bytes).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use zip::ZipArchive;
+
+    fn entries() -> Vec<ArchiveEntry> {
+        vec![
+            ArchiveEntry {
+                name: "对话.md".to_string(),
+                data: "加密导出的对话\n".repeat(100).into_bytes(),
+            },
+            ArchiveEntry {
+                name: "附件/说明.txt".to_string(),
+                data: b"notes".to_vec(),
+            },
+        ]
+    }
+
+    #[test]
+    fn encrypted_zip_round_trip() {
+        let zip = encrypted_zip(&entries(), "correct horse").unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(zip)).unwrap();
+        assert_eq!(archive.len(), 2);
+        for expected in entries() {
+            assert!(
+                archive.by_name(&expected.name).is_err(),
+                "未加密时不应能读取"
+            );
+            let mut file = archive
+                .by_name_decrypt(&expected.name, b"correct horse")
+                .unwrap();
+            let mut data = Vec::new();
+            file.read_to_end(&mut data).unwrap();
+            assert_eq!(data, expected.data);
+        }
+        assert!(archive
+            .by_name_decrypt("对话.md", b"wrong password")
+            .is_err());
+    }
+
+    #[test]
+    fn plain_zip_round_trip() {
+        let zip = plain_zip(&entries()).unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(zip)).unwrap();
+        for expected in entries() {
+            let mut data = Vec::new();
+            archive
+                .by_name(&expected.name)
+                .unwrap()
+                .read_to_end(&mut data)
+                .unwrap();
+            assert_eq!(data, expected.data);
+        }
+    }
+}
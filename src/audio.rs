@@ -0,0 +1,118 @@
+use log::debug;
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::Duration;
+
+// 合成中的语音：从通道接收采样，数据还没到时输出静音，发送端关闭且数据播完后结束
+pub struct StreamingSource {
+    receiver: Receiver<Vec<i16>>,
+    current: std::vec::IntoIter<i16>,
+    sample_rate: u32,
+}
+
+impl StreamingSource {
+    pub fn new(receiver: Receiver<Vec<i16>>, sample_rate: u32) -> Self {
+        Self {
+            receiver,
+            current: Vec::new().into_iter(),
+            sample_rate,
+        }
+    }
+}
+
+impl Iterator for StreamingSource {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        loop {
+            if let Some(sample) = self.current.next() {
+                return Some(sample);
+            }
+            match self.receiver.try_recv() {
+                Ok(samples) => self.current = samples.into_iter(),
+                Err(TryRecvError::Empty) => return Some(0),
+                Err(TryRecvError::Disconnected) => return None,
+            }
+        }
+    }
+}
+
+impl Source for StreamingSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+// 在程序内播放音频，同一时间只播放一段；音频设备在第一次播放时打开
+#[derive(Default)]
+pub struct AudioPlayer {
+    output: Option<(OutputStream, OutputStreamHandle)>,
+    current: Option<(PathBuf, Sink)>,
+}
+
+impl AudioPlayer {
+    fn sink(&mut self) -> Result<Sink, String> {
+        if self.output.is_none() {
+            let output =
+                OutputStream::try_default().map_err(|e| format!("打开音频设备失败: {}", e))?;
+            self.output = Some(output);
+        }
+        let (_, handle) = self.output.as_ref().expect("音频设备已打开");
+        Sink::try_new(handle).map_err(|e| e.to_string())
+    }
+
+    fn start(
+        &mut self,
+        path: &Path,
+        source: impl Source<Item = i16> + Send + 'static,
+    ) -> Result<(), String> {
+        self.stop();
+        let sink = self.sink()?;
+        sink.append(source);
+        self.current = Some((path.to_path_buf(), sink));
+        Ok(())
+    }
+
+    // 播放缓存的音频文件
+    pub fn play(&mut self, path: &Path) -> Result<(), String> {
+        debug!("播放音频: {:?}", path);
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let source = Decoder::new(BufReader::new(file)).map_err(|e| e.to_string())?;
+        self.start(path, source)
+    }
+
+    // 播放合成中的语音，path 是合成完成后的缓存文件，用来标识正在朗读的内容
+    pub fn play_stream(&mut self, path: &Path, source: StreamingSource) -> Result<(), String> {
+        debug!("边合成边播放: {:?}", path);
+        self.start(path, source)
+    }
+
+    pub fn stop(&mut self) {
+        if let Some((_, sink)) = self.current.take() {
+            sink.stop();
+        }
+    }
+
+    // 正在播放的音频，播放结束后返回 None
+    pub fn playing(&mut self) -> Option<&Path> {
+        if self.current.as_ref().is_some_and(|(_, sink)| sink.empty()) {
+            self.current = None;
+        }
+        self.current.as_ref().map(|(path, _)| path.as_path())
+    }
+}
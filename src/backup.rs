@@ -0,0 +1,147 @@
+// 自动备份：启动时把对话记录和配置复制到数据目录的 backups/ 下以时间命名的目录，只保留最近的若干份
+use crate::config::CONFIG_FILE;
+use crate::models::CHAT_LIST_FILE;
+use crate::paths;
+use crate::utils;
+use chrono::{Local, NaiveDateTime};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+pub const BACKUP_DIR: &str = "backups";
+const NAME_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+pub fn backup_dir() -> PathBuf {
+    paths::data_file(BACKUP_DIR)
+}
+
+// 备份的文件：备份目录中的文件名和实际位置
+fn files() -> [(&'static str, PathBuf); 2] {
+    [
+        (CHAT_LIST_FILE, paths::data_file(CHAT_LIST_FILE)),
+        (CONFIG_FILE, paths::config_file(CONFIG_FILE)),
+    ]
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct BackupConfig {
+    pub enabled: bool,
+    // 保留的备份份数
+    pub keep: usize,
+    // 每天只备份一次，关闭时每次启动都备份
+    pub daily: bool,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            keep: 7,
+            daily: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub path: PathBuf,
+    pub created: NaiveDateTime,
+    pub size: u64,
+}
+
+impl Snapshot {
+    pub fn label(&self) -> String {
+        self.created.format("%Y-%m-%d %H:%M:%S").to_string()
+    }
+}
+
+// 已有的备份，最新的在前
+pub async fn list() -> Vec<Snapshot> {
+    let mut snapshots = Vec::new();
+    let Ok(mut entries) = fs::read_dir(backup_dir()).await else {
+        return snapshots;
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Ok(created) = NaiveDateTime::parse_from_str(&name, NAME_FORMAT) else {
+            continue;
+        };
+        let mut size = 0;
+        for (name, _) in files() {
+            if let Ok(metadata) = fs::metadata(entry.path().join(name)).await {
+                size += metadata.len();
+            }
+        }
+        snapshots.push(Snapshot {
+            path: entry.path(),
+            created,
+            size,
+        });
+    }
+    snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.created));
+    snapshots
+}
+
+pub async fn create() -> io::Result<Snapshot> {
+    let created = Local::now().naive_local();
+    let path = backup_dir().join(created.format(NAME_FORMAT).to_string());
+    fs::create_dir_all(&path).await?;
+    let mut size = 0;
+    for (name, file) in files() {
+        if fs::try_exists(&file).await.unwrap_or(false) {
+            size += fs::copy(&file, path.join(name)).await?;
+        }
+    }
+    debug!("已创建备份: {}", path.display());
+    Ok(Snapshot {
+        path,
+        created,
+        size,
+    })
+}
+
+// 删除超出份数的旧备份，返回删除的份数
+pub async fn rotate(keep: usize) -> io::Result<usize> {
+    let snapshots = list().await;
+    let mut removed = 0;
+    for snapshot in snapshots.iter().skip(keep.max(1)) {
+        fs::remove_dir_all(&snapshot.path).await?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+// 启动时的自动备份；按天备份时今天已有备份则跳过
+pub async fn run_scheduled(config: &BackupConfig) -> io::Result<Option<Snapshot>> {
+    if !config.enabled {
+        return Ok(None);
+    }
+    let today = Local::now().date_naive();
+    if config.daily
+        && list()
+            .await
+            .first()
+            .is_some_and(|latest| latest.created.date() == today)
+    {
+        return Ok(None);
+    }
+    let snapshot = create().await?;
+    rotate(config.keep).await?;
+    Ok(Some(snapshot))
+}
+
+// 从备份恢复：先备份当前的数据，再用原子写入替换
+pub async fn restore(snapshot: &Path) -> io::Result<()> {
+    create().await?;
+    for (name, file) in files() {
+        let source = snapshot.join(name);
+        if fs::try_exists(&source).await.unwrap_or(false) {
+            utils::write_atomic(&file, fs::read(&source).await?).await?;
+        }
+    }
+    debug!("已从备份恢复: {}", snapshot.display());
+    Ok(())
+}
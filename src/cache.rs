@@ -0,0 +1,136 @@
+// 图片和语音缓存的容量控制和目录迁移
+use log::{debug, error};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::fs;
+
+#[derive(Debug, Default)]
+pub struct EvictionReport {
+    pub removed: usize,
+    pub freed_bytes: u64,
+}
+
+// 缓存文件按文件名匹配对话中的图片路径，文件名是唯一的 UUID
+pub fn file_name(path: &str) -> Option<&str> {
+    Path::new(path).file_name().and_then(|name| name.to_str())
+}
+
+// 很多系统关闭了访问时间记录，取访问时间和修改时间中较新的一个
+fn last_used(metadata: &std::fs::Metadata) -> SystemTime {
+    let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+    metadata
+        .accessed()
+        .map_or(modified, |accessed| accessed.max(modified))
+}
+
+// 目录下的缓存文件及其最近使用时间和大小，跳过正在写入的 .part 文件
+async fn cached_files(dir: &Path) -> io::Result<Vec<(SystemTime, u64, PathBuf)>> {
+    let mut files = Vec::new();
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(files),
+        Err(e) => return Err(e),
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        let metadata = entry.metadata().await?;
+        let path = entry.path();
+        if metadata.is_file() && path.extension().is_none_or(|ext| ext != "part") {
+            files.push((last_used(&metadata), metadata.len(), path));
+        }
+    }
+    Ok(files)
+}
+
+async fn remove(path: &Path, size: u64, report: &mut EvictionReport) -> bool {
+    match fs::remove_file(path).await {
+        Ok(()) => {
+            report.removed += 1;
+            report.freed_bytes += size;
+            true
+        }
+        Err(e) => {
+            error!("删除缓存文件失败: {:?} - {}", path, e);
+            false
+        }
+    }
+}
+
+// 缓存超过上限时，按最近使用时间从旧到新删除不再被任何对话引用的文件
+pub async fn evict(
+    dir: &Path,
+    max_bytes: u64,
+    referenced: &HashSet<String>,
+) -> io::Result<EvictionReport> {
+    let mut report = EvictionReport::default();
+    let mut files = cached_files(dir).await?;
+    let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+    debug!("{:?} 共 {} 个文件，{} 字节", dir, files.len(), total);
+    if total <= max_bytes {
+        return Ok(report);
+    }
+
+    files.sort_by_key(|(used, _, _)| *used);
+    for (_, size, path) in files {
+        if total <= max_bytes {
+            break;
+        }
+        let in_use = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| referenced.contains(name));
+        if !in_use && remove(&path, size, &mut report).await {
+            total -= size;
+        }
+    }
+    Ok(report)
+}
+
+// 删除超过 max_age 没有使用过的缓存文件
+pub async fn evict_older_than(dir: &Path, max_age: Duration) -> io::Result<EvictionReport> {
+    let mut report = EvictionReport::default();
+    let now = SystemTime::now();
+    for (used, size, path) in cached_files(dir).await? {
+        if now.duration_since(used).is_ok_and(|age| age > max_age) {
+            remove(&path, size, &mut report).await;
+        }
+    }
+    Ok(report)
+}
+
+// 把缓存文件移动到新目录，跨磁盘时先复制再删除；返回文件名到新路径的映射
+pub async fn migrate(from: &Path, to: &Path) -> io::Result<HashMap<String, PathBuf>> {
+    fs::create_dir_all(to).await?;
+    let mut moved = HashMap::new();
+    let mut entries = match fs::read_dir(from).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(moved),
+        Err(e) => return Err(e),
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        if !entry.file_type().await?.is_file() {
+            continue;
+        }
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        let target = to.join(&name);
+        if fs::rename(entry.path(), &target).await.is_err() {
+            fs::copy(entry.path(), &target).await?;
+            fs::remove_file(entry.path()).await?;
+        }
+        moved.insert(name, target);
+    }
+    debug!("已迁移 {} 个缓存文件到 {:?}", moved.len(), to);
+    Ok(moved)
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const MB: f64 = 1024.0 * 1024.0;
+    if bytes as f64 >= MB {
+        format!("{:.1} MB", bytes as f64 / MB)
+    } else {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    }
+}
@@ -0,0 +1,186 @@
+// 模型能力：按模型名称判断是否支持图片输入、工具调用和 JSON 模式，也可以向 OpenAI 兼容接口发送探测请求
+use crate::api::{self, ApiError};
+use crate::providers::{ApiTarget, ProviderKind};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use std::collections::BTreeMap;
+
+// 1x1 的透明 PNG，用于探测图片输入
+const PROBE_IMAGE: &str = "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+const PROBE_MAX_TOKENS: u64 = 16;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Capabilities {
+    pub vision: bool,
+    pub tools: bool,
+    pub json_mode: bool,
+}
+
+impl Capabilities {
+    // 悬停提示中的说明
+    pub fn summary(&self) -> String {
+        let mark = |supported: bool| if supported { "✔" } else { "✘" };
+        format!(
+            "图片输入 {}  工具调用 {}  JSON 模式 {}",
+            mark(self.vision),
+            mark(self.tools),
+            mark(self.json_mode)
+        )
+    }
+}
+
+// 模型的能力：先查配置中记录的（手动填写或探测得到），再按名称判断；无法判断时返回 None
+pub fn resolve(
+    known: &BTreeMap<String, Capabilities>,
+    provider: ProviderKind,
+    model: &str,
+) -> Option<Capabilities> {
+    known
+        .get(model)
+        .copied()
+        .or_else(|| lookup(provider, model))
+}
+
+// 按模型名称判断常见模型的能力
+pub fn lookup(provider: ProviderKind, model: &str) -> Option<Capabilities> {
+    let model = model.to_lowercase();
+    let name = model.rsplit('/').next().unwrap_or(&model);
+    let has = |patterns: &[&str]| patterns.iter().any(|pattern| name.contains(pattern));
+
+    if provider == ProviderKind::Claude || name.starts_with("claude") {
+        let legacy = has(&["claude-2", "claude-instant"]);
+        return Some(Capabilities {
+            vision: !legacy,
+            tools: !legacy,
+            // Claude 没有 response_format 参数
+            json_mode: false,
+        });
+    }
+    if provider == ProviderKind::Gemini || name.starts_with("gemini") {
+        let text_only = name == "gemini-pro" || name.starts_with("gemini-1.0-pro");
+        return Some(Capabilities {
+            vision: !text_only || name.contains("vision"),
+            tools: true,
+            json_mode: !text_only,
+        });
+    }
+    if name.starts_with("gpt-3.5") {
+        return Some(Capabilities {
+            vision: false,
+            tools: true,
+            json_mode: !has(&["-0301", "-0613"]),
+        });
+    }
+    if name.starts_with("gpt-4") || name.starts_with("gpt-5") || name.starts_with("chatgpt-4o") {
+        let legacy = matches!(name, "gpt-4" | "gpt-4-32k")
+            || has(&["gpt-4-0314", "gpt-4-0613", "gpt-4-32k-"]);
+        return Some(Capabilities {
+            vision: !legacy,
+            tools: !name.starts_with("chatgpt-4o"),
+            json_mode: !legacy,
+        });
+    }
+    if name.starts_with("o1") || name.starts_with("o3") || name.starts_with("o4") {
+        let early = has(&["o1-mini", "o1-preview"]);
+        return Some(Capabilities {
+            vision: !early && !name.starts_with("o3-mini"),
+            tools: !early,
+            json_mode: !early,
+        });
+    }
+    if name.starts_with("deepseek") {
+        let reasoner = has(&["reasoner", "r1"]);
+        return Some(Capabilities {
+            vision: has(&["vl"]),
+            tools: !reasoner,
+            json_mode: !reasoner,
+        });
+    }
+    if has(&[
+        "vision",
+        "-vl",
+        "llava",
+        "pixtral",
+        "glm-4v",
+        "minicpm-v",
+        "gemma3",
+        "gemma-3",
+    ]) {
+        return Some(Capabilities {
+            vision: true,
+            tools: has(&["qwen", "pixtral", "llama-3.2", "llama-4"]),
+            json_mode: has(&["qwen", "pixtral"]),
+        });
+    }
+    if has(&[
+        "qwen",
+        "mistral",
+        "mixtral",
+        "llama-3.1",
+        "llama-3.3",
+        "llama3.1",
+        "llama3.3",
+    ]) {
+        return Some(Capabilities {
+            vision: false,
+            tools: true,
+            json_mode: has(&["qwen", "mistral", "mixtral"]),
+        });
+    }
+    None
+}
+
+// 依次发送普通请求和分别带图片、工具、JSON 模式的请求，被接口以 4xx 拒绝的视为不支持
+//
+// 只用于 OpenAI 兼容接口，其他服务商的请求格式由本程序转换，探测结果没有意义
+pub async fn probe(
+    client: &Client,
+    target: &ApiTarget,
+    model: &str,
+) -> Result<Capabilities, String> {
+    // JSON 模式要求消息中出现 “JSON” 一词
+    let text = json!([{ "role": "user", "content": "Reply with the JSON object {\"ok\": true}" }]);
+    let base = json!({ "model": model, "messages": text, "max_tokens": PROBE_MAX_TOKENS });
+    if let Err(e) = api::complete(client, target, &base).await {
+        return Err(format!("模型无法正常回复: {}", e));
+    }
+
+    let mut vision = base.clone();
+    vision["messages"] = json!([{
+        "role": "user",
+        "content": [
+            { "type": "text", "text": "Reply with ok" },
+            { "type": "image_url", "image_url": { "url": PROBE_IMAGE } }
+        ]
+    }]);
+    let mut tools = base.clone();
+    tools["tools"] = json!([{
+        "type": "function",
+        "function": {
+            "name": "ping",
+            "description": "Does nothing",
+            "parameters": { "type": "object", "properties": {} }
+        }
+    }]);
+    let mut json_mode = base.clone();
+    json_mode["response_format"] = json!({ "type": "json_object" });
+
+    Ok(Capabilities {
+        vision: supports(client, target, &vision).await?,
+        tools: supports(client, target, &tools).await?,
+        json_mode: supports(client, target, &json_mode).await?,
+    })
+}
+
+async fn supports(
+    client: &Client,
+    target: &ApiTarget,
+    payload: &JsonValue,
+) -> Result<bool, String> {
+    match api::complete(client, target, payload).await {
+        Ok(_) => Ok(true),
+        Err(ApiError::HttpError(response)) if response.status().is_client_error() => Ok(false),
+        Err(e) => Err(e.to_string()),
+    }
+}
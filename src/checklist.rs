@@ -0,0 +1,55 @@
+use crate::models::{self, ChatTask, Message};
+use serde_json::{json, Value as JsonValue};
+
+const EXTRACTION_PROMPT: &str = "请从以上对话中提取需要完成的任务或目标，以 JSON 字符串数组的形式返回，例如 [\"任务一\", \"任务二\"]。每个任务不超过20个字，只返回 JSON，不要任何解释。如果没有任务，返回 []。";
+
+// 构建任务提取请求
+pub fn build_payload(model: &str, messages: &[Message]) -> JsonValue {
+    let mut request_messages: Vec<JsonValue> = models::context_messages(messages)
+        .map(|msg| {
+            json!({
+                "role": msg.role,
+                "content": msg.content
+            })
+        })
+        .collect();
+    request_messages.push(json!({
+        "role": "user",
+        "content": EXTRACTION_PROMPT
+    }));
+
+    json!({
+        "model": model,
+        "messages": request_messages,
+        "temperature": 0.0
+    })
+}
+
+// 解析模型返回的任务列表，兼容被代码块包裹的 JSON
+pub fn parse_tasks(content: &str) -> Vec<String> {
+    let start = content.find('[');
+    let end = content.rfind(']');
+    let json_text = match (start, end) {
+        (Some(start), Some(end)) if start < end => &content[start..=end],
+        _ => return Vec::new(),
+    };
+
+    serde_json::from_str::<Vec<String>>(json_text)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|task| task.trim().to_string())
+        .filter(|task| !task.is_empty())
+        .collect()
+}
+
+// 合并新提取的任务，保留已有任务及其完成状态
+pub fn merge_tasks(existing: &mut Vec<ChatTask>, extracted: Vec<String>) {
+    for text in extracted {
+        let exists = existing
+            .iter()
+            .any(|task| task.text.trim().eq_ignore_ascii_case(&text));
+        if !exists {
+            existing.push(ChatTask { text, done: false });
+        }
+    }
+}
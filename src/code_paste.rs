@@ -0,0 +1,215 @@
+// 智能粘贴：粘贴的文本看起来像代码时自动包进带语言标记的代码块
+//
+// 只按缩进、括号、分号、shebang 和常见关键字粗略判断，猜错时由输入框上方的撤销按钮恢复原文
+const MIN_LINES: usize = 2;
+
+// 粘贴的文本是否像代码
+pub fn looks_like_code(text: &str) -> bool {
+    let text = text.trim_matches('\n');
+    if text.contains("```") {
+        return false;
+    }
+    if text.starts_with("#!") {
+        return true;
+    }
+    let lines: Vec<&str> = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    if lines.len() < MIN_LINES {
+        return false;
+    }
+    let code_lines = lines.iter().filter(|line| is_code_line(line)).count();
+    code_lines >= MIN_LINES && code_lines * 2 >= lines.len()
+}
+
+fn is_code_line(line: &str) -> bool {
+    let trimmed = line.trim();
+    let indented = line.starts_with("    ") || line.starts_with('\t');
+    // 缩进的列表项和引用是普通文本
+    if indented
+        && (trimmed.starts_with("- ") || trimmed.starts_with("* ") || trimmed.starts_with("> "))
+    {
+        return false;
+    }
+    const KEYWORDS: &[&str] = &[
+        "fn ",
+        "pub ",
+        "let ",
+        "use ",
+        "impl ",
+        "def ",
+        "class ",
+        "import ",
+        "from ",
+        "return ",
+        "if (",
+        "for (",
+        "while (",
+        "func ",
+        "package ",
+        "#include",
+        "#define",
+        "const ",
+        "var ",
+        "function ",
+        "public ",
+        "private ",
+        "static ",
+        "SELECT ",
+        "INSERT ",
+        "UPDATE ",
+        "CREATE ",
+        "<?php",
+        "<div",
+        "<html",
+        "//",
+        "/*",
+    ];
+    indented
+        || trimmed.ends_with(['{', '}', ';'])
+        || trimmed.starts_with('}')
+        || KEYWORDS.iter().any(|keyword| trimmed.starts_with(keyword))
+        || trimmed.contains(" => ")
+}
+
+// 猜测代码的语言，返回代码块的语言标记；无法判断时返回 None
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let text = text.trim();
+    if let Some(shebang) = text.lines().next().filter(|line| line.starts_with("#!")) {
+        return [
+            ("python", "python"),
+            ("node", "javascript"),
+            ("ruby", "ruby"),
+            ("perl", "perl"),
+            ("sh", "bash"),
+        ]
+        .iter()
+        .find(|(interpreter, _)| shebang.contains(interpreter))
+        .map(|(_, language)| *language);
+    }
+    let has = |patterns: &[&str]| patterns.iter().any(|pattern| text.contains(pattern));
+    let line_starts = |patterns: &[&str]| {
+        text.lines()
+            .map(str::trim_start)
+            .any(|line| patterns.iter().any(|pattern| line.starts_with(pattern)))
+    };
+
+    if text.starts_with("<?php") {
+        return Some("php");
+    }
+    if (text.starts_with('{') || text.starts_with('['))
+        && serde_json::from_str::<serde_json::Value>(text).is_ok()
+    {
+        return Some("json");
+    }
+    if line_starts(&[
+        "<!DOCTYPE",
+        "<html",
+        "<div",
+        "<head",
+        "<body",
+        "<span",
+        "<p>",
+    ]) {
+        return Some("html");
+    }
+    if line_starts(&[
+        "fn ",
+        "pub fn ",
+        "impl ",
+        "use std::",
+        "pub struct ",
+        "#[derive",
+    ]) || has(&["let mut ", "println!(", "-> Result<", "&mut self", "&self"])
+    {
+        return Some("rust");
+    }
+    if line_starts(&["package main", "func "]) || has(&[" := ", "fmt.Print"]) {
+        return Some("go");
+    }
+    if line_starts(&["#include"]) {
+        return if has(&[
+            "std::",
+            "cout",
+            "template<",
+            "template <",
+            "class ",
+            "namespace ",
+        ]) {
+            Some("cpp")
+        } else {
+            Some("c")
+        };
+    }
+    if line_starts(&["using System", "namespace "]) || has(&["Console.Write"]) {
+        return Some("csharp");
+    }
+    if has(&["System.out.", "public static void main"])
+        || line_starts(&["import java.", "package "])
+    {
+        return Some("java");
+    }
+    if (line_starts(&["def ", "elif ", "from ", "import "]) && !has(&[";\n", "{\n"]))
+        || (has(&["self.", "print(", "__init__"]) && !has(&["{\n"]))
+    {
+        return Some("python");
+    }
+    if line_starts(&["interface ", "type ", "export interface ", "export type "])
+        || has(&[": string", ": number", ": boolean"])
+    {
+        return Some("typescript");
+    }
+    if line_starts(&["function ", "const ", "let ", "var ", "export ", "import "])
+        || has(&["=> {", "console.log", "require(", "document."])
+    {
+        return Some("javascript");
+    }
+    let upper = text.to_uppercase();
+    if [
+        "SELECT ",
+        "INSERT INTO",
+        "UPDATE ",
+        "CREATE TABLE",
+        "DELETE FROM",
+    ]
+    .iter()
+    .any(|keyword| upper.starts_with(keyword))
+    {
+        return Some("sql");
+    }
+    if line_starts(&["echo ", "export ", "cd ", "sudo ", "apt ", "if [", "for "])
+        || has(&["$(", "${"])
+    {
+        return Some("bash");
+    }
+    if line_starts(&["["]) && has(&[" = "]) && !has(&["{", ";"]) {
+        return Some("toml");
+    }
+    // 选择器 { 属性: 值; }
+    if has(&["{\n"]) && has(&[": "]) && has(&[";\n"]) && !has(&["(", "="]) {
+        return Some("css");
+    }
+    None
+}
+
+// 把代码包进代码块；prefix_newline 表示粘贴位置前面还有同一行的文字
+pub fn fence(code: &str, language: Option<&str>, prefix_newline: bool) -> String {
+    format!(
+        "{}```{}\n{}\n```\n",
+        if prefix_newline { "\n" } else { "" },
+        language.unwrap_or_default(),
+        code.trim_matches('\n')
+    )
+}
+
+// 光标位置是否已在代码块中
+pub fn inside_fence(text: &str, cursor: usize) -> bool {
+    let before: String = text.chars().take(cursor).collect();
+    before
+        .lines()
+        .filter(|line| line.trim_start().starts_with("```"))
+        .count()
+        % 2
+        == 1
+}
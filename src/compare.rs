@@ -0,0 +1,46 @@
+use crate::models::Message;
+use crate::reply_stream::ReplyStream;
+
+// 对比模式一次可以选择的模型数量
+pub const MIN_MODELS: usize = 2;
+pub const MAX_MODELS: usize = 4;
+
+// 同一条消息并发发送给多个模型，各模型的回复并排显示，可以选择一条加入对话
+pub struct Comparison {
+    pub chat_id: String,
+    pub prompt: Message,
+    pub columns: Vec<CompareColumn>,
+}
+
+pub struct CompareColumn {
+    pub model: String,
+    pub stream: ReplyStream,
+    pub task_id: u64,
+    pub done: bool,
+}
+
+impl Comparison {
+    pub fn new(chat_id: String, prompt: Message) -> Self {
+        Self {
+            chat_id,
+            prompt,
+            columns: Vec::new(),
+        }
+    }
+
+    // 读取所有模型已到达的片段，返回本次刚完成的列
+    pub fn poll(&mut self) -> Vec<usize> {
+        let mut finished = Vec::new();
+        for (index, column) in self.columns.iter_mut().enumerate() {
+            if !column.done && column.stream.poll() {
+                column.done = true;
+                finished.push(index);
+            }
+        }
+        finished
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.columns.iter().any(|column| !column.done)
+    }
+}
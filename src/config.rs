@@ -1,55 +1,673 @@
+use crate::app_lock::LockConfig;
+use crate::backup::BackupConfig;
+use crate::capabilities::Capabilities;
+use crate::content_filter::FilterMode;
+use crate::context::{self, ContextStrategy};
+use crate::costs::{self, ModelPrice};
+use crate::doh::DohProvider;
+use crate::features::FeatureFlags;
+use crate::imagegen;
+use crate::knowledge;
+use crate::paths;
+use crate::postprocess::PostProcessor;
+use crate::providers::{ApiTarget, ProviderKind};
+use crate::resolver::IpPreference;
+use crate::speech;
+use crate::titles::TitleConfig;
+use crate::utils;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::error::Error as StdError;
+use std::path::Path;
 use tokio::fs;
 
-#[derive(Serialize, Deserialize, Debug)]
+pub const CONFIG_FILE: &str = "dream.toml";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Config {
     pub api_key: String,
     pub api: ApiConfig,
     pub chat: ChatConfig,
+    #[serde(default)]
+    pub snippets: Vec<Snippet>,
+    #[serde(default = "default_follow_up_actions")]
+    pub follow_up_actions: Vec<FollowUpAction>,
+    // 回复完成后依次应用的后处理步骤
+    #[serde(default)]
+    pub post_processors: Vec<PostProcessor>,
+    #[serde(default = "default_claude_config")]
+    pub claude: ProviderConfig,
+    #[serde(default = "default_gemini_config")]
+    pub gemini: ProviderConfig,
+    #[serde(default)]
+    pub azure: AzureConfig,
+    #[serde(default)]
+    pub content_filter: ContentFilterConfig,
+    // 各模型的单价（美元 / 百万 token）
+    #[serde(default = "costs::default_pricing")]
+    pub pricing: BTreeMap<String, ModelPrice>,
+    // 各模型的能力，手动填写或在界面中探测得到，优先于按名称的判断
+    #[serde(default)]
+    pub capabilities: BTreeMap<String, Capabilities>,
+    #[serde(default)]
+    pub proxy: ProxyConfig,
+    #[serde(default)]
+    pub failover: FailoverConfig,
+    #[serde(default)]
+    pub network: NetworkConfig,
+    #[serde(default)]
+    pub profiles: Vec<ApiProfile>,
+    #[serde(default)]
+    pub features: FeatureFlags,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    #[serde(default)]
+    pub titles: TitleConfig,
+    #[serde(default)]
+    pub backup: BackupConfig,
+    #[serde(default)]
+    pub lock: LockConfig,
+}
+
+// 命名的 API 配置档案（地址、密钥和常用模型），可在界面中快速切换
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ApiProfile {
+    pub name: String,
+    pub provider: ProviderKind,
+    // 为空时使用服务商的默认地址
+    pub endpoint: String,
+    pub api_key: String,
+    pub models: Vec<String>,
+}
+
+impl ApiProfile {
+    pub fn target(&self) -> ApiTarget {
+        ApiTarget {
+            provider: self.provider,
+            endpoint: self.endpoint.clone(),
+            api_key: self.api_key.clone(),
+        }
+    }
+}
+
+// 网络设置：可选用 DNS-over-HTTPS 解析域名（绕过被污染的系统 DNS）、限定 IP 协议版本和绑定本地地址
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct NetworkConfig {
+    pub doh_enabled: bool,
+    pub doh_provider: DohProvider,
+    // 自定义 DoH 服务地址
+    pub doh_url: String,
+    pub ip_preference: IpPreference,
+    // 本地 IP 地址，或 Linux 下的网卡名（如 eth0），为空时由系统决定
+    pub bind: String,
+}
+
+// 图片缓存目录和容量上限，超出上限时清理最久未使用且不再被对话引用的文件；
+// 语音缓存可以重新合成，超出上限或超过保留天数时直接清理
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub image_dir: String,
+    // 单位 MB，0 表示不限制
+    pub max_size_mb: u64,
+    pub audio_dir: String,
+    // 单位 MB，0 表示不限制
+    pub audio_max_size_mb: u64,
+    // 0 表示不按时间清理
+    pub audio_max_age_days: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            image_dir: paths::cache_subdir("images").to_string_lossy().to_string(),
+            max_size_mb: 1024,
+            audio_dir: paths::cache_subdir("audio").to_string_lossy().to_string(),
+            audio_max_size_mb: 256,
+            audio_max_age_days: 30,
+        }
+    }
+}
+
+// 主服务商连续失败时临时切换到的备用服务商和模型
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct FailoverConfig {
+    pub enabled: bool,
+    pub provider: ProviderKind,
+    pub model: String,
+}
+
+// 代理模式：跟随系统（环境变量和系统设置）、直连或手动指定
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum ProxyMode {
+    #[default]
+    System,
+    Direct,
+    Manual,
+}
+
+impl ProxyMode {
+    pub const ALL: [ProxyMode; 3] = [ProxyMode::System, ProxyMode::Direct, ProxyMode::Manual];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ProxyMode::System => "跟随系统",
+            ProxyMode::Direct => "不使用代理",
+            ProxyMode::Manual => "手动设置",
+        }
+    }
+}
+
+// 手动代理地址支持 http:// 和 https://（socks5:// 需要启用 reqwest 的 socks 特性），用户名为空时不进行认证
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ProxyConfig {
+    pub mode: ProxyMode,
+    pub url: String,
+    pub username: String,
+    pub password: String,
+}
+
+// 发送前检查提示词的本地过滤列表，规则以 / 包围时按正则匹配
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ContentFilterConfig {
+    pub mode: FilterMode,
+    pub terms: Vec<String>,
+}
+
+// Azure OpenAI 的连接信息，端点由资源名和部署 ID 拼接
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AzureConfig {
+    pub api_key: String,
+    pub resource_name: String,
+    // 为空时使用模型名作为部署 ID
+    pub deployment_id: String,
+    pub api_version: String,
+}
+
+impl Default for AzureConfig {
+    fn default() -> Self {
+        Self {
+            api_key: String::new(),
+            resource_name: String::new(),
+            deployment_id: String::new(),
+            api_version: "2024-06-01".to_string(),
+        }
+    }
+}
+
+impl AzureConfig {
+    // 生成请求地址；未指定部署 ID 时保留 {deployment} 占位符，由请求的模型名替换
+    pub fn endpoint(&self) -> String {
+        let deployment = if self.deployment_id.trim().is_empty() {
+            "{deployment}"
+        } else {
+            self.deployment_id.trim()
+        };
+        format!(
+            "https://{}.openai.azure.com/openai/deployments/{}/chat/completions?api-version={}",
+            self.resource_name.trim(),
+            deployment,
+            self.api_version.trim()
+        )
+    }
+}
+
+// 非 OpenAI 服务商的连接信息；端点为空时使用服务商的默认地址
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ProviderConfig {
+    pub api_key: String,
+    pub endpoint: String,
+}
+
+impl ProviderConfig {
+    fn with_default_endpoint(provider: ProviderKind) -> Self {
+        Self {
+            api_key: String::new(),
+            endpoint: provider.default_endpoint().to_string(),
+        }
+    }
+}
+
+fn default_claude_config() -> ProviderConfig {
+    ProviderConfig::with_default_endpoint(ProviderKind::Claude)
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+fn default_gemini_config() -> ProviderConfig {
+    ProviderConfig::with_default_endpoint(ProviderKind::Gemini)
+}
+
+// 助手回复下方的快捷追问按钮
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FollowUpAction {
+    pub label: String,
+    pub prompt: String,
+}
+
+fn default_follow_up_actions() -> Vec<FollowUpAction> {
+    [
+        ("更简短", "请把上面的回答精简一些，只保留要点。"),
+        ("更详细", "请把上面的回答展开讲得更详细一些。"),
+        ("举例", "请结合具体的例子说明上面的回答。"),
+    ]
+    .into_iter()
+    .map(|(label, prompt)| FollowUpAction {
+        label: label.to_string(),
+        prompt: prompt.to_string(),
+    })
+    .collect()
+}
+
+// 输入框文本缩写，例如 ";sig" 展开为签名
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Snippet {
+    pub trigger: String,
+    pub expansion: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ApiConfig {
     pub endpoint: String,
     pub model: String,
     pub available_models: Vec<String>,
+    // 默认服务商，角色可以单独指定
+    #[serde(default)]
+    pub provider: ProviderKind,
+    // 当前选择的配置档案名称，为空时使用上面的服务商设置
+    #[serde(default)]
+    pub active_profile: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ChatConfig {
     pub system_prompt: String,
     pub temperature: f64,
+    #[serde(default = "default_top_p")]
+    pub top_p: f64,
+    #[serde(default)]
+    pub frequency_penalty: f64,
+    #[serde(default)]
+    pub presence_penalty: f64,
     pub retry_enabled: bool,
     pub max_retries: i64,
+    // 流式回复超过这个秒数没有新数据时提示停滞，0 表示不检测
+    #[serde(default = "default_stall_timeout_secs")]
+    pub stall_timeout_secs: u64,
+    // 回复完成后接收 JSON 通知的地址，对话可以单独设置，为空时不发送
+    #[serde(default)]
+    pub webhook_url: String,
+    // 无痕对话超过这个分钟数没有新消息时清除，0 表示只在关闭时清除
+    #[serde(default = "default_incognito_minutes")]
+    pub incognito_minutes: u64,
+    // 粘贴像代码的文本时自动包进代码块
+    #[serde(default = "default_true")]
+    pub smart_code_paste: bool,
+    // 发送消息时附加与问题相关的长期记忆
+    #[serde(default = "default_true")]
+    pub long_term_memory: bool,
+    // 对话有变化时把 Markdown 记录重新导出到这个目录（如同步盘中的文件夹），为空时不导出
+    #[serde(default)]
+    pub auto_export_dir: String,
     pub dark_mode: bool,
+    #[serde(default)]
+    pub keybinding_mode: KeybindingMode,
+    #[serde(default)]
+    pub send_key: SendKey,
+    // 输入区高度占窗口高度的比例，窗口大小变化后按比例恢复
+    #[serde(default = "default_input_height_ratio")]
+    pub input_height_ratio: f32,
+    #[serde(default)]
+    pub auto_extract_tasks: bool,
+    #[serde(default)]
+    pub group_by_role: bool,
+    #[serde(default)]
+    pub ghost_text_enabled: bool,
+    #[serde(default = "default_helper_model")]
+    pub ghost_text_model: String,
+    #[serde(default)]
+    pub smart_replies_enabled: bool,
+    #[serde(default = "default_helper_model")]
+    pub suggestion_model: String,
+    #[serde(default = "default_true")]
+    pub mask_secrets_in_exports: bool,
+    // 允许外部听写工具通过本地 socket / 命名管道向输入框写入文本
+    #[serde(default)]
+    pub dictation_bridge_enabled: bool,
+    // 主选区快速提问时附加的提示词
+    #[serde(default = "default_quick_ask_prompt")]
+    pub quick_ask_prompt: String,
+    // 推理模型的思考强度，其他模型会忽略
+    #[serde(default)]
+    pub reasoning_effort: ReasoningEffort,
+    // 在请求中附加隐藏指令，要求模型使用与用户消息相同的语言回复
+    #[serde(default)]
+    pub match_reply_language: bool,
+    // 对话超出模型上下文窗口时的裁剪策略，以及“只保留最近 N 条”的 N
+    #[serde(default)]
+    pub context_strategy: ContextStrategy,
+    #[serde(default = "context::default_keep_last")]
+    pub context_keep_last: usize,
+    // 在后台把长对话中较早的消息摘要成对话记忆
+    #[serde(default)]
+    pub auto_memory: bool,
+    // 在回复下方标注链接、路径、命令和代码标识符，并提供快捷操作
+    #[serde(default = "default_true")]
+    pub entity_actions: bool,
+    // 朗读回复使用的语音合成模型和音色
+    #[serde(default = "speech::default_model")]
+    pub tts_model: String,
+    #[serde(default = "speech::default_voice")]
+    pub tts_voice: String,
+    // /image 命令使用的图片生成模型
+    #[serde(default = "imagegen::default_model")]
+    pub image_model: String,
+    // 知识库使用的嵌入模型和每次检索的文本块数量
+    #[serde(default = "knowledge::default_model")]
+    pub embedding_model: String,
+    #[serde(default = "knowledge::default_top_k")]
+    pub knowledge_top_k: usize,
+    // 是否在本地记录诊断统计
+    #[serde(default)]
+    pub metrics_enabled: bool,
+}
+
+fn default_quick_ask_prompt() -> String {
+    crate::quick_ask::DEFAULT_PROMPT.to_string()
+}
+
+// 推理模型的 reasoning_effort 参数
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl ReasoningEffort {
+    pub const ALL: [ReasoningEffort; 3] = [
+        ReasoningEffort::Low,
+        ReasoningEffort::Medium,
+        ReasoningEffort::High,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ReasoningEffort::Low => "低",
+            ReasoningEffort::Medium => "中",
+            ReasoningEffort::High => "高",
+        }
+    }
+
+    pub fn api_value(&self) -> &'static str {
+        match self {
+            ReasoningEffort::Low => "low",
+            ReasoningEffort::Medium => "medium",
+            ReasoningEffort::High => "high",
+        }
+    }
+}
+
+// 输入框的按键绑定模式
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum KeybindingMode {
+    #[default]
+    Default,
+    Vim,
+    Emacs,
+}
+
+impl KeybindingMode {
+    pub const ALL: [KeybindingMode; 3] = [
+        KeybindingMode::Default,
+        KeybindingMode::Vim,
+        KeybindingMode::Emacs,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            KeybindingMode::Default => "默认",
+            KeybindingMode::Vim => "Vim",
+            KeybindingMode::Emacs => "Emacs",
+        }
+    }
+}
+
+// 发送消息的快捷键，其他带 Enter 的组合键用于换行
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum SendKey {
+    #[default]
+    Enter,
+    CtrlEnter,
+    CmdEnter,
+}
+
+impl SendKey {
+    pub const ALL: [SendKey; 3] = [SendKey::Enter, SendKey::CtrlEnter, SendKey::CmdEnter];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SendKey::Enter => "Enter",
+            SendKey::CtrlEnter => "Ctrl+Enter",
+            SendKey::CmdEnter => "Cmd+Enter",
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_stall_timeout_secs() -> u64 {
+    15
+}
+
+fn default_incognito_minutes() -> u64 {
+    30
+}
+
+fn default_top_p() -> f64 {
+    1.0
+}
+
+fn default_input_height_ratio() -> f32 {
+    0.25
+}
+
+// 输入补全、回复建议等辅助功能使用的低成本模型
+fn default_helper_model() -> String {
+    "gpt-3.5-turbo".to_string()
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             api_key: String::new(),
-            api: ApiConfig {
-                endpoint: "https://api.openai.com/v1/chat/completions".to_string(),
-                model: "gpt-4-vision-preview".to_string(),
-                available_models: vec![
-                    "gpt-4-vision-preview".to_string(),
-                    "gpt-4".to_string(),
-                    "gpt-3.5-turbo".to_string(),
-                ],
-            },
-            chat: ChatConfig {
-                system_prompt: "你是一个有帮助的助手。".to_string(),
-                temperature: 0.7,
-                retry_enabled: true,
-                max_retries: 10,
-                dark_mode: true,
-            },
+            api: ApiConfig::default(),
+            chat: ChatConfig::default(),
+            snippets: Vec::new(),
+            follow_up_actions: default_follow_up_actions(),
+            post_processors: Vec::new(),
+            claude: default_claude_config(),
+            gemini: default_gemini_config(),
+            azure: AzureConfig::default(),
+            content_filter: ContentFilterConfig::default(),
+            pricing: costs::default_pricing(),
+            capabilities: BTreeMap::new(),
+            proxy: ProxyConfig::default(),
+            failover: FailoverConfig::default(),
+            network: NetworkConfig::default(),
+            profiles: Vec::new(),
+            features: FeatureFlags::default(),
+            cache: CacheConfig::default(),
+            titles: TitleConfig::default(),
+            backup: BackupConfig::default(),
+            lock: LockConfig::default(),
+        }
+    }
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: "https://api.openai.com/v1/chat/completions".to_string(),
+            model: "gpt-4-vision-preview".to_string(),
+            available_models: vec![
+                "gpt-4-vision-preview".to_string(),
+                "gpt-4".to_string(),
+                "gpt-3.5-turbo".to_string(),
+            ],
+            provider: ProviderKind::OpenAi,
+            active_profile: None,
+        }
+    }
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            system_prompt: "你是一个有帮助的助手。".to_string(),
+            temperature: 0.7,
+            top_p: 1.0,
+            frequency_penalty: 0.0,
+            presence_penalty: 0.0,
+            retry_enabled: true,
+            max_retries: 10,
+            stall_timeout_secs: default_stall_timeout_secs(),
+            webhook_url: String::new(),
+            incognito_minutes: default_incognito_minutes(),
+            smart_code_paste: true,
+            long_term_memory: true,
+            auto_export_dir: String::new(),
+            dark_mode: true,
+            keybinding_mode: KeybindingMode::Default,
+            send_key: SendKey::Enter,
+            input_height_ratio: default_input_height_ratio(),
+            auto_extract_tasks: false,
+            group_by_role: false,
+            ghost_text_enabled: false,
+            ghost_text_model: default_helper_model(),
+            smart_replies_enabled: false,
+            suggestion_model: default_helper_model(),
+            mask_secrets_in_exports: true,
+            dictation_bridge_enabled: false,
+            quick_ask_prompt: default_quick_ask_prompt(),
+            reasoning_effort: ReasoningEffort::default(),
+            match_reply_language: false,
+            context_strategy: ContextStrategy::default(),
+            context_keep_last: context::default_keep_last(),
+            auto_memory: false,
+            entity_actions: true,
+            tts_model: speech::default_model(),
+            tts_voice: speech::default_voice(),
+            image_model: imagegen::default_model(),
+            embedding_model: knowledge::default_model(),
+            knowledge_top_k: knowledge::default_top_k(),
+            metrics_enabled: false,
+        }
+    }
+}
+
+// 导出时用于替换敏感信息的占位符
+pub const MASKED_SECRET: &str = "********";
+
+// 可单独重置的配置分区
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigSection {
+    All,
+    Api,
+    Chat,
+}
+
+impl ConfigSection {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ConfigSection::All => "全部配置",
+            ConfigSection::Api => "API 配置",
+            ConfigSection::Chat => "聊天配置",
+        }
+    }
+}
+
+impl Config {
+    // 返回隐藏了密钥的副本，用于导出配置和诊断包；
+    // 除 API Key 外还隐藏应用锁的摘要、可能带有令牌的 Webhook 地址和代理地址中的密码
+    pub fn masked(&self) -> Config {
+        let mut config = self.clone();
+        if !config.api_key.is_empty() {
+            config.api_key = MASKED_SECRET.to_string();
+        }
+        if let Ok(mut url) = reqwest::Url::parse(config.proxy.url.trim()) {
+            if url.password().is_some() && url.set_password(Some(MASKED_SECRET)).is_ok() {
+                config.proxy.url = url.to_string();
+            }
+        }
+        for api_key in [
+            &mut config.claude.api_key,
+            &mut config.gemini.api_key,
+            &mut config.azure.api_key,
+            &mut config.proxy.password,
+            &mut config.lock.salt,
+            &mut config.lock.hash,
+            &mut config.chat.webhook_url,
+        ]
+        .into_iter()
+        .chain(
+            config
+                .profiles
+                .iter_mut()
+                .map(|profile| &mut profile.api_key),
+        ) {
+            if !api_key.is_empty() {
+                *api_key = MASKED_SECRET.to_string();
+            }
+        }
+        config
+    }
+
+    pub fn reset_section(&mut self, section: ConfigSection) {
+        match section {
+            ConfigSection::All => {
+                // 重置全部时保留 API Key 和配置档案，避免用户需要重新填写；
+                // 应用锁也保留，否则不输入密码就能通过重置配置解除锁定
+                let api_key = std::mem::take(&mut self.api_key);
+                let profiles = std::mem::take(&mut self.profiles);
+                let claude_api_key = std::mem::take(&mut self.claude.api_key);
+                let gemini_api_key = std::mem::take(&mut self.gemini.api_key);
+                let azure_api_key = std::mem::take(&mut self.azure.api_key);
+                let lock = std::mem::take(&mut self.lock);
+                *self = Config::default();
+                self.api_key = api_key;
+                self.claude.api_key = claude_api_key;
+                self.gemini.api_key = gemini_api_key;
+                self.azure.api_key = azure_api_key;
+                self.profiles = profiles;
+                self.lock = lock;
+            }
+            ConfigSection::Api => {
+                self.api = ApiConfig::default();
+                self.claude.endpoint = ProviderKind::Claude.default_endpoint().to_string();
+                self.gemini.endpoint = ProviderKind::Gemini.default_endpoint().to_string();
+                self.azure = AzureConfig {
+                    api_key: std::mem::take(&mut self.azure.api_key),
+                    ..AzureConfig::default()
+                };
+            }
+            ConfigSection::Chat => self.chat = ChatConfig::default(),
         }
     }
 }
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum ConfigError {
     IoError(std::io::Error),
     TomlError(toml::ser::Error),
@@ -95,14 +713,81 @@ impl StdError for ConfigError {
 }
 
 pub async fn load_config() -> Config {
-    match fs::read_to_string("dream.toml").await {
-        Ok(content) => toml::from_str(&content).unwrap_or_default(),
-        Err(_) => Config::default(),
+    match utils::load_with_backup(&paths::config_file(CONFIG_FILE), toml::from_str::<Config>).await
+    {
+        Some(Ok(config)) => config,
+        _ => Config::default(),
     }
 }
 
 pub async fn save_config(config: &Config) -> Result<(), ConfigError> {
     let toml_string = toml::to_string_pretty(config)?;
-    fs::write("dream.toml", toml_string).await?;
+    utils::write_atomic(&paths::config_file(CONFIG_FILE), toml_string).await?;
+    Ok(())
+}
+
+pub async fn export_config(config: &Config, path: &Path) -> Result<(), ConfigError> {
+    let toml_string = toml::to_string_pretty(&config.masked())?;
+    fs::write(path, toml_string).await?;
     Ok(())
 }
+
+// 导入配置文件；若导入的密钥是被隐藏的占位符，则保留当前密钥
+pub async fn import_config(path: &Path, current: &Config) -> Result<Config, ConfigError> {
+    let content = fs::read_to_string(path).await?;
+    let mut config: Config = toml::from_str(&content)?;
+    if config.api_key == MASKED_SECRET || config.api_key.is_empty() {
+        config.api_key = current.api_key.clone();
+    }
+    for (api_key, current_key) in [
+        (&mut config.claude.api_key, &current.claude.api_key),
+        (&mut config.gemini.api_key, &current.gemini.api_key),
+        (&mut config.azure.api_key, &current.azure.api_key),
+        (&mut config.proxy.password, &current.proxy.password),
+    ] {
+        if api_key == MASKED_SECRET || api_key.is_empty() {
+            *api_key = current_key.clone();
+        }
+    }
+    // Webhook 和代理地址只在被隐藏时保留当前值，导出时本来为空的仍然为空
+    for (value, current_value) in [
+        (&mut config.chat.webhook_url, &current.chat.webhook_url),
+        (&mut config.proxy.url, &current.proxy.url),
+    ] {
+        if value.contains(MASKED_SECRET) {
+            *value = current_value.clone();
+        }
+    }
+    // 应用锁的摘要被隐藏时保留当前的密码
+    if config.lock.salt == MASKED_SECRET || config.lock.hash == MASKED_SECRET {
+        config.lock = current.lock.clone();
+    }
+    // 配置档案按名称对应
+    for profile in &mut config.profiles {
+        if profile.api_key == MASKED_SECRET || profile.api_key.is_empty() {
+            if let Some(current_profile) = current.profiles.iter().find(|p| p.name == profile.name)
+            {
+                profile.api_key = current_profile.api_key.clone();
+            }
+        }
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reset_all_keeps_the_app_lock() {
+        let mut config = Config::default();
+        config.lock.enabled = true;
+        config.lock.salt = "salt".to_string();
+        config.lock.hash = "hash".to_string();
+        config.chat.tts_voice = "nova".to_string();
+        config.reset_section(ConfigSection::All);
+        assert!(config.lock.enabled);
+        assert_eq!(config.lock.hash, "hash");
+        assert_eq!(config.chat.tts_voice, ChatConfig::default().tts_voice);
+    }
+}
@@ -0,0 +1,114 @@
+use log::warn;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// 命中过滤词时的处理方式
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterMode {
+    #[default]
+    Off,
+    Warn,
+    Block,
+}
+
+impl FilterMode {
+    pub const ALL: [FilterMode; 3] = [FilterMode::Off, FilterMode::Warn, FilterMode::Block];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FilterMode::Off => "关闭",
+            FilterMode::Warn => "发送前提醒",
+            FilterMode::Block => "禁止发送",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct FilterError {
+    pub term: String,
+    pub source: regex::Error,
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "过滤规则 {} 无效: {}", self.term, self.source)
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+// 单条过滤规则：以 / 包围的按正则匹配，其余按不区分大小写的关键词匹配
+fn compile_term(term: &str) -> Result<Regex, FilterError> {
+    let pattern = match term
+        .strip_prefix('/')
+        .and_then(|rest| rest.strip_suffix('/'))
+    {
+        Some(pattern) if !pattern.is_empty() => pattern.to_string(),
+        _ => format!("(?i){}", regex::escape(term)),
+    };
+    Regex::new(&pattern).map_err(|source| FilterError {
+        term: term.to_string(),
+        source,
+    })
+}
+
+// 检查所有规则是否有效，返回第一个错误
+pub fn validate_terms<S: AsRef<str>>(terms: &[S]) -> Result<(), FilterError> {
+    for term in terms {
+        let term = term.as_ref().trim();
+        if !term.is_empty() {
+            compile_term(term)?;
+        }
+    }
+    Ok(())
+}
+
+// 编译后的过滤器
+pub struct ContentFilter {
+    pub mode: FilterMode,
+    rules: Vec<(String, Regex)>,
+}
+
+impl ContentFilter {
+    // 无效的规则会被跳过并记录日志，避免一条错误规则导致无法发送
+    pub fn new<S: AsRef<str>>(mode: FilterMode, terms: &[S]) -> Self {
+        let rules = if mode == FilterMode::Off {
+            Vec::new()
+        } else {
+            terms
+                .iter()
+                .map(|term| term.as_ref().trim())
+                .filter(|term| !term.is_empty())
+                .filter_map(|term| match compile_term(term) {
+                    Ok(regex) => Some((term.to_string(), regex)),
+                    Err(e) => {
+                        warn!("{}", e);
+                        None
+                    }
+                })
+                .collect()
+        };
+        Self { mode, rules }
+    }
+
+    // 返回文本命中的规则
+    pub fn matches(&self, text: &str) -> Vec<String> {
+        self.rules
+            .iter()
+            .filter(|(_, regex)| regex.is_match(text))
+            .map(|(term, _)| term.clone())
+            .collect()
+    }
+}
+
+// 解析设置中每行一条的规则文本
+pub fn parse_terms(input: &str) -> Vec<String> {
+    input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
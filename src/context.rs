@@ -0,0 +1,233 @@
+// 上下文裁剪：对话超出模型上下文窗口前，按设置的策略丢弃或摘要较早的消息
+use crate::api;
+use crate::models::Message;
+use crate::providers::ApiTarget;
+use crate::tokens;
+use log::{debug, error};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+// 为模型回复预留的 token 数，窗口较小时按比例预留
+const REPLY_RESERVE: usize = 4096;
+// 摘要本身占用的 token 上限
+const SUMMARY_RESERVE: usize = 1024;
+// 摘要请求中每条消息最多保留的字符数
+const SUMMARY_MESSAGE_CHARS: usize = 2000;
+const SUMMARY_PROMPT: &str = "请把下面这段对话压缩成一段简洁的摘要，保留关键事实、结论、用户的偏好和尚未解决的问题，不超过 500 字，直接输出摘要。";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextStrategy {
+    // 全部发送，由接口自行报错
+    #[default]
+    Off,
+    // 超出窗口时丢弃最早的消息
+    DropOldest,
+    // 只保留最近 N 条消息
+    KeepLast,
+    // 超出窗口时把较早的消息摘要后再丢弃
+    Summarize,
+}
+
+impl ContextStrategy {
+    pub const ALL: [ContextStrategy; 4] = [
+        ContextStrategy::Off,
+        ContextStrategy::DropOldest,
+        ContextStrategy::KeepLast,
+        ContextStrategy::Summarize,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ContextStrategy::Off => "不裁剪",
+            ContextStrategy::DropOldest => "丢弃最早的消息",
+            ContextStrategy::KeepLast => "只保留最近 N 条",
+            ContextStrategy::Summarize => "摘要后丢弃",
+        }
+    }
+}
+
+pub fn default_keep_last() -> usize {
+    20
+}
+
+// 按策略拆分历史消息（最后一条是本次要回复的消息，总会保留），
+// 返回（发送的消息，需要摘要的较早消息）；只有摘要策略会返回后者
+pub fn trim(
+    strategy: ContextStrategy,
+    keep_last: usize,
+    model: &str,
+    system_prompt: &str,
+    history: Vec<Message>,
+) -> (Vec<Message>, Vec<Message>) {
+    let split = match strategy {
+        ContextStrategy::Off => 0,
+        ContextStrategy::KeepLast => history.len().saturating_sub(keep_last.max(1)),
+        ContextStrategy::DropOldest | ContextStrategy::Summarize => {
+            let window = tokens::context_window(model);
+            let mut reserve = REPLY_RESERVE.min(window / 4);
+            if strategy == ContextStrategy::Summarize {
+                reserve += SUMMARY_RESERVE.min(window / 8);
+            }
+            let budget = window
+                .saturating_sub(reserve)
+                .saturating_sub(tokens::count(model, system_prompt));
+            let mut used = 0;
+            let mut split = history.len();
+            for (index, message) in history.iter().enumerate().rev() {
+                used += tokens::count(model, &message.text_with_attachments()) + 3;
+                if used > budget && split < history.len() {
+                    break;
+                }
+                split = index;
+            }
+            split
+        }
+    };
+    if split == 0 {
+        return (history, Vec::new());
+    }
+    debug!(
+        "上下文裁剪（{}）: 去掉较早的 {} 条消息",
+        strategy.label(),
+        split
+    );
+    let mut kept = history;
+    let dropped: Vec<Message> = kept.drain(..split).collect();
+    if strategy == ContextStrategy::Summarize {
+        (kept, dropped)
+    } else {
+        (kept, Vec::new())
+    }
+}
+
+// 记录在请求清单中，说明哪些消息被摘要代替
+pub fn summary_note(dropped: usize) -> String {
+    format!("（较早的 {} 条消息以摘要代替）", dropped)
+}
+
+// 消息列表的指纹（FNV-1a），用于判断摘要覆盖的消息是否被修改；结果需要跨版本保持稳定
+pub fn fingerprint(messages: &[Message]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for message in messages {
+        for byte in message
+            .role
+            .bytes()
+            .chain([0])
+            .chain(message.content.bytes())
+            .chain([0])
+        {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+#[derive(Clone)]
+struct CachedSummary {
+    covered: usize,
+    fingerprint: u64,
+    text: String,
+}
+
+// 每个对话已生成的摘要，后续请求只需要把新丢弃的消息并入已有摘要
+#[derive(Clone, Default)]
+pub struct SummaryCache {
+    entries: Arc<Mutex<HashMap<String, CachedSummary>>>,
+}
+
+impl SummaryCache {
+    pub fn forget(&self, chat_id: &str) {
+        self.entries.lock().unwrap().remove(chat_id);
+    }
+
+    // 返回附加了摘要的系统提示词；没有需要摘要的消息或摘要失败时原样返回
+    pub async fn summarized_prompt(
+        &self,
+        client: &Client,
+        target: &ApiTarget,
+        model: &str,
+        chat_id: &str,
+        system_prompt: &str,
+        dropped: &[Message],
+    ) -> String {
+        if dropped.is_empty() {
+            return system_prompt.to_string();
+        }
+        let cached = self.entries.lock().unwrap().get(chat_id).cloned();
+        let previous = cached.filter(|cached| {
+            cached.covered <= dropped.len()
+                && cached.fingerprint == fingerprint(&dropped[..cached.covered])
+        });
+        let summary = match &previous {
+            Some(previous) if previous.covered == dropped.len() => previous.text.clone(),
+            _ => {
+                let (earlier, new) = match &previous {
+                    Some(previous) => (Some(previous.text.as_str()), &dropped[previous.covered..]),
+                    None => (None, dropped),
+                };
+                match summarize(client, target, model, earlier, new).await {
+                    Ok(text) => {
+                        self.entries.lock().unwrap().insert(
+                            chat_id.to_string(),
+                            CachedSummary {
+                                covered: dropped.len(),
+                                fingerprint: fingerprint(dropped),
+                                text: text.clone(),
+                            },
+                        );
+                        text
+                    }
+                    Err(e) => {
+                        error!("生成上下文摘要失败，直接丢弃较早的消息: {}", e);
+                        match previous {
+                            Some(previous) => previous.text,
+                            None => return system_prompt.to_string(),
+                        }
+                    }
+                }
+            }
+        };
+        format!("{}\n\n以下是之前对话的摘要：\n{}", system_prompt, summary)
+    }
+}
+
+// 把已有的摘要和新的消息合并成一段新的摘要
+pub async fn summarize(
+    client: &Client,
+    target: &ApiTarget,
+    model: &str,
+    previous: Option<&str>,
+    messages: &[Message],
+) -> Result<String, String> {
+    let mut transcript = String::new();
+    if let Some(previous) = previous {
+        transcript.push_str(&format!("（更早的摘要）{}\n\n", previous));
+    }
+    for message in messages {
+        let text: String = message
+            .content
+            .chars()
+            .take(SUMMARY_MESSAGE_CHARS)
+            .collect();
+        transcript.push_str(&format!("{}: {}\n\n", message.role, text));
+    }
+    let payload = json!({
+        "model": model,
+        "messages": [
+            { "role": "system", "content": SUMMARY_PROMPT },
+            { "role": "user", "content": transcript }
+        ]
+    });
+    let summary = api::complete(client, target, &payload)
+        .await
+        .map_err(|e| e.to_string())?;
+    if summary.is_empty() {
+        return Err("摘要为空".to_string());
+    }
+    Ok(summary)
+}
@@ -0,0 +1,101 @@
+use crate::models::{Message, TokenUsage};
+use crate::paths;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const LEDGER_FILE: &str = "cost_ledger.json";
+
+// 模型单价，单位为美元 / 百万 token
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+}
+
+impl ModelPrice {
+    pub fn cost(&self, usage: TokenUsage) -> f64 {
+        (usage.prompt_tokens as f64 * self.input + usage.completion_tokens as f64 * self.output)
+            / 1_000_000.0
+    }
+}
+
+pub fn default_pricing() -> BTreeMap<String, ModelPrice> {
+    [
+        ("gpt-3.5-turbo", 0.5, 1.5),
+        ("gpt-4o", 2.5, 10.0),
+        ("gpt-4o-mini", 0.15, 0.6),
+        ("o1", 15.0, 60.0),
+        ("o1-mini", 1.1, 4.4),
+        ("o3-mini", 1.1, 4.4),
+        ("claude-3-5-sonnet", 3.0, 15.0),
+        ("claude-3-5-haiku", 0.8, 4.0),
+        ("gemini-1.5-pro", 1.25, 5.0),
+        ("gemini-1.5-flash", 0.075, 0.3),
+    ]
+    .into_iter()
+    .map(|(model, input, output)| (model.to_string(), ModelPrice { input, output }))
+    .collect()
+}
+
+// 先精确匹配模型名，再取最长的前缀匹配（如 gpt-4o-2024-08-06 使用 gpt-4o 的价格）
+pub fn price_for<'a>(
+    pricing: &'a BTreeMap<String, ModelPrice>,
+    model: &str,
+) -> Option<&'a ModelPrice> {
+    pricing.get(model).or_else(|| {
+        pricing
+            .iter()
+            .filter(|(name, _)| model.starts_with(name.as_str()))
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, price)| price)
+    })
+}
+
+// 对话中所有回复的费用之和
+pub fn messages_cost(messages: &[Message]) -> f64 {
+    messages.iter().filter_map(|msg| msg.cost).sum()
+}
+
+pub fn format_cost(cost: f64) -> String {
+    if cost < 0.01 {
+        format!("${:.4}", cost)
+    } else {
+        format!("${:.2}", cost)
+    }
+}
+
+// 按天累计的费用
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct CostLedger {
+    pub days: BTreeMap<String, f64>,
+}
+
+impl CostLedger {
+    pub fn add(&mut self, cost: f64) {
+        let today = Local::now().format("%Y-%m-%d").to_string();
+        *self.days.entry(today).or_insert(0.0) += cost;
+    }
+
+    pub fn today(&self) -> f64 {
+        let today = Local::now().format("%Y-%m-%d").to_string();
+        self.days.get(&today).copied().unwrap_or(0.0)
+    }
+
+    pub fn total(&self) -> f64 {
+        self.days.values().sum()
+    }
+}
+
+pub async fn load_ledger() -> CostLedger {
+    match tokio::fs::read_to_string(paths::data_file(LEDGER_FILE)).await {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+        Err(_) => CostLedger::default(),
+    }
+}
+
+pub async fn save_ledger(ledger: &CostLedger) -> Result<(), Box<dyn std::error::Error>> {
+    let json = serde_json::to_string_pretty(ledger)?;
+    tokio::fs::write(paths::data_file(LEDGER_FILE), json).await?;
+    Ok(())
+}
@@ -0,0 +1,148 @@
+// 通过 dream:// 链接或二维码分享角色和提示词：模板包压缩后编码在链接里，不经过任何服务器
+use crate::templates::{self, TemplateBundle, TemplateError};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64, Engine};
+use eframe::egui;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use qrcode::{Color, EcLevel, QrCode};
+use std::io::{self, Read, Write};
+
+pub const SCHEME: &str = "dream";
+const IMPORT_PREFIX: &str = "dream://import?bundle=";
+// 解压后的模板包最大字节数，防止恶意链接占用大量内存
+const MAX_BUNDLE_BYTES: u64 = 1024 * 1024;
+// 二维码每个模块的像素数和四周留白的模块数
+const QR_MODULE_PIXELS: usize = 4;
+const QR_QUIET_ZONE: usize = 4;
+
+pub fn is_link(text: &str) -> bool {
+    text.trim()
+        .to_ascii_lowercase()
+        .starts_with(&format!("{}://", SCHEME))
+}
+
+pub fn encode(bundle: &TemplateBundle) -> io::Result<String> {
+    let json = serde_json::to_vec(bundle)?;
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(&json)?;
+    Ok(format!(
+        "{}{}",
+        IMPORT_PREFIX,
+        BASE64.encode(encoder.finish()?)
+    ))
+}
+
+pub fn decode(link: &str) -> Result<TemplateBundle, TemplateError> {
+    let invalid = |message: &str| TemplateError::Invalid(message.to_string());
+    let data = link
+        .trim()
+        .strip_prefix(IMPORT_PREFIX)
+        .ok_or_else(|| invalid("不是 dream://import 链接"))?;
+    // 链接可能被附加其他参数
+    let data = data.split('&').next().unwrap_or_default();
+    let compressed = BASE64
+        .decode(data.trim_end_matches('='))
+        .map_err(|_| invalid("链接内容已损坏"))?;
+    let mut json = String::new();
+    DeflateDecoder::new(compressed.as_slice())
+        .take(MAX_BUNDLE_BYTES)
+        .read_to_string(&mut json)
+        .map_err(|_| invalid("链接内容已损坏"))?;
+    templates::parse_bundle(&json)
+}
+
+// 生成链接的二维码；内容超出二维码容量时返回错误
+pub fn qr_image(link: &str) -> Result<egui::ColorImage, String> {
+    let code = QrCode::with_error_correction_level(link.as_bytes(), EcLevel::L)
+        .map_err(|_| "内容太长，无法生成二维码，请改用链接分享".to_string())?;
+    let modules = code.width();
+    let colors = code.to_colors();
+    let size = (modules + QR_QUIET_ZONE * 2) * QR_MODULE_PIXELS;
+    let mut image = egui::ColorImage::new([size, size], egui::Color32::WHITE);
+    for y in 0..modules {
+        for x in 0..modules {
+            if colors[y * modules + x] != Color::Dark {
+                continue;
+            }
+            for dy in 0..QR_MODULE_PIXELS {
+                for dx in 0..QR_MODULE_PIXELS {
+                    let px = (x + QR_QUIET_ZONE) * QR_MODULE_PIXELS + dx;
+                    let py = (y + QR_QUIET_ZONE) * QR_MODULE_PIXELS + dy;
+                    image[(px, py)] = egui::Color32::BLACK;
+                }
+            }
+        }
+    }
+    Ok(image)
+}
+
+// 把 dream:// 链接注册给当前程序，点击链接时以 open-link <URL> 启动
+#[cfg(target_os = "linux")]
+pub fn register_handler() -> io::Result<String> {
+    let exe = std::env::current_exe()?;
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".local/share"))
+        })
+        .ok_or_else(|| io::Error::other("未找到 HOME 目录"))?;
+    let applications = data_home.join("applications");
+    std::fs::create_dir_all(&applications)?;
+    let desktop_name = "dream-url-handler.desktop";
+    let entry = format!(
+        "[Desktop Entry]\nType=Application\nName=Dream\nExec=\"{}\" open-link %u\nNoDisplay=true\nMimeType=x-scheme-handler/{};\n",
+        exe.to_string_lossy().replace('"', "\\\""),
+        SCHEME
+    );
+    std::fs::write(applications.join(desktop_name), entry)?;
+    let status = std::process::Command::new("xdg-mime")
+        .args([
+            "default",
+            desktop_name,
+            &format!("x-scheme-handler/{}", SCHEME),
+        ])
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::other("xdg-mime 设置默认程序失败"));
+    }
+    Ok(applications
+        .join(desktop_name)
+        .to_string_lossy()
+        .to_string())
+}
+
+#[cfg(windows)]
+pub fn register_handler() -> io::Result<String> {
+    let exe = std::env::current_exe()?;
+    let key = format!(r"HKCU\Software\Classes\{}", SCHEME);
+    let command = format!("\"{}\" open-link \"%1\"", exe.to_string_lossy());
+    let entries: [(String, Vec<&str>); 3] = [
+        (key.clone(), vec!["/ve", "/d", "URL:Dream"]),
+        (key.clone(), vec!["/v", "URL Protocol", "/d", ""]),
+        (
+            format!(r"{}\shell\open\command", key),
+            vec!["/ve", "/d", &command],
+        ),
+    ];
+    for (path, args) in entries {
+        let status = std::process::Command::new("reg")
+            .arg("add")
+            .arg(&path)
+            .args(args)
+            .arg("/f")
+            .status()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("写入注册表失败: {}", path)));
+        }
+    }
+    Ok(key)
+}
+
+// macOS 的 URL 类型只能在应用包的 Info.plist 中声明，由系统通过 Apple Event 传递
+#[cfg(not(any(target_os = "linux", windows)))]
+pub fn register_handler() -> io::Result<String> {
+    Err(io::Error::other(
+        "当前系统需要在应用包的 Info.plist 中声明 dream:// 链接类型",
+    ))
+}
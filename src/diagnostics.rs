@@ -0,0 +1,169 @@
+// 诊断包：把脱敏后的日志、配置、版本和系统信息以及最近一次失败的请求打包成 ZIP，附在问题反馈中
+//
+// 日志在写出时同时保留最近的若干行；所有文本都经过密钥检测，请求头和查询参数中的密钥已由请求检查器隐藏
+use crate::archive::{self, ArchiveEntry};
+use crate::config::Config;
+use crate::features::Feature;
+use crate::inspector::RequestInspector;
+use crate::secrets;
+use chrono::Local;
+use std::collections::VecDeque;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+
+const MAX_LOG_LINES: usize = 2000;
+
+static LOG_LINES: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+// 由日志格式化函数调用，保留最近的日志
+pub fn record_log(line: String) {
+    if let Ok(mut lines) = LOG_LINES.lock() {
+        if lines.len() >= MAX_LOG_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+}
+
+fn recent_logs() -> String {
+    LOG_LINES
+        .lock()
+        .map(|lines| {
+            lines
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .unwrap_or_default()
+}
+
+pub fn default_file_name() -> String {
+    format!(
+        "dream-diagnostics-{}.zip",
+        Local::now().format("%Y%m%d-%H%M%S")
+    )
+}
+
+// 操作系统的名称和版本，获取失败时只返回平台名
+fn os_description() -> String {
+    let platform = format!("{} {}", std::env::consts::OS, std::env::consts::ARCH);
+    #[cfg(target_os = "linux")]
+    let version = std::fs::read_to_string("/etc/os-release")
+        .ok()
+        .and_then(|content| {
+            content.lines().find_map(|line| {
+                line.strip_prefix("PRETTY_NAME=")
+                    .map(|name| name.trim_matches('"').to_string())
+            })
+        });
+    #[cfg(target_os = "macos")]
+    let version = std::process::Command::new("sw_vers")
+        .arg("-productVersion")
+        .output()
+        .ok()
+        .map(|output| format!("macOS {}", String::from_utf8_lossy(&output.stdout).trim()));
+    #[cfg(target_os = "windows")]
+    let version = std::process::Command::new("cmd")
+        .args(["/C", "ver"])
+        .output()
+        .ok()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    let version: Option<String> = None;
+    match version.filter(|version| !version.is_empty()) {
+        Some(version) => format!("{} ({})", version, platform),
+        None => platform,
+    }
+}
+
+fn info(config: &Config, safe_mode: bool) -> String {
+    let features: Vec<&str> = Feature::ALL
+        .iter()
+        .filter(|feature| config.features.is_enabled(**feature))
+        .map(|feature| feature.label())
+        .collect();
+    format!(
+        "版本: {}\n系统: {}\n生成时间: {}\n安全模式: {}\n服务商: {:?}\n模型: {}\n实验功能: {}\n",
+        env!("CARGO_PKG_VERSION"),
+        os_description(),
+        Local::now().format("%Y-%m-%d %H:%M:%S %:z"),
+        if safe_mode { "是" } else { "否" },
+        config.api.provider,
+        config.api.model,
+        if features.is_empty() {
+            "无".to_string()
+        } else {
+            features.join("、")
+        }
+    )
+}
+
+// 和导出配置一样隐藏密钥，再检查其他字段中疑似密钥的文本
+fn sanitized_config(config: &Config) -> String {
+    toml::to_string_pretty(&config.masked())
+        .map(|text| secrets::mask_secrets(&text))
+        .unwrap_or_else(|e| format!("无法序列化配置: {}", e))
+}
+
+fn request_report(request: &RequestInspector, failed: bool) -> String {
+    let headers = |headers: &[(String, String)]| {
+        headers
+            .iter()
+            .map(|(name, value)| format!("{}: {}\n", name, value))
+            .collect::<String>()
+    };
+    let report = format!(
+        "{}\n\n{} {}\n第 {} 次尝试\n\n## 请求头\n{}\n## 请求体\n{}\n\n## 状态\n{}\n\n## 响应头\n{}\n## 原始响应{}\n{}\n",
+        if failed {
+            "最近一次失败的请求"
+        } else {
+            "最近一次请求（没有失败的请求）"
+        },
+        request.method,
+        request.url,
+        request.attempts,
+        headers(&request.request_headers),
+        request.payload,
+        request.status.as_deref().unwrap_or("没有收到响应"),
+        headers(&request.response_headers),
+        if request.truncated { "（已截断）" } else { "" },
+        request.raw
+    );
+    secrets::mask_secrets(&report)
+}
+
+// 生成诊断包的文件；request 为最近一次失败的请求，没有时为最近一次请求
+pub fn bundle_entries(
+    config: &Config,
+    safe_mode: bool,
+    request: (&RequestInspector, bool),
+) -> Vec<ArchiveEntry> {
+    let mut entries = vec![
+        ArchiveEntry {
+            name: "info.txt".to_string(),
+            data: info(config, safe_mode).into_bytes(),
+        },
+        ArchiveEntry {
+            name: "config.toml".to_string(),
+            data: sanitized_config(config).into_bytes(),
+        },
+        ArchiveEntry {
+            name: "log.txt".to_string(),
+            data: secrets::mask_secrets(&recent_logs()).into_bytes(),
+        },
+    ];
+    let (request, failed) = request;
+    if !request.is_empty() {
+        entries.push(ArchiveEntry {
+            name: "request.txt".to_string(),
+            data: request_report(request, failed).into_bytes(),
+        });
+    }
+    entries
+}
+
+pub async fn write_bundle(path: &Path, entries: Vec<ArchiveEntry>) -> io::Result<()> {
+    archive::write_plain_zip(path, entries).await
+}
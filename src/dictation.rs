@@ -0,0 +1,88 @@
+use crate::paths;
+use crate::utils::Utf8Decoder;
+use eframe::egui;
+use log::{debug, error};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::mpsc;
+
+// 外部听写工具写入文本的本地地址
+#[cfg(unix)]
+pub fn bridge_address() -> String {
+    paths::socket_file("dream-dictation.sock")
+        .to_string_lossy()
+        .to_string()
+}
+
+#[cfg(windows)]
+pub fn bridge_address() -> String {
+    paths::pipe_name("dream-dictation")
+}
+
+// 将读到的字节按 UTF-8 解码后转发，多字节字符被拆开时等待后续数据
+async fn forward<R: AsyncRead + Unpin>(
+    mut reader: R,
+    sender: mpsc::UnboundedSender<String>,
+    ctx: egui::Context,
+) {
+    let mut buf = [0u8; 4096];
+    let mut decoder = Utf8Decoder::default();
+    loop {
+        let n = match reader.read(&mut buf).await {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) => {
+                error!("读取听写输入失败: {}", e);
+                break;
+            }
+        };
+        let text = decoder.push(&buf[..n]);
+        if text.is_empty() {
+            continue;
+        }
+        if sender.send(text).is_err() {
+            break;
+        }
+        ctx.request_repaint();
+    }
+    let text = decoder.finish();
+    if !text.is_empty() && sender.send(text).is_ok() {
+        ctx.request_repaint();
+    }
+    debug!("听写连接已断开");
+}
+
+// 监听本地 socket，每个连接写入的文本都会转发到输入框
+#[cfg(unix)]
+pub async fn serve(
+    sender: mpsc::UnboundedSender<String>,
+    ctx: egui::Context,
+) -> std::io::Result<()> {
+    let address = bridge_address();
+    let listener = paths::bind_socket(std::path::Path::new(&address))?;
+    debug!("听写桥接已启动: {}", address);
+    loop {
+        let (stream, _) = listener.accept().await?;
+        tokio::spawn(forward(stream, sender.clone(), ctx.clone()));
+    }
+}
+
+// 监听命名管道，每次连接后创建新的管道实例等待下一个客户端
+#[cfg(windows)]
+pub async fn serve(
+    sender: mpsc::UnboundedSender<String>,
+    ctx: egui::Context,
+) -> std::io::Result<()> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let address = bridge_address();
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(&address)?;
+    debug!("听写桥接已启动: {}", address);
+    loop {
+        server.connect().await?;
+        let client = server;
+        server = ServerOptions::new().create(&address)?;
+        tokio::spawn(forward(client, sender.clone(), ctx.clone()));
+    }
+}
@@ -0,0 +1,84 @@
+// 按词比较两段文本：英文和数字按单词，中文等其他字符逐字比较
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffOp {
+    Same(String),
+    Added(String),
+    Removed(String),
+}
+
+// 超过该词数时不再逐词比较，直接显示整段替换
+const MAX_TOKENS: usize = 1000;
+
+fn tokenize(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (index, ch) in text.char_indices() {
+        if ch.is_ascii_alphanumeric() || ch == '_' {
+            start.get_or_insert(index);
+            continue;
+        }
+        if let Some(begin) = start.take() {
+            tokens.push(&text[begin..index]);
+        }
+        tokens.push(&text[index..index + ch.len_utf8()]);
+    }
+    if let Some(begin) = start {
+        tokens.push(&text[begin..]);
+    }
+    tokens
+}
+
+fn push(ops: &mut Vec<DiffOp>, op: DiffOp) {
+    match (ops.last_mut(), op) {
+        (Some(DiffOp::Same(last)), DiffOp::Same(text))
+        | (Some(DiffOp::Added(last)), DiffOp::Added(text))
+        | (Some(DiffOp::Removed(last)), DiffOp::Removed(text)) => last.push_str(&text),
+        (_, op) => ops.push(op),
+    }
+}
+
+pub fn word_diff(old: &str, new: &str) -> Vec<DiffOp> {
+    let old_tokens = tokenize(old);
+    let new_tokens = tokenize(new);
+    let mut ops = Vec::new();
+    if old_tokens.len() > MAX_TOKENS || new_tokens.len() > MAX_TOKENS {
+        push(&mut ops, DiffOp::Removed(old.to_string()));
+        push(&mut ops, DiffOp::Added(new.to_string()));
+        return ops;
+    }
+
+    // 最长公共子序列：lcs[i][j] 为 old[i..] 和 new[j..] 的公共长度
+    let (n, m) = (old_tokens.len(), new_tokens.len());
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old_tokens[i] == new_tokens[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if old_tokens[i] == new_tokens[j] {
+            push(&mut ops, DiffOp::Same(old_tokens[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            push(&mut ops, DiffOp::Removed(old_tokens[i].to_string()));
+            i += 1;
+        } else {
+            push(&mut ops, DiffOp::Added(new_tokens[j].to_string()));
+            j += 1;
+        }
+    }
+    for token in &old_tokens[i..] {
+        push(&mut ops, DiffOp::Removed(token.to_string()));
+    }
+    for token in &new_tokens[j..] {
+        push(&mut ops, DiffOp::Added(token.to_string()));
+    }
+    ops
+}
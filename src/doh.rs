@@ -0,0 +1,134 @@
+use log::debug;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+// 解析结果至少缓存这么久，避免每个请求都查询一次
+const MIN_TTL: Duration = Duration::from_secs(60);
+
+// DNS-over-HTTPS 服务；内置服务直接使用 IP 地址，查询本身不依赖系统 DNS
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum DohProvider {
+    #[default]
+    Cloudflare,
+    Google,
+    Custom,
+}
+
+impl DohProvider {
+    pub const ALL: [DohProvider; 3] = [
+        DohProvider::Cloudflare,
+        DohProvider::Google,
+        DohProvider::Custom,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DohProvider::Cloudflare => "Cloudflare (1.1.1.1)",
+            DohProvider::Google => "Google (8.8.8.8)",
+            DohProvider::Custom => "自定义",
+        }
+    }
+
+    // 查询地址，需支持 application/dns-json 格式
+    pub fn url<'a>(&self, custom_url: &'a str) -> &'a str {
+        match self {
+            DohProvider::Cloudflare => "https://1.1.1.1/dns-query",
+            DohProvider::Google => "https://8.8.8.8/resolve",
+            DohProvider::Custom => custom_url.trim(),
+        }
+    }
+}
+
+struct CachedLookup {
+    addrs: Vec<IpAddr>,
+    expires_at: Instant,
+}
+
+// 通过 DoH 服务解析域名，由 resolver::AppResolver 接入 reqwest
+#[derive(Clone)]
+pub struct DohResolver {
+    client: Client,
+    url: String,
+    cache: Arc<Mutex<HashMap<String, CachedLookup>>>,
+}
+
+impl DohResolver {
+    pub fn new(url: &str) -> Result<Self, reqwest::Error> {
+        let client = Client::builder().timeout(Duration::from_secs(10)).build()?;
+        Ok(Self {
+            client,
+            url: url.to_string(),
+            cache: Arc::default(),
+        })
+    }
+
+    // 查询一种记录（A 或 AAAA），返回地址和 TTL
+    async fn query(&self, host: &str, record_type: &str) -> Result<Vec<(IpAddr, u64)>, BoxError> {
+        let response: JsonValue = self
+            .client
+            .get(&self.url)
+            .query(&[("name", host), ("type", record_type)])
+            .header(reqwest::header::ACCEPT, "application/dns-json")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        // CNAME 记录的 data 是域名，解析为 IP 失败时跳过
+        Ok(response["Answer"]
+            .as_array()
+            .map(|answers| {
+                answers
+                    .iter()
+                    .filter_map(|answer| {
+                        let ip = answer["data"].as_str()?.parse().ok()?;
+                        Some((ip, answer["TTL"].as_u64().unwrap_or(0)))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    pub async fn lookup(&self, host: &str) -> Result<Vec<IpAddr>, BoxError> {
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return Ok(vec![ip]);
+        }
+        if let Some(cached) = self.cache.lock().unwrap().get(host) {
+            if cached.expires_at > Instant::now() {
+                return Ok(cached.addrs.clone());
+            }
+        }
+
+        let (v4, v6) = tokio::join!(self.query(host, "A"), self.query(host, "AAAA"));
+        let records: Vec<(IpAddr, u64)> = match (v4, v6) {
+            (Err(e), Err(_)) => return Err(e),
+            (v4, v6) => v4
+                .unwrap_or_default()
+                .into_iter()
+                .chain(v6.unwrap_or_default())
+                .collect(),
+        };
+        if records.is_empty() {
+            return Err(format!("DoH 未返回 {} 的地址", host).into());
+        }
+        debug!("DoH 解析 {}: {:?}", host, records);
+
+        let ttl = records.iter().map(|(_, ttl)| *ttl).min().unwrap_or(0);
+        let addrs: Vec<IpAddr> = records.into_iter().map(|(ip, _)| ip).collect();
+        self.cache.lock().unwrap().insert(
+            host.to_string(),
+            CachedLookup {
+                addrs: addrs.clone(),
+                expires_at: Instant::now() + MIN_TTL.max(Duration::from_secs(ttl)),
+            },
+        );
+        Ok(addrs)
+    }
+}
@@ -0,0 +1,175 @@
+// 内置 emoji 索引：(短代码, emoji, 搜索关键词)
+pub const EMOJI_INDEX: &[(&str, &str, &str)] = &[
+    ("smile", "😄", "笑 开心 happy"),
+    ("grin", "😁", "笑 露齿 happy"),
+    ("joy", "😂", "笑哭 哈哈 laugh"),
+    ("rofl", "🤣", "笑 打滚 laugh"),
+    ("wink", "😉", "眨眼"),
+    ("blush", "😊", "害羞 微笑"),
+    ("heart_eyes", "😍", "喜欢 爱 love"),
+    ("kissing_heart", "😘", "亲亲 kiss"),
+    ("thinking", "🤔", "思考 想"),
+    ("neutral_face", "😐", "无语 面无表情"),
+    ("expressionless", "😑", "无语"),
+    ("roll_eyes", "🙄", "白眼"),
+    ("smirk", "😏", "得意"),
+    ("relieved", "😌", "放松 安心"),
+    ("sleepy", "😪", "困 睡"),
+    ("sleeping", "😴", "睡觉 zzz"),
+    ("sunglasses", "😎", "酷 墨镜 cool"),
+    ("nerd", "🤓", "书呆子 学霸"),
+    ("confused", "😕", "困惑"),
+    ("worried", "😟", "担心"),
+    ("cry", "😢", "哭 难过 sad"),
+    ("sob", "😭", "大哭 sad"),
+    ("scream", "😱", "惊恐 害怕"),
+    ("angry", "😠", "生气"),
+    ("rage", "😡", "愤怒"),
+    ("exploding_head", "🤯", "震惊 爆炸"),
+    ("sweat_smile", "😅", "尴尬 汗"),
+    ("upside_down", "🙃", "倒脸"),
+    ("zipper_mouth", "🤐", "闭嘴"),
+    ("shushing", "🤫", "嘘 安静"),
+    ("partying", "🥳", "庆祝 派对 party"),
+    ("pleading", "🥺", "可怜 拜托"),
+    ("thumbsup", "👍", "赞 好 ok yes"),
+    ("thumbsdown", "👎", "踩 不好 no"),
+    ("ok_hand", "👌", "好的 ok"),
+    ("clap", "👏", "鼓掌"),
+    ("pray", "🙏", "拜托 谢谢 祈祷"),
+    ("wave", "👋", "你好 再见 hi bye"),
+    ("raised_hands", "🙌", "欢呼"),
+    ("muscle", "💪", "加油 强壮"),
+    ("point_right", "👉", "指向 右"),
+    ("point_left", "👈", "指向 左"),
+    ("eyes", "👀", "看 眼睛"),
+    ("brain", "🧠", "大脑 思考"),
+    ("heart", "❤️", "爱心 love"),
+    ("broken_heart", "💔", "心碎"),
+    ("sparkles", "✨", "闪亮 星星"),
+    ("star", "⭐", "星星"),
+    ("fire", "🔥", "火 热门"),
+    ("100", "💯", "满分"),
+    ("boom", "💥", "爆炸"),
+    ("zap", "⚡", "闪电 快"),
+    ("rocket", "🚀", "火箭 发布 launch"),
+    ("tada", "🎉", "庆祝 完成"),
+    ("gift", "🎁", "礼物"),
+    ("trophy", "🏆", "奖杯 冠军"),
+    ("bulb", "💡", "灯泡 想法 idea"),
+    ("memo", "📝", "笔记 备忘 note"),
+    ("book", "📖", "书 阅读"),
+    ("books", "📚", "书籍 学习"),
+    ("pushpin", "📌", "图钉 置顶"),
+    ("link", "🔗", "链接"),
+    ("lock", "🔒", "锁 安全"),
+    ("key", "🔑", "钥匙 密钥"),
+    ("mag", "🔍", "搜索 放大镜"),
+    ("gear", "⚙️", "设置 齿轮"),
+    ("wrench", "🔧", "扳手 工具"),
+    ("hammer", "🔨", "锤子"),
+    ("bug", "🐛", "虫 缺陷"),
+    ("computer", "💻", "电脑 笔记本"),
+    ("keyboard", "⌨️", "键盘"),
+    ("phone", "📱", "手机"),
+    ("email", "📧", "邮件"),
+    ("calendar", "📅", "日历 日期"),
+    ("clock", "🕐", "时间 钟"),
+    ("hourglass", "⏳", "等待 沙漏"),
+    ("warning", "⚠️", "警告 注意"),
+    ("x", "❌", "错误 否"),
+    ("white_check_mark", "✅", "完成 正确 done"),
+    ("question", "❓", "问题 疑问"),
+    ("exclamation", "❗", "感叹 重要"),
+    ("chart", "📈", "图表 增长"),
+    ("moneybag", "💰", "钱 money"),
+    ("coffee", "☕", "咖啡"),
+    ("tea", "🍵", "茶"),
+    ("beer", "🍺", "啤酒"),
+    ("pizza", "🍕", "披萨"),
+    ("cake", "🍰", "蛋糕"),
+    ("apple", "🍎", "苹果"),
+    ("sun", "☀️", "太阳 晴"),
+    ("moon", "🌙", "月亮 晚安"),
+    ("cloud", "☁️", "云"),
+    ("umbrella", "☔", "雨伞 下雨"),
+    ("snowflake", "❄️", "雪花 冷"),
+    ("earth", "🌏", "地球 世界"),
+    ("cat", "🐱", "猫"),
+    ("dog", "🐶", "狗"),
+    ("panda", "🐼", "熊猫"),
+    ("rabbit", "🐰", "兔子"),
+    ("robot", "🤖", "机器人 ai"),
+];
+
+// 根据短代码精确查找 emoji
+pub fn lookup(shortcode: &str) -> Option<&'static str> {
+    EMOJI_INDEX
+        .iter()
+        .find(|(code, _, _)| *code == shortcode)
+        .map(|(_, emoji, _)| *emoji)
+}
+
+// 按短代码前缀或关键词搜索 emoji
+pub fn search(query: &str) -> Vec<(&'static str, &'static str)> {
+    let query = query.trim().to_lowercase();
+    let mut prefix_matches = Vec::new();
+    let mut other_matches = Vec::new();
+
+    for (code, emoji, keywords) in EMOJI_INDEX {
+        if query.is_empty() || code.starts_with(&query) {
+            prefix_matches.push((*code, *emoji));
+        } else if code.contains(&query) || keywords.contains(&query) {
+            other_matches.push((*code, *emoji));
+        }
+    }
+
+    prefix_matches.extend(other_matches);
+    prefix_matches
+}
+
+// 查找输入末尾尚未完成的短代码（如 "你好 :smi"），返回冒号后的部分
+pub fn pending_shortcode(text: &str) -> Option<&str> {
+    let start = text.rfind(':')?;
+    let code = &text[start + 1..];
+    let preceded_by_word = text[..start]
+        .chars()
+        .last()
+        .is_some_and(|c| c.is_ascii_alphanumeric());
+
+    if code.len() >= 2
+        && !preceded_by_word
+        && code.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        Some(code)
+    } else {
+        None
+    }
+}
+
+// 将输入末尾未完成的短代码替换为 emoji
+pub fn complete_pending(text: &mut String, emoji: &str) {
+    if let Some(code) = pending_shortcode(text) {
+        let new_len = text.len() - code.len() - 1;
+        text.truncate(new_len);
+        text.push_str(emoji);
+    }
+}
+
+// 将输入末尾刚输入完整的 ":shortcode:" 展开为 emoji
+pub fn expand_trailing_shortcode(text: &mut String) -> bool {
+    let Some(body) = text.strip_suffix(':') else {
+        return false;
+    };
+    let Some(code) = pending_shortcode(body) else {
+        return false;
+    };
+    let Some(emoji) = lookup(code) else {
+        return false;
+    };
+
+    let new_len = body.len() - code.len() - 1;
+    text.truncate(new_len);
+    text.push_str(emoji);
+    true
+}
@@ -0,0 +1,186 @@
+// 回复中的实体标注：渲染后从文本中识别链接、文件路径、命令和代码标识符，提供复制、打开、运行和搜索等快捷操作
+use crate::utils;
+use regex::Regex;
+use std::path::Path;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+// 每条回复最多标注的实体数
+const MAX_ENTITIES: usize = 24;
+// 命令输出最多保留的字符数
+const MAX_OUTPUT_CHARS: usize = 20_000;
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(60);
+// 视为命令的代码块语言
+const SHELL_LANGUAGES: [&str; 8] = [
+    "sh",
+    "bash",
+    "zsh",
+    "shell",
+    "console",
+    "terminal",
+    "powershell",
+    "cmd",
+];
+
+static URL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"https?://[^\s<>"'`\]\)]+"#).unwrap());
+static INLINE_CODE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"`([^`\n]+)`").unwrap());
+static PATH: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:~|\.{1,2})?/[\w.\-/]+$|^[A-Za-z]:\\[\w.\-\\]+$|^[\w.\-]+(?:/[\w.\-]+)+\.\w+$|^[\w\-]+\.(?:rs|toml|json|ya?ml|md|txt|py|js|ts|tsx|go|c|h|cpp|java|sh|conf|ini|lock|log)$").unwrap()
+});
+static IDENTIFIER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[A-Za-z_]\w*(?:(?:::|\.)[A-Za-z_]\w*)*(?:\(\))?$").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntityKind {
+    Url,
+    Path,
+    Command,
+    Identifier,
+}
+
+impl EntityKind {
+    pub fn icon(&self) -> &'static str {
+        match self {
+            EntityKind::Url => "\u{f0c1}",
+            EntityKind::Path => "\u{f15b}",
+            EntityKind::Command => "\u{f120}",
+            EntityKind::Identifier => "\u{f121}",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            EntityKind::Url => "链接",
+            EntityKind::Path => "文件路径",
+            EntityKind::Command => "命令",
+            EntityKind::Identifier => "代码标识符",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entity {
+    pub kind: EntityKind,
+    pub text: String,
+}
+
+// 按出现顺序识别实体，重复的只保留一次
+pub fn find_entities(text: &str) -> Vec<Entity> {
+    let mut entities: Vec<Entity> = Vec::new();
+    let mut push = |kind: EntityKind, value: &str| {
+        let value = value.trim();
+        if value.is_empty() || entities.iter().any(|entity| entity.text == value) {
+            return;
+        }
+        entities.push(Entity {
+            kind,
+            text: value.to_string(),
+        });
+    };
+
+    let mut prose = String::new();
+    let mut fence: Option<bool> = None;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if let Some(language) = trimmed.strip_prefix("```") {
+            fence = match fence {
+                Some(_) => None,
+                None => Some(SHELL_LANGUAGES.contains(&language.trim().to_lowercase().as_str())),
+            };
+            continue;
+        }
+        match fence {
+            Some(true) => {
+                if let Some(command) = command_line(trimmed) {
+                    push(EntityKind::Command, command);
+                }
+            }
+            Some(false) => {}
+            None => {
+                prose.push_str(line);
+                prose.push('\n');
+            }
+        }
+    }
+
+    for url in URL.find_iter(&prose) {
+        push(
+            EntityKind::Url,
+            url.as_str()
+                .trim_end_matches(['.', ',', ';', ':', '!', '?', '。', '，']),
+        );
+    }
+    for code in INLINE_CODE.captures_iter(&prose) {
+        let code = code[1].trim();
+        if code.starts_with("http://") || code.starts_with("https://") {
+            continue;
+        }
+        if let Some(command) = code.strip_prefix("$ ") {
+            push(EntityKind::Command, command);
+        } else if PATH.is_match(code) {
+            push(EntityKind::Path, code);
+        } else if code.len() >= 3 && IDENTIFIER.is_match(code) {
+            push(EntityKind::Identifier, code);
+        }
+    }
+    entities.truncate(MAX_ENTITIES);
+    entities
+}
+
+// 代码块中的一行命令：去掉提示符，跳过注释和输出
+fn command_line(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if line.is_empty()
+        || line.starts_with('#')
+        || line.starts_with("//")
+        || line.starts_with("REM ")
+    {
+        return None;
+    }
+    Some(
+        line.strip_prefix("$ ")
+            .or_else(|| line.strip_prefix("> "))
+            .or_else(|| line.strip_prefix("PS> "))
+            .unwrap_or(line),
+    )
+}
+
+// 路径对应的 file:// 地址；文件不存在时返回 None
+pub fn file_url(path: &str) -> Option<String> {
+    let expanded = match path.strip_prefix("~/") {
+        Some(rest) => std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .map(|home| Path::new(&home).join(rest))?,
+        None => Path::new(path).to_path_buf(),
+    };
+    let absolute = expanded.canonicalize().ok()?;
+    Some(format!(
+        "file://{}",
+        absolute.to_string_lossy().replace('\\', "/")
+    ))
+}
+
+// 在系统 shell 中运行命令，返回合并后的标准输出和标准错误
+pub async fn run_command(command: &str) -> Result<String, String> {
+    let output = tokio::time::timeout(COMMAND_TIMEOUT, utils::shell_command(command).output())
+        .await
+        .map_err(|_| format!("命令运行超过 {} 秒，已终止", COMMAND_TIMEOUT.as_secs()))?
+        .map_err(|e| format!("无法启动命令: {}", e))?;
+    let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !stderr.is_empty() {
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(&stderr);
+    }
+    if text.chars().count() > MAX_OUTPUT_CHARS {
+        text = text.chars().take(MAX_OUTPUT_CHARS).collect();
+        text.push_str("\n……（输出过长，已截断）");
+    }
+    match output.status.code() {
+        Some(0) => Ok(text),
+        Some(code) => Err(format!("退出码 {}\n{}", code, text)),
+        None => Err(format!("命令被信号终止\n{}", text)),
+    }
+}
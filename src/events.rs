@@ -0,0 +1,29 @@
+use crate::inspector::InspectEvent;
+use crate::models::TokenUsage;
+
+// 后台请求通过消息通道发送给界面的事件
+#[derive(Debug, Clone)]
+pub enum UiEvent {
+    // 回复正文的新增片段
+    Delta(String),
+    // 推理模型思考过程的新增片段
+    Reasoning(String),
+    // 目前为止的 token 用量
+    Usage(TokenUsage),
+    // 重试等临时提示
+    Notice(String),
+    // 重试成功，清除之前的提示
+    ClearNotices,
+    Error(String),
+    // 用户消息中的图片已缓存到本地
+    ImageResolved(String),
+    // 供请求检查器显示的原始请求和响应
+    Inspect(InspectEvent),
+    // 流式响应中已经有多少秒没有收到数据
+    Stalled(u64),
+    // 停滞后重新收到数据
+    Resumed,
+    // 发送前检索到并加入请求的补充上下文
+    Retrieved(Vec<String>),
+    Done,
+}
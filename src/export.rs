@@ -0,0 +1,564 @@
+use crate::archive::{self, ArchiveEntry};
+use crate::models::{Chat, Message, MessageKind};
+use crate::secrets;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use log::warn;
+use pulldown_cmark::{html, CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::LazyLock;
+use syntect::highlighting::ThemeSet;
+use syntect::html::{css_for_theme_with_class_style, ClassStyle, ClassedHTMLGenerator};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+use tokio::fs;
+use uuid::Uuid;
+
+// 对话包的格式标识和版本
+const BUNDLE_FORMAT: &str = "dream-chat";
+const BUNDLE_VERSION: u32 = 1;
+
+static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+
+const HTML_STYLE: &str = "body{max-width:860px;margin:2em auto;padding:0 1em;font-family:-apple-system,'Segoe UI','PingFang SC','Microsoft YaHei',sans-serif;line-height:1.6;color:#24292f;background:#fff}
+header{border-bottom:1px solid #d0d7de;margin-bottom:1.5em}
+header p{color:#57606a;font-size:.9em}
+.message{margin:1.2em 0;padding:.8em 1.2em;border-radius:8px}
+.user{background:#f0f6ff}
+.assistant{background:#f6f8fa}
+.speaker{font-weight:bold;color:#57606a;font-size:.9em}
+hr.divider{border:none;border-top:1px dashed #d0d7de;margin:2em 0}
+pre{background:#fff;border:1px solid #d0d7de;border-radius:6px;padding:.8em;overflow-x:auto}
+code{font-family:ui-monospace,Consolas,'Courier New',monospace;font-size:.9em}
+table{border-collapse:collapse}
+th,td{border:1px solid #d0d7de;padding:.3em .6em}
+img{max-width:100%;border-radius:6px}
+";
+
+// 生成可用作文件名的安全名称，附带对话 ID 前缀避免重名
+pub fn export_file_name(chat: &Chat, extension: &str) -> String {
+    let name: String = chat
+        .name
+        .chars()
+        .filter(|c| !c.is_control() && !"\\/:*?\"<>|".contains(*c))
+        .collect();
+    let name = name.trim();
+    let name = if name.is_empty() { "对话" } else { name };
+    let short_id: String = chat.id.chars().take(8).collect();
+    format!("{}-{}.{}", name, short_id, extension)
+}
+
+// 将对话转换为 Markdown 文本，mask_secrets 为真时隐藏疑似密钥
+pub fn chat_to_markdown(chat: &Chat, mask_secrets: bool) -> String {
+    let mut output = format!("# {}\n\n", chat.name);
+    output.push_str(&format!(
+        "> 创建于 {}，更新于 {}\n\n",
+        chat.created_at.format("%Y-%m-%d %H:%M"),
+        chat.updated_at.format("%Y-%m-%d %H:%M")
+    ));
+
+    for msg in &chat.messages {
+        match msg.kind {
+            MessageKind::Chat => {}
+            MessageKind::Divider => {
+                output.push_str("---\n\n");
+                continue;
+            }
+            _ => continue,
+        }
+        let speaker = match msg.role.as_str() {
+            "user" => "You",
+            "assistant" => "AI",
+            other => other,
+        };
+        let text = msg.text_with_attachments();
+        let text = if mask_secrets {
+            secrets::mask_secrets(&text)
+        } else {
+            text
+        };
+        output.push_str(&format!("## {}\n\n{}\n\n", speaker, text));
+        if let Some(path) = &msg.image_path {
+            output.push_str(&format!("![image]({})\n\n", path));
+        }
+    }
+    output
+}
+
+// 将对话导出为 Markdown 文件，返回写入的路径
+pub async fn export_chat_markdown(
+    chat: &Chat,
+    dir: &Path,
+    mask_secrets: bool,
+) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir).await?;
+    let path = dir.join(export_file_name(chat, "md"));
+    fs::write(&path, chat_to_markdown(chat, mask_secrets)).await?;
+    Ok(path)
+}
+
+// 对话内容的指纹，用于判断自动导出后对话是否有变化
+pub fn fingerprint(chat: &Chat) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    chat.name.hash(&mut hasher);
+    for message in &chat.messages {
+        message.role.hash(&mut hasher);
+        message.content.hash(&mut hasher);
+        message.speaker.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+// 自动导出：重新写入对话的 Markdown 记录，并删除对话改名前留下的旧文件
+pub async fn mirror_chat_markdown(
+    chat: &Chat,
+    dir: &Path,
+    mask_secrets: bool,
+) -> io::Result<PathBuf> {
+    let path = export_chat_markdown(chat, dir, mask_secrets).await?;
+    remove_mirrored(dir, &chat.id, Some(&path)).await?;
+    Ok(path)
+}
+
+// 删除自动导出目录中属于该对话的文件，keep 为要保留的文件
+pub async fn remove_mirrored(dir: &Path, chat_id: &str, keep: Option<&Path>) -> io::Result<()> {
+    let short_id: String = chat_id.chars().take(8).collect();
+    let suffix = format!("-{}.md", short_id);
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().ends_with(&suffix) && Some(path.as_path()) != keep {
+            fs::remove_file(&path).await?;
+        }
+    }
+    Ok(())
+}
+
+// 将多个对话导出为 AES 加密的 ZIP 压缩包，每个对话一个 Markdown 文件
+pub async fn export_encrypted_archive(
+    chats: &[&Chat],
+    path: &Path,
+    password: &str,
+    mask_secrets: bool,
+) -> io::Result<()> {
+    let entries = chats
+        .iter()
+        .map(|chat| ArchiveEntry {
+            name: export_file_name(chat, "md"),
+            data: chat_to_markdown(chat, mask_secrets).into_bytes(),
+        })
+        .collect();
+    archive::write_encrypted_zip(path, entries, password.to_string()).await
+}
+
+// 将对话导出为单个 HTML 文件：Markdown 渲染为 HTML，代码块按语言着色，图片以 base64 内嵌，便于归档或通过邮件发送
+pub async fn chat_to_html(chat: &Chat, mask_secrets: bool) -> String {
+    let mut body = String::new();
+    for msg in &chat.messages {
+        match msg.kind {
+            MessageKind::Chat => {}
+            MessageKind::Divider => {
+                body.push_str("<hr class=\"divider\">\n");
+                continue;
+            }
+            _ => continue,
+        }
+        let speaker = match msg.role.as_str() {
+            "user" => "You",
+            "assistant" => "AI",
+            other => other,
+        };
+        let text = msg.text_with_attachments();
+        let text = if mask_secrets {
+            secrets::mask_secrets(&text)
+        } else {
+            text
+        };
+        body.push_str(&format!(
+            "<section class=\"message {}\">\n<div class=\"speaker\">{}</div>\n{}",
+            escape_html(&msg.role),
+            escape_html(speaker),
+            markdown_to_html(&text)
+        ));
+        if let Some(path) = &msg.image_path {
+            match embedded_image(path).await {
+                Some(src) => body.push_str(&format!("<p><img src=\"{}\"></p>\n", src)),
+                None => body.push_str(&format!("<p>[图片: {}]</p>\n", escape_html(path))),
+            }
+        }
+        body.push_str("</section>\n");
+    }
+    let code_style = ThemeSet::load_defaults()
+        .themes
+        .get("InspiredGitHub")
+        .and_then(|theme| css_for_theme_with_class_style(theme, ClassStyle::Spaced).ok())
+        .unwrap_or_default();
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"zh-CN\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>\n{style}{code_style}</style>\n</head>\n<body>\n<header>\n<h1>{title}</h1>\n<p>创建于 {created}，更新于 {updated}</p>\n</header>\n{body}</body>\n</html>\n",
+        title = escape_html(&chat.name),
+        style = HTML_STYLE,
+        code_style = code_style,
+        created = chat.created_at.format("%Y-%m-%d %H:%M"),
+        updated = chat.updated_at.format("%Y-%m-%d %H:%M"),
+        body = body,
+    )
+}
+
+// 将对话导出为 HTML 文件
+pub async fn export_chat_html(chat: &Chat, path: &Path, mask_secrets: bool) -> io::Result<()> {
+    fs::write(path, chat_to_html(chat, mask_secrets).await).await
+}
+
+// 单个对话的 JSON 包：消息、对话配置和引用的图片（以 base64 内嵌），可以在其他电脑上导入
+#[derive(Serialize, Deserialize)]
+pub struct ChatBundle {
+    pub format: String,
+    pub version: u32,
+    pub chat: Chat,
+    // 图片的原路径 -> base64 数据
+    #[serde(default)]
+    pub images: BTreeMap<String, String>,
+}
+
+// 对话及其保留的历史版本中的所有消息
+fn visit_messages(messages: &mut [Message], f: &mut impl FnMut(&mut Message)) {
+    for message in messages {
+        f(message);
+        visit_messages(&mut message.previous_versions, f);
+    }
+}
+
+// 只在本机有意义的设置：导出时不写入，导入时也不信任对话包中的值，
+// 否则别人的对话包可以把回复追加到任意文件、把对话发送到第三方或读取本地文件夹
+fn clear_local_settings(chat: &mut Chat) {
+    chat.role_id = None;
+    chat.tee = None;
+    chat.knowledge_folder = None;
+    chat.webhook_url = None;
+    chat.group_members.clear();
+}
+
+pub async fn export_chat_bundle(chat: &Chat, path: &Path, mask_secrets: bool) -> io::Result<()> {
+    let mut chat = chat.clone();
+    clear_local_settings(&mut chat);
+    let mut image_paths = Vec::new();
+    visit_messages(&mut chat.messages, &mut |message| {
+        if mask_secrets {
+            message.content = secrets::mask_secrets(&message.content);
+        }
+        if let Some(path) = &message.image_path {
+            image_paths.push(path.clone());
+        }
+    });
+    let mut images = BTreeMap::new();
+    for image_path in image_paths {
+        if images.contains_key(&image_path) {
+            continue;
+        }
+        match fs::read(&image_path).await {
+            Ok(data) => {
+                images.insert(image_path, BASE64.encode(data));
+            }
+            Err(e) => warn!("对话包中缺少图片 {}: {}", image_path, e),
+        }
+    }
+    let bundle = ChatBundle {
+        format: BUNDLE_FORMAT.to_string(),
+        version: BUNDLE_VERSION,
+        chat,
+        images,
+    };
+    let json = serde_json::to_string_pretty(&bundle).map_err(io::Error::other)?;
+    fs::write(path, json).await
+}
+
+// 导入对话包：图片写入图片缓存目录，对话使用新的 ID，避免和已有对话重复
+pub async fn import_chat_bundle(path: &Path, image_dir: &Path) -> io::Result<Chat> {
+    let text = fs::read_to_string(path).await?;
+    let bundle: ChatBundle =
+        serde_json::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if bundle.format != BUNDLE_FORMAT {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "不是 dream 导出的对话包",
+        ));
+    }
+    if bundle.version > BUNDLE_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("对话包版本 {} 过新，请先升级", bundle.version),
+        ));
+    }
+
+    let mut restored = BTreeMap::new();
+    if !bundle.images.is_empty() {
+        fs::create_dir_all(image_dir).await?;
+    }
+    for (original, data) in &bundle.images {
+        let Ok(data) = BASE64.decode(data) else {
+            warn!("对话包中的图片数据无效: {}", original);
+            continue;
+        };
+        let extension = Path::new(original).extension().map_or_else(
+            || "jpg".to_string(),
+            |ext| ext.to_string_lossy().to_string(),
+        );
+        let target = image_dir.join(format!("{}.{}", Uuid::new_v4(), extension));
+        fs::write(&target, data).await?;
+        restored.insert(original.clone(), target.to_string_lossy().to_string());
+    }
+
+    let mut chat = bundle.chat;
+    chat.id = Uuid::new_v4().to_string();
+    clear_local_settings(&mut chat);
+    // 文件夹和置顶是导出方的整理方式
+    chat.pinned = false;
+    chat.folder = None;
+    chat.incognito = false;
+    visit_messages(&mut chat.messages, &mut |message| {
+        if let Some(path) = message.image_path.take() {
+            message.image_path = restored.get(&path).cloned();
+        }
+    });
+    Ok(chat)
+}
+
+// 渲染 Markdown，围栏代码块替换为着色后的 HTML
+fn markdown_to_html(markdown: &str) -> String {
+    let parser = Parser::new_ext(
+        markdown,
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS,
+    );
+    let mut events = Vec::new();
+    let mut code: Option<(String, String)> = None;
+    for event in parser {
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => parse_info(&info).0,
+                    CodeBlockKind::Indented => "text".to_string(),
+                };
+                code = Some((language, String::new()));
+            }
+            Event::Text(text) if code.is_some() => {
+                if let Some((_, content)) = code.as_mut() {
+                    content.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some((language, content)) = code.take() {
+                    events.push(Event::Html(highlight_code(&language, &content).into()));
+                }
+            }
+            // 回复中的原始 HTML 按文本显示
+            Event::Html(raw) | Event::InlineHtml(raw) => events.push(Event::Text(raw)),
+            event => events.push(event),
+        }
+    }
+    let mut output = String::new();
+    html::push_html(&mut output, events.into_iter());
+    output
+}
+
+fn highlight_code(language: &str, code: &str) -> String {
+    let syntax = SYNTAX_SET
+        .find_syntax_by_token(language)
+        .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text());
+    let mut generator =
+        ClassedHTMLGenerator::new_with_class_style(syntax, &SYNTAX_SET, ClassStyle::Spaced);
+    for line in LinesWithEndings::from(code) {
+        if generator
+            .parse_html_for_line_which_includes_newline(line)
+            .is_err()
+        {
+            return format!("<pre><code>{}</code></pre>\n", escape_html(code));
+        }
+    }
+    format!(
+        "<pre class=\"code\" data-language=\"{}\"><code>{}</code></pre>\n",
+        escape_html(language),
+        generator.finalize()
+    )
+}
+
+// 读取本地图片并转换为 data URL，文件不存在时返回 None
+async fn embedded_image(path: &str) -> Option<String> {
+    let data = fs::read(path).await.ok()?;
+    let extension = Path::new(path)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let mime = match extension.as_str() {
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "bmp" => "image/bmp",
+        _ => "image/jpeg",
+    };
+    Some(format!("data:{};base64,{}", mime, BASE64.encode(data)))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// 从助手回复中提取的代码块
+pub struct CodeBlock {
+    pub language: String,
+    // 回复中提示的文件名，如 ```rust:src/main.rs 或代码块前一行的 **src/main.rs**
+    pub file_name: Option<String>,
+    pub content: String,
+}
+
+fn looks_like_file_name(token: &str) -> bool {
+    let token = token.trim();
+    !token.is_empty()
+        && !token.contains(char::is_whitespace)
+        && token.rsplit_once('.').is_some_and(|(stem, ext)| {
+            !stem.is_empty() && !ext.is_empty() && ext.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+}
+
+// 代码块前一行中的文件名，如 “**src/main.rs**”、“`app.py`:”、“文件 main.go：”
+fn file_name_from_line(line: &str) -> Option<String> {
+    line.split(|c: char| c.is_whitespace() || "*`:：()（）".contains(c))
+        .rev()
+        .find(|token| looks_like_file_name(token))
+        .map(str::to_string)
+}
+
+// 解析代码块的信息字符串，返回语言和文件名提示
+fn parse_info(info: &str) -> (String, Option<String>) {
+    let mut parts = info.split_whitespace();
+    let first = parts.next().unwrap_or_default();
+    let (language, mut file_name) = match first.split_once(':') {
+        Some((language, path)) if looks_like_file_name(path) => {
+            (language.to_string(), Some(path.to_string()))
+        }
+        _ => (first.to_string(), None),
+    };
+    for part in parts {
+        if let Some((key, value)) = part.split_once('=') {
+            let value = value.trim_matches(|c| c == '"' || c == '\'');
+            if matches!(key, "title" | "file" | "filename") && looks_like_file_name(value) {
+                file_name = Some(value.to_string());
+            }
+        }
+    }
+    let language = if language.is_empty() {
+        "text".to_string()
+    } else {
+        language.to_lowercase()
+    };
+    (language, file_name)
+}
+
+pub fn extract_code_blocks(chat: &Chat) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    for msg in chat.messages.iter().filter(|msg| msg.is_reply()) {
+        let mut previous_line = "";
+        let mut current: Option<(String, Option<String>, Vec<&str>)> = None;
+        for line in msg.content.lines() {
+            let trimmed = line.trim_start();
+            match current.take() {
+                Some((language, file_name, lines)) if trimmed.starts_with("```") => {
+                    blocks.push(CodeBlock {
+                        language,
+                        file_name,
+                        content: lines.join("\n") + "\n",
+                    });
+                }
+                Some((language, file_name, mut lines)) => {
+                    lines.push(line);
+                    current = Some((language, file_name, lines));
+                }
+                None => {
+                    if let Some(info) = trimmed.strip_prefix("```") {
+                        let (language, file_name) = parse_info(info);
+                        let file_name = file_name.or_else(|| file_name_from_line(previous_line));
+                        current = Some((language, file_name, Vec::new()));
+                    } else if !trimmed.is_empty() {
+                        previous_line = trimmed;
+                    }
+                }
+            }
+        }
+    }
+    blocks
+}
+
+fn extension_for(language: &str) -> &str {
+    match language {
+        "rust" | "rs" => "rs",
+        "python" | "py" => "py",
+        "javascript" | "js" => "js",
+        "typescript" | "ts" => "ts",
+        "go" | "golang" => "go",
+        "c++" | "cpp" => "cpp",
+        "csharp" | "c#" | "cs" => "cs",
+        "bash" | "sh" | "shell" | "zsh" => "sh",
+        "yaml" | "yml" => "yaml",
+        "markdown" | "md" => "md",
+        "kotlin" | "kt" => "kt",
+        "ruby" | "rb" => "rb",
+        "text" | "plaintext" | "txt" => "txt",
+        other if other.chars().all(|c| c.is_ascii_alphanumeric()) => other,
+        _ => "txt",
+    }
+}
+
+// 只保留普通路径分量，避免写到导出目录之外
+fn safe_relative_path(file_name: &str) -> Option<PathBuf> {
+    let path: PathBuf = Path::new(file_name)
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part),
+            _ => None,
+        })
+        .collect();
+    (path.components().count() > 0).then_some(path)
+}
+
+// 将对话中的代码块写入目录：有文件名提示的按原路径保存（同名文件以最后一次为准），
+// 其余按语言分目录编号保存；返回写入的文件数
+pub async fn export_code_blocks(chat: &Chat, dir: &Path) -> io::Result<usize> {
+    let mut written = 0;
+    let mut counters: std::collections::HashMap<String, usize> = Default::default();
+    for block in extract_code_blocks(chat) {
+        let path = match block.file_name.as_deref().and_then(safe_relative_path) {
+            Some(path) => dir.join(path),
+            None => {
+                let counter = counters.entry(block.language.clone()).or_insert(0);
+                *counter += 1;
+                let language_dir: String = block
+                    .language
+                    .chars()
+                    .filter(|c| c.is_ascii_alphanumeric() || *c == '+' || *c == '#')
+                    .collect();
+                dir.join(if language_dir.is_empty() {
+                    "text".to_string()
+                } else {
+                    language_dir
+                })
+                .join(format!(
+                    "snippet-{}.{}",
+                    counter,
+                    extension_for(&block.language)
+                ))
+            }
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(&path, block.content).await?;
+        written += 1;
+    }
+    Ok(written)
+}
@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+// 应用内更新日志，编译时嵌入
+pub const CHANGELOG: &str = include_str!("../assets/CHANGELOG.md");
+
+// 默认关闭的实验功能，在设置的“实验功能”页中开启
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Feature {
+    RealtimeVoice,
+    Agents,
+    Plugins,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 3] = [Feature::RealtimeVoice, Feature::Agents, Feature::Plugins];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Feature::RealtimeVoice => "实时语音对话",
+            Feature::Agents => "智能体",
+            Feature::Plugins => "插件",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Feature::RealtimeVoice => "通过麦克风与模型进行低延迟的语音对话",
+            Feature::Agents => "允许模型调用工具并自动执行多步任务",
+            Feature::Plugins => "加载第三方插件扩展客户端功能",
+        }
+    }
+}
+
+// 已开启的实验功能，保存在配置文件中
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct FeatureFlags {
+    #[serde(default)]
+    pub enabled: BTreeSet<Feature>,
+}
+
+impl FeatureFlags {
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        self.enabled.contains(&feature)
+    }
+
+    pub fn set(&mut self, feature: Feature, enabled: bool) {
+        if enabled {
+            self.enabled.insert(feature);
+        } else {
+            self.enabled.remove(&feature);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use eframe::egui::{FontData, FontDefinitions, FontFamily};
+use log::debug;
+use std::path::Path;
+use std::sync::Arc;
+
+// 各平台常见的中日韩字体（按优先级排列）
+#[cfg(target_os = "windows")]
+const CJK_FONT_CANDIDATES: &[&str] = &[
+    "C:\\Windows\\Fonts\\msyh.ttc",
+    "C:\\Windows\\Fonts\\msyh.ttf",
+    "C:\\Windows\\Fonts\\simhei.ttf",
+    "C:\\Windows\\Fonts\\simsun.ttc",
+];
+
+#[cfg(target_os = "macos")]
+const CJK_FONT_CANDIDATES: &[&str] = &[
+    "/System/Library/Fonts/PingFang.ttc",
+    "/System/Library/Fonts/STHeiti Medium.ttc",
+    "/Library/Fonts/Arial Unicode.ttf",
+];
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const CJK_FONT_CANDIDATES: &[&str] = &[
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/truetype/wqy/wqy-microhei.ttc",
+    "/usr/share/fonts/wenquanyi/wqy-microhei/wqy-microhei.ttc",
+];
+
+// 单色 emoji 字体（彩色位图字体无法被 egui 渲染）
+#[cfg(target_os = "windows")]
+const EMOJI_FONT_CANDIDATES: &[&str] = &[
+    "C:\\Windows\\Fonts\\seguiemj.ttf",
+    "C:\\Windows\\Fonts\\seguisym.ttf",
+];
+
+#[cfg(target_os = "macos")]
+const EMOJI_FONT_CANDIDATES: &[&str] = &["/System/Library/Fonts/Apple Symbols.ttf"];
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const EMOJI_FONT_CANDIDATES: &[&str] = &[
+    "/usr/share/fonts/truetype/noto/NotoEmoji-Regular.ttf",
+    "/usr/share/fonts/noto/NotoEmoji-Regular.ttf",
+    "/usr/share/fonts/truetype/ancient-scripts/Symbola_hint.ttf",
+    "/usr/share/fonts/TTF/Symbola.ttf",
+];
+
+// 加载第一个存在的候选字体，返回注册后的字体名称
+fn load_first_available(
+    fonts: &mut FontDefinitions,
+    name: &str,
+    candidates: &[&str],
+) -> Option<String> {
+    for candidate in candidates {
+        let path = Path::new(candidate);
+        if !path.exists() {
+            continue;
+        }
+        match std::fs::read(path) {
+            Ok(bytes) => {
+                debug!("加载系统字体: {}", candidate);
+                fonts
+                    .font_data
+                    .insert(name.to_owned(), Arc::new(FontData::from_owned(bytes)));
+                return Some(name.to_owned());
+            }
+            Err(e) => debug!("读取系统字体失败: {} - {}", candidate, e),
+        }
+    }
+    None
+}
+
+// 程序内置的图标、中文和等宽字体
+pub fn bundled_fonts() -> FontDefinitions {
+    let mut fonts = FontDefinitions::default();
+
+    fonts.families.clear(); // 清除所有默认字体族
+
+    // 添加 fa solid 字体
+    fonts.font_data.insert(
+        "fa-solid".to_owned(),
+        Arc::new(FontData::from_static(include_bytes!("../assets/fonts/fa6-900.otf"))),
+    );
+
+    // 添加 PingFang SC 字体（macOS 系统字体）
+    fonts.font_data.insert(
+        "PingFang-SC".to_owned(),
+        Arc::new(FontData::from_static(include_bytes!("../assets/fonts/pfsc.otf"))),
+    );
+
+    // 添加jetbrains mono字体
+    fonts.font_data.insert(
+        "jetbrains".to_owned(),
+        Arc::new(FontData::from_static(include_bytes!("../assets/fonts/jetbrains-regular.ttf"))),
+    );
+
+    for family in [FontFamily::Proportional, FontFamily::Monospace] {
+        fonts.families.insert(
+            family,
+            vec![
+                "jetbrains".to_owned(),
+                "fa-solid".to_owned(),
+                "PingFang-SC".to_owned(),
+            ],
+        );
+    }
+    fonts
+}
+
+// 在内置字体之后追加系统字体作为后备，避免出现方块字
+pub fn add_system_fallback_fonts(fonts: &mut FontDefinitions) {
+    let fallbacks: Vec<String> = [
+        load_first_available(fonts, "system-cjk", CJK_FONT_CANDIDATES),
+        load_first_available(fonts, "system-emoji", EMOJI_FONT_CANDIDATES),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    if fallbacks.is_empty() {
+        debug!("未找到可用的系统后备字体");
+        return;
+    }
+
+    for family in [FontFamily::Proportional, FontFamily::Monospace] {
+        let names = fonts.families.entry(family).or_default();
+        names.extend(fallbacks.iter().cloned());
+    }
+}
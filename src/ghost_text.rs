@@ -0,0 +1,157 @@
+use serde_json::{json, Value as JsonValue};
+use std::time::{Duration, Instant};
+
+// 停止输入多久后请求补全
+pub const PAUSE_DELAY: Duration = Duration::from_millis(600);
+// 两次补全请求之间的最短间隔
+pub const MIN_REQUEST_INTERVAL: Duration = Duration::from_millis(1500);
+// 草稿太短时不请求补全
+const MIN_DRAFT_CHARS: usize = 4;
+// 补全建议的最大字符数
+const MAX_SUGGESTION_CHARS: usize = 80;
+
+pub fn build_payload(model: &str, draft: &str) -> JsonValue {
+    json!({
+        "model": model,
+        "messages": [
+            {
+                "role": "system",
+                "content": "你是输入补全引擎。用户正在输入一段发给 AI 助手的提示词，请续写它。\
+                            只输出紧接在末尾之后的续写内容，不要重复已有文本，不要解释，不超过一句话。"
+            },
+            {
+                "role": "user",
+                "content": draft
+            }
+        ],
+        "temperature": 0.2,
+        "max_tokens": 32,
+        "stream": false
+    })
+}
+
+// 清理模型返回的续写内容，只保留第一行并补上必要的空格
+pub fn clean_completion(draft: &str, completion: &str) -> Option<String> {
+    let completion = completion.trim_matches(|c| c == '"' || c == '`');
+    // 模型有时会把草稿完整重复一遍
+    let completion = completion.strip_prefix(draft).unwrap_or(completion);
+    let line = completion.lines().next()?.trim_end();
+    let line = line.trim_start();
+    if line.is_empty() {
+        return None;
+    }
+
+    let mut suggestion: String = line.chars().take(MAX_SUGGESTION_CHARS).collect();
+    let needs_space = draft
+        .chars()
+        .last()
+        .is_some_and(|c| c.is_ascii_alphanumeric() || c == ',' || c == '.')
+        && suggestion
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphanumeric());
+    if needs_space {
+        suggestion.insert(0, ' ');
+    }
+    Some(suggestion)
+}
+
+// 当前显示的补全建议，suffix 接在 draft 之后
+struct Suggestion {
+    draft: String,
+    suffix: String,
+}
+
+// 输入框补全的防抖、限流和请求状态
+#[derive(Default)]
+pub struct GhostTextState {
+    suggestion: Option<Suggestion>,
+    last_edit: Option<Instant>,
+    last_request: Option<Instant>,
+    last_requested_draft: String,
+    in_flight: Option<u64>,
+}
+
+impl GhostTextState {
+    // 返回与当前输入匹配的剩余建议；用户照着建议继续输入时建议仍然有效
+    pub fn suffix_for(&self, text: &str) -> Option<&str> {
+        let suggestion = self.suggestion.as_ref()?;
+        let typed = text.strip_prefix(suggestion.draft.as_str())?;
+        let rest = suggestion.suffix.strip_prefix(typed)?;
+        (!rest.is_empty()).then_some(rest)
+    }
+
+    // 输入变化时调用，返回需要取消的请求
+    pub fn on_edit(&mut self, text: &str) -> Option<u64> {
+        self.last_edit = Some(Instant::now());
+        if self.suffix_for(text).is_none() {
+            self.suggestion = None;
+        }
+        self.in_flight.take()
+    }
+
+    // 丢弃建议并返回需要取消的请求
+    pub fn clear(&mut self) -> Option<u64> {
+        self.suggestion = None;
+        self.last_edit = None;
+        self.in_flight.take()
+    }
+
+    // 是否有等待请求的草稿（不考虑时间）
+    fn has_pending_draft(&self, text: &str) -> bool {
+        self.in_flight.is_none()
+            && self.suggestion.is_none()
+            && self.last_edit.is_some()
+            && text.trim().chars().count() >= MIN_DRAFT_CHARS
+            && text != self.last_requested_draft
+    }
+
+    pub fn should_request(&self, text: &str) -> bool {
+        let now = Instant::now();
+        self.has_pending_draft(text)
+            && self
+                .last_edit
+                .is_some_and(|edit| now.duration_since(edit) >= PAUSE_DELAY)
+            && self
+                .last_request
+                .is_none_or(|request| now.duration_since(request) >= MIN_REQUEST_INTERVAL)
+    }
+
+    pub fn start_request(&mut self, draft: &str, task_id: u64) {
+        self.last_request = Some(Instant::now());
+        self.last_requested_draft = draft.to_string();
+        self.in_flight = Some(task_id);
+    }
+
+    // 收到补全结果；草稿已经变化的结果直接丢弃
+    pub fn receive(&mut self, draft: String, suffix: Option<String>, current_text: &str) {
+        if self.last_requested_draft == draft {
+            self.in_flight = None;
+        }
+        if let Some(suffix) = suffix.filter(|_| draft == current_text) {
+            self.suggestion = Some(Suggestion { draft, suffix });
+        }
+    }
+
+    // Tab 接受建议，返回是否修改了输入
+    pub fn accept(&mut self, text: &mut String) -> bool {
+        let Some(rest) = self.suffix_for(text).map(str::to_string) else {
+            return false;
+        };
+        text.push_str(&rest);
+        self.suggestion = None;
+        true
+    }
+
+    // 下一次可能需要请求的时间，用于安排重绘
+    pub fn next_check(&self, text: &str) -> Option<Duration> {
+        if !self.has_pending_draft(text) {
+            return None;
+        }
+        let wait_pause = PAUSE_DELAY.saturating_sub(self.last_edit?.elapsed());
+        let wait_interval = self.last_request.map_or(Duration::ZERO, |request| {
+            MIN_REQUEST_INTERVAL.saturating_sub(request.elapsed())
+        });
+        Some(wait_pause.max(wait_interval))
+    }
+}
@@ -0,0 +1,56 @@
+use crate::models::{self, Message};
+use crate::reply_stream::ReplyStream;
+use std::collections::VecDeque;
+
+// 群聊中正在进行的一轮：用户发言后，未静音的角色依次回复
+pub struct GroupTurn {
+    pub chat_id: String,
+    // 还没有回复的角色 ID
+    pub pending: VecDeque<String>,
+    pub current: Option<GroupReply>,
+}
+
+// 正在回复的角色
+pub struct GroupReply {
+    pub name: String,
+    pub stream: ReplyStream,
+}
+
+impl GroupTurn {
+    pub fn new(chat_id: String, role_ids: Vec<String>) -> Self {
+        Self {
+            chat_id,
+            pending: role_ids.into(),
+            current: None,
+        }
+    }
+}
+
+// 去掉角色名称前的图标，作为群聊中的发言人名称
+pub fn speaker_name(role_name: &str) -> String {
+    role_name.trim_start_matches('\u{f544}').trim().to_string()
+}
+
+// 从某个角色的视角整理上下文：它自己的发言作为助手消息，其他角色的发言带上名字作为用户消息
+pub fn history_for(messages: &[Message], speaker: &str) -> Vec<Message> {
+    models::context_messages(messages)
+        .map(|msg| match msg.speaker.as_deref() {
+            Some(name) if name != speaker => {
+                let mut msg = msg.clone();
+                msg.role = "user".to_string();
+                msg.content = format!("{}：{}", name, msg.content);
+                msg
+            }
+            _ => msg.clone(),
+        })
+        .collect()
+}
+
+// 告诉角色它身处群聊以及其他参与者是谁
+pub fn directive(speaker: &str, members: &[String]) -> String {
+    format!(
+        "你正在参与一个群聊，参与者有：用户、{}。请以「{}」的身份发言，其他参与者的发言以「名字：」开头，不要替其他人发言。",
+        members.join("、"),
+        speaker
+    )
+}
@@ -0,0 +1,92 @@
+use log::debug;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// 连续失败达到该次数后认为服务商可能故障
+pub const OUTAGE_THRESHOLD: u32 = 3;
+// 故障状态的持续时间，过后重新尝试该端点
+const COOLDOWN: Duration = Duration::from_secs(60);
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Default)]
+struct EndpointState {
+    consecutive_failures: u32,
+    last_failure: Option<Instant>,
+}
+
+impl EndpointState {
+    // 超过冷却时间后不再计入失败次数
+    fn active_failures(&self) -> u32 {
+        match self.last_failure {
+            Some(at) if at.elapsed() < COOLDOWN => self.consecutive_failures,
+            _ => 0,
+        }
+    }
+}
+
+// 按端点统计连续的连接失败、5xx 和收到响应前的超时，在所有请求之间共享
+#[derive(Clone, Default)]
+pub struct EndpointHealth {
+    states: Arc<Mutex<HashMap<String, EndpointState>>>,
+}
+
+impl EndpointHealth {
+    pub fn record_failure(&self, endpoint: &str) -> u32 {
+        let mut states = self.states.lock().unwrap();
+        let state = states.entry(endpoint.to_string()).or_default();
+        state.consecutive_failures = state.active_failures() + 1;
+        state.last_failure = Some(Instant::now());
+        debug!(
+            "端点连续失败 {} 次: {}",
+            state.consecutive_failures, endpoint
+        );
+        state.consecutive_failures
+    }
+
+    pub fn record_success(&self, endpoint: &str) {
+        if self.states.lock().unwrap().remove(endpoint).is_some() {
+            debug!("端点已恢复: {}", endpoint);
+        }
+    }
+
+    pub fn failures(&self, endpoint: &str) -> u32 {
+        self.states
+            .lock()
+            .unwrap()
+            .get(endpoint)
+            .map_or(0, EndpointState::active_failures)
+    }
+
+    pub fn is_down(&self, endpoint: &str) -> bool {
+        self.failures(endpoint) >= OUTAGE_THRESHOLD
+    }
+
+    // 距离重新尝试该端点的剩余时间
+    pub fn recovery_in(&self, endpoint: &str) -> Option<Duration> {
+        let states = self.states.lock().unwrap();
+        let state = states.get(endpoint)?;
+        if state.active_failures() < OUTAGE_THRESHOLD {
+            return None;
+        }
+        state
+            .last_failure
+            .map(|at| COOLDOWN.saturating_sub(at.elapsed()))
+    }
+
+    // 发送请求前的等待时间，随连续失败次数指数增长
+    pub fn backoff(&self, endpoint: &str) -> Duration {
+        match self.failures(endpoint) {
+            0 => Duration::ZERO,
+            failures => BASE_BACKOFF
+                .saturating_mul(1 << (failures - 1).min(10))
+                .min(MAX_BACKOFF),
+        }
+    }
+
+    // 用户手动重试时清除失败记录
+    pub fn reset(&self, endpoint: &str) {
+        self.states.lock().unwrap().remove(endpoint);
+    }
+}
@@ -0,0 +1,47 @@
+// 消息图片的字节缓存：每个文件只读取一次，解码后的纹理由 egui 的图片加载器缓存
+use eframe::egui::{self, load::Bytes};
+use log::error;
+use std::collections::HashMap;
+
+#[derive(Default, Clone)]
+pub struct ImageCache {
+    // 读取失败的文件记为 None，避免每帧重复读取
+    entries: HashMap<String, Option<Bytes>>,
+    // 已删除但纹理尚未释放的图片
+    released: Vec<String>,
+}
+
+fn image_uri(path: &str) -> String {
+    format!("bytes://{}", path)
+}
+
+impl ImageCache {
+    pub fn image(&mut self, path: &str) -> Option<egui::Image<'static>> {
+        let bytes =
+            self.entries
+                .entry(path.to_string())
+                .or_insert_with(|| match std::fs::read(path) {
+                    Ok(data) => Some(Bytes::from(data)),
+                    Err(e) => {
+                        error!("读取图片失败: {} - {}", path, e);
+                        None
+                    }
+                });
+        bytes
+            .clone()
+            .map(|bytes| egui::Image::from_bytes(image_uri(path), bytes))
+    }
+
+    // 图片文件被删除后丢弃缓存，纹理在下一帧释放
+    pub fn forget(&mut self, path: &str) {
+        if self.entries.remove(path).is_some() {
+            self.released.push(image_uri(path));
+        }
+    }
+
+    pub fn release_textures(&mut self, ctx: &egui::Context) {
+        for uri in self.released.drain(..) {
+            ctx.forget_image(&uri);
+        }
+    }
+}
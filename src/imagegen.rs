@@ -0,0 +1,111 @@
+use crate::providers::{ApiTarget, ProviderKind};
+use crate::utils;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use log::debug;
+use reqwest::Client;
+use serde_json::{json, Value as JsonValue};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use uuid::Uuid;
+
+pub const COMMAND: &str = "/image";
+const IMAGE_SIZE: &str = "1024x1024";
+
+pub fn default_model() -> String {
+    "dall-e-3".to_string()
+}
+
+// 解析 “/image 描述” 命令，返回图片描述
+pub fn parse_command(input: &str) -> Option<&str> {
+    let rest = input.trim().strip_prefix(COMMAND)?;
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some(rest.trim())
+}
+
+// 由对话接口地址推出图片生成接口地址，目前支持 OpenAI 兼容接口和 Azure OpenAI
+fn image_request(
+    client: &Client,
+    target: &ApiTarget,
+    model: &str,
+) -> Result<reqwest::RequestBuilder, String> {
+    let url = target.url();
+    if !url.contains("chat/completions") {
+        return Err(format!("无法从地址推出图片生成接口: {}", url));
+    }
+    let url = url.replace("chat/completions", "images/generations");
+    match target.provider {
+        ProviderKind::OpenAi => Ok(client
+            .post(url)
+            .header("Authorization", format!("Bearer {}", target.api_key))),
+        ProviderKind::Azure => Ok(client
+            .post(url.replace("{deployment}", model))
+            .header("api-key", &target.api_key)),
+        provider => Err(format!("{} 不支持图片生成", provider.label())),
+    }
+}
+
+// 生成图片并保存到图片缓存目录；接口可能返回 base64 数据或临时下载地址
+pub async fn generate(
+    client: &Client,
+    target: &ApiTarget,
+    model: &str,
+    prompt: &str,
+    cache_dir: &Path,
+) -> Result<PathBuf, String> {
+    debug!("请求生成图片: {}", prompt);
+    let response = image_request(client, target, model)?
+        .json(&json!({
+            "model": model,
+            "prompt": prompt,
+            "n": 1,
+            "size": IMAGE_SIZE
+        }))
+        .send()
+        .await
+        .map_err(|e| format!("图片生成请求失败: {}", e))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("图片生成失败: HTTP {} {}", status, body));
+    }
+    let body: JsonValue = response
+        .json()
+        .await
+        .map_err(|e| format!("解析图片生成结果失败: {}", e))?;
+
+    let image = &body["data"][0];
+    let bytes = if let Some(data) = image["b64_json"].as_str() {
+        BASE64
+            .decode(data)
+            .map_err(|e| format!("解码图片失败: {}", e))?
+    } else if let Some(url) = image["url"].as_str() {
+        client
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("下载图片失败: {}", e))?
+            .bytes()
+            .await
+            .map_err(|e| format!("下载图片失败: {}", e))?
+            .to_vec()
+    } else {
+        return Err("图片生成结果中没有图片".to_string());
+    };
+
+    let extension = image::guess_format(&bytes)
+        .ok()
+        .and_then(|format| format.extensions_str().first().copied())
+        .unwrap_or("png");
+    let cache_dir = utils::ensure_cache_dir(cache_dir)
+        .await
+        .map_err(|e| format!("创建图片缓存目录失败: {}", e))?;
+    let path = cache_dir.join(format!("{}.{}", Uuid::new_v4(), extension));
+    fs::write(&path, bytes)
+        .await
+        .map_err(|e| format!("保存图片失败: {}", e))?;
+    debug!("图片已保存: {:?}", path);
+    Ok(path)
+}
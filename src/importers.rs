@@ -0,0 +1,323 @@
+// 从其他客户端导入对话：识别 Chatbox、NextChat 和 LM Studio 导出的 JSON，把角色、模型和系统提示词映射到对话和对话配置
+use crate::models::{Chat, ChatConfig, Message, SamplingParams};
+use chrono::{DateTime, TimeZone, Utc};
+use serde_json::Value as JsonValue;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SourceFormat {
+    Chatbox,
+    NextChat,
+    LmStudio,
+}
+
+impl SourceFormat {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SourceFormat::Chatbox => "Chatbox",
+            SourceFormat::NextChat => "NextChat",
+            SourceFormat::LmStudio => "LM Studio",
+        }
+    }
+}
+
+// 解析前的中间结果，确认导入时再转换为对话
+#[derive(Clone)]
+pub struct ImportedChat {
+    pub name: String,
+    pub model: Option<String>,
+    pub system_prompt: Option<String>,
+    pub temperature: Option<f32>,
+    // (角色, 内容)，只保留 user 和 assistant
+    pub messages: Vec<(String, String)>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+impl ImportedChat {
+    pub fn into_chat(self, defaults: SamplingParams) -> Chat {
+        let name = if self.name.trim().is_empty() {
+            "导入的对话".to_string()
+        } else {
+            self.name.trim().to_string()
+        };
+        let mut chat = Chat::new(name);
+        chat.has_been_renamed = true;
+        if let Some(created_at) = self.created_at {
+            chat.created_at = created_at;
+        }
+        if self.model.is_some() || self.system_prompt.is_some() || self.temperature.is_some() {
+            chat.config = Some(ChatConfig {
+                model_name: self.model.unwrap_or_default(),
+                system_prompt: self.system_prompt.unwrap_or_default(),
+                temperature: self.temperature.unwrap_or(defaults.temperature),
+                top_p: defaults.top_p,
+                frequency_penalty: defaults.frequency_penalty,
+                presence_penalty: defaults.presence_penalty,
+                greeting: None,
+                greeting_in_context: false,
+                provider: None,
+                filter_mode: None,
+                filter_terms: Vec::new(),
+                profile: None,
+            });
+        }
+        chat.messages = self
+            .messages
+            .into_iter()
+            .map(|(role, content)| match role.as_str() {
+                "user" => Message::new_user(content, None),
+                _ => Message::new_assistant(content),
+            })
+            .collect();
+        chat
+    }
+}
+
+pub fn parse(text: &str) -> Result<(SourceFormat, Vec<ImportedChat>), String> {
+    let json: JsonValue =
+        serde_json::from_str(text).map_err(|e| format!("不是有效的 JSON: {}", e))?;
+    let (format, chats) = if let Some(chats) = parse_nextchat(&json) {
+        (SourceFormat::NextChat, chats)
+    } else if let Some(chats) = parse_chatbox(&json) {
+        (SourceFormat::Chatbox, chats)
+    } else if let Some(chat) = parse_lmstudio(&json) {
+        (SourceFormat::LmStudio, vec![chat])
+    } else {
+        return Err("无法识别的导出格式，支持 Chatbox、NextChat 和 LM Studio".to_string());
+    };
+    let chats: Vec<_> = chats
+        .into_iter()
+        .filter(|chat| !chat.messages.is_empty())
+        .collect();
+    if chats.is_empty() {
+        return Err("文件中没有可导入的对话".to_string());
+    }
+    Ok((format, chats))
+}
+
+// NextChat 的备份：{"chat-next-web-store": {"sessions": [...]}}，也接受单个会话
+fn parse_nextchat(json: &JsonValue) -> Option<Vec<ImportedChat>> {
+    let sessions = match json.pointer("/chat-next-web-store/sessions") {
+        Some(sessions) => sessions.as_array()?.iter().collect(),
+        None if json.get("topic").is_some() && json.get("mask").is_some() => vec![json],
+        None => return None,
+    };
+    Some(
+        sessions
+            .into_iter()
+            .map(|session| {
+                let model_config = session.pointer("/mask/modelConfig");
+                let mut system_prompt = Vec::new();
+                for message in session
+                    .pointer("/mask/context")
+                    .and_then(JsonValue::as_array)
+                    .into_iter()
+                    .flatten()
+                {
+                    if message.get("role").and_then(JsonValue::as_str) == Some("system") {
+                        system_prompt.push(content_text(message.get("content")));
+                    }
+                }
+                ImportedChat {
+                    name: string_field(session, "topic").unwrap_or_default(),
+                    model: model_config.and_then(|config| string_field(config, "model")),
+                    system_prompt: join_prompt(system_prompt),
+                    temperature: model_config.and_then(|config| float_field(config, "temperature")),
+                    messages: role_messages(session.get("messages")),
+                    created_at: session.get("lastUpdate").and_then(timestamp),
+                }
+            })
+            .collect(),
+    )
+}
+
+// Chatbox 的导出：{"chat-sessions": [...]}，旧版本按 "session:<id>" 分开保存
+fn parse_chatbox(json: &JsonValue) -> Option<Vec<ImportedChat>> {
+    let object = json.as_object()?;
+    let sessions: Vec<&JsonValue> = match object.get("chat-sessions") {
+        Some(sessions) => sessions.as_array()?.iter().collect(),
+        None => object
+            .iter()
+            .filter(|(key, _)| key.starts_with("session:"))
+            .map(|(_, session)| session)
+            .collect(),
+    };
+    if sessions.is_empty() {
+        return None;
+    }
+    Some(
+        sessions
+            .into_iter()
+            .map(|session| {
+                let settings = session.get("settings");
+                let messages = session.get("messages");
+                let system_prompt = messages
+                    .and_then(JsonValue::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter(|message| {
+                        message.get("role").and_then(JsonValue::as_str) == Some("system")
+                    })
+                    .map(chatbox_text)
+                    .collect();
+                let model = settings
+                    .and_then(|settings| {
+                        ["modelId", "model", "chatgptModel", "ollamaModel"]
+                            .iter()
+                            .find_map(|key| string_field(settings, key))
+                    })
+                    .or_else(|| {
+                        messages
+                            .and_then(JsonValue::as_array)?
+                            .iter()
+                            .rev()
+                            .find_map(|message| string_field(message, "model"))
+                    });
+                ImportedChat {
+                    name: string_field(session, "name").unwrap_or_default(),
+                    model,
+                    system_prompt: join_prompt(system_prompt),
+                    temperature: settings.and_then(|settings| float_field(settings, "temperature")),
+                    messages: messages
+                        .and_then(JsonValue::as_array)
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|message| {
+                            let role = chat_role(message)?;
+                            Some((role, chatbox_text(message)))
+                        })
+                        .filter(|(_, content)| !content.trim().is_empty())
+                        .collect(),
+                    created_at: messages
+                        .and_then(JsonValue::as_array)
+                        .and_then(|messages| messages.first())
+                        .and_then(|message| message.get("timestamp"))
+                        .and_then(timestamp),
+                }
+            })
+            .collect(),
+    )
+}
+
+// Chatbox 新版本把正文放在 contentParts 中
+fn chatbox_text(message: &JsonValue) -> String {
+    match message.get("contentParts") {
+        Some(parts) => content_text(Some(parts)),
+        None => content_text(message.get("content")),
+    }
+}
+
+// LM Studio 的单个对话文件：每条消息有多个版本，取当前选中的版本
+fn parse_lmstudio(json: &JsonValue) -> Option<ImportedChat> {
+    let messages = json.get("messages")?.as_array()?;
+    let mut imported = Vec::new();
+    for message in messages {
+        let selected = match message.get("versions").and_then(JsonValue::as_array) {
+            Some(versions) => {
+                let index = message
+                    .get("currentlySelected")
+                    .and_then(JsonValue::as_u64)
+                    .unwrap_or(0) as usize;
+                match versions.get(index).or_else(|| versions.last()) {
+                    Some(version) => version,
+                    None => continue,
+                }
+            }
+            None => message,
+        };
+        let Some(role) = chat_role(selected) else {
+            continue;
+        };
+        let content = match selected.get("steps").and_then(JsonValue::as_array) {
+            Some(steps) => steps
+                .iter()
+                .filter(|step| step.get("type").and_then(JsonValue::as_str) == Some("contentBlock"))
+                .map(|step| content_text(step.get("content")))
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+            None => content_text(selected.get("content")),
+        };
+        if !content.trim().is_empty() {
+            imported.push((role, content));
+        }
+    }
+    let model = json
+        .pointer("/lastUsedModel/indexedModelIdentifier")
+        .or_else(|| json.pointer("/lastUsedModel/identifier"))
+        .and_then(JsonValue::as_str)
+        .map(str::to_string);
+    Some(ImportedChat {
+        name: string_field(json, "name").unwrap_or_default(),
+        model,
+        system_prompt: string_field(json, "systemPrompt").filter(|prompt| !prompt.is_empty()),
+        temperature: None,
+        messages: imported,
+        created_at: json.get("createdAt").and_then(timestamp),
+    })
+}
+
+fn role_messages(messages: Option<&JsonValue>) -> Vec<(String, String)> {
+    messages
+        .and_then(JsonValue::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|message| {
+            let role = chat_role(message)?;
+            Some((role, content_text(message.get("content"))))
+        })
+        .filter(|(_, content)| !content.trim().is_empty())
+        .collect()
+}
+
+fn chat_role(message: &JsonValue) -> Option<String> {
+    match message.get("role").and_then(JsonValue::as_str)? {
+        "user" => Some("user".to_string()),
+        "assistant" | "bot" => Some("assistant".to_string()),
+        _ => None,
+    }
+}
+
+// 正文可能是字符串，也可能是 [{"type": "text", "text": ...}] 形式的多段内容
+fn content_text(content: Option<&JsonValue>) -> String {
+    match content {
+        Some(JsonValue::String(text)) => text.clone(),
+        Some(JsonValue::Array(parts)) => parts
+            .iter()
+            .filter_map(|part| match part {
+                JsonValue::String(text) => Some(text.as_str()),
+                _ => part.get("text").and_then(JsonValue::as_str),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+fn join_prompt(parts: Vec<String>) -> Option<String> {
+    let prompt = parts.join("\n\n");
+    (!prompt.trim().is_empty()).then_some(prompt)
+}
+
+fn string_field(json: &JsonValue, key: &str) -> Option<String> {
+    json.get(key)
+        .and_then(JsonValue::as_str)
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+fn float_field(json: &JsonValue, key: &str) -> Option<f32> {
+    json.get(key)
+        .and_then(JsonValue::as_f64)
+        .map(|value| value as f32)
+}
+
+// 毫秒时间戳或 RFC 3339 字符串
+fn timestamp(value: &JsonValue) -> Option<DateTime<Utc>> {
+    match value {
+        JsonValue::Number(number) => Utc.timestamp_millis_opt(number.as_i64()?).single(),
+        JsonValue::String(text) => DateTime::parse_from_rfc3339(text)
+            .ok()
+            .map(|time| time.with_timezone(&Utc)),
+        _ => None,
+    }
+}
@@ -0,0 +1,111 @@
+use crate::paths;
+use eframe::egui;
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+// 其他程序发给正在运行的实例的请求，每个连接发送一个 JSON 编码的请求
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum InboxMessage {
+    // 选中的文本，作为附件放入新对话
+    Selection(String),
+    // 打开指定 ID 的对话
+    OpenChat(String),
+    // 点击 dream:// 链接传来的导入链接
+    OpenLink(String),
+    // 用主选区内容快速提问，由桌面环境中绑定的全局快捷键触发
+    QuickAsk,
+}
+
+// 其他程序（macOS 服务、命令行）向正在运行的实例发送文本的本地地址
+#[cfg(unix)]
+pub fn inbox_address() -> String {
+    paths::socket_file("dream-inbox.sock")
+        .to_string_lossy()
+        .to_string()
+}
+
+#[cfg(windows)]
+pub fn inbox_address() -> String {
+    paths::pipe_name("dream-inbox")
+}
+
+// 将请求发送给正在运行的实例，没有实例在监听时返回错误
+#[cfg(unix)]
+pub async fn send(message: &InboxMessage) -> std::io::Result<()> {
+    let json = serde_json::to_vec(message)?;
+    let mut stream = tokio::net::UnixStream::connect(inbox_address()).await?;
+    stream.write_all(&json).await?;
+    stream.shutdown().await
+}
+
+#[cfg(windows)]
+pub async fn send(message: &InboxMessage) -> std::io::Result<()> {
+    use tokio::net::windows::named_pipe::ClientOptions;
+
+    let json = serde_json::to_vec(message)?;
+    let mut client = ClientOptions::new().open(inbox_address())?;
+    client.write_all(&json).await?;
+    client.shutdown().await
+}
+
+// 每个连接发送一个完整的请求，读到连接关闭为止
+async fn receive<R: AsyncRead + Unpin>(
+    mut reader: R,
+    sender: mpsc::UnboundedSender<InboxMessage>,
+    ctx: egui::Context,
+) {
+    let mut bytes = Vec::new();
+    if let Err(e) = reader.read_to_end(&mut bytes).await {
+        error!("读取外部发送的请求失败: {}", e);
+        return;
+    }
+    let message = match serde_json::from_slice::<InboxMessage>(&bytes) {
+        Ok(InboxMessage::Selection(text)) if text.trim().is_empty() => return,
+        Ok(InboxMessage::Selection(text)) => InboxMessage::Selection(text.trim().to_string()),
+        Ok(message) => message,
+        Err(e) => {
+            error!("外部发送的请求无效: {}", e);
+            return;
+        }
+    };
+    debug!("收到外部发送的请求: {} 字节", bytes.len());
+    let _ = sender.send(message);
+    ctx.request_repaint();
+}
+
+#[cfg(unix)]
+pub async fn serve(
+    sender: mpsc::UnboundedSender<InboxMessage>,
+    ctx: egui::Context,
+) -> std::io::Result<()> {
+    let address = inbox_address();
+    let listener = paths::bind_socket(std::path::Path::new(&address))?;
+    debug!("外部文本通道已启动: {}", address);
+    loop {
+        let (stream, _) = listener.accept().await?;
+        tokio::spawn(receive(stream, sender.clone(), ctx.clone()));
+    }
+}
+
+#[cfg(windows)]
+pub async fn serve(
+    sender: mpsc::UnboundedSender<InboxMessage>,
+    ctx: egui::Context,
+) -> std::io::Result<()> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let address = inbox_address();
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(&address)?;
+    debug!("外部文本通道已启动: {}", address);
+    loop {
+        server.connect().await?;
+        let client = server;
+        server = ServerOptions::new().create(&address)?;
+        tokio::spawn(receive(client, sender.clone(), ctx.clone()));
+    }
+}
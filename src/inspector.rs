@@ -0,0 +1,134 @@
+// 请求检查器：记录最近一次对话请求实际发送的内容和收到的原始数据，用于排查网关问题
+use reqwest::header::HeaderMap;
+use reqwest::{Request, Response};
+use serde_json::Value as JsonValue;
+
+// 原始响应最多保留的字节数，超出部分丢弃
+const MAX_RAW_BYTES: usize = 512 * 1024;
+// 值需要隐藏的请求头和查询参数
+const SENSITIVE_HEADERS: [&str; 4] = ["authorization", "x-api-key", "api-key", "x-goog-api-key"];
+const SENSITIVE_PARAMS: [&str; 2] = ["key", "api_key"];
+const MASK: &str = "******";
+
+#[derive(Debug, Clone)]
+pub enum InspectEvent {
+    Request {
+        method: String,
+        url: String,
+        headers: Vec<(String, String)>,
+        body: String,
+    },
+    Response {
+        status: String,
+        headers: Vec<(String, String)>,
+    },
+    // 解码后的响应片段，保持原样
+    Raw(String),
+}
+
+impl InspectEvent {
+    pub fn request(request: &Request) -> Self {
+        let mut url = request.url().clone();
+        if url.query().is_some() {
+            let pairs: Vec<(String, String)> = url
+                .query_pairs()
+                .map(|(name, value)| {
+                    let value = if SENSITIVE_PARAMS.contains(&name.as_ref()) {
+                        MASK.to_string()
+                    } else {
+                        value.into_owned()
+                    };
+                    (name.into_owned(), value)
+                })
+                .collect();
+            url.query_pairs_mut().clear().extend_pairs(pairs);
+        }
+        let body = request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+            .unwrap_or_default();
+        InspectEvent::Request {
+            method: request.method().to_string(),
+            url: url.to_string(),
+            headers: header_pairs(request.headers()),
+            body,
+        }
+    }
+
+    pub fn response(response: &Response) -> Self {
+        InspectEvent::Response {
+            status: response.status().to_string(),
+            headers: header_pairs(response.headers()),
+        }
+    }
+}
+
+fn header_pairs(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if SENSITIVE_HEADERS.contains(&name.as_str()) {
+                MASK.to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+#[derive(Default, Clone)]
+pub struct RequestInspector {
+    pub method: String,
+    pub url: String,
+    pub request_headers: Vec<(String, String)>,
+    // 格式化后的请求体
+    pub payload: String,
+    pub status: Option<String>,
+    pub response_headers: Vec<(String, String)>,
+    pub raw: String,
+    pub truncated: bool,
+    // 包括重试在内的发送次数，只显示最后一次的数据
+    pub attempts: u32,
+}
+
+impl RequestInspector {
+    pub fn is_empty(&self) -> bool {
+        self.attempts == 0
+    }
+
+    pub fn record(&mut self, event: InspectEvent) {
+        match event {
+            InspectEvent::Request {
+                method,
+                url,
+                headers,
+                body,
+            } => {
+                *self = Self {
+                    method,
+                    url,
+                    request_headers: headers,
+                    payload: serde_json::from_str::<JsonValue>(&body)
+                        .ok()
+                        .and_then(|json| serde_json::to_string_pretty(&json).ok())
+                        .unwrap_or(body),
+                    attempts: self.attempts + 1,
+                    ..Self::default()
+                };
+            }
+            InspectEvent::Response { status, headers } => {
+                self.status = Some(status);
+                self.response_headers = headers;
+            }
+            InspectEvent::Raw(text) => {
+                if self.raw.len() + text.len() > MAX_RAW_BYTES {
+                    self.truncated = true;
+                } else {
+                    self.raw.push_str(&text);
+                }
+            }
+        }
+    }
+}
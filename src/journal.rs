@@ -0,0 +1,160 @@
+// 日记对话：每天第一次发送时自动开始带日期的分段，之前几天的内容以滚动摘要的形式保留在上下文中
+use crate::api;
+use crate::models::{Message, MessageKind};
+use crate::providers::ApiTarget;
+use chrono::{Datelike, Local, Months, NaiveDate};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeSet;
+
+pub const JOURNAL_NAME: &str = "\u{f073} 日记";
+const SUMMARY_PROMPT: &str = "你在帮用户维护日记的滚动摘要。请把已有的摘要和新的几天日记合并成一段新的摘要，按日期保留重要的事件、情绪、计划和待办，越早的内容越简略，不超过 800 字，直接输出摘要。";
+const WEEKDAYS: [&str; 7] = [
+    "星期一",
+    "星期二",
+    "星期三",
+    "星期四",
+    "星期五",
+    "星期六",
+    "星期日",
+];
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct JournalState {
+    // 之前几天的滚动摘要
+    #[serde(default)]
+    pub summary: String,
+    // 摘要已经包含到哪一天
+    #[serde(default)]
+    pub summarized_through: Option<NaiveDate>,
+}
+
+pub fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+pub fn section(date: NaiveDate) -> Message {
+    Message::new_event(MessageKind::DaySection, date.to_string())
+}
+
+pub fn section_date(message: &Message) -> Option<NaiveDate> {
+    (message.kind == MessageKind::DaySection)
+        .then(|| message.content.parse().ok())
+        .flatten()
+}
+
+// 最后一个分段的日期
+pub fn current_section(messages: &[Message]) -> Option<NaiveDate> {
+    messages.iter().rev().find_map(section_date)
+}
+
+pub fn dates(messages: &[Message]) -> BTreeSet<NaiveDate> {
+    messages.iter().filter_map(section_date).collect()
+}
+
+pub fn format_date(date: NaiveDate) -> String {
+    format!(
+        "{} {}",
+        date.format("%Y年%m月%d日"),
+        WEEKDAYS[date.weekday().num_days_from_monday() as usize]
+    )
+}
+
+// 还没有并入摘要、且早于 before 的各天日记
+pub fn pending_days(
+    messages: &[Message],
+    state: &JournalState,
+    before: NaiveDate,
+) -> Vec<(NaiveDate, Vec<Message>)> {
+    let mut days: Vec<(NaiveDate, Vec<Message>)> = Vec::new();
+    for message in messages {
+        if let Some(date) = section_date(message) {
+            days.push((date, Vec::new()));
+        } else if let Some((_, entries)) = days.last_mut() {
+            if message.kind.is_chat() {
+                entries.push(message.clone());
+            }
+        }
+    }
+    days.retain(|(date, entries)| {
+        *date < before
+            && state
+                .summarized_through
+                .is_none_or(|through| *date > through)
+            && !entries.is_empty()
+    });
+    days
+}
+
+pub fn prompt_with(system_prompt: &str, state: &JournalState) -> String {
+    if state.summary.is_empty() {
+        return format!("{}\n\n今天是 {}。", system_prompt, format_date(today()));
+    }
+    format!(
+        "{}\n\n今天是 {}。之前几天日记的摘要：\n{}",
+        system_prompt,
+        format_date(today()),
+        state.summary
+    )
+}
+
+pub async fn summarize(
+    client: &Client,
+    target: &ApiTarget,
+    model: &str,
+    previous: &str,
+    days: &[(NaiveDate, Vec<Message>)],
+) -> Result<String, String> {
+    let mut transcript = String::new();
+    if !previous.is_empty() {
+        transcript.push_str(&format!("（已有摘要）{}\n\n", previous));
+    }
+    for (date, entries) in days {
+        transcript.push_str(&format!("## {}\n", format_date(*date)));
+        for message in entries {
+            transcript.push_str(&format!("{}: {}\n", message.role, message.content));
+        }
+        transcript.push('\n');
+    }
+    let payload = json!({
+        "model": model,
+        "messages": [
+            { "role": "system", "content": SUMMARY_PROMPT },
+            { "role": "user", "content": transcript }
+        ]
+    });
+    let summary = api::complete(client, target, &payload)
+        .await
+        .map_err(|e| e.to_string())?;
+    if summary.is_empty() {
+        return Err("摘要为空".to_string());
+    }
+    Ok(summary)
+}
+
+pub fn month_start(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap_or(date)
+}
+
+pub fn shift_month(month: NaiveDate, forward: bool) -> NaiveDate {
+    let shifted = if forward {
+        month.checked_add_months(Months::new(1))
+    } else {
+        month.checked_sub_months(Months::new(1))
+    };
+    shifted.unwrap_or(month)
+}
+
+// 月历的排列：第一天之前空出的格数（周一开始）和这个月的天数
+pub fn month_layout(month: NaiveDate) -> (u32, u32) {
+    let offset = month.weekday().num_days_from_monday();
+    let days = shift_month(month, true)
+        .pred_opt()
+        .map_or(30, |last| last.day());
+    (offset, days)
+}
+
+pub fn day_in_month(month: NaiveDate, day: u32) -> Option<NaiveDate> {
+    month.with_day(day)
+}
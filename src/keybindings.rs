@@ -0,0 +1,320 @@
+use crate::config::{KeybindingMode, SendKey};
+use eframe::egui::{Event, ImeEvent, Key, Modifiers};
+
+// 模态编辑状态（Vim 普通模式及未完成的组合键）
+#[derive(Clone, Default)]
+pub struct ModalState {
+    pub vim_normal: bool,
+    pending: Option<char>,
+}
+
+impl ModalState {
+    pub fn mode_label(&self, mode: KeybindingMode) -> Option<&'static str> {
+        match mode {
+            KeybindingMode::Vim if self.vim_normal => Some("-- NORMAL --"),
+            KeybindingMode::Vim => Some("-- INSERT --"),
+            KeybindingMode::Emacs => Some("Emacs"),
+            KeybindingMode::Default => None,
+        }
+    }
+}
+
+// 输入法组合状态：组合期间的 Enter 用于确认候选词，不应发送消息
+#[derive(Clone, Default)]
+pub struct ImeState {
+    composing: bool,
+}
+
+impl ImeState {
+    // 根据本帧的输入法事件更新状态，返回输入法是否正在使用
+    pub fn update(&mut self, events: &[Event]) -> bool {
+        let mut active = false;
+        for event in events {
+            if let Event::Ime(ime) = event {
+                active = true;
+                match ime {
+                    ImeEvent::Preedit(text) => self.composing = !text.is_empty(),
+                    ImeEvent::Commit(_) | ImeEvent::Disabled => self.composing = false,
+                    ImeEvent::Enabled => {}
+                }
+            }
+        }
+        active || self.composing
+    }
+}
+
+fn is_send_combination(send_key: SendKey, modifiers: Modifiers) -> bool {
+    if modifiers.shift || modifiers.alt {
+        return false;
+    }
+    match send_key {
+        SendKey::Enter => !modifiers.ctrl && !modifiers.mac_cmd,
+        SendKey::CtrlEnter => modifiers.ctrl,
+        SendKey::CmdEnter => modifiers.mac_cmd,
+    }
+}
+
+// 移除本帧的发送快捷键事件，避免输入框插入换行；返回是否按下了发送键
+pub fn take_send_key(send_key: SendKey, events: &mut Vec<Event>) -> bool {
+    let before = events.len();
+    events.retain(|event| {
+        !matches!(
+            event,
+            Event::Key {
+                key: Key::Enter,
+                pressed: true,
+                modifiers,
+                ..
+            } if is_send_combination(send_key, *modifiers)
+        )
+    });
+    events.len() != before
+}
+
+fn line_start(chars: &[char], cursor: usize) -> usize {
+    chars[..cursor]
+        .iter()
+        .rposition(|c| *c == '\n')
+        .map_or(0, |index| index + 1)
+}
+
+fn line_end(chars: &[char], cursor: usize) -> usize {
+    chars[cursor..]
+        .iter()
+        .position(|c| *c == '\n')
+        .map_or(chars.len(), |index| cursor + index)
+}
+
+// 上下移动光标，尽量保持列位置
+fn vertical_move(chars: &[char], cursor: usize, down: bool) -> usize {
+    let start = line_start(chars, cursor);
+    let column = cursor - start;
+    if down {
+        let end = line_end(chars, cursor);
+        if end >= chars.len() {
+            return cursor;
+        }
+        let next_start = end + 1;
+        let next_end = line_end(chars, next_start);
+        (next_start + column).min(next_end)
+    } else {
+        if start == 0 {
+            return cursor;
+        }
+        let prev_start = line_start(chars, start - 1);
+        (prev_start + column).min(start - 1)
+    }
+}
+
+fn next_word(chars: &[char], cursor: usize) -> usize {
+    let mut index = cursor;
+    while index < chars.len() && !chars[index].is_whitespace() {
+        index += 1;
+    }
+    while index < chars.len() && chars[index].is_whitespace() {
+        index += 1;
+    }
+    index
+}
+
+fn prev_word(chars: &[char], cursor: usize) -> usize {
+    let mut index = cursor;
+    while index > 0 && chars[index - 1].is_whitespace() {
+        index -= 1;
+    }
+    while index > 0 && !chars[index - 1].is_whitespace() {
+        index -= 1;
+    }
+    index
+}
+
+// 编辑器内部使用字符数组，处理完成后写回文本
+struct Buffer {
+    chars: Vec<char>,
+    cursor: usize,
+    changed: bool,
+}
+
+impl Buffer {
+    fn delete_range(&mut self, start: usize, end: usize) {
+        if start < end {
+            self.chars.drain(start..end);
+            self.cursor = start;
+            self.changed = true;
+        }
+    }
+
+    fn insert(&mut self, at: usize, c: char) {
+        self.chars.insert(at, c);
+        self.changed = true;
+    }
+}
+
+fn handle_emacs(buffer: &mut Buffer, key: Key, modifiers: Modifiers) -> bool {
+    let chars = &buffer.chars;
+    let cursor = buffer.cursor;
+    if modifiers.ctrl && !modifiers.alt {
+        buffer.cursor = match key {
+            Key::A => line_start(chars, cursor),
+            Key::E => line_end(chars, cursor),
+            Key::F => (cursor + 1).min(chars.len()),
+            Key::B => cursor.saturating_sub(1),
+            Key::N => vertical_move(chars, cursor, true),
+            Key::P => vertical_move(chars, cursor, false),
+            Key::K => {
+                // 删除到行尾；光标位于行尾时删除换行符
+                let end = line_end(chars, cursor);
+                let end = if end == cursor {
+                    (end + 1).min(chars.len())
+                } else {
+                    end
+                };
+                buffer.delete_range(cursor, end);
+                return true;
+            }
+            Key::D => {
+                let end = (cursor + 1).min(chars.len());
+                buffer.delete_range(cursor, end);
+                return true;
+            }
+            _ => return false,
+        };
+        return true;
+    }
+    if modifiers.alt && !modifiers.ctrl {
+        buffer.cursor = match key {
+            Key::F => next_word(chars, cursor),
+            Key::B => prev_word(chars, cursor),
+            _ => return false,
+        };
+        return true;
+    }
+    false
+}
+
+fn handle_vim_normal(state: &mut ModalState, buffer: &mut Buffer, c: char) {
+    let pending = state.pending.take();
+    let chars = &buffer.chars;
+    let cursor = buffer.cursor;
+    let start = line_start(chars, cursor);
+    let end = line_end(chars, cursor);
+
+    match (pending, c) {
+        (Some('d'), 'd') => {
+            // 删除整行（包含换行符）
+            let delete_end = (end + 1).min(chars.len());
+            let delete_start = if delete_end == end && start > 0 {
+                start - 1
+            } else {
+                start
+            };
+            buffer.delete_range(delete_start, delete_end);
+            let chars = &buffer.chars;
+            buffer.cursor = line_start(chars, buffer.cursor.min(chars.len()));
+        }
+        (Some('g'), 'g') => buffer.cursor = 0,
+        (None, 'd') | (None, 'g') => state.pending = Some(c),
+        (_, 'h') => buffer.cursor = cursor.saturating_sub(1).max(start),
+        (_, 'l') => buffer.cursor = (cursor + 1).min(end),
+        (_, 'j') => buffer.cursor = vertical_move(chars, cursor, true),
+        (_, 'k') => buffer.cursor = vertical_move(chars, cursor, false),
+        (_, '0') => buffer.cursor = start,
+        (_, '$') => buffer.cursor = end,
+        (_, 'w') => buffer.cursor = next_word(chars, cursor),
+        (_, 'b') => buffer.cursor = prev_word(chars, cursor),
+        (_, 'G') => buffer.cursor = chars.len(),
+        (_, 'x') if cursor < end => buffer.delete_range(cursor, cursor + 1),
+        (_, 'i') => state.vim_normal = false,
+        (_, 'a') => {
+            buffer.cursor = (cursor + 1).min(end);
+            state.vim_normal = false;
+        }
+        (_, 'A') => {
+            buffer.cursor = end;
+            state.vim_normal = false;
+        }
+        (_, 'I') => {
+            buffer.cursor = start;
+            state.vim_normal = false;
+        }
+        (_, 'o') => {
+            buffer.insert(end, '\n');
+            buffer.cursor = end + 1;
+            state.vim_normal = false;
+        }
+        (_, 'O') => {
+            buffer.insert(start, '\n');
+            buffer.cursor = start;
+            state.vim_normal = false;
+        }
+        _ => {}
+    }
+}
+
+// 处理输入事件：被消费的事件会从列表中移除，返回 (新文本, 新光标位置)
+pub fn handle_events(
+    mode: KeybindingMode,
+    state: &mut ModalState,
+    text: &str,
+    cursor: usize,
+    events: &mut Vec<Event>,
+) -> Option<(String, usize)> {
+    if mode == KeybindingMode::Default {
+        return None;
+    }
+
+    let mut buffer = Buffer {
+        chars: text.chars().collect(),
+        cursor: cursor.min(text.chars().count()),
+        changed: false,
+    };
+    let cursor_before = buffer.cursor;
+
+    events.retain(|event| match (mode, event) {
+        (
+            KeybindingMode::Emacs,
+            Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+                ..
+            },
+        ) => !handle_emacs(&mut buffer, *key, *modifiers),
+        (
+            KeybindingMode::Vim,
+            Event::Key {
+                key: Key::Escape,
+                pressed,
+                ..
+            },
+        ) => {
+            if *pressed {
+                state.vim_normal = true;
+                state.pending = None;
+            }
+            false
+        }
+        (KeybindingMode::Vim, Event::Text(input)) if state.vim_normal => {
+            for c in input.chars() {
+                handle_vim_normal(state, &mut buffer, c);
+            }
+            false
+        }
+        // 普通模式下屏蔽会修改文本的按键
+        (
+            KeybindingMode::Vim,
+            Event::Key {
+                key: Key::Enter | Key::Backspace | Key::Delete | Key::Tab,
+                ..
+            },
+        ) if state.vim_normal => false,
+        (KeybindingMode::Vim, Event::Paste(_)) if state.vim_normal => false,
+        _ => true,
+    });
+
+    if buffer.changed || buffer.cursor != cursor_before {
+        Some((buffer.chars.into_iter().collect(), buffer.cursor))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,326 @@
+use crate::models::Message;
+use crate::paths;
+use crate::providers::{ApiTarget, ProviderKind};
+use log::debug;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+// 每个文本块的大致长度和相邻块的重叠长度（字符）
+const CHUNK_CHARS: usize = 1200;
+const CHUNK_OVERLAP: usize = 200;
+// 单次请求嵌入的文本块数量
+const EMBED_BATCH: usize = 64;
+// 单个文件过大时跳过，避免误选整个日志目录
+const MAX_FILE_BYTES: u64 = 2 * 1024 * 1024;
+const EXTENSIONS: [&str; 4] = ["md", "markdown", "txt", "rst"];
+
+pub fn default_model() -> String {
+    "text-embedding-3-small".to_string()
+}
+
+pub fn default_top_k() -> usize {
+    4
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct KnowledgeChunk {
+    // 相对于知识库文件夹的路径
+    pub source: String,
+    pub text: String,
+    pub vector: Vec<f32>,
+}
+
+// 对话关联的文件夹及其向量索引，保存在本地缓存目录
+#[derive(Serialize, Deserialize, Clone)]
+pub struct KnowledgeIndex {
+    pub folder: String,
+    pub model: String,
+    pub chunks: Vec<KnowledgeChunk>,
+}
+
+fn index_path(chat_id: &str) -> PathBuf {
+    paths::cache_subdir("knowledge").join(format!("{}.json", chat_id))
+}
+
+pub async fn load_index(chat_id: &str) -> Result<KnowledgeIndex, String> {
+    let content = fs::read_to_string(index_path(chat_id))
+        .await
+        .map_err(|e| format!("读取知识库索引失败: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("解析知识库索引失败: {}", e))
+}
+
+async fn save_index(chat_id: &str, index: &KnowledgeIndex) -> Result<(), String> {
+    let path = index_path(chat_id);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .await
+            .map_err(|e| format!("创建知识库目录失败: {}", e))?;
+    }
+    let json = serde_json::to_string(index).map_err(|e| e.to_string())?;
+    fs::write(&path, json)
+        .await
+        .map_err(|e| format!("保存知识库索引失败: {}", e))
+}
+
+pub async fn remove_index(chat_id: &str) {
+    let _ = fs::remove_file(index_path(chat_id)).await;
+}
+
+// 递归读取文件夹中的文本和 Markdown 文件，跳过隐藏目录
+async fn collect_documents(folder: &Path) -> std::io::Result<Vec<(String, String)>> {
+    let mut documents = Vec::new();
+    let mut pending = vec![folder.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let metadata = entry.metadata().await?;
+            if metadata.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            let supported = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+            if !supported || metadata.len() > MAX_FILE_BYTES {
+                continue;
+            }
+            match fs::read_to_string(&path).await {
+                Ok(content) => {
+                    let relative = path.strip_prefix(folder).unwrap_or(&path);
+                    documents.push((relative.to_string_lossy().to_string(), content));
+                }
+                Err(e) => debug!("跳过无法读取的文件 {:?}: {}", path, e),
+            }
+        }
+    }
+    documents.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(documents)
+}
+
+// 按段落拆分文本，段落过长时按字符截断，相邻块保留部分重叠
+pub fn split_chunks(text: &str) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        if !current.is_empty() && current.chars().count() + paragraph.chars().count() > CHUNK_CHARS
+        {
+            let overlap: String = {
+                let chars: Vec<char> = current.chars().collect();
+                chars[chars.len().saturating_sub(CHUNK_OVERLAP)..]
+                    .iter()
+                    .collect()
+            };
+            chunks.push(std::mem::replace(&mut current, overlap));
+        }
+        if !current.is_empty() {
+            current.push_str("\n\n");
+        }
+        current.push_str(paragraph);
+        while current.chars().count() > CHUNK_CHARS {
+            let head: String = current.chars().take(CHUNK_CHARS).collect();
+            let tail: String = current.chars().skip(CHUNK_CHARS - CHUNK_OVERLAP).collect();
+            chunks.push(head);
+            current = tail;
+        }
+    }
+    if !current.trim().is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+// 由对话接口地址推出嵌入接口地址，目前支持 OpenAI 兼容接口和 Azure OpenAI
+fn embedding_request(
+    client: &Client,
+    target: &ApiTarget,
+    model: &str,
+) -> Result<reqwest::RequestBuilder, String> {
+    let url = target.url();
+    if !url.contains("chat/completions") {
+        return Err(format!("无法从地址推出嵌入接口: {}", url));
+    }
+    let url = url.replace("chat/completions", "embeddings");
+    match target.provider {
+        ProviderKind::OpenAi => Ok(client
+            .post(url)
+            .header("Authorization", format!("Bearer {}", target.api_key))),
+        ProviderKind::Azure => Ok(client
+            .post(url.replace("{deployment}", model))
+            .header("api-key", &target.api_key)),
+        provider => Err(format!("{} 不支持嵌入接口", provider.label())),
+    }
+}
+
+pub async fn embed(
+    client: &Client,
+    target: &ApiTarget,
+    model: &str,
+    inputs: &[String],
+) -> Result<Vec<Vec<f32>>, String> {
+    let response = embedding_request(client, target, model)?
+        .json(&json!({
+            "model": model,
+            "input": inputs
+        }))
+        .send()
+        .await
+        .map_err(|e| format!("嵌入请求失败: {}", e))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("嵌入请求失败: HTTP {} {}", status, body));
+    }
+    let body: JsonValue = response
+        .json()
+        .await
+        .map_err(|e| format!("解析嵌入结果失败: {}", e))?;
+    let mut items: Vec<(u64, Vec<f32>)> = body["data"]
+        .as_array()
+        .map(|data| {
+            data.iter()
+                .map(|item| {
+                    let vector = item["embedding"]
+                        .as_array()
+                        .map(|values| {
+                            values
+                                .iter()
+                                .filter_map(|v| v.as_f64())
+                                .map(|v| v as f32)
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                    (item["index"].as_u64().unwrap_or(0), vector)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    if items.len() != inputs.len() {
+        return Err(format!(
+            "嵌入结果数量不匹配: 期望 {}，实际 {}",
+            inputs.len(),
+            items.len()
+        ));
+    }
+    items.sort_by_key(|(index, _)| *index);
+    Ok(items.into_iter().map(|(_, vector)| vector).collect())
+}
+
+// 读取文件夹、拆分并嵌入所有文本块，完成后保存索引，返回文本块数量
+pub async fn build_index(
+    client: &Client,
+    target: &ApiTarget,
+    model: &str,
+    chat_id: &str,
+    folder: &Path,
+) -> Result<usize, String> {
+    let documents = collect_documents(folder)
+        .await
+        .map_err(|e| format!("读取知识库文件夹失败: {}", e))?;
+    let pieces: Vec<(String, String)> = documents
+        .iter()
+        .flat_map(|(source, content)| {
+            split_chunks(content)
+                .into_iter()
+                .map(move |text| (source.clone(), text))
+        })
+        .collect();
+    if pieces.is_empty() {
+        return Err("文件夹中没有可用的文本或 Markdown 文件".to_string());
+    }
+    debug!(
+        "知识库 {:?}: {} 个文件，{} 个文本块",
+        folder,
+        documents.len(),
+        pieces.len()
+    );
+
+    let mut chunks = Vec::with_capacity(pieces.len());
+    for batch in pieces.chunks(EMBED_BATCH) {
+        let inputs: Vec<String> = batch.iter().map(|(_, text)| text.clone()).collect();
+        let vectors = embed(client, target, model, &inputs).await?;
+        chunks.extend(
+            batch
+                .iter()
+                .zip(vectors)
+                .map(|((source, text), vector)| KnowledgeChunk {
+                    source: source.clone(),
+                    text: text.clone(),
+                    vector,
+                }),
+        );
+    }
+    let count = chunks.len();
+    let index = KnowledgeIndex {
+        folder: folder.to_string_lossy().to_string(),
+        model: model.to_string(),
+        chunks,
+    };
+    save_index(chat_id, &index).await?;
+    Ok(count)
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+// 与问题最相关的 k 个文本块
+pub fn top_chunks<'a>(
+    index: &'a KnowledgeIndex,
+    query: &[f32],
+    k: usize,
+) -> Vec<&'a KnowledgeChunk> {
+    let mut scored: Vec<(f32, &KnowledgeChunk)> = index
+        .chunks
+        .iter()
+        .map(|chunk| (cosine_similarity(&chunk.vector, query), chunk))
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored.into_iter().take(k).map(|(_, chunk)| chunk).collect()
+}
+
+// 检索与问题相关的内容，生成放在问题前面的系统消息
+pub async fn retrieve_context(
+    client: &Client,
+    target: &ApiTarget,
+    chat_id: &str,
+    question: &Message,
+    top_k: usize,
+) -> Result<Option<String>, String> {
+    let question = question.content.trim();
+    if question.is_empty() {
+        return Ok(None);
+    }
+    let index = load_index(chat_id).await?;
+    let query = embed(client, target, &index.model, &[question.to_string()])
+        .await?
+        .pop()
+        .unwrap_or_default();
+    let chunks = top_chunks(&index, &query, top_k);
+    if chunks.is_empty() {
+        return Ok(None);
+    }
+    debug!("知识库检索到 {} 个相关文本块", chunks.len());
+
+    let mut context =
+        "以下是从用户的本地文档中检索到的相关内容，回答时请优先参考，并注明来源文件：\n"
+            .to_string();
+    for chunk in chunks {
+        context.push_str(&format!("\n--- {} ---\n{}\n", chunk.source, chunk.text));
+    }
+    Ok(Some(context))
+}
@@ -0,0 +1,73 @@
+// 根据文字的书写系统在本地粗略判断用户消息的语言，用于提示模型保持回复语言一致
+
+// 去掉代码块，避免粘贴的英文代码影响判断；朗读时同样跳过代码
+pub fn prose(text: &str) -> String {
+    let mut in_code = false;
+    let mut result = String::new();
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if !in_code {
+            result.push_str(line);
+            result.push('\n');
+        }
+    }
+    result
+}
+
+// 返回语言的英文名称；拉丁字母等无法可靠区分的文字返回 None
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let mut kana = 0;
+    let mut counts: [(&str, usize); 9] = [
+        ("Chinese", 0),
+        ("Korean", 0),
+        ("Russian", 0),
+        ("Arabic", 0),
+        ("Hebrew", 0),
+        ("Greek", 0),
+        ("Thai", 0),
+        ("Hindi", 0),
+        ("Latin", 0),
+    ];
+    for c in prose(text).chars() {
+        let index = match c as u32 {
+            0x3040..=0x30FF => {
+                kana += 1;
+                continue;
+            }
+            0x4E00..=0x9FFF | 0x3400..=0x4DBF => 0,
+            0xAC00..=0xD7AF | 0x1100..=0x11FF => 1,
+            0x0400..=0x04FF => 2,
+            0x0600..=0x06FF => 3,
+            0x0590..=0x05FF => 4,
+            0x0370..=0x03FF => 5,
+            0x0E00..=0x0E7F => 6,
+            0x0900..=0x097F => 7,
+            _ if c.is_alphabetic() && (c.is_ascii() || ('\u{00C0}'..='\u{024F}').contains(&c)) => 8,
+            _ => continue,
+        };
+        counts[index].1 += 1;
+    }
+    // 日文混用汉字和假名，出现假名即视为日文
+    if kana > 0 {
+        return Some("Japanese");
+    }
+    counts
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .max_by_key(|(_, count)| *count)
+        .and_then(|(language, _)| (language != "Latin").then_some(language))
+}
+
+// 附加在请求中的隐藏指令，不修改界面上的系统提示词
+pub fn reply_directive(user_message: &str) -> String {
+    match detect_language(user_message) {
+        Some(language) => format!(
+            "Always respond in {}, the language of the user's latest message, unless the user explicitly asks for another language.",
+            language
+        ),
+        None => "Always respond in the same language as the user's latest message, unless the user explicitly asks for another language.".to_string(),
+    }
+}
@@ -0,0 +1,170 @@
+use std::path::PathBuf;
+use uuid::Uuid;
+
+const SERVICE_NAME: &str = "Ask Dream about selection";
+
+const INFO_PLIST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>NSServices</key>
+	<array>
+		<dict>
+			<key>NSMenuItem</key>
+			<dict>
+				<key>default</key>
+				<string>{name}</string>
+			</dict>
+			<key>NSMessage</key>
+			<string>runWorkflowAsService</string>
+			<key>NSSendTypes</key>
+			<array>
+				<string>public.utf8-plain-text</string>
+			</array>
+		</dict>
+	</array>
+</dict>
+</plist>
+"#;
+
+// 只包含一个“运行 Shell 脚本”动作的快速操作，选中的文本通过标准输入传入
+const DOCUMENT_WFLOW: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>AMApplicationBuild</key>
+	<string>523</string>
+	<key>AMApplicationVersion</key>
+	<string>2.10</string>
+	<key>AMDocumentVersion</key>
+	<string>2</string>
+	<key>actions</key>
+	<array>
+		<dict>
+			<key>action</key>
+			<dict>
+				<key>AMAccepts</key>
+				<dict>
+					<key>Container</key>
+					<string>List</string>
+					<key>Optional</key>
+					<true/>
+					<key>Types</key>
+					<array>
+						<string>com.apple.cocoa.string</string>
+					</array>
+				</dict>
+				<key>AMActionVersion</key>
+				<string>2.0.3</string>
+				<key>AMApplication</key>
+				<array>
+					<string>Automator</string>
+				</array>
+				<key>AMProvides</key>
+				<dict>
+					<key>Container</key>
+					<string>List</string>
+					<key>Types</key>
+					<array>
+						<string>com.apple.cocoa.string</string>
+					</array>
+				</dict>
+				<key>ActionBundlePath</key>
+				<string>/System/Library/Automator/Run Shell Script.action</string>
+				<key>ActionName</key>
+				<string>Run Shell Script</string>
+				<key>ActionParameters</key>
+				<dict>
+					<key>COMMAND_STRING</key>
+					<string>{command}</string>
+					<key>CheckedForUserDefaultShell</key>
+					<true/>
+					<key>inputMethod</key>
+					<integer>0</integer>
+					<key>shell</key>
+					<string>/bin/bash</string>
+					<key>source</key>
+					<string></string>
+				</dict>
+				<key>BundleIdentifier</key>
+				<string>com.apple.RunShellScript</string>
+				<key>CFBundleVersion</key>
+				<string>2.0.3</string>
+				<key>CanShowSelectedItemsWhenRun</key>
+				<false/>
+				<key>CanShowWhenRun</key>
+				<true/>
+				<key>Category</key>
+				<array>
+					<string>AMCategoryUtilities</string>
+				</array>
+				<key>Class Name</key>
+				<string>RunShellScriptAction</string>
+				<key>InputUUID</key>
+				<string>{input_uuid}</string>
+				<key>OutputUUID</key>
+				<string>{output_uuid}</string>
+				<key>UUID</key>
+				<string>{uuid}</string>
+			</dict>
+		</dict>
+	</array>
+	<key>connectors</key>
+	<dict/>
+	<key>workflowMetaData</key>
+	<dict>
+		<key>serviceInputTypeIdentifier</key>
+		<string>com.apple.Automator.text</string>
+		<key>serviceOutputTypeIdentifier</key>
+		<string>com.apple.Automator.nothing</string>
+		<key>serviceProcessesInput</key>
+		<integer>0</integer>
+		<key>workflowTypeIdentifier</key>
+		<string>com.apple.Automator.servicesMenu</string>
+	</dict>
+</dict>
+</plist>
+"#;
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn uuid_string() -> String {
+    Uuid::new_v4().to_string().to_uppercase()
+}
+
+// 在 ~/Library/Services 下生成快速操作，返回安装路径
+pub fn install() -> std::io::Result<PathBuf> {
+    let home = std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .ok_or_else(|| std::io::Error::other("未找到 HOME 目录"))?;
+    let exe = std::env::current_exe()?;
+    let workflow = home
+        .join("Library/Services")
+        .join(format!("{}.workflow", SERVICE_NAME));
+    let contents = workflow.join("Contents");
+    std::fs::create_dir_all(&contents)?;
+
+    // 后台启动，避免应用窗口打开期间服务一直处于运行状态
+    let exe = exe.to_string_lossy().replace('\'', r"'\''");
+    let command = format!(
+        "text=$(cat)\nprintf '%s' \"$text\" | '{}' ask-selection >/dev/null 2>&1 &",
+        exe
+    );
+    std::fs::write(
+        contents.join("Info.plist"),
+        INFO_PLIST.replace("{name}", SERVICE_NAME),
+    )?;
+    std::fs::write(
+        contents.join("document.wflow"),
+        DOCUMENT_WFLOW
+            .replace("{command}", &xml_escape(&command))
+            .replace("{input_uuid}", &uuid_string())
+            .replace("{output_uuid}", &uuid_string())
+            .replace("{uuid}", &uuid_string()),
+    )?;
+    Ok(workflow)
+}
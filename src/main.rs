@@ -1,73 +1,180 @@
 // #![windows_subsystem = "windows"]
 mod api;
+mod app_lock;
+mod archive;
+mod audio;
+mod backup;
+mod cache;
+mod capabilities;
+mod checklist;
+mod code_paste;
+mod compare;
 mod config;
+mod content_filter;
+mod context;
+mod costs;
+mod deep_link;
+mod diagnostics;
+mod dictation;
+mod diff;
+mod doh;
+mod emoji;
+mod entities;
+mod events;
+mod export;
+mod features;
+mod fonts;
+mod ghost_text;
+mod group_chat;
+mod health;
+mod image_cache;
+mod imagegen;
+mod importers;
+mod inbox;
+mod inspector;
+mod journal;
+mod keybindings;
+mod knowledge;
+mod language;
+#[cfg(target_os = "macos")]
+mod macos_service;
+mod memory;
+mod memory_store;
+mod metrics;
 mod models;
+mod notifications;
+mod outline;
+mod path_complete;
+mod paths;
+mod platform;
+mod postprocess;
+mod providers;
+mod quick_ask;
+mod replay;
+mod reply_stream;
+mod resolver;
+mod retrieval;
+mod review;
+mod secrets;
+mod snippets;
+mod speech;
+mod suggestions;
+mod task_registry;
+mod tee;
+mod templates;
+mod titles;
+mod tokens;
+mod turn_retry;
 mod ui;
 mod utils;
+mod webhook;
 
-use eframe::egui::{self, FontDefinitions, FontFamily};
+use std::io::Read;
 use tokio::runtime::Runtime;
 use ui::ChatApp;
 
+// 应用名称，也是默认的窗口标题
+pub const APP_NAME: &str = "ChatGPT Client";
+
 fn main() -> Result<(), eframe::Error> {
     utils::setup_logger();
 
     let runtime = Runtime::new().unwrap();
 
+    // --safe-mode：不读取配置和对话记录，用于存储文件损坏时打开程序
+    let safe_mode = std::env::args().any(|arg| arg == "--safe-mode");
+    if !safe_mode {
+        runtime.block_on(paths::migrate_legacy());
+    }
+
+    // ask-selection：从标准输入读取选中的文本，优先交给正在运行的实例
+    // open-chat <ID>：由跳转列表启动，打开指定对话
+    // open-link <URL>：点击 dream:// 链接启动，导入链接中的模板
+    // quick-ask：用主选区内容快速提问，可以在桌面环境中绑定为全局快捷键
+    let mut open_chat_id = None;
+    let mut open_link = None;
+    let mut quick_ask = false;
+    let selection = match std::env::args().nth(1).as_deref() {
+        Some("open-chat") => {
+            let id = std::env::args().nth(2).unwrap_or_default();
+            let message = inbox::InboxMessage::OpenChat(id.clone());
+            if runtime.block_on(inbox::send(&message)).is_ok() {
+                return Ok(());
+            }
+            open_chat_id = Some(id);
+            None
+        }
+        Some("open-link") => {
+            let link = std::env::args().nth(2).unwrap_or_default();
+            let message = inbox::InboxMessage::OpenLink(link.clone());
+            if runtime.block_on(inbox::send(&message)).is_ok() {
+                return Ok(());
+            }
+            open_link = Some(link);
+            None
+        }
+        Some("quick-ask") => {
+            if runtime
+                .block_on(inbox::send(&inbox::InboxMessage::QuickAsk))
+                .is_ok()
+            {
+                return Ok(());
+            }
+            quick_ask = true;
+            None
+        }
+        Some("ask-selection") => {
+            let mut text = String::new();
+            if let Err(e) = std::io::stdin().read_to_string(&mut text) {
+                eprintln!("读取标准输入失败: {}", e);
+            }
+            let message = inbox::InboxMessage::Selection(text.clone());
+            if runtime.block_on(inbox::send(&message)).is_ok() {
+                return Ok(());
+            }
+            Some(text)
+        }
+        #[cfg(target_os = "macos")]
+        Some("install-service") => {
+            match macos_service::install() {
+                Ok(path) => println!("已安装服务: {}", path.display()),
+                Err(e) => eprintln!("安装服务失败: {}", e),
+            }
+            return Ok(());
+        }
+        _ => None,
+    };
+
     let options = eframe::NativeOptions {
         viewport: eframe::egui::ViewportBuilder::default().with_inner_size([600.0, 600.0]),
         ..Default::default()
     };
 
     eframe::run_native(
-        "ChatGPT Client",
+        APP_NAME,
         options,
         Box::new(move |cc| {
-            let mut fonts = FontDefinitions::default();
-
-            fonts.families.clear(); // 清除所有默认字体族
-
-            // 添加 fa solid 字体
-            fonts.font_data.insert(
-                "fa-solid".to_owned(),
-                egui::FontData::from_static(include_bytes!(".././assets/fonts/fa6-900.otf")),
-            );
-
-            // 添加 PingFang SC 字体（macOS 系统字体）
-            fonts.font_data.insert(
-                "PingFang-SC".to_owned(),
-                egui::FontData::from_static(include_bytes!("../assets/fonts/pfsc.otf")),
-            );
-
-            // 添加jetbrains mono字体
-            fonts.font_data.insert(
-                "jetbrains".to_owned(),
-                egui::FontData::from_static(include_bytes!(
-                    "../assets/fonts/jetbrains-regular.ttf"
-                )),
-            );
-
-            fonts.families.insert(
-                FontFamily::Proportional,
-                vec![
-                    "jetbrains".to_owned(),
-                    "fa-solid".to_owned(),
-                    "PingFang-SC".to_owned(),
-                ],
-            );
+            let mut fonts = fonts::bundled_fonts();
 
-            fonts.families.insert(
-                FontFamily::Monospace,
-                vec![
-                    "jetbrains".to_owned(),
-                    "fa-solid".to_owned(),
-                    "PingFang-SC".to_owned(),
-                ],
-            );
+            // 追加系统中文和 emoji 字体作为后备
+            fonts::add_system_fallback_fonts(&mut fonts);
 
             cc.egui_ctx.set_fonts(fonts);
+            egui_extras::install_image_loaders(&cc.egui_ctx);
 
-            let app = ChatApp::new(runtime);
+            let mut app = ChatApp::new(runtime, safe_mode);
+            if let Some(text) = selection {
+                app.open_with_selection(text);
+            }
+            if let Some(id) = open_chat_id {
+                app.open_chat(id);
+            }
+            if let Some(link) = open_link {
+                app.open_link(&link);
+            }
+            if quick_ask {
+                app.start_quick_ask();
+            }
             Ok(Box::new(app) as Box<dyn eframe::App>)
         }),
     )
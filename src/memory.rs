@@ -0,0 +1,62 @@
+// 对话记忆：在后台把长对话中较早的消息摘要成一段记忆保存在对话上，发送请求时代替这些消息
+use crate::context;
+use crate::models::{self, Chat, ChatMemory, Message};
+use chrono::Utc;
+
+// 最近的这些消息总是原样发送，不会并入记忆
+pub const KEEP_RECENT: usize = 20;
+// 未并入记忆的较早消息达到这个数量才更新，避免每条回复都发一次摘要请求
+const MIN_NEW_MESSAGES: usize = 10;
+
+// 需要并入记忆的消息：返回（更新后覆盖的消息数，新增的消息，仍然有效的旧记忆）
+pub fn pending(chat: &Chat) -> Option<(usize, Vec<Message>, Option<String>)> {
+    let messages: Vec<Message> = models::context_messages(&chat.messages).cloned().collect();
+    let target = messages.len().saturating_sub(KEEP_RECENT);
+    let previous = chat
+        .memory
+        .as_ref()
+        .filter(|memory| is_valid(memory, &messages));
+    let covered = previous.map_or(0, |memory| memory.covered);
+    (target >= covered + MIN_NEW_MESSAGES).then(|| {
+        (
+            target,
+            messages[covered..target].to_vec(),
+            previous.map(|memory| memory.text.clone()),
+        )
+    })
+}
+
+// 记忆覆盖的消息被编辑或删除后不再可用
+fn is_valid(memory: &ChatMemory, history: &[Message]) -> bool {
+    memory.covered < history.len()
+        && memory.fingerprint == context::fingerprint(&history[..memory.covered])
+}
+
+// 摘要完成后生成新的记忆，指纹按生成时的消息计算
+pub fn build(chat_messages: &[Message], covered: usize, text: String) -> ChatMemory {
+    let messages: Vec<Message> = models::context_messages(chat_messages).cloned().collect();
+    ChatMemory {
+        text,
+        covered,
+        fingerprint: context::fingerprint(&messages[..covered.min(messages.len())]),
+        updated_at: Utc::now(),
+    }
+}
+
+// 用记忆代替它覆盖的消息，返回剩下的历史和附加了记忆的系统提示词
+pub fn apply(
+    memory: Option<&ChatMemory>,
+    system_prompt: &str,
+    history: Vec<Message>,
+) -> (Vec<Message>, String) {
+    match memory.filter(|memory| is_valid(memory, &history)) {
+        Some(memory) => (
+            history[memory.covered..].to_vec(),
+            format!(
+                "{}\n\n对话记忆（之前 {} 条消息的摘要）：\n{}",
+                system_prompt, memory.covered, memory.text
+            ),
+        ),
+        None => (history, system_prompt.to_string()),
+    }
+}
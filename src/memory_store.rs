@@ -0,0 +1,160 @@
+// 长期记忆：在任意消息上点击“记住”后，把消息提炼成一条简短的事实保存下来；
+// 发送消息时按嵌入相似度取出相关的几条附加到请求中，对所有对话生效
+use crate::api;
+use crate::knowledge;
+use crate::paths;
+use crate::providers::ApiTarget;
+use crate::utils;
+use chrono::{DateTime, Utc};
+use log::{debug, warn};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+pub const MEMORY_FILE: &str = "memories.json";
+// 每次请求最多附加的记忆条数
+const TOP_K: usize = 3;
+// 相似度低于这个值的记忆视为与问题无关
+const MIN_SIMILARITY: f32 = 0.3;
+const DISTILL_PROMPT: &str = "把下面的内容提炼成一条关于用户的、以后对话中值得记住的事实或偏好。\
+只输出这一句话，使用与原文相同的语言，不要加引号或解释。";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MemoryEntry {
+    pub id: String,
+    pub text: String,
+    // 从哪个对话中记住的
+    pub source_chat: Option<String>,
+    pub created_at: DateTime<Utc>,
+    // 生成向量时使用的嵌入模型，与当前设置不同时需要重新嵌入
+    #[serde(default)]
+    pub model: String,
+    #[serde(default)]
+    pub vector: Vec<f32>,
+}
+
+impl MemoryEntry {
+    pub fn is_embedded(&self, model: &str) -> bool {
+        self.model == model && !self.vector.is_empty()
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct MemoryStore {
+    pub entries: Vec<MemoryEntry>,
+}
+
+// 后台任务的结果
+pub enum MemoryEvent {
+    Added(MemoryEntry),
+    Embedded {
+        id: String,
+        model: String,
+        vector: Vec<f32>,
+    },
+    Failed(String),
+}
+
+pub async fn load() -> MemoryStore {
+    match tokio::fs::read_to_string(paths::data_file(MEMORY_FILE)).await {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+        Err(_) => MemoryStore::default(),
+    }
+}
+
+pub async fn save(store: &MemoryStore) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(store).map_err(|e| e.to_string())?;
+    utils::write_atomic(&paths::data_file(MEMORY_FILE), json)
+        .await
+        .map_err(|e| format!("保存长期记忆失败: {}", e))
+}
+
+// 提炼并嵌入一条新记忆；嵌入失败时仍然保存文字，之后可以在记忆管理中重新嵌入
+pub async fn remember(
+    client: &Client,
+    target: &ApiTarget,
+    model: &str,
+    embedding_model: &str,
+    text: &str,
+    source_chat: Option<String>,
+) -> Result<MemoryEntry, String> {
+    let payload = json!({
+        "model": model,
+        "messages": [
+            { "role": "system", "content": DISTILL_PROMPT },
+            { "role": "user", "content": text }
+        ],
+        "stream": false
+    });
+    let fact = api::complete(client, target, &payload)
+        .await
+        .map_err(|e| format!("提炼记忆失败: {}", e))?;
+    let fact = fact.trim().trim_matches(['"', '“', '”']).trim().to_string();
+    if fact.is_empty() {
+        return Err("提炼记忆失败: 模型没有返回内容".to_string());
+    }
+    let mut entry = MemoryEntry {
+        id: Uuid::new_v4().to_string(),
+        text: fact,
+        source_chat,
+        created_at: Utc::now(),
+        model: String::new(),
+        vector: Vec::new(),
+    };
+    match knowledge::embed(client, target, embedding_model, &[entry.text.clone()]).await {
+        Ok(mut vectors) => {
+            entry.vector = vectors.pop().unwrap_or_default();
+            entry.model = embedding_model.to_string();
+        }
+        Err(e) => warn!("记忆嵌入失败，将只保存文字: {}", e),
+    }
+    Ok(entry)
+}
+
+pub async fn embed_entry(
+    client: &Client,
+    target: &ApiTarget,
+    embedding_model: &str,
+    text: &str,
+) -> Result<Vec<f32>, String> {
+    knowledge::embed(client, target, embedding_model, &[text.to_string()])
+        .await?
+        .pop()
+        .ok_or_else(|| "嵌入结果为空".to_string())
+}
+
+// 与问题相关的记忆，生成放在问题前面的系统消息
+pub async fn retrieve_context(
+    client: &Client,
+    target: &ApiTarget,
+    embedding_model: &str,
+    entries: &[MemoryEntry],
+    question: &str,
+) -> Result<Option<String>, String> {
+    let question = question.trim();
+    let entries: Vec<&MemoryEntry> = entries
+        .iter()
+        .filter(|entry| entry.is_embedded(embedding_model))
+        .collect();
+    if question.is_empty() || entries.is_empty() {
+        return Ok(None);
+    }
+    let query = embed_entry(client, target, embedding_model, question).await?;
+    let mut scored: Vec<(f32, &MemoryEntry)> = entries
+        .into_iter()
+        .map(|entry| (knowledge::cosine_similarity(&entry.vector, &query), entry))
+        .filter(|(score, _)| *score >= MIN_SIMILARITY)
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    if scored.is_empty() {
+        return Ok(None);
+    }
+    debug!("检索到 {} 条相关的长期记忆", scored.len().min(TOP_K));
+
+    let mut context = "以下是之前记住的关于用户的信息，仅在与问题相关时参考：\n".to_string();
+    for (_, entry) in scored.into_iter().take(TOP_K) {
+        context.push_str(&format!("- {}\n", entry.text));
+    }
+    Ok(Some(context))
+}
@@ -0,0 +1,193 @@
+// 本地诊断统计：请求次数、错误率、延迟和帧耗时，只写入本机文件，不会上传
+use crate::paths;
+use chrono::Local;
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const METRICS_FILE: &str = "metrics.json";
+
+// 直方图各桶的上界（毫秒），最后一个桶收集所有更大的值
+pub const LATENCY_BOUNDS_MS: [u64; 8] =
+    [500, 1_000, 2_000, 5_000, 10_000, 20_000, 60_000, u64::MAX];
+pub const FRAME_BOUNDS_MS: [u64; 6] = [4, 8, 16, 33, 66, u64::MAX];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Histogram {
+    pub counts: Vec<u64>,
+    pub sum_ms: f64,
+}
+
+impl Histogram {
+    fn record(&mut self, bounds: &[u64], elapsed: Duration) {
+        let ms = elapsed.as_secs_f64() * 1000.0;
+        let bucket = bounds
+            .iter()
+            .position(|&bound| ms <= bound as f64)
+            .unwrap_or(bounds.len() - 1);
+        self.counts.resize(bounds.len(), 0);
+        self.counts[bucket] += 1;
+        self.sum_ms += ms;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    pub fn mean_ms(&self) -> Option<f64> {
+        match self.count() {
+            0 => None,
+            count => Some(self.sum_ms / count as f64),
+        }
+    }
+
+    // 近似分位数：返回包含该分位的桶的上界
+    pub fn percentile(&self, bounds: &[u64], p: f64) -> Option<u64> {
+        let target = (self.count() as f64 * p).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (count, bound) in self.counts.iter().zip(bounds) {
+            seen += count;
+            if seen >= target {
+                return Some(*bound);
+            }
+        }
+        None
+    }
+}
+
+pub fn bucket_label(bounds: &[u64], index: usize) -> String {
+    match bounds.get(index) {
+        Some(&u64::MAX) => format!("> {}", format_ms(bounds[index.saturating_sub(1)])),
+        Some(&bound) => format!("≤ {}", format_ms(bound)),
+        None => String::new(),
+    }
+}
+
+pub fn format_ms(ms: u64) -> String {
+    if ms == u64::MAX {
+        "更久".to_string()
+    } else if ms >= 1000 {
+        format!("{}s", ms as f64 / 1000.0)
+    } else {
+        format!("{}ms", ms)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MetricsData {
+    // 开始统计的时间
+    pub since: String,
+    pub requests: u64,
+    pub errors: u64,
+    #[serde(default)]
+    pub request_latency: Histogram,
+    #[serde(default)]
+    pub frame_time: Histogram,
+}
+
+impl Default for MetricsData {
+    fn default() -> Self {
+        Self {
+            since: Local::now().format("%Y-%m-%d %H:%M").to_string(),
+            requests: 0,
+            errors: 0,
+            request_latency: Histogram::default(),
+            frame_time: Histogram::default(),
+        }
+    }
+}
+
+impl MetricsData {
+    pub fn error_rate(&self) -> Option<f64> {
+        (self.requests > 0).then(|| self.errors as f64 / self.requests as f64)
+    }
+}
+
+#[derive(Default)]
+struct MetricsState {
+    enabled: bool,
+    data: MetricsData,
+}
+
+// 在界面和后台请求之间共享，未开启时记录操作什么也不做
+#[derive(Clone, Default)]
+pub struct Metrics {
+    state: Arc<Mutex<MetricsState>>,
+}
+
+impl Metrics {
+    pub fn new(enabled: bool, data: MetricsData) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(MetricsState { enabled, data })),
+        }
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.state.lock().unwrap().enabled = enabled;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.state.lock().unwrap().enabled
+    }
+
+    pub fn record_frame(&self, elapsed: Duration) {
+        let mut state = self.state.lock().unwrap();
+        if state.enabled {
+            state.data.frame_time.record(&FRAME_BOUNDS_MS, elapsed);
+        }
+    }
+
+    // 一次完整的请求（包括流式接收和重试）结束后记录，并写入文件
+    pub async fn finish_request(&self, started: Instant, success: bool) {
+        {
+            let mut state = self.state.lock().unwrap();
+            if !state.enabled {
+                return;
+            }
+            state.data.requests += 1;
+            if !success {
+                state.data.errors += 1;
+            }
+            state
+                .data
+                .request_latency
+                .record(&LATENCY_BOUNDS_MS, started.elapsed());
+        }
+        self.save().await;
+    }
+
+    pub fn snapshot(&self) -> MetricsData {
+        self.state.lock().unwrap().data.clone()
+    }
+
+    pub fn reset(&self) {
+        self.state.lock().unwrap().data = MetricsData::default();
+    }
+
+    pub async fn save(&self) {
+        let data = {
+            let state = self.state.lock().unwrap();
+            if !state.enabled {
+                return;
+            }
+            state.data.clone()
+        };
+        let result = match serde_json::to_string_pretty(&data) {
+            Ok(json) => tokio::fs::write(paths::data_file(METRICS_FILE), json)
+                .await
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = result {
+            error!("保存诊断统计失败: {}", e);
+        }
+    }
+}
+
+pub async fn load() -> MetricsData {
+    match tokio::fs::read_to_string(paths::data_file(METRICS_FILE)).await {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+        Err(_) => MetricsData::default(),
+    }
+}
@@ -1,3 +1,6 @@
+use crate::content_filter::FilterMode;
+use crate::journal::JournalState;
+use crate::providers::ProviderKind;
 use crate::utils;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
@@ -5,17 +8,191 @@ use serde_json::{json, Value as JsonValue};
 use std::path::Path;
 use uuid::Uuid;
 
+// 消息类型；错误、提示和分割线等只在界面中显示，不会发送给模型，也不会导出
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum MessageKind {
+    #[default]
+    Chat,
+    Error,
+    Notice,
+    // 之前的消息不再作为上下文发送
+    Divider,
+    // 日记中每天的分段，内容是日期；之前的消息同样不再作为上下文
+    DaySection,
+    ToolCall,
+    ToolResult,
+}
+
+impl MessageKind {
+    pub fn is_chat(self) -> bool {
+        self == MessageKind::Chat
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Message {
     pub role: String,
     pub content: String,
+    #[serde(default)]
+    pub kind: MessageKind,
     pub image_path: Option<String>,
+    #[serde(default)]
+    pub text_attachments: Vec<TextAttachment>,
+    // 助手消息对应请求实际发送的上下文
+    #[serde(default)]
+    pub request_manifest: Option<RequestManifest>,
+    // 推理模型返回的思考过程，不会加入后续请求
+    #[serde(default)]
+    pub reasoning: Option<String>,
+    // 服务商返回的 token 用量
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+    // 按收到回复时的单价计算的费用（美元）
+    #[serde(default)]
+    pub cost: Option<f64>,
+    // 重新生成前的回复，最早的在前
+    #[serde(default)]
+    pub previous_versions: Vec<Message>,
+    // 编辑后重新发送的用户消息保留最初的文本
+    #[serde(default)]
+    pub original_content: Option<String>,
+    // 群聊中回复的角色名称
+    #[serde(default)]
+    pub speaker: Option<String>,
+    // 经过后处理的回复保留处理前的原文
+    #[serde(default)]
+    pub raw_content: Option<String>,
+}
+
+// 一次请求的 token 用量
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl TokenUsage {
+    // 部分服务商分多次返回输入和输出用量，只用非零值覆盖
+    pub fn merge(&mut self, other: TokenUsage) {
+        if other.prompt_tokens > 0 {
+            self.prompt_tokens = other.prompt_tokens;
+        }
+        if other.completion_tokens > 0 {
+            self.completion_tokens = other.completion_tokens;
+        }
+    }
+}
+
+// 上下文清单中每条消息保留的摘录长度
+const MANIFEST_EXCERPT_CHARS: usize = 200;
+
+// 一次请求实际发送的内容摘要
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RequestManifest {
+    pub sent_at: DateTime<Utc>,
+    pub provider: ProviderKind,
+    pub model: String,
+    pub system_prompt: String,
+    pub params: SamplingParams,
+    pub entries: Vec<ManifestEntry>,
+    // 通过“用不同参数重试”生成时使用的覆盖项
+    #[serde(default)]
+    pub overrides: Option<RetryOverrides>,
+}
+
+impl RequestManifest {
+    // 检索到的补充上下文和实际请求一样放在最后一条消息之前
+    pub fn insert_retrieved(&mut self, contexts: &[String]) {
+        let at = self.entries.len().saturating_sub(1);
+        self.entries.splice(
+            at..at,
+            contexts
+                .iter()
+                .map(|context| ManifestEntry::from_text("system", context)),
+        );
+    }
+}
+
+// 重试单条回复时指定的模型、温度和附加指令
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RetryOverrides {
+    pub model: String,
+    pub temperature: f32,
+    #[serde(default)]
+    pub instruction: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ManifestEntry {
+    pub role: String,
+    pub excerpt: String,
+    pub char_count: usize,
+    #[serde(default)]
+    pub attachments: Vec<String>,
+    #[serde(default)]
+    pub has_image: bool,
+}
+
+impl ManifestEntry {
+    pub fn from_text(role: &str, text: &str) -> Self {
+        Self {
+            role: role.to_string(),
+            excerpt: text.chars().take(MANIFEST_EXCERPT_CHARS).collect(),
+            char_count: text.chars().count(),
+            attachments: Vec::new(),
+            has_image: false,
+        }
+    }
+
+    pub fn from_message(msg: &Message) -> Self {
+        let mut entry = Self::from_text(&msg.role, &msg.content);
+        entry.attachments = msg
+            .text_attachments
+            .iter()
+            .map(|attachment| attachment.name.clone())
+            .collect();
+        entry.has_image = msg.image_path.is_some();
+        entry
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.excerpt.chars().count() < self.char_count
+    }
+}
+
+// 以附件形式发送的大段文本（如粘贴的日志）
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TextAttachment {
+    pub name: String,
+    pub content: String,
+}
+
+impl TextAttachment {
+    pub fn line_count(&self) -> usize {
+        self.content.lines().count()
+    }
 }
 
 impl Message {
+    // 消息正文加上文本附件，用于构建请求
+    pub fn text_with_attachments(&self) -> String {
+        let mut text = self.content.clone();
+        for attachment in &self.text_attachments {
+            if !text.is_empty() {
+                text.push_str("\n\n");
+            }
+            text.push_str(&format!(
+                "附件 {}:\n```\n{}\n```",
+                attachment.name, attachment.content
+            ));
+        }
+        text
+    }
+
+    // 助手消息中的图片由 /image 命令生成，不作为图片内容发送
     pub async fn to_api_content(&self) -> std::io::Result<JsonValue> {
         match &self.image_path {
-            Some(path) => {
+            Some(path) if self.role == "user" => {
                 let base64_image = utils::get_image_base64(Path::new(path)).await?;
                 Ok(json!([
                     {
@@ -26,11 +203,11 @@ impl Message {
                     },
                     {
                         "type": "text",
-                        "text": self.content
+                        "text": self.text_with_attachments()
                     }
                 ]))
             }
-            None => Ok(json!(self.content)),
+            _ => Ok(json!(self.text_with_attachments())),
         }
     }
 
@@ -38,7 +215,17 @@ impl Message {
         Self {
             role: "user".to_string(),
             content,
+            kind: MessageKind::Chat,
             image_path,
+            text_attachments: Vec::new(),
+            request_manifest: None,
+            reasoning: None,
+            usage: None,
+            cost: None,
+            previous_versions: Vec::new(),
+            original_content: None,
+            speaker: None,
+            raw_content: None,
         }
     }
 
@@ -46,11 +233,63 @@ impl Message {
         Self {
             role: "assistant".to_string(),
             content,
+            kind: MessageKind::Chat,
             image_path: None,
+            text_attachments: Vec::new(),
+            request_manifest: None,
+            reasoning: None,
+            usage: None,
+            cost: None,
+            previous_versions: Vec::new(),
+            original_content: None,
+            speaker: None,
+            raw_content: None,
+        }
+    }
+
+    // 界面中显示的错误、提示等非对话消息
+    pub fn new_event(kind: MessageKind, content: String) -> Self {
+        Self {
+            kind,
+            ..Self::new_assistant(content)
+        }
+    }
+
+    // 模型的正常回复，不包括错误和提示
+    pub fn is_reply(&self) -> bool {
+        self.role == "assistant" && self.kind.is_chat()
+    }
+
+    // 用重新生成的回复替换当前回复，原回复保留为历史版本（失败的回复直接丢弃）
+    pub fn replace_reply(&mut self, reply: Message) {
+        let mut previous = std::mem::replace(self, reply);
+        self.previous_versions = std::mem::take(&mut previous.previous_versions);
+        if previous.is_reply() {
+            self.previous_versions.push(previous);
+        }
+    }
+
+    // 换回某个历史版本，当前回复变为最新的历史版本
+    pub fn restore_version(&mut self, version: usize) {
+        if version >= self.previous_versions.len() {
+            return;
         }
+        let mut restored = self.previous_versions.remove(version);
+        restored.previous_versions = std::mem::take(&mut self.previous_versions);
+        let current = std::mem::replace(self, restored);
+        self.previous_versions.push(current);
     }
 }
 
+// 发送给模型的上下文：最后一条分割线之后的对话消息
+pub fn context_messages(messages: &[Message]) -> impl Iterator<Item = &Message> {
+    let start = messages
+        .iter()
+        .rposition(|msg| matches!(msg.kind, MessageKind::Divider | MessageKind::DaySection))
+        .map_or(0, |index| index + 1);
+    messages[start..].iter().filter(|msg| msg.kind.is_chat())
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ChatHistory(pub Vec<Message>);
 
@@ -60,10 +299,7 @@ impl ChatHistory {
     }
 
     pub fn last_message_is_assistant(&self) -> bool {
-        self.0
-            .last()
-            .map(|msg| msg.role == "assistant")
-            .unwrap_or(false)
+        self.0.last().map(Message::is_reply).unwrap_or(false)
     }
 }
 
@@ -72,6 +308,59 @@ pub struct ChatConfig {
     pub model_name: String,
     pub system_prompt: String,
     pub temperature: f32,
+    #[serde(default = "default_top_p")]
+    pub top_p: f32,
+    #[serde(default)]
+    pub frequency_penalty: f32,
+    #[serde(default)]
+    pub presence_penalty: f32,
+    // 角色开场白，在用户输入前显示
+    #[serde(default)]
+    pub greeting: Option<String>,
+    // 开场白是否作为助手消息加入请求上下文
+    #[serde(default)]
+    pub greeting_in_context: bool,
+    // 为空时使用设置中的默认服务商
+    #[serde(default)]
+    pub provider: Option<ProviderKind>,
+    // 为空时使用设置中的过滤方式
+    #[serde(default)]
+    pub filter_mode: Option<FilterMode>,
+    // 在全局过滤列表之外追加的规则
+    #[serde(default)]
+    pub filter_terms: Vec<String>,
+    // 使用的 API 配置档案名称，为空时跟随设置
+    #[serde(default)]
+    pub profile: Option<String>,
+}
+
+fn default_top_p() -> f32 {
+    1.0
+}
+
+impl ChatConfig {
+    pub fn sampling_params(&self) -> SamplingParams {
+        SamplingParams {
+            temperature: self.temperature,
+            top_p: self.top_p,
+            frequency_penalty: self.frequency_penalty,
+            presence_penalty: self.presence_penalty,
+        }
+    }
+
+    pub fn set_sampling_params(&mut self, params: SamplingParams) {
+        self.temperature = params.temperature;
+        self.top_p = params.top_p;
+        self.frequency_penalty = params.frequency_penalty;
+        self.presence_penalty = params.presence_penalty;
+    }
+
+    pub fn greeting_text(&self) -> Option<&str> {
+        self.greeting
+            .as_deref()
+            .map(str::trim)
+            .filter(|text| !text.is_empty())
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -83,9 +372,97 @@ pub struct Chat {
     pub config: Option<ChatConfig>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub tasks: Vec<ChatTask>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub archived: bool,
+    // 由角色派生的对话记录其来源角色的 ID
+    #[serde(default)]
+    pub role_id: Option<String>,
+    // 将回复同步追加到外部文件
+    #[serde(default)]
+    pub tee: Option<TeeTarget>,
+    // 关联的知识库文件夹，索引保存在本地缓存中
+    #[serde(default)]
+    pub knowledge_folder: Option<String>,
+    // 群聊的成员角色，为空时是普通对话
+    #[serde(default)]
+    pub group_members: Vec<GroupMember>,
+    // 较早消息的摘要，开启对话记忆后代替这些消息发送
+    #[serde(default)]
+    pub memory: Option<ChatMemory>,
+    // 日记对话的滚动摘要，为空时是普通对话
+    #[serde(default)]
+    pub journal: Option<JournalState>,
+    // 输入框上方临时选择的模型，只影响之后发送的消息，优先于角色和全局设置
+    #[serde(default)]
+    pub model_override: Option<String>,
+    // 本对话的 Webhook 地址，优先于全局设置
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+    // 无痕对话只保存在内存中，不写入对话记录
+    #[serde(skip)]
+    pub incognito: bool,
+    // 所在的文件夹，对应 ChatList.folders 中的名称
+    #[serde(default)]
+    pub folder: Option<String>,
+    // 置顶的对话显示在侧边栏最上方，不参与角色和普通对话的分组
+    #[serde(default)]
+    pub pinned: bool,
+    // 本对话中关闭的后处理步骤名称
+    #[serde(default)]
+    pub disabled_post_processors: Vec<String>,
+}
+
+// 对话记忆：覆盖从第一条开始的若干条对话消息，指纹用于发现这些消息被编辑或删除
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ChatMemory {
+    pub text: String,
+    pub covered: usize,
+    pub fingerprint: u64,
+    pub updated_at: DateTime<Utc>,
+}
+
+// 群聊成员：引用角色对话的 ID，静音的角色不参与回复
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GroupMember {
+    pub role_id: String,
+    #[serde(default)]
+    pub muted: bool,
+}
+
+// 对话的外部输出文件
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TeeTarget {
+    pub path: String,
+    // 为 true 时随流式输出实时写入，否则在回复完成后写入
+    #[serde(default)]
+    pub live: bool,
+}
+
+// 对话中讨论的任务清单项
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ChatTask {
+    pub text: String,
+    pub done: bool,
 }
 
 impl Chat {
+    // 角色以带图标前缀的对话形式保存
+    pub fn is_role(&self) -> bool {
+        self.name.starts_with('\u{f544}')
+    }
+
+    pub fn is_group(&self) -> bool {
+        !self.group_members.is_empty()
+    }
+
+    pub fn is_journal(&self) -> bool {
+        self.journal.is_some()
+    }
+
     pub fn new(name: String) -> Self {
         let now = Utc::now();
         Self {
@@ -96,6 +473,21 @@ impl Chat {
             config: None,
             created_at: now,
             updated_at: now,
+            tasks: Vec::new(),
+            tags: Vec::new(),
+            archived: false,
+            role_id: None,
+            tee: None,
+            knowledge_folder: None,
+            group_members: Vec::new(),
+            memory: None,
+            journal: None,
+            model_override: None,
+            webhook_url: None,
+            incognito: false,
+            folder: None,
+            pinned: false,
+            disabled_post_processors: Vec::new(),
         }
     }
 
@@ -104,17 +496,239 @@ impl Chat {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+pub const CHAT_LIST_FILE: &str = "chat_list.json";
+
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct ChatList {
     pub chats: Vec<Chat>,
     pub current_chat_id: Option<String>,
+    // 用户创建的文件夹，按创建顺序显示在侧边栏
+    #[serde(default)]
+    pub folders: Vec<String>,
 }
 
-impl Default for ChatList {
-    fn default() -> Self {
-        Self {
-            chats: Vec::new(),
-            current_chat_id: None,
+// 发送请求时实际生效的配置
+#[derive(Clone, Debug, PartialEq)]
+pub struct EffectiveConfig {
+    pub model_name: String,
+    pub system_prompt: String,
+    pub params: SamplingParams,
+    pub provider: ProviderKind,
+    pub profile: Option<String>,
+}
+
+impl EffectiveConfig {
+    // 角色配置优先；角色未指定模型时沿用全局模型
+    pub fn resolve(chat_config: Option<&ChatConfig>, global: EffectiveConfig) -> EffectiveConfig {
+        match chat_config {
+            Some(config) => EffectiveConfig {
+                model_name: if config.model_name.trim().is_empty() {
+                    global.model_name
+                } else {
+                    config.model_name.clone()
+                },
+                system_prompt: config.system_prompt.clone(),
+                params: config.sampling_params(),
+                provider: config.provider.unwrap_or(global.provider),
+                // 角色指定了服务商但没有指定档案时，不沿用全局档案
+                profile: match (&config.profile, config.provider) {
+                    (Some(profile), _) => Some(profile.clone()),
+                    (None, Some(_)) => None,
+                    (None, None) => global.profile,
+                },
+            },
+            None => global,
+        }
+    }
+}
+
+// 采样参数组合
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct SamplingParams {
+    pub temperature: f32,
+    pub top_p: f32,
+    pub frequency_penalty: f32,
+    pub presence_penalty: f32,
+}
+
+// 面向普通用户的参数预设
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ParamPreset {
+    Precise,
+    Balanced,
+    Creative,
+}
+
+impl ParamPreset {
+    pub const ALL: [ParamPreset; 3] = [
+        ParamPreset::Precise,
+        ParamPreset::Balanced,
+        ParamPreset::Creative,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ParamPreset::Precise => "精确",
+            ParamPreset::Balanced => "平衡",
+            ParamPreset::Creative => "创意",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            ParamPreset::Precise => "Temperature 为 0，回答稳定、可复现",
+            ParamPreset::Balanced => "兼顾准确性与多样性",
+            ParamPreset::Creative => "更发散、更有想象力的回答",
+        }
+    }
+
+    pub fn params(&self) -> SamplingParams {
+        match self {
+            ParamPreset::Precise => SamplingParams {
+                temperature: 0.0,
+                top_p: 1.0,
+                frequency_penalty: 0.0,
+                presence_penalty: 0.0,
+            },
+            ParamPreset::Balanced => SamplingParams {
+                temperature: 0.7,
+                top_p: 1.0,
+                frequency_penalty: 0.0,
+                presence_penalty: 0.0,
+            },
+            ParamPreset::Creative => SamplingParams {
+                temperature: 1.2,
+                top_p: 0.95,
+                frequency_penalty: 0.3,
+                presence_penalty: 0.6,
+            },
+        }
+    }
+
+    // 查找与给定参数完全一致的预设
+    pub fn matching(params: SamplingParams) -> Option<ParamPreset> {
+        Self::ALL
+            .into_iter()
+            .find(|preset| preset.params() == params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn global() -> EffectiveConfig {
+        EffectiveConfig {
+            model_name: "gpt-4o-mini".to_string(),
+            system_prompt: "全局提示词".to_string(),
+            params: ParamPreset::Balanced.params(),
+            provider: ProviderKind::OpenAi,
+            profile: Some("工作".to_string()),
         }
     }
+
+    fn role() -> ChatConfig {
+        ChatConfig {
+            model_name: "claude-3-5-sonnet".to_string(),
+            system_prompt: "你是一位翻译".to_string(),
+            temperature: 0.2,
+            top_p: 0.9,
+            frequency_penalty: 0.5,
+            presence_penalty: 0.1,
+            greeting: None,
+            greeting_in_context: false,
+            provider: None,
+            filter_mode: None,
+            filter_terms: Vec::new(),
+            profile: None,
+        }
+    }
+
+    #[test]
+    fn without_role_uses_global() {
+        let effective = EffectiveConfig::resolve(None, global());
+        assert_eq!(effective.model_name, "gpt-4o-mini");
+        assert_eq!(effective.system_prompt, "全局提示词");
+        assert_eq!(effective.params, ParamPreset::Balanced.params());
+        assert_eq!(effective.provider, ProviderKind::OpenAi);
+        assert_eq!(effective.profile.as_deref(), Some("工作"));
+    }
+
+    #[test]
+    fn role_overrides_global() {
+        let role = role();
+        let effective = EffectiveConfig::resolve(Some(&role), global());
+        assert_eq!(effective.model_name, "claude-3-5-sonnet");
+        assert_eq!(effective.system_prompt, "你是一位翻译");
+        assert_eq!(effective.params, role.sampling_params());
+        // 角色没有指定服务商和档案时沿用全局设置
+        assert_eq!(effective.provider, ProviderKind::OpenAi);
+        assert_eq!(effective.profile.as_deref(), Some("工作"));
+    }
+
+    #[test]
+    fn role_without_model_falls_back_to_global_model() {
+        let role = ChatConfig {
+            model_name: "  ".to_string(),
+            ..role()
+        };
+        let effective = EffectiveConfig::resolve(Some(&role), global());
+        assert_eq!(effective.model_name, "gpt-4o-mini");
+        assert_eq!(effective.system_prompt, "你是一位翻译");
+    }
+
+    #[test]
+    fn role_profile_overrides_global_profile() {
+        let role = ChatConfig {
+            profile: Some("个人".to_string()),
+            ..role()
+        };
+        let effective = EffectiveConfig::resolve(Some(&role), global());
+        assert_eq!(effective.profile.as_deref(), Some("个人"));
+
+        let role = ChatConfig {
+            provider: Some(ProviderKind::Claude),
+            profile: Some("个人".to_string()),
+            ..role
+        };
+        let effective = EffectiveConfig::resolve(Some(&role), global());
+        assert_eq!(effective.provider, ProviderKind::Claude);
+        assert_eq!(effective.profile.as_deref(), Some("个人"));
+    }
+
+    #[test]
+    fn role_provider_without_profile_drops_global_profile() {
+        let role = ChatConfig {
+            provider: Some(ProviderKind::Gemini),
+            ..role()
+        };
+        let effective = EffectiveConfig::resolve(Some(&role), global());
+        assert_eq!(effective.provider, ProviderKind::Gemini);
+        // 全局档案属于另一个服务商，不能沿用
+        assert_eq!(effective.profile, None);
+    }
+
+    #[test]
+    fn retrieved_context_goes_before_the_question() {
+        let mut manifest = RequestManifest {
+            sent_at: Utc::now(),
+            provider: ProviderKind::OpenAi,
+            model: "gpt-4o-mini".to_string(),
+            system_prompt: String::new(),
+            params: ParamPreset::Balanced.params(),
+            entries: vec![
+                ManifestEntry::from_text("assistant", "你好"),
+                ManifestEntry::from_text("user", "报销流程是什么？"),
+            ],
+            overrides: None,
+        };
+        manifest.insert_retrieved(&["--- 报销.md ---".to_string()]);
+        let roles: Vec<&str> = manifest
+            .entries
+            .iter()
+            .map(|entry| entry.role.as_str())
+            .collect();
+        assert_eq!(roles, ["assistant", "system", "user"]);
+        assert_eq!(manifest.entries[1].excerpt, "--- 报销.md ---");
+    }
 }
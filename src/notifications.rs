@@ -0,0 +1,48 @@
+// 应用内通知：后台任务的结果（例如缓存清理）记录在这里，在通知窗口中查看
+use chrono::{DateTime, Local};
+
+// 只保留最近的通知
+const MAX_NOTIFICATIONS: usize = 50;
+
+#[derive(Clone)]
+pub struct Notification {
+    pub time: DateTime<Local>,
+    pub text: String,
+}
+
+#[derive(Default, Clone)]
+pub struct Notifications {
+    items: Vec<Notification>,
+    unread: usize,
+}
+
+impl Notifications {
+    pub fn push(&mut self, text: String) {
+        self.items.push(Notification {
+            time: Local::now(),
+            text,
+        });
+        if self.items.len() > MAX_NOTIFICATIONS {
+            self.items.remove(0);
+        }
+        self.unread = (self.unread + 1).min(self.items.len());
+    }
+
+    // 从新到旧
+    pub fn items(&self) -> impl Iterator<Item = &Notification> {
+        self.items.iter().rev()
+    }
+
+    pub fn unread(&self) -> usize {
+        self.unread
+    }
+
+    pub fn mark_read(&mut self) {
+        self.unread = 0;
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.unread = 0;
+    }
+}
@@ -0,0 +1,74 @@
+// 长回复的大纲：从 Markdown 语法树中取出标题，回复按标题拆成几段分别渲染，点击大纲即可滚动到对应的段落
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+
+// 标题数达到这个数量时才显示大纲
+pub const MIN_HEADINGS: usize = 3;
+
+pub struct Heading {
+    // 1 到 6
+    pub level: usize,
+    pub title: String,
+    // 标题在原文中的起始字节位置
+    pub start: usize,
+}
+
+pub fn headings(markdown: &str) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    let mut current: Option<Heading> = None;
+    let parser = Parser::new_ext(
+        markdown,
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH,
+    );
+    for (event, range) in parser.into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                current = Some(Heading {
+                    level: heading_level(level),
+                    title: String::new(),
+                    start: range.start,
+                });
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(heading) = current.as_mut() {
+                    heading.title.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some(heading) = current.take().filter(|h| !h.title.trim().is_empty()) {
+                    headings.push(heading);
+                }
+            }
+            _ => {}
+        }
+    }
+    headings
+}
+
+fn heading_level(level: HeadingLevel) -> usize {
+    match level {
+        HeadingLevel::H1 => 1,
+        HeadingLevel::H2 => 2,
+        HeadingLevel::H3 => 3,
+        HeadingLevel::H4 => 4,
+        HeadingLevel::H5 => 5,
+        HeadingLevel::H6 => 6,
+    }
+}
+
+// 按标题拆分原文，返回（第一个标题之前的内容，每个标题开始的段落）
+pub fn split<'a>(markdown: &'a str, headings: &[Heading]) -> (&'a str, Vec<&'a str>) {
+    let Some(first) = headings.first() else {
+        return (markdown, Vec::new());
+    };
+    let sections = headings
+        .iter()
+        .enumerate()
+        .map(|(index, heading)| {
+            let end = headings
+                .get(index + 1)
+                .map_or(markdown.len(), |next| next.start);
+            &markdown[heading.start..end]
+        })
+        .collect();
+    (&markdown[..first.start], sections)
+}
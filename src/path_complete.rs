@@ -0,0 +1,133 @@
+// 输入框中的文件路径补全：对话关联了文件夹时，输入 ./ 或 src/ 这样的路径会从该文件夹中模糊匹配文件
+use std::path::Path;
+use tokio::fs;
+
+// 文件夹中最多收集的路径数量，超出时只补全已收集的部分
+const MAX_PATHS: usize = 20_000;
+// 依赖和构建产物目录，补全时没有意义
+const SKIPPED_DIRS: [&str; 6] = [
+    "node_modules",
+    "target",
+    "dist",
+    "build",
+    "__pycache__",
+    "venv",
+];
+
+// 递归列出文件夹中的文件和目录，返回以 / 分隔的相对路径，目录以 / 结尾；跳过隐藏目录
+pub async fn list_paths(folder: &Path) -> std::io::Result<Vec<String>> {
+    let mut paths = Vec::new();
+    let mut pending = vec![folder.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') {
+                continue;
+            }
+            let path = entry.path();
+            let relative = path
+                .strip_prefix(folder)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            if entry.file_type().await?.is_dir() {
+                if SKIPPED_DIRS.contains(&name.as_str()) {
+                    continue;
+                }
+                paths.push(format!("{}/", relative));
+                pending.push(path);
+            } else {
+                paths.push(relative);
+            }
+            if paths.len() >= MAX_PATHS {
+                paths.sort();
+                return Ok(paths);
+            }
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+// 查找输入末尾正在输入的路径：以 ./ 开头，或包含 / 的一段不含空白的文字；网址不算
+pub fn pending_path(text: &str) -> Option<&str> {
+    let start = text
+        .rfind(|c: char| c.is_whitespace() || matches!(c, '`' | '(' | '"' | '\''))
+        .map_or(0, |index| {
+            index + text[index..].chars().next().map_or(1, char::len_utf8)
+        });
+    let token = &text[start..];
+    if token.is_empty() || token.contains("://") || token.starts_with("//") {
+        return None;
+    }
+    if token.starts_with("./") || token.contains('/') {
+        Some(token)
+    } else {
+        None
+    }
+}
+
+// 模糊匹配的得分，越大越好；查询中的字符按顺序出现在路径中才算匹配
+fn score(query: &str, path: &str) -> Option<i64> {
+    let query = query.to_lowercase();
+    let candidate = path.to_lowercase();
+    let file_start = candidate
+        .trim_end_matches('/')
+        .rfind('/')
+        .map_or(0, |i| i + 1);
+    if candidate.starts_with(&query) {
+        return Some(10_000 - path.len() as i64);
+    }
+    let mut score = 0;
+    let mut search_from = 0;
+    let mut previous = None;
+    for c in query.chars() {
+        let offset = candidate[search_from..].find(c)?;
+        let index = search_from + offset;
+        // 连续匹配和匹配在路径段开头的字符加分
+        if previous.is_some_and(|previous| previous + 1 == index) {
+            score += 5;
+        }
+        if index == 0 || candidate[..index].ends_with(['/', '_', '-', '.']) {
+            score += 3;
+        }
+        if index >= file_start {
+            score += 1;
+        }
+        previous = Some(index);
+        search_from = index + c.len_utf8();
+    }
+    Some(score * 10 - path.len() as i64)
+}
+
+// 按得分返回最匹配的若干路径
+pub fn search<'a>(paths: &'a [String], pending: &str, limit: usize) -> Vec<&'a str> {
+    let query = pending.trim_start_matches("./");
+    // 已经输入了完整的文件路径
+    if !query.ends_with('/') && paths.iter().any(|path| path == query) {
+        return Vec::new();
+    }
+    let mut matches: Vec<(i64, &str)> = paths
+        .iter()
+        .filter_map(|path| score(query, path).map(|score| (score, path.as_str())))
+        .collect();
+    matches.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+    matches
+        .into_iter()
+        .take(limit)
+        .map(|(_, path)| path)
+        .collect()
+}
+
+// 把输入末尾的路径替换为选中的路径，保留输入时的 ./ 前缀
+pub fn complete_pending(text: &mut String, path: &str) {
+    let Some(pending) = pending_path(text) else {
+        return;
+    };
+    let prefix = if pending.starts_with("./") { "./" } else { "" };
+    let new_len = text.len() - pending.len();
+    text.truncate(new_len);
+    text.push_str(prefix);
+    text.push_str(path);
+}
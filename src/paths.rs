@@ -0,0 +1,242 @@
+// 配置、数据和缓存的存放位置：使用系统约定的目录，与启动时的工作目录无关
+//
+// Linux 为 ~/.config/dream、~/.local/share/dream 和 ~/.cache/dream，
+// macOS 为 ~/Library/Application Support/com.ssyqq.dream 和 ~/Library/Caches/com.ssyqq.dream，
+// Windows 为 %APPDATA%\ssyqq\dream 和 %LOCALAPPDATA%\ssyqq\dream 下的 config、data 和 cache
+use crate::backup::BACKUP_DIR;
+use crate::config::CONFIG_FILE;
+use crate::models::CHAT_LIST_FILE;
+use crate::utils;
+use directories::ProjectDirs;
+use log::{debug, error};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+// 旧版本写在工作目录下的图片缓存目录，也是当时 image_dir 的默认值
+pub const LEGACY_IMAGE_DIR: &str = ".cache/images";
+
+struct Dirs {
+    config: PathBuf,
+    data: PathBuf,
+    cache: PathBuf,
+}
+
+// 首次使用时确定并创建目录；无法确定用户目录时退回到工作目录，与旧版本一致
+fn dirs() -> &'static Dirs {
+    static DIRS: OnceLock<Dirs> = OnceLock::new();
+    DIRS.get_or_init(|| {
+        let dirs = match ProjectDirs::from("com", "ssyqq", "dream") {
+            Some(project) => Dirs {
+                config: project.config_dir().to_path_buf(),
+                data: project.data_dir().to_path_buf(),
+                cache: project.cache_dir().to_path_buf(),
+            },
+            None => {
+                error!("无法确定用户目录，数据将保存在工作目录中");
+                Dirs {
+                    config: PathBuf::from("."),
+                    data: PathBuf::from("."),
+                    cache: PathBuf::from(".cache"),
+                }
+            }
+        };
+        for dir in [&dirs.config, &dirs.data, &dirs.cache] {
+            if let Err(e) = fs::create_dir_all(dir) {
+                error!("创建目录 {} 失败: {}", dir.display(), e);
+            }
+        }
+        debug!(
+            "配置目录: {}，数据目录: {}，缓存目录: {}",
+            dirs.config.display(),
+            dirs.data.display(),
+            dirs.cache.display()
+        );
+        dirs
+    })
+}
+
+pub fn config_dir() -> &'static Path {
+    &dirs().config
+}
+
+pub fn data_dir() -> &'static Path {
+    &dirs().data
+}
+
+pub fn config_file(name: &str) -> PathBuf {
+    dirs().config.join(name)
+}
+
+pub fn data_file(name: &str) -> PathBuf {
+    dirs().data.join(name)
+}
+
+pub fn cache_subdir(name: &str) -> PathBuf {
+    dirs().cache.join(name)
+}
+
+// 本机进程间通信的 socket 文件：放在只有当前用户能访问的运行时目录（$XDG_RUNTIME_DIR），
+// 没有时放在数据目录；不放在共享的临时目录中，避免被其他用户抢占或和其他用户的实例冲突
+#[cfg(unix)]
+pub fn socket_file(name: &str) -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir).join(name),
+        None => data_file(name),
+    }
+}
+
+// 监听 socket 文件：清理上次异常退出留下的 socket（不删除其他类型的文件），只允许当前用户连接
+#[cfg(unix)]
+pub fn bind_socket(path: &Path) -> io::Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    if fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        fs::remove_file(path)?;
+    }
+    let listener = tokio::net::UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+// 命名管道在整台机器上共用一个命名空间，名称中带上用户名，避免多个用户的实例冲突
+#[cfg(windows)]
+pub fn pipe_name(name: &str) -> String {
+    format!(
+        r"\\.\pipe\{}-{}",
+        name,
+        std::env::var("USERNAME").unwrap_or_default()
+    )
+}
+
+// 把旧版本写在工作目录下的文件移动到新目录；新目录中已有同名文件时保留新文件，不覆盖
+//
+// .cache/images、backups 这样的名称很常见（例如在主目录中启动时的 ~/.cache），
+// 只有工作目录中有旧版本的配置或对话记录、能确定是 dream 的目录时才迁移
+pub async fn migrate_legacy() {
+    if !Path::new(CONFIG_FILE).is_file() && !Path::new(CHAT_LIST_FILE).is_file() {
+        return;
+    }
+    let mut moves = vec![(PathBuf::from(CONFIG_FILE), config_file(CONFIG_FILE))];
+    for name in [
+        CHAT_LIST_FILE,
+        crate::costs::LEDGER_FILE,
+        crate::metrics::METRICS_FILE,
+        crate::memory_store::MEMORY_FILE,
+        BACKUP_DIR,
+    ] {
+        moves.push((PathBuf::from(name), data_file(name)));
+    }
+    for name in ["audio", "knowledge"] {
+        moves.push((Path::new(".cache").join(name), cache_subdir(name)));
+    }
+    // 损坏时用来恢复的 .bak 备份也一起移动
+    for (from, to) in moves.clone() {
+        moves.push((utils::backup_path(&from), utils::backup_path(&to)));
+    }
+
+    for (from, to) in moves {
+        if !from.exists() || to.exists() {
+            continue;
+        }
+        match move_path(&from, &to) {
+            Ok(()) => debug!("已将 {} 迁移到 {}", from.display(), to.display()),
+            Err(e) => error!("迁移 {} 失败: {}", from.display(), e),
+        }
+    }
+
+    if Path::new(LEGACY_IMAGE_DIR).is_dir() && !cache_subdir("images").exists() {
+        if let Err(e) = migrate_images().await {
+            error!("迁移图片缓存失败: {}", e);
+        }
+    }
+}
+
+// 移动图片缓存，并更新配置中的缓存目录和对话记录中的图片路径；
+// 只有配置中的图片缓存目录就是这个旧目录时才移动
+async fn migrate_images() -> io::Result<()> {
+    let to = cache_subdir("images");
+    let config_path = config_file(CONFIG_FILE);
+    let Some(mut config) = fs::read_to_string(&config_path)
+        .ok()
+        .and_then(|content| content.parse::<toml::Table>().ok())
+    else {
+        return Ok(());
+    };
+    let Some(image_dir) = config
+        .get_mut("cache")
+        .and_then(|cache| cache.get_mut("image_dir"))
+        .filter(|image_dir| image_dir.as_str() == Some(LEGACY_IMAGE_DIR))
+    else {
+        return Ok(());
+    };
+    *image_dir = toml::Value::String(to.to_string_lossy().to_string());
+    utils::write_atomic(&config_path, config.to_string()).await?;
+    move_path(Path::new(LEGACY_IMAGE_DIR), &to)?;
+
+    let chat_list_path = data_file(CHAT_LIST_FILE);
+    if let Ok(content) = fs::read_to_string(&chat_list_path) {
+        if let Ok(mut chat_list) = serde_json::from_str::<serde_json::Value>(&content) {
+            rewrite_image_paths(&mut chat_list, &to);
+            utils::write_atomic(&chat_list_path, serde_json::to_string_pretty(&chat_list)?).await?;
+        }
+    }
+    debug!("已将图片缓存迁移到 {}", to.display());
+    Ok(())
+}
+
+// 对话记录中以旧缓存目录开头的 image_path 改为新目录下的同名文件，包括重新生成前的旧版本
+fn rewrite_image_paths(value: &mut serde_json::Value, dir: &Path) {
+    match value {
+        serde_json::Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                match value {
+                    serde_json::Value::String(path) if key == "image_path" => {
+                        let legacy = Path::new(path.as_str());
+                        if let (Ok(_), Some(name)) =
+                            (legacy.strip_prefix(LEGACY_IMAGE_DIR), legacy.file_name())
+                        {
+                            *path = dir.join(name).to_string_lossy().to_string();
+                        }
+                    }
+                    _ => rewrite_image_paths(value, dir),
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                rewrite_image_paths(item, dir);
+            }
+        }
+        _ => {}
+    }
+}
+
+// 先尝试重命名，跨磁盘时复制后删除
+fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    copy_recursive(from, to)?;
+    if from.is_dir() {
+        fs::remove_dir_all(from)
+    } else {
+        fs::remove_file(from)
+    }
+}
+
+fn copy_recursive(from: &Path, to: &Path) -> io::Result<()> {
+    if !from.is_dir() {
+        return fs::copy(from, to).map(|_| ());
+    }
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,137 @@
+// 与操作系统桌面环境的集成：Windows 任务栏进度和跳转列表，其他平台为空实现
+
+// 跳转列表中显示的最近对话数量
+pub const RECENT_CHAT_LIMIT: usize = 5;
+
+#[derive(Default)]
+pub struct PlatformIntegration {
+    // 已发布到跳转列表的对话（ID 和名称），没有变化时不重复写入
+    published_chats: Vec<(String, String)>,
+    progress_shown: bool,
+    #[cfg(windows)]
+    taskbar: Option<windows_impl::Taskbar>,
+}
+
+impl PlatformIntegration {
+    // 更新跳转列表中的最近对话
+    pub fn update_recent_chats(&mut self, chats: Vec<(String, String)>) {
+        if chats == self.published_chats {
+            return;
+        }
+        #[cfg(windows)]
+        match std::env::current_exe() {
+            Ok(exe) => {
+                if let Err(e) = windows_impl::publish_jump_list(&exe, &chats) {
+                    log::error!("更新跳转列表失败: {}", e);
+                }
+            }
+            Err(e) => log::error!("获取程序路径失败: {}", e),
+        }
+        self.published_chats = chats;
+    }
+
+    // 生成回复期间在任务栏图标上显示进度
+    pub fn set_streaming(&mut self, frame: &eframe::Frame, streaming: bool) {
+        if streaming == self.progress_shown {
+            return;
+        }
+        self.progress_shown = streaming;
+        #[cfg(windows)]
+        {
+            if self.taskbar.is_none() {
+                self.taskbar = windows_impl::Taskbar::new(frame);
+            }
+            if let Some(taskbar) = &self.taskbar {
+                taskbar.set_progress(streaming);
+            }
+        }
+        #[cfg(not(windows))]
+        let _ = frame;
+    }
+}
+
+#[cfg(windows)]
+mod windows_impl {
+    use log::error;
+    use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+    use std::path::Path;
+    use windows::core::{Interface, Result, HSTRING, PROPVARIANT};
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::Storage::EnhancedStorage::PKEY_Title;
+    use windows::Win32::System::Com::{
+        CoCreateInstance, CoInitializeEx, CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED,
+    };
+    use windows::Win32::UI::Shell::Common::IObjectArray;
+    use windows::Win32::UI::Shell::PropertiesSystem::IPropertyStore;
+    use windows::Win32::UI::Shell::{
+        DestinationList, EnumerableObjectCollection, ICustomDestinationList, IObjectCollection,
+        IShellLinkW, ITaskbarList3, ShellLink, TaskbarList, TBPF_INDETERMINATE, TBPF_NOPROGRESS,
+    };
+
+    pub struct Taskbar {
+        list: ITaskbarList3,
+        hwnd: HWND,
+    }
+
+    impl Taskbar {
+        pub fn new(frame: &eframe::Frame) -> Option<Self> {
+            let hwnd = match frame.window_handle().ok()?.as_raw() {
+                RawWindowHandle::Win32(handle) => HWND(handle.hwnd.get() as *mut _),
+                _ => return None,
+            };
+            let list = unsafe {
+                let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+                let list: ITaskbarList3 =
+                    match CoCreateInstance(&TaskbarList, None, CLSCTX_INPROC_SERVER) {
+                        Ok(list) => list,
+                        Err(e) => {
+                            error!("初始化任务栏失败: {}", e);
+                            return None;
+                        }
+                    };
+                list.HrInit().ok()?;
+                list
+            };
+            Some(Self { list, hwnd })
+        }
+
+        pub fn set_progress(&self, streaming: bool) {
+            let state = if streaming {
+                TBPF_INDETERMINATE
+            } else {
+                TBPF_NOPROGRESS
+            };
+            if let Err(e) = unsafe { self.list.SetProgressState(self.hwnd, state) } {
+                error!("设置任务栏进度失败: {}", e);
+            }
+        }
+    }
+
+    // 每个条目以 open-chat <ID> 参数重新启动程序，由正在运行的实例打开对应对话
+    pub fn publish_jump_list(exe: &Path, chats: &[(String, String)]) -> Result<()> {
+        unsafe {
+            let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+            let list: ICustomDestinationList =
+                CoCreateInstance(&DestinationList, None, CLSCTX_INPROC_SERVER)?;
+            let mut max_slots = 0u32;
+            let _removed: IObjectArray = list.BeginList(&mut max_slots)?;
+
+            let collection: IObjectCollection =
+                CoCreateInstance(&EnumerableObjectCollection, None, CLSCTX_INPROC_SERVER)?;
+            for (id, name) in chats {
+                let link: IShellLinkW = CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER)?;
+                link.SetPath(&HSTRING::from(exe.as_os_str()))?;
+                link.SetArguments(&HSTRING::from(format!("open-chat {}", id)))?;
+                link.SetDescription(&HSTRING::from(name.as_str()))?;
+                let store: IPropertyStore = link.cast()?;
+                store.SetValue(&PKEY_Title, &PROPVARIANT::from(name.as_str()))?;
+                store.Commit()?;
+                collection.AddObject(&link)?;
+            }
+
+            let items: IObjectArray = collection.cast()?;
+            list.AppendCategory(&HSTRING::from("最近对话"), &items)?;
+            list.CommitList()
+        }
+    }
+}
@@ -0,0 +1,217 @@
+// 回复后处理：回复完成后、显示和保存之前，按顺序对助手的回复做变换（去掉套话、换算单位、自动翻译等）
+//
+// 每一步是正则替换或插件钩子；插件钩子在系统 shell 中运行命令，回复从标准输入传入，标准输出作为新的回复
+use crate::utils;
+use log::{debug, warn};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StepKind {
+    #[default]
+    Regex,
+    Hook,
+}
+
+impl StepKind {
+    pub const ALL: [StepKind; 2] = [StepKind::Regex, StepKind::Hook];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            StepKind::Regex => "正则替换",
+            StepKind::Hook => "插件钩子",
+        }
+    }
+}
+
+// 配置中的一个处理步骤，对话可以单独关闭其中的某些步骤
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PostProcessor {
+    pub name: String,
+    pub enabled: bool,
+    pub kind: StepKind,
+    // 正则替换：替换文本中可以用 $1、${name} 引用分组
+    pub pattern: String,
+    pub replacement: String,
+    // 插件钩子：要运行的命令
+    pub command: String,
+}
+
+impl Default for PostProcessor {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            enabled: true,
+            kind: StepKind::Regex,
+            pattern: String::new(),
+            replacement: String::new(),
+            command: String::new(),
+        }
+    }
+}
+
+impl PostProcessor {
+    // 设置中显示的名称，没有填写时用步骤类型代替
+    pub fn display_name(&self) -> &str {
+        if self.name.trim().is_empty() {
+            self.kind.label()
+        } else {
+            self.name.trim()
+        }
+    }
+
+    // 检查步骤是否有效，返回错误说明
+    pub fn validate(&self) -> Result<(), String> {
+        match self.kind {
+            StepKind::Regex if self.pattern.is_empty() => Err("正则表达式为空".to_string()),
+            StepKind::Regex => Regex::new(&self.pattern)
+                .map(|_| ())
+                .map_err(|e| format!("正则表达式无效: {}", e)),
+            StepKind::Hook if self.command.trim().is_empty() => Err("命令为空".to_string()),
+            StepKind::Hook => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Step {
+    Replace {
+        name: String,
+        regex: Regex,
+        replacement: String,
+    },
+    Hook {
+        name: String,
+        command: String,
+    },
+}
+
+// 编译后的处理流水线
+#[derive(Clone, Default)]
+pub struct Pipeline {
+    steps: Vec<Step>,
+}
+
+impl Pipeline {
+    // disabled 是对话中关闭的步骤名称；未开启插件功能时跳过插件钩子，无效的步骤跳过并记录日志
+    pub fn new(processors: &[PostProcessor], disabled: &[String], hooks_allowed: bool) -> Self {
+        let steps = processors
+            .iter()
+            .filter(|processor| processor.enabled)
+            .filter(|processor| !disabled.iter().any(|name| name == processor.display_name()))
+            .filter_map(|processor| {
+                let name = processor.display_name().to_string();
+                match processor.kind {
+                    StepKind::Regex => match Regex::new(&processor.pattern) {
+                        Ok(regex) if !processor.pattern.is_empty() => Some(Step::Replace {
+                            name,
+                            regex,
+                            replacement: processor.replacement.clone(),
+                        }),
+                        Ok(_) => None,
+                        Err(e) => {
+                            warn!("后处理步骤 {} 的正则表达式无效: {}", name, e);
+                            None
+                        }
+                    },
+                    StepKind::Hook if !hooks_allowed => {
+                        debug!("未开启插件功能，跳过后处理步骤 {}", name);
+                        None
+                    }
+                    StepKind::Hook if processor.command.trim().is_empty() => None,
+                    StepKind::Hook => Some(Step::Hook {
+                        name,
+                        command: processor.command.trim().to_string(),
+                    }),
+                }
+            })
+            .collect();
+        Self { steps }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    // 包含插件钩子时需要在后台运行
+    pub fn has_hooks(&self) -> bool {
+        self.steps
+            .iter()
+            .any(|step| matches!(step, Step::Hook { .. }))
+    }
+
+    // 依次运行所有步骤，返回处理后的文本和出错的步骤说明；出错的步骤保留上一步的结果
+    pub async fn run(&self, text: &str) -> (String, Vec<String>) {
+        let mut text = text.to_string();
+        let mut errors = Vec::new();
+        for step in &self.steps {
+            match step {
+                Step::Replace {
+                    name,
+                    regex,
+                    replacement,
+                } => {
+                    if regex.is_match(&text) {
+                        debug!("后处理步骤 {} 命中", name);
+                        text = regex.replace_all(&text, replacement.as_str()).into_owned();
+                    }
+                }
+                Step::Hook { name, command } => match run_hook(command, &text).await {
+                    Ok(output) => text = output,
+                    Err(e) => errors.push(format!("后处理步骤 {} 失败: {}", name, e)),
+                },
+            }
+        }
+        (text, errors)
+    }
+}
+
+// 后台处理完成的回复
+pub struct Processed {
+    pub chat_id: String,
+    pub index: usize,
+    pub raw: String,
+    pub text: String,
+    pub errors: Vec<String>,
+}
+
+async fn run_hook(command: &str, input: &str) -> Result<String, String> {
+    let mut child = utils::shell_command(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("无法启动命令: {}", e))?;
+    // 在单独的任务中写入，避免输出较多时双方都在等待管道
+    if let Some(mut stdin) = child.stdin.take() {
+        let input = input.to_string();
+        tokio::spawn(async move {
+            let _ = stdin.write_all(input.as_bytes()).await;
+        });
+    }
+    let output = tokio::time::timeout(HOOK_TIMEOUT, child.wait_with_output())
+        .await
+        .map_err(|_| format!("运行超过 {} 秒，已终止", HOOK_TIMEOUT.as_secs()))?
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(format!(
+            "退出码 {}: {}",
+            output.status.code().unwrap_or(-1),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let text = String::from_utf8_lossy(&output.stdout)
+        .trim_end()
+        .to_string();
+    if text.trim().is_empty() {
+        return Err("没有输出，保留原回复".to_string());
+    }
+    Ok(text)
+}
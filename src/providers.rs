@@ -0,0 +1,541 @@
+use crate::models::TokenUsage;
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value as JsonValue};
+
+const OPENAI_DEFAULT_ENDPOINT: &str = "https://api.openai.com/v1/chat/completions";
+const CLAUDE_DEFAULT_ENDPOINT: &str = "https://api.anthropic.com/v1/messages";
+// Gemini 的端点只包含 API 根地址，模型和方法在请求时拼接
+const GEMINI_DEFAULT_ENDPOINT: &str = "https://generativelanguage.googleapis.com/v1beta";
+const CLAUDE_API_VERSION: &str = "2023-06-01";
+// Claude 的 messages API 要求必须指定 max_tokens
+const CLAUDE_DEFAULT_MAX_TOKENS: u64 = 4096;
+
+// 可选的 API 服务商
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    #[default]
+    OpenAi,
+    Claude,
+    Gemini,
+    Azure,
+}
+
+impl ProviderKind {
+    pub const ALL: [ProviderKind; 4] = [
+        ProviderKind::OpenAi,
+        ProviderKind::Azure,
+        ProviderKind::Claude,
+        ProviderKind::Gemini,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ProviderKind::OpenAi => "OpenAI 兼容",
+            ProviderKind::Claude => "Anthropic Claude",
+            ProviderKind::Gemini => "Google Gemini",
+            ProviderKind::Azure => "Azure OpenAI",
+        }
+    }
+
+    pub fn default_endpoint(&self) -> &'static str {
+        match self {
+            ProviderKind::OpenAi => OPENAI_DEFAULT_ENDPOINT,
+            ProviderKind::Claude => CLAUDE_DEFAULT_ENDPOINT,
+            ProviderKind::Gemini => GEMINI_DEFAULT_ENDPOINT,
+            // Azure 的端点由资源名和部署 ID 拼接，没有通用默认值
+            ProviderKind::Azure => "",
+        }
+    }
+
+    pub fn provider(&self) -> &'static dyn Provider {
+        match self {
+            ProviderKind::OpenAi => &OpenAiProvider,
+            ProviderKind::Claude => &ClaudeProvider,
+            ProviderKind::Gemini => &GeminiProvider,
+            ProviderKind::Azure => &AzureProvider,
+        }
+    }
+}
+
+// 服务商的请求格式、认证方式和流式响应格式
+//
+// 请求体统一按 OpenAI chat-completions 格式构建，由各服务商转换为自己的格式
+pub trait Provider: Send + Sync {
+    // 添加认证头并转换请求体
+    fn build_request(
+        &self,
+        client: &Client,
+        endpoint: &str,
+        api_key: &str,
+        payload: &JsonValue,
+    ) -> RequestBuilder;
+
+    // 从一条流式事件中提取增量文本
+    fn stream_delta<'a>(&self, event: &'a JsonValue) -> Option<&'a str>;
+
+    // 从一条流式事件中提取模型返回的思考过程
+    fn reasoning_delta<'a>(&self, _event: &'a JsonValue) -> Option<&'a str> {
+        None
+    }
+
+    // 从一条流式事件中提取 token 用量
+    fn stream_usage(&self, _event: &JsonValue) -> Option<TokenUsage> {
+        None
+    }
+
+    // 事件是否表示流结束（OpenAI 使用 [DONE] 标记，不经过这里）
+    fn is_stream_end(&self, event: &JsonValue) -> bool;
+
+    // 从非流式响应中提取回复文本
+    fn response_text<'a>(&self, response: &'a JsonValue) -> Option<&'a str>;
+}
+
+// o1 / o3 / o4 系列推理模型
+pub fn is_reasoning_model(model: &str) -> bool {
+    let name = model.rsplit('/').next().unwrap_or(model);
+    ["o1", "o3", "o4"]
+        .iter()
+        .any(|prefix| name == *prefix || name.starts_with(&format!("{}-", prefix)))
+}
+
+// 推理模型不接受采样参数，系统提示词改用 developer 角色；
+// 其他模型不认识 reasoning_effort，需要去掉
+fn openai_payload(payload: &JsonValue) -> JsonValue {
+    let mut payload = payload.clone();
+    let model = payload["model"].as_str().unwrap_or_default().to_string();
+    let Some(object) = payload.as_object_mut() else {
+        return payload;
+    };
+    if !is_reasoning_model(&model) {
+        object.remove("reasoning_effort");
+        return payload;
+    }
+    for key in [
+        "temperature",
+        "top_p",
+        "frequency_penalty",
+        "presence_penalty",
+    ] {
+        object.remove(key);
+    }
+    if let Some(max_tokens) = object.remove("max_tokens") {
+        object.insert("max_completion_tokens".to_string(), max_tokens);
+    }
+    if let Some(messages) = object.get_mut("messages").and_then(JsonValue::as_array_mut) {
+        for message in messages {
+            if message["role"] == "system" {
+                message["role"] = json!("developer");
+            }
+        }
+    }
+    payload
+}
+
+pub struct OpenAiProvider;
+
+impl Provider for OpenAiProvider {
+    fn build_request(
+        &self,
+        client: &Client,
+        endpoint: &str,
+        api_key: &str,
+        payload: &JsonValue,
+    ) -> RequestBuilder {
+        client
+            .post(endpoint)
+            .header("Authorization", format!("Bearer {}", api_key))
+            .header("Content-Type", "application/json")
+            .json(&openai_payload(payload))
+    }
+
+    fn stream_delta<'a>(&self, event: &'a JsonValue) -> Option<&'a str> {
+        event["choices"][0]["delta"]["content"].as_str()
+    }
+
+    // DeepSeek、OpenRouter 等兼容接口会在 reasoning_content / reasoning 中返回思考过程
+    fn reasoning_delta<'a>(&self, event: &'a JsonValue) -> Option<&'a str> {
+        let delta = &event["choices"][0]["delta"];
+        delta["reasoning_content"]
+            .as_str()
+            .or_else(|| delta["reasoning"].as_str())
+    }
+
+    // 请求 stream_options.include_usage 后，最后一个事件携带用量
+    fn stream_usage(&self, event: &JsonValue) -> Option<TokenUsage> {
+        let usage = event.get("usage").filter(|usage| usage.is_object())?;
+        Some(TokenUsage {
+            prompt_tokens: usage["prompt_tokens"].as_u64().unwrap_or(0),
+            completion_tokens: usage["completion_tokens"].as_u64().unwrap_or(0),
+        })
+    }
+
+    fn is_stream_end(&self, _event: &JsonValue) -> bool {
+        false
+    }
+
+    fn response_text<'a>(&self, response: &'a JsonValue) -> Option<&'a str> {
+        response["choices"][0]["message"]["content"].as_str()
+    }
+}
+
+// Azure OpenAI 使用与 OpenAI 相同的请求和流式格式，只是地址和认证方式不同
+pub struct AzureProvider;
+
+impl Provider for AzureProvider {
+    fn build_request(
+        &self,
+        client: &Client,
+        endpoint: &str,
+        api_key: &str,
+        payload: &JsonValue,
+    ) -> RequestBuilder {
+        let model = payload["model"].as_str().unwrap_or_default();
+        client
+            .post(endpoint.replace("{deployment}", model))
+            .header("api-key", api_key)
+            .header("Content-Type", "application/json")
+            .json(&openai_payload(payload))
+    }
+
+    fn stream_delta<'a>(&self, event: &'a JsonValue) -> Option<&'a str> {
+        OpenAiProvider.stream_delta(event)
+    }
+
+    fn reasoning_delta<'a>(&self, event: &'a JsonValue) -> Option<&'a str> {
+        OpenAiProvider.reasoning_delta(event)
+    }
+
+    fn stream_usage(&self, event: &JsonValue) -> Option<TokenUsage> {
+        OpenAiProvider.stream_usage(event)
+    }
+
+    fn is_stream_end(&self, event: &JsonValue) -> bool {
+        OpenAiProvider.is_stream_end(event)
+    }
+
+    fn response_text<'a>(&self, response: &'a JsonValue) -> Option<&'a str> {
+        OpenAiProvider.response_text(response)
+    }
+}
+
+pub struct ClaudeProvider;
+
+impl ClaudeProvider {
+    // 将 OpenAI 格式的请求体转换为 Claude messages API 格式
+    fn convert_payload(payload: &JsonValue) -> JsonValue {
+        let mut system_parts = Vec::new();
+        let mut messages = Vec::new();
+        for message in payload["messages"].as_array().into_iter().flatten() {
+            let role = message["role"].as_str().unwrap_or("user");
+            if role == "system" {
+                if let Some(text) = message["content"].as_str() {
+                    system_parts.push(text.to_string());
+                }
+                continue;
+            }
+            messages.push(json!({
+                "role": role,
+                "content": Self::convert_content(&message["content"]),
+            }));
+        }
+
+        let mut body = Map::new();
+        body.insert("model".to_string(), payload["model"].clone());
+        body.insert("messages".to_string(), JsonValue::Array(messages));
+        body.insert(
+            "max_tokens".to_string(),
+            json!(payload["max_tokens"]
+                .as_u64()
+                .unwrap_or(CLAUDE_DEFAULT_MAX_TOKENS)),
+        );
+        if !system_parts.is_empty() {
+            body.insert("system".to_string(), json!(system_parts.join("\n\n")));
+        }
+        // Claude 的 temperature 范围是 0 到 1，且不支持频率/存在惩罚
+        if let Some(temperature) = payload["temperature"].as_f64() {
+            body.insert(
+                "temperature".to_string(),
+                json!(temperature.clamp(0.0, 1.0)),
+            );
+        }
+        if let Some(top_p) = payload["top_p"].as_f64() {
+            body.insert("top_p".to_string(), json!(top_p));
+        }
+        if let Some(stream) = payload["stream"].as_bool() {
+            body.insert("stream".to_string(), json!(stream));
+        }
+        JsonValue::Object(body)
+    }
+
+    // 图片从 data URL 转换为 base64 图片块
+    fn convert_content(content: &JsonValue) -> JsonValue {
+        let Some(parts) = content.as_array() else {
+            return content.clone();
+        };
+        let converted: Vec<JsonValue> = parts
+            .iter()
+            .map(|part| match part["type"].as_str() {
+                Some("image_url") => {
+                    let url = part["image_url"]["url"].as_str().unwrap_or_default();
+                    let (media_type, data) = url
+                        .strip_prefix("data:")
+                        .and_then(|rest| rest.split_once(";base64,"))
+                        .unwrap_or(("image/jpeg", url));
+                    json!({
+                        "type": "image",
+                        "source": {
+                            "type": "base64",
+                            "media_type": media_type,
+                            "data": data,
+                        }
+                    })
+                }
+                _ => part.clone(),
+            })
+            .collect();
+        JsonValue::Array(converted)
+    }
+}
+
+impl Provider for ClaudeProvider {
+    fn build_request(
+        &self,
+        client: &Client,
+        endpoint: &str,
+        api_key: &str,
+        payload: &JsonValue,
+    ) -> RequestBuilder {
+        client
+            .post(endpoint)
+            .header("x-api-key", api_key)
+            .header("anthropic-version", CLAUDE_API_VERSION)
+            .header("Content-Type", "application/json")
+            .json(&Self::convert_payload(payload))
+    }
+
+    fn stream_delta<'a>(&self, event: &'a JsonValue) -> Option<&'a str> {
+        if event["type"] == "content_block_delta" {
+            event["delta"]["text"].as_str()
+        } else {
+            None
+        }
+    }
+
+    // 扩展思考以 thinking_delta 的形式返回
+    fn reasoning_delta<'a>(&self, event: &'a JsonValue) -> Option<&'a str> {
+        if event["type"] == "content_block_delta" && event["delta"]["type"] == "thinking_delta" {
+            event["delta"]["thinking"].as_str()
+        } else {
+            None
+        }
+    }
+
+    // 输入用量在 message_start 中，输出用量在 message_delta 中
+    fn stream_usage(&self, event: &JsonValue) -> Option<TokenUsage> {
+        let usage = if event["type"] == "message_start" {
+            &event["message"]["usage"]
+        } else if event["type"] == "message_delta" {
+            &event["usage"]
+        } else {
+            return None;
+        };
+        if !usage.is_object() {
+            return None;
+        }
+        Some(TokenUsage {
+            prompt_tokens: usage["input_tokens"].as_u64().unwrap_or(0),
+            completion_tokens: usage["output_tokens"].as_u64().unwrap_or(0),
+        })
+    }
+
+    fn is_stream_end(&self, event: &JsonValue) -> bool {
+        event["type"] == "message_stop"
+    }
+
+    fn response_text<'a>(&self, response: &'a JsonValue) -> Option<&'a str> {
+        response["content"]
+            .as_array()?
+            .iter()
+            .find(|block| block["type"] == "text")?["text"]
+            .as_str()
+    }
+}
+
+pub struct GeminiProvider;
+
+impl GeminiProvider {
+    // 将 OpenAI 格式的请求体转换为 Gemini generateContent 格式
+    fn convert_payload(payload: &JsonValue) -> JsonValue {
+        let mut system_parts = Vec::new();
+        let mut contents = Vec::new();
+        for message in payload["messages"].as_array().into_iter().flatten() {
+            let role = match message["role"].as_str().unwrap_or("user") {
+                "system" => {
+                    if let Some(text) = message["content"].as_str() {
+                        system_parts.push(json!({ "text": text }));
+                    }
+                    continue;
+                }
+                "assistant" => "model",
+                _ => "user",
+            };
+            contents.push(json!({
+                "role": role,
+                "parts": Self::convert_parts(&message["content"]),
+            }));
+        }
+
+        let mut generation_config = Map::new();
+        for (from, to) in [
+            ("temperature", "temperature"),
+            ("top_p", "topP"),
+            ("max_tokens", "maxOutputTokens"),
+            ("frequency_penalty", "frequencyPenalty"),
+            ("presence_penalty", "presencePenalty"),
+        ] {
+            if let Some(value) = payload.get(from).filter(|v| !v.is_null()) {
+                generation_config.insert(to.to_string(), value.clone());
+            }
+        }
+
+        let mut body = Map::new();
+        body.insert("contents".to_string(), JsonValue::Array(contents));
+        if !system_parts.is_empty() {
+            body.insert(
+                "systemInstruction".to_string(),
+                json!({ "parts": system_parts }),
+            );
+        }
+        if !generation_config.is_empty() {
+            body.insert(
+                "generationConfig".to_string(),
+                JsonValue::Object(generation_config),
+            );
+        }
+        JsonValue::Object(body)
+    }
+
+    // 文本和图片都转换为 parts，图片使用 inline_data
+    fn convert_parts(content: &JsonValue) -> JsonValue {
+        let Some(parts) = content.as_array() else {
+            return json!([{ "text": content.as_str().unwrap_or_default() }]);
+        };
+        let converted: Vec<JsonValue> = parts
+            .iter()
+            .map(|part| match part["type"].as_str() {
+                Some("image_url") => {
+                    let url = part["image_url"]["url"].as_str().unwrap_or_default();
+                    let (mime_type, data) = url
+                        .strip_prefix("data:")
+                        .and_then(|rest| rest.split_once(";base64,"))
+                        .unwrap_or(("image/jpeg", url));
+                    json!({
+                        "inline_data": {
+                            "mime_type": mime_type,
+                            "data": data,
+                        }
+                    })
+                }
+                _ => json!({ "text": part["text"].as_str().unwrap_or_default() }),
+            })
+            .collect();
+        JsonValue::Array(converted)
+    }
+}
+
+impl Provider for GeminiProvider {
+    fn build_request(
+        &self,
+        client: &Client,
+        endpoint: &str,
+        api_key: &str,
+        payload: &JsonValue,
+    ) -> RequestBuilder {
+        let model = payload["model"].as_str().unwrap_or_default();
+        let stream = payload["stream"].as_bool().unwrap_or(false);
+        let method = if stream {
+            "streamGenerateContent"
+        } else {
+            "generateContent"
+        };
+        let url = format!(
+            "{}/models/{}:{}",
+            endpoint.trim_end_matches('/'),
+            model,
+            method
+        );
+
+        let mut request = client.post(url).query(&[("key", api_key)]);
+        if stream {
+            // 使用 SSE 格式，与其他服务商共用解析逻辑
+            request = request.query(&[("alt", "sse")]);
+        }
+        request
+            .header("Content-Type", "application/json")
+            .json(&Self::convert_payload(payload))
+    }
+
+    fn stream_delta<'a>(&self, event: &'a JsonValue) -> Option<&'a str> {
+        let part = &event["candidates"][0]["content"]["parts"][0];
+        if part["thought"] == true {
+            return None;
+        }
+        part["text"].as_str()
+    }
+
+    // 思考摘要以 thought 为 true 的片段返回
+    fn reasoning_delta<'a>(&self, event: &'a JsonValue) -> Option<&'a str> {
+        let part = &event["candidates"][0]["content"]["parts"][0];
+        if part["thought"] == true {
+            part["text"].as_str()
+        } else {
+            None
+        }
+    }
+
+    // 每个事件都携带累计的用量
+    fn stream_usage(&self, event: &JsonValue) -> Option<TokenUsage> {
+        let usage = event
+            .get("usageMetadata")
+            .filter(|usage| usage.is_object())?;
+        Some(TokenUsage {
+            prompt_tokens: usage["promptTokenCount"].as_u64().unwrap_or(0),
+            completion_tokens: usage["candidatesTokenCount"].as_u64().unwrap_or(0),
+        })
+    }
+
+    fn is_stream_end(&self, event: &JsonValue) -> bool {
+        event["candidates"][0]["finishReason"].is_string()
+    }
+
+    fn response_text<'a>(&self, response: &'a JsonValue) -> Option<&'a str> {
+        response["candidates"][0]["content"]["parts"][0]["text"].as_str()
+    }
+}
+
+// 一次请求的目标：服务商、地址和密钥
+#[derive(Clone, Debug)]
+pub struct ApiTarget {
+    pub provider: ProviderKind,
+    pub endpoint: String,
+    pub api_key: String,
+}
+
+impl ApiTarget {
+    // 实际请求的地址，未填写时使用服务商的默认地址
+    pub fn url(&self) -> &str {
+        if self.endpoint.trim().is_empty() {
+            self.provider.default_endpoint()
+        } else {
+            self.endpoint.trim()
+        }
+    }
+
+    pub fn request(&self, client: &Client, payload: &JsonValue) -> RequestBuilder {
+        self.provider
+            .provider()
+            .build_request(client, self.url(), &self.api_key, payload)
+    }
+}
@@ -0,0 +1,134 @@
+use crate::events::UiEvent;
+use serde_json::{json, Value as JsonValue};
+use std::sync::LazyLock;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+// 主选区内容过长时截断，避免误选整页内容
+const MAX_SELECTION_CHARS: usize = 8000;
+
+// 选区的所有者没有响应时剪贴板工具会一直等待，超时后换下一个工具
+const SELECTION_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub const DEFAULT_PROMPT: &str = "解释一下这段内容：";
+
+// 在桌面环境中绑定快捷键用的命令，程序路径在运行期间不会变化
+pub static COMMAND: LazyLock<String> = LazyLock::new(|| {
+    let exe = std::env::current_exe().map_or_else(
+        |_| "dream".to_string(),
+        |exe| exe.to_string_lossy().to_string(),
+    );
+    format!("{} quick-ask", exe)
+});
+
+// 依次尝试 Wayland 和 X11 的命令行工具读取主选区（鼠标中键缓冲区）
+#[cfg(target_os = "linux")]
+pub async fn read_primary_selection() -> Result<String, String> {
+    let commands: [(&str, &[&str]); 3] = [
+        ("wl-paste", &["--primary", "--no-newline"]),
+        ("xclip", &["-o", "-selection", "primary"]),
+        ("xsel", &["--primary", "--output"]),
+    ];
+    for (program, args) in commands {
+        let output = tokio::process::Command::new(program)
+            .args(args)
+            .kill_on_drop(true)
+            .output();
+        match tokio::time::timeout(SELECTION_TIMEOUT, output).await {
+            Ok(Ok(output)) if output.status.success() => {
+                let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                if text.is_empty() {
+                    return Err("主选区为空，请先用鼠标选中文字".to_string());
+                }
+                return Ok(text.chars().take(MAX_SELECTION_CHARS).collect());
+            }
+            _ => continue,
+        }
+    }
+    Err("无法读取主选区，请安装 wl-clipboard、xclip 或 xsel".to_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub async fn read_primary_selection() -> Result<String, String> {
+    Err("主选区快速提问仅支持 Linux".to_string())
+}
+
+// 构建快速提问请求
+pub fn build_payload(model: &str, prompt: &str, selection: &str) -> JsonValue {
+    json!({
+        "model": model,
+        "messages": [{
+            "role": "user",
+            "content": format!("{}\n\n{}", prompt.trim(), selection)
+        }],
+        "stream": true
+    })
+}
+
+// 悬浮窗中的一次快速提问
+pub struct QuickAsk {
+    pub selection: String,
+    pub answer: String,
+    pub error: Option<String>,
+    pub receiver: Option<mpsc::UnboundedReceiver<UiEvent>>,
+    // 正在后台读取的主选区
+    pub selection_receiver: Option<oneshot::Receiver<Result<String, String>>>,
+}
+
+impl QuickAsk {
+    pub fn failed(error: String) -> Self {
+        Self {
+            selection: String::new(),
+            answer: String::new(),
+            error: Some(error),
+            receiver: None,
+            selection_receiver: None,
+        }
+    }
+
+    pub fn reading(selection_receiver: oneshot::Receiver<Result<String, String>>) -> Self {
+        Self {
+            selection: String::new(),
+            answer: String::new(),
+            error: None,
+            receiver: None,
+            selection_receiver: Some(selection_receiver),
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.receiver.is_some() || self.selection_receiver.is_some()
+    }
+
+    // 主选区读取完成时返回结果
+    pub fn take_selection(&mut self) -> Option<Result<String, String>> {
+        let receiver = self.selection_receiver.as_mut()?;
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(oneshot::error::TryRecvError::Empty) => return None,
+            Err(oneshot::error::TryRecvError::Closed) => Err("读取主选区失败".to_string()),
+        };
+        self.selection_receiver = None;
+        Some(result)
+    }
+
+    // 读取已到达的流式片段
+    pub fn poll(&mut self) {
+        let Some(receiver) = &mut self.receiver else {
+            return;
+        };
+        let mut done = false;
+        while let Ok(event) = receiver.try_recv() {
+            match event {
+                UiEvent::Done => done = true,
+                UiEvent::ClearNotices => self.error = None,
+                UiEvent::Notice(text) | UiEvent::Error(text) => self.error = Some(text),
+                UiEvent::Delta(text) => self.answer.push_str(&text),
+                _ => {}
+            }
+        }
+        if done {
+            self.receiver = None;
+        }
+    }
+}
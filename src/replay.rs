@@ -0,0 +1,39 @@
+use crate::models::Message;
+use crate::reply_stream::ReplyStream;
+use std::collections::VecDeque;
+
+// 将已有对话的用户消息逐条发送给新模型，回复写入新建的对话
+pub struct Replay {
+    pub source_name: String,
+    pub chat_id: String,
+    pub model: String,
+    pub turns: VecDeque<Message>,
+    pub total: usize,
+    pub failed: usize,
+    // 正在接收回复的一轮
+    pub stream: Option<ReplyStream>,
+}
+
+impl Replay {
+    pub fn new(source_name: String, chat_id: String, model: String, messages: &[Message]) -> Self {
+        let turns: VecDeque<Message> = messages
+            .iter()
+            .filter(|msg| msg.role == "user")
+            .cloned()
+            .collect();
+        Self {
+            source_name,
+            chat_id,
+            model,
+            total: turns.len(),
+            turns,
+            failed: 0,
+            stream: None,
+        }
+    }
+
+    // 已完成的轮数
+    pub fn completed(&self) -> usize {
+        self.total - self.turns.len() - usize::from(self.stream.is_some())
+    }
+}
@@ -0,0 +1,116 @@
+use crate::config::ReasoningEffort;
+use crate::events::UiEvent;
+use crate::models::{self, Message, RequestManifest, SamplingParams};
+use serde_json::{json, Value as JsonValue};
+use tokio::sync::mpsc;
+
+// 在主消息流之外单独接收的一条回复（单条重试、批量重放）
+pub struct ReplyStream {
+    pub reply: Message,
+    pub error: Option<String>,
+    receiver: mpsc::UnboundedReceiver<UiEvent>,
+}
+
+impl ReplyStream {
+    pub fn new(manifest: RequestManifest, receiver: mpsc::UnboundedReceiver<UiEvent>) -> Self {
+        let mut reply = Message::new_assistant(String::new());
+        reply.request_manifest = Some(manifest);
+        Self {
+            reply,
+            error: None,
+            receiver,
+        }
+    }
+
+    // 读取已到达的流式片段，返回是否已经完成（任务被取消时也视为完成）
+    pub fn poll(&mut self) -> bool {
+        loop {
+            let event = match self.receiver.try_recv() {
+                Ok(event) => event,
+                Err(mpsc::error::TryRecvError::Empty) => return false,
+                Err(mpsc::error::TryRecvError::Disconnected) => {
+                    self.error.get_or_insert_with(|| "请求已取消".to_string());
+                    return true;
+                }
+            };
+            match event {
+                UiEvent::Done => return true,
+                UiEvent::ClearNotices => self.error = None,
+                UiEvent::Usage(usage) => self.reply.usage = Some(usage),
+                UiEvent::Reasoning(text) => {
+                    self.reply
+                        .reasoning
+                        .get_or_insert_with(String::new)
+                        .push_str(&text);
+                }
+                UiEvent::Notice(text) | UiEvent::Error(text) => self.error = Some(text),
+                UiEvent::Delta(text) => self.reply.content.push_str(&text),
+                UiEvent::Retrieved(contexts) => {
+                    if let Some(manifest) = self.reply.request_manifest.as_mut() {
+                        manifest.insert_retrieved(&contexts);
+                    }
+                }
+                UiEvent::ImageResolved(_)
+                | UiEvent::Inspect(_)
+                | UiEvent::Stalled(_)
+                | UiEvent::Resumed => {}
+            }
+        }
+    }
+}
+
+// 构建请求的消息列表；directives 作为系统消息放在最后一条消息之前，非对话消息不会发送
+pub async fn build_messages(
+    system_prompt: &str,
+    greeting: Option<&str>,
+    history: &[Message],
+    directives: &[String],
+) -> Vec<JsonValue> {
+    let mut messages = vec![json!({
+        "role": "system",
+        "content": system_prompt
+    })];
+    if let Some(greeting) = greeting {
+        messages.push(json!({
+            "role": "assistant",
+            "content": greeting
+        }));
+    }
+    let history: Vec<&Message> = models::context_messages(history).collect();
+    for (position, msg) in history.iter().enumerate() {
+        if position + 1 == history.len() {
+            for directive in directives.iter().filter(|text| !text.is_empty()) {
+                messages.push(json!({
+                    "role": "system",
+                    "content": directive
+                }));
+            }
+        }
+        if let Ok(content) = msg.to_api_content().await {
+            messages.push(json!({
+                "role": msg.role,
+                "content": content
+            }));
+        }
+    }
+    messages
+}
+
+pub fn stream_payload(
+    model: &str,
+    messages: Vec<JsonValue>,
+    params: SamplingParams,
+    reasoning_effort: ReasoningEffort,
+) -> JsonValue {
+    json!({
+        "model": model,
+        "messages": messages,
+        "temperature": params.temperature,
+        "top_p": params.top_p,
+        "frequency_penalty": params.frequency_penalty,
+        "presence_penalty": params.presence_penalty,
+        "reasoning_effort": reasoning_effort.api_value(),
+        "stream": true,
+        "stream_options": { "include_usage": true }
+    })
+}
@@ -0,0 +1,77 @@
+use crate::doh::{BoxError, DohResolver};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+
+// 连接时使用的 IP 协议版本；部分 VPN 下 IPv6 无法访问 API
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum IpPreference {
+    #[default]
+    Auto,
+    Ipv4Only,
+    Ipv6Only,
+}
+
+impl IpPreference {
+    pub const ALL: [IpPreference; 3] = [
+        IpPreference::Auto,
+        IpPreference::Ipv4Only,
+        IpPreference::Ipv6Only,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            IpPreference::Auto => "自动",
+            IpPreference::Ipv4Only => "仅 IPv4",
+            IpPreference::Ipv6Only => "仅 IPv6",
+        }
+    }
+
+    fn allows(&self, ip: &IpAddr) -> bool {
+        match self {
+            IpPreference::Auto => true,
+            IpPreference::Ipv4Only => ip.is_ipv4(),
+            IpPreference::Ipv6Only => ip.is_ipv6(),
+        }
+    }
+}
+
+// 使用 DoH 或系统 DNS 解析域名，并按协议版本过滤结果
+#[derive(Clone)]
+pub struct AppResolver {
+    pub doh: Option<DohResolver>,
+    pub preference: IpPreference,
+}
+
+impl AppResolver {
+    async fn lookup(&self, host: &str) -> Result<Vec<IpAddr>, BoxError> {
+        let addrs = match &self.doh {
+            Some(doh) => doh.lookup(host).await?,
+            None => tokio::net::lookup_host((host, 0))
+                .await?
+                .map(|addr| addr.ip())
+                .collect(),
+        };
+        let addrs: Vec<IpAddr> = addrs
+            .into_iter()
+            .filter(|ip| self.preference.allows(ip))
+            .collect();
+        if addrs.is_empty() {
+            return Err(format!("{} 没有{}地址", host, self.preference.label()).into());
+        }
+        Ok(addrs)
+    }
+}
+
+impl Resolve for AppResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.clone();
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs = resolver.lookup(&host).await?;
+            // 端口由连接器按请求地址填写
+            let addrs: Addrs = Box::new(addrs.into_iter().map(|ip| SocketAddr::new(ip, 0)));
+            Ok(addrs)
+        })
+    }
+}
@@ -0,0 +1,66 @@
+use crate::events::UiEvent;
+use crate::knowledge;
+use crate::memory_store::{self, MemoryEntry};
+use crate::models::Message;
+use crate::providers::ApiTarget;
+use log::error;
+use reqwest::Client;
+use tokio::sync::mpsc;
+
+// 发送请求前按问题检索的补充上下文（知识库片段和长期记忆），所有构建请求的地方共用
+#[derive(Clone, Default)]
+pub struct Retrieval {
+    // 关联了知识库的对话
+    pub knowledge_chat_id: Option<String>,
+    pub knowledge_top_k: usize,
+    // 未开启长期记忆时为空
+    pub memories: Vec<MemoryEntry>,
+    pub embedding_model: String,
+}
+
+impl Retrieval {
+    // 按问题检索，返回放在问题前面的系统消息；检索结果同时发给界面记录到请求清单中，检索失败时只记录日志
+    pub async fn retrieve(
+        &self,
+        client: &Client,
+        target: &ApiTarget,
+        question: &Message,
+        tx: &mpsc::UnboundedSender<UiEvent>,
+    ) -> Vec<String> {
+        let mut contexts = Vec::new();
+        if let Some(chat_id) = &self.knowledge_chat_id {
+            match knowledge::retrieve_context(
+                client,
+                target,
+                chat_id,
+                question,
+                self.knowledge_top_k,
+            )
+            .await
+            {
+                Ok(Some(context)) => contexts.push(context),
+                Ok(None) => {}
+                Err(e) => error!("知识库检索失败: {}", e),
+            }
+        }
+        if !self.memories.is_empty() {
+            match memory_store::retrieve_context(
+                client,
+                target,
+                &self.embedding_model,
+                &self.memories,
+                &question.content,
+            )
+            .await
+            {
+                Ok(Some(context)) => contexts.push(context),
+                Ok(None) => {}
+                Err(e) => error!("长期记忆检索失败: {}", e),
+            }
+        }
+        if !contexts.is_empty() {
+            let _ = tx.send(UiEvent::Retrieved(contexts.clone()));
+        }
+        contexts
+    }
+}
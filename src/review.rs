@@ -0,0 +1,242 @@
+// 代码审查模式：附件是 git diff / 补丁时，要求模型按“文件:行号”给出意见，并把意见对应到渲染后的差异上
+use crate::models::Message;
+use regex::Regex;
+use std::sync::LazyLock;
+
+pub const REVIEW_DIRECTIVE: &str = "用户附加了代码差异，请进行代码审查。每条意见单独成段，以“文件路径:行号”开头（行号使用修改后的文件中的行号），例如“src/main.rs:42 这里可能会越界”，最后给出总体评价。";
+
+// 行内的文件引用：path/to/file.ext:12、path/to/file.ext 第 12 行、path/to/file.ext line 12
+static FILE_REFERENCE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"([\w./\-]+\.\w+)(?:`)?(?::(\d+)|\s*(?:第\s*(\d+)\s*行|(?i:line)\s*(\d+)))?")
+        .unwrap()
+});
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineKind {
+    Hunk,
+    Context,
+    Added,
+    Removed,
+}
+
+pub struct DiffLine {
+    pub kind: LineKind,
+    pub old: Option<u32>,
+    pub new: Option<u32>,
+    pub text: String,
+}
+
+pub struct DiffFile {
+    pub path: String,
+    pub lines: Vec<DiffLine>,
+}
+
+pub struct ReviewComment {
+    // 对应的差异文件序号
+    pub file: usize,
+    pub line: Option<u32>,
+    pub text: String,
+}
+
+pub fn is_diff(text: &str) -> bool {
+    let mut has_old = false;
+    let mut has_new = false;
+    for line in text.lines() {
+        if line.starts_with("diff --git ") {
+            return true;
+        }
+        has_old |= line.starts_with("--- ");
+        has_new |= line.starts_with("+++ ");
+        if has_old && has_new && line.starts_with("@@ ") {
+            return true;
+        }
+    }
+    false
+}
+
+// 消息附带的差异：优先使用附件，其次是正文
+pub fn message_diff(message: &Message) -> Option<&str> {
+    message
+        .text_attachments
+        .iter()
+        .map(|attachment| attachment.content.as_str())
+        .chain([message.content.as_str()])
+        .find(|text| is_diff(text))
+}
+
+pub fn parse_diff(text: &str) -> Vec<DiffFile> {
+    let mut files: Vec<DiffFile> = Vec::new();
+    let (mut old, mut new) = (0, 0);
+    for line in text.lines() {
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            let path = rest.split(" b/").nth(1).unwrap_or(rest);
+            files.push(DiffFile {
+                path: path.to_string(),
+                lines: Vec::new(),
+            });
+            continue;
+        }
+        if let Some(path) = line.strip_prefix("+++ ") {
+            let path = path
+                .trim_start_matches("b/")
+                .split('\t')
+                .next()
+                .unwrap_or(path);
+            match files.last_mut() {
+                Some(file) if file.lines.is_empty() => file.path = path.to_string(),
+                _ => files.push(DiffFile {
+                    path: path.to_string(),
+                    lines: Vec::new(),
+                }),
+            }
+            continue;
+        }
+        if line.starts_with("--- ") || line.starts_with("index ") {
+            continue;
+        }
+        let Some(file) = files.last_mut() else {
+            continue;
+        };
+        if let Some(header) = line.strip_prefix("@@ ") {
+            let mut ranges = header.split_whitespace();
+            old = hunk_start(ranges.next(), '-');
+            new = hunk_start(ranges.next(), '+');
+            file.lines.push(DiffLine {
+                kind: LineKind::Hunk,
+                old: None,
+                new: None,
+                text: line.to_string(),
+            });
+            continue;
+        }
+        let (kind, old_line, new_line) = match line.chars().next() {
+            Some('+') => {
+                new += 1;
+                (LineKind::Added, None, Some(new - 1))
+            }
+            Some('-') => {
+                old += 1;
+                (LineKind::Removed, Some(old - 1), None)
+            }
+            Some(' ') | None => {
+                old += 1;
+                new += 1;
+                (LineKind::Context, Some(old - 1), Some(new - 1))
+            }
+            // “\ No newline at end of file” 等说明
+            _ => continue,
+        };
+        file.lines.push(DiffLine {
+            kind,
+            old: old_line,
+            new: new_line,
+            text: line.get(1..).unwrap_or_default().to_string(),
+        });
+    }
+    files
+}
+
+fn hunk_start(range: Option<&str>, sign: char) -> u32 {
+    range
+        .and_then(|range| range.strip_prefix(sign))
+        .and_then(|range| range.split(',').next())
+        .and_then(|start| start.parse().ok())
+        .unwrap_or(1)
+}
+
+// 从回复中拆出审查意见：每段以文件引用开头或包含文件引用的段落作为一条意见
+pub fn parse_comments(reply: &str, files: &[DiffFile]) -> Vec<ReviewComment> {
+    let mut comments = Vec::new();
+    for paragraph in paragraphs(reply) {
+        let reference = FILE_REFERENCE
+            .captures_iter(&paragraph)
+            .find_map(|captures| {
+                let path = captures.get(1)?.as_str();
+                let file = find_file(files, path)?;
+                let line = (2..=4)
+                    .find_map(|index| captures.get(index))
+                    .and_then(|line| line.as_str().parse().ok());
+                Some((file, line))
+            });
+        if let Some((file, line)) = reference {
+            comments.push(ReviewComment {
+                file,
+                line,
+                text: paragraph,
+            });
+        }
+    }
+    comments
+}
+
+// 按空行和列表项拆分段落
+fn paragraphs(text: &str) -> Vec<String> {
+    let mut paragraphs = Vec::new();
+    let mut current = String::new();
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let is_item = trimmed.starts_with("- ")
+            || trimmed.starts_with("* ")
+            || trimmed
+                .split_once(". ")
+                .is_some_and(|(n, _)| n.chars().all(|c| c.is_ascii_digit()));
+        if (trimmed.is_empty() || is_item) && !current.trim().is_empty() {
+            paragraphs.push(std::mem::take(&mut current).trim().to_string());
+        }
+        if !trimmed.is_empty() {
+            if !current.is_empty() {
+                current.push('\n');
+            }
+            current.push_str(line);
+        }
+    }
+    if !current.trim().is_empty() {
+        paragraphs.push(current.trim().to_string());
+    }
+    paragraphs
+}
+
+// 回复中的路径可能是相对路径或只有文件名，按后缀匹配
+fn find_file(files: &[DiffFile], path: &str) -> Option<usize> {
+    let path = path.trim_start_matches("./");
+    files.iter().position(|file| file.path == path).or_else(|| {
+        files.iter().position(|file| {
+            file.path.ends_with(path) && file.path[..file.path.len() - path.len()].ends_with('/')
+        })
+    })
+}
+
+// 导出的审查摘要（Markdown）
+pub fn summary_markdown(files: &[DiffFile], comments: &[ReviewComment], reply: &str) -> String {
+    let mut summary = String::from("# 代码审查摘要\n\n");
+    for (index, file) in files.iter().enumerate() {
+        let added = file
+            .lines
+            .iter()
+            .filter(|l| l.kind == LineKind::Added)
+            .count();
+        let removed = file
+            .lines
+            .iter()
+            .filter(|l| l.kind == LineKind::Removed)
+            .count();
+        summary.push_str(&format!("## {} (+{} -{})\n\n", file.path, added, removed));
+        let file_comments: Vec<_> = comments.iter().filter(|c| c.file == index).collect();
+        if file_comments.is_empty() {
+            summary.push_str("没有意见。\n\n");
+        }
+        for comment in file_comments {
+            match comment.line {
+                Some(line) => summary.push_str(&format!("- 第 {} 行: ", line)),
+                None => summary.push_str("- "),
+            }
+            summary.push_str(&comment.text.replace('\n', "\n  "));
+            summary.push('\n');
+        }
+        summary.push('\n');
+    }
+    summary.push_str("## 完整回复\n\n");
+    summary.push_str(reply);
+    summary.push('\n');
+    summary
+}
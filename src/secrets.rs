@@ -0,0 +1,160 @@
+use std::ops::Range;
+
+// 高熵字符串的最短长度
+const MIN_ENTROPY_TOKEN_LEN: usize = 32;
+// 香农熵阈值（比特/字符），十六进制哈希最多为 4.0，不会被误判
+const ENTROPY_THRESHOLD: f64 = 4.2;
+
+// 已知格式的密钥前缀及其说明，附带最短长度
+const KNOWN_PREFIXES: &[(&str, &str, usize)] = &[
+    ("sk-ant-", "Anthropic 密钥", 40),
+    ("sk-", "OpenAI 密钥", 20),
+    ("ghp_", "GitHub 令牌", 36),
+    ("gho_", "GitHub 令牌", 36),
+    ("github_pat_", "GitHub 令牌", 40),
+    ("xoxb-", "Slack 令牌", 20),
+    ("xoxp-", "Slack 令牌", 20),
+    ("AKIA", "AWS 访问密钥", 20),
+    ("AIza", "Google API 密钥", 39),
+    ("glpat-", "GitLab 令牌", 26),
+];
+
+// 在文本中发现的疑似密钥
+#[derive(Debug, Clone)]
+pub struct SecretMatch {
+    pub range: Range<usize>,
+    pub kind: &'static str,
+}
+
+fn is_token_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+' | '/' | '=' | '.')
+}
+
+fn shannon_entropy(token: &str) -> f64 {
+    let mut counts = [0usize; 128];
+    for b in token.bytes() {
+        counts[(b & 0x7f) as usize] += 1;
+    }
+    let len = token.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+fn classify_token(token: &str) -> Option<&'static str> {
+    for (prefix, kind, min_len) in KNOWN_PREFIXES {
+        if token.starts_with(prefix) && token.len() >= *min_len {
+            return Some(kind);
+        }
+    }
+
+    // JWT：三段 base64url，以 eyJ 开头
+    if token.starts_with("eyJ") && token.matches('.').count() == 2 && token.len() >= 36 {
+        return Some("JWT 令牌");
+    }
+
+    let has_digit = token.chars().any(|c| c.is_ascii_digit());
+    let has_letter = token.chars().any(|c| c.is_ascii_alphabetic());
+    // 路径和域名中的斜杠、点较多，不按高熵处理
+    let separators = token.chars().filter(|c| matches!(c, '/' | '.')).count();
+    if token.len() >= MIN_ENTROPY_TOKEN_LEN
+        && has_digit
+        && has_letter
+        && separators <= 2
+        && shannon_entropy(token) >= ENTROPY_THRESHOLD
+    {
+        return Some("高熵字符串");
+    }
+    None
+}
+
+// 查找 PEM 格式的私钥块
+fn find_private_keys(text: &str, matches: &mut Vec<SecretMatch>) {
+    let mut offset = 0;
+    while let Some(start) = text[offset..].find("-----BEGIN ") {
+        let start = offset + start;
+        let Some(header_end) = text[start..].find("-----\n").map(|i| start + i) else {
+            break;
+        };
+        if !text[start..header_end].ends_with("PRIVATE KEY") {
+            offset = header_end;
+            continue;
+        }
+        let end = text[header_end..]
+            .find("-----END ")
+            .and_then(|i| {
+                let footer = header_end + i;
+                text[footer + 9..].find("-----").map(|j| footer + 9 + j + 5)
+            })
+            .unwrap_or(text.len());
+        matches.push(SecretMatch {
+            range: start..end,
+            kind: "私钥",
+        });
+        offset = end;
+    }
+}
+
+// 扫描文本中疑似泄露的密钥或凭据，按位置排序
+pub fn find_secrets(text: &str) -> Vec<SecretMatch> {
+    let mut matches = Vec::new();
+    find_private_keys(text, &mut matches);
+
+    let mut token_start = None;
+    for (index, c) in text
+        .char_indices()
+        .chain(std::iter::once((text.len(), ' ')))
+    {
+        if is_token_char(c) {
+            token_start.get_or_insert(index);
+            continue;
+        }
+        let Some(start) = token_start.take() else {
+            continue;
+        };
+        // 去掉句末的标点
+        let token = text[start..index].trim_end_matches(['.', '-', '=']);
+        let end = start + token.len();
+        if matches.iter().any(|m| m.range.contains(&start)) {
+            continue;
+        }
+        if let Some(kind) = classify_token(token) {
+            matches.push(SecretMatch {
+                range: start..end,
+                kind,
+            });
+        }
+    }
+
+    matches.sort_by_key(|m| m.range.start);
+    matches
+}
+
+// 将疑似密钥替换为只保留开头几个字符的占位符
+pub fn mask_secrets(text: &str) -> String {
+    let matches = find_secrets(text);
+    if matches.is_empty() {
+        return text.to_string();
+    }
+
+    let mut output = String::with_capacity(text.len());
+    let mut last = 0;
+    for secret in matches {
+        output.push_str(&text[last..secret.range.start]);
+        let value = &text[secret.range.clone()];
+        if secret.kind == "私钥" {
+            output.push_str("[已隐藏的私钥]");
+        } else {
+            let prefix: String = value.chars().take(4).collect();
+            output.push_str(&format!("{}****", prefix));
+        }
+        last = secret.range.end;
+    }
+    output.push_str(&text[last..]);
+    output
+}
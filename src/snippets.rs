@@ -0,0 +1,34 @@
+use crate::config::Snippet;
+
+// 查找输入末尾匹配的缩写（末尾单词需与触发词完全一致）
+fn trailing_match<'a>(text: &str, snippets: &'a [Snippet]) -> Option<&'a Snippet> {
+    let word = text.rsplit(char::is_whitespace).next().unwrap_or_default();
+    if word.is_empty() {
+        return None;
+    }
+    snippets
+        .iter()
+        .find(|snippet| !snippet.trigger.is_empty() && snippet.trigger == word)
+}
+
+// 输入末尾是否为某个缩写触发词
+pub fn has_trailing_trigger(text: &str, snippets: &[Snippet]) -> bool {
+    trailing_match(text, snippets).is_some()
+}
+
+// 展开输入末尾的缩写；若文本以空格结尾则先去掉空格再匹配，并在展开后保留空格
+pub fn expand_trailing(text: &mut String, snippets: &[Snippet]) -> bool {
+    let (body, delimiter) = match text.strip_suffix(' ') {
+        Some(body) => (body, " "),
+        None => (text.as_str(), ""),
+    };
+    let Some(snippet) = trailing_match(body, snippets) else {
+        return false;
+    };
+
+    let new_len = body.len() - snippet.trigger.len();
+    let expansion = format!("{}{}", snippet.expansion, delimiter);
+    text.truncate(new_len);
+    text.push_str(&expansion);
+    true
+}
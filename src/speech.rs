@@ -0,0 +1,217 @@
+use crate::language;
+use crate::providers::{ApiTarget, ProviderKind};
+use futures_util::StreamExt;
+use log::debug;
+use reqwest::Client;
+use serde_json::json;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use tokio::fs;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+// OpenAI 语音合成接口单次最多接受 4096 个字符
+const MAX_INPUT_CHARS: usize = 4096;
+
+// 接口返回的 pcm 格式：24kHz、16 位有符号小端、单声道
+pub const SAMPLE_RATE: u32 = 24_000;
+
+pub const VOICES: [&str; 6] = ["alloy", "echo", "fable", "onyx", "nova", "shimmer"];
+
+pub fn default_model() -> String {
+    "tts-1".to_string()
+}
+
+pub fn default_voice() -> String {
+    "alloy".to_string()
+}
+
+// 朗读时跳过代码块
+fn speech_input(text: &str) -> String {
+    language::prose(text)
+        .trim()
+        .chars()
+        .take(MAX_INPUT_CHARS)
+        .collect()
+}
+
+// FNV-1a，保证缓存文件名在不同版本的程序之间保持一致
+fn stable_hash(parts: &[&str]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in parts {
+        for byte in part.bytes().chain(std::iter::once(0)) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+// 语音缓存目录在设置中配置，文件名由模型、声音和文字决定
+pub fn cache_path(dir: &Path, model: &str, voice: &str, text: &str) -> PathBuf {
+    dir.join(format!("{:016x}.wav", stable_hash(&[model, voice, text])))
+}
+
+// 缓存文件的 WAV 文件头，data_len 为采样数据的字节数
+fn wav_header(data_len: u32) -> Vec<u8> {
+    let mut header = Vec::with_capacity(44);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&(36 + data_len).to_le_bytes());
+    header.extend_from_slice(b"WAVEfmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    header.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    header.extend_from_slice(&2u16.to_le_bytes());
+    header.extend_from_slice(&16u16.to_le_bytes());
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&data_len.to_le_bytes());
+    header
+}
+
+// 把一块 pcm 数据转成采样；网络分块可能把一个采样拆开，剩下的半个采样留到下一块
+fn decode_samples(pending: &mut Option<u8>, mut bytes: &[u8]) -> Vec<i16> {
+    let mut samples = Vec::with_capacity(bytes.len() / 2 + 1);
+    if let Some(low) = *pending {
+        let Some((&high, rest)) = bytes.split_first() else {
+            return samples;
+        };
+        samples.push(i16::from_le_bytes([low, high]));
+        bytes = rest;
+    }
+    let mut pairs = bytes.chunks_exact(2);
+    samples.extend(
+        pairs
+            .by_ref()
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]])),
+    );
+    *pending = pairs.remainder().first().copied();
+    samples
+}
+
+// 由对话接口地址推出语音合成接口地址，目前支持 OpenAI 兼容接口和 Azure OpenAI
+fn speech_request(
+    client: &Client,
+    target: &ApiTarget,
+    model: &str,
+) -> Result<reqwest::RequestBuilder, String> {
+    let url = target.url();
+    if !url.contains("chat/completions") {
+        return Err(format!("无法从地址推出语音合成接口: {}", url));
+    }
+    let url = url.replace("chat/completions", "audio/speech");
+    match target.provider {
+        ProviderKind::OpenAi => Ok(client
+            .post(url)
+            .header("Authorization", format!("Bearer {}", target.api_key))),
+        ProviderKind::Azure => Ok(client
+            .post(url.replace("{deployment}", model))
+            .header("api-key", &target.api_key)),
+        provider => Err(format!("{} 不支持语音合成", provider.label())),
+    }
+}
+
+// 合成语音：边接收边把采样发给播放器，同时写入缓存文件；播放器停止后不再继续接收
+pub async fn synthesize(
+    client: &Client,
+    target: &ApiTarget,
+    model: &str,
+    voice: &str,
+    text: &str,
+    path: &Path,
+    samples: Sender<Vec<i16>>,
+) -> Result<PathBuf, String> {
+    let input = speech_input(text);
+    if input.is_empty() {
+        return Err("没有可朗读的文字".to_string());
+    }
+
+    debug!("请求语音合成: {} 字", input.chars().count());
+    let response = speech_request(client, target, model)?
+        .json(&json!({
+            "model": model,
+            "voice": voice,
+            "input": input,
+            "response_format": "pcm"
+        }))
+        .send()
+        .await
+        .map_err(|e| format!("语音合成请求失败: {}", e))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("语音合成失败: HTTP {} {}", status, body));
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .await
+            .map_err(|e| format!("创建语音缓存目录失败: {}", e))?;
+    }
+    // 先写入临时文件，下载中断时不会留下不完整的缓存
+    let partial = path.with_extension("part");
+    let result = receive(response, &partial, samples).await;
+    if result.is_err() {
+        let _ = fs::remove_file(&partial).await;
+    }
+    result?;
+    fs::rename(&partial, path)
+        .await
+        .map_err(|e| format!("保存音频文件失败: {}", e))?;
+    Ok(path.to_path_buf())
+}
+
+async fn receive(
+    response: reqwest::Response,
+    partial: &Path,
+    samples: Sender<Vec<i16>>,
+) -> Result<(), String> {
+    let mut file = fs::File::create(partial)
+        .await
+        .map_err(|e| format!("创建音频文件失败: {}", e))?;
+    file.write_all(&wav_header(0))
+        .await
+        .map_err(|e| format!("写入音频文件失败: {}", e))?;
+    let mut data_len: u32 = 0;
+    let mut pending = None;
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| format!("接收音频失败: {}", e))?;
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| format!("写入音频文件失败: {}", e))?;
+        data_len = data_len.saturating_add(chunk.len() as u32);
+        if samples.send(decode_samples(&mut pending, &chunk)).is_err() {
+            return Err("朗读已停止".to_string());
+        }
+    }
+    // 数据接收完后补上文件头中的长度
+    file.seek(SeekFrom::Start(0))
+        .await
+        .map_err(|e| format!("写入音频文件失败: {}", e))?;
+    file.write_all(&wav_header(data_len))
+        .await
+        .map_err(|e| format!("写入音频文件失败: {}", e))?;
+    file.flush()
+        .await
+        .map_err(|e| format!("写入音频文件失败: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_split_across_chunks() {
+        let mut pending = None;
+        assert_eq!(decode_samples(&mut pending, &[0x01, 0x00, 0x34]), vec![1]);
+        assert_eq!(pending, Some(0x34));
+        assert_eq!(decode_samples(&mut pending, &[]), Vec::<i16>::new());
+        assert_eq!(
+            decode_samples(&mut pending, &[0x12, 0xff, 0xff]),
+            vec![0x1234, -1]
+        );
+        assert_eq!(pending, None);
+    }
+}
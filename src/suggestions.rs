@@ -0,0 +1,100 @@
+use crate::models::{self, Message};
+use serde_json::{json, Value as JsonValue};
+
+// 最多显示的建议数量
+const MAX_SUGGESTIONS: usize = 3;
+// 单条建议的最大字符数
+const MAX_SUGGESTION_CHARS: usize = 40;
+// 只取最近几条消息作为上下文，控制请求大小
+const CONTEXT_MESSAGES: usize = 4;
+
+const SUGGESTION_PROMPT: &str = "根据以上对话，站在用户的角度给出 2 到 3 个简短的后续问题，每个不超过20个字。以 JSON 字符串数组的形式返回，例如 [\"问题一\", \"问题二\"]，只返回 JSON，不要任何解释。";
+
+// 构建后续问题建议请求
+pub fn build_payload(model: &str, messages: &[Message]) -> JsonValue {
+    let context: Vec<&Message> = models::context_messages(messages).collect();
+    let start = context.len().saturating_sub(CONTEXT_MESSAGES);
+    let mut request_messages: Vec<JsonValue> = context[start..]
+        .iter()
+        .map(|msg| {
+            json!({
+                "role": msg.role,
+                "content": msg.content
+            })
+        })
+        .collect();
+    request_messages.push(json!({
+        "role": "user",
+        "content": SUGGESTION_PROMPT
+    }));
+
+    json!({
+        "model": model,
+        "messages": request_messages,
+        "temperature": 0.7,
+        "max_tokens": 120
+    })
+}
+
+fn normalize(suggestions: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    for suggestion in suggestions {
+        let suggestion = suggestion.trim();
+        if suggestion.is_empty() || suggestion.chars().count() > MAX_SUGGESTION_CHARS {
+            continue;
+        }
+        if !result.iter().any(|s| s == suggestion) {
+            result.push(suggestion.to_string());
+        }
+        if result.len() == MAX_SUGGESTIONS {
+            break;
+        }
+    }
+    result
+}
+
+// 解析模型返回的建议，兼容被代码块包裹的 JSON 和逐行列出的格式
+pub fn parse_suggestions(content: &str) -> Vec<String> {
+    let start = content.find('[');
+    let end = content.rfind(']');
+    if let (Some(start), Some(end)) = (start, end) {
+        if start < end {
+            if let Ok(items) = serde_json::from_str::<Vec<String>>(&content[start..=end]) {
+                return normalize(items);
+            }
+        }
+    }
+
+    normalize(content.lines().map(|line| {
+        line.trim()
+            .trim_start_matches(|c: char| c.is_ascii_digit() || "-*.、) ".contains(c))
+            .to_string()
+    }))
+}
+
+// 从回复中提取助手反问用户的问题，用于模型请求失败时
+pub fn extract_from_answer(answer: &str) -> Vec<String> {
+    let mut questions = Vec::new();
+    let mut current = String::new();
+    for c in answer.chars() {
+        current.push(c);
+        if matches!(c, '。' | '！' | '!' | '\n') {
+            current.clear();
+        } else if matches!(c, '？' | '?') {
+            questions.push(std::mem::take(&mut current));
+        }
+    }
+
+    // 回复末尾的问题通常是在引导下一步
+    let questions: Vec<String> = questions
+        .into_iter()
+        .rev()
+        .map(|q| {
+            q.trim()
+                .trim_start_matches(['-', '*', '#', '>'])
+                .trim()
+                .to_string()
+        })
+        .collect();
+    normalize(questions)
+}
@@ -0,0 +1,107 @@
+use log::debug;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+use tokio::task::{AbortHandle, JoinHandle};
+
+// 已登记的后台任务
+pub struct TaskEntry {
+    pub id: u64,
+    pub name: String,
+    pub chat_id: Option<String>,
+    pub started_at: Instant,
+    abort_handle: AbortHandle,
+}
+
+impl TaskEntry {
+    pub fn elapsed(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+}
+
+// 跟踪所有后台任务，支持按对话取消和退出时统一取消
+#[derive(Default)]
+pub struct TaskRegistry {
+    entries: Vec<TaskEntry>,
+    next_id: u64,
+}
+
+impl TaskRegistry {
+    // 在运行时中启动任务并登记
+    pub fn spawn<F>(
+        &mut self,
+        handle: &Handle,
+        name: &str,
+        chat_id: Option<String>,
+        future: F,
+    ) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let join_handle = handle.spawn(future);
+        self.track(name, chat_id, join_handle.abort_handle());
+        join_handle
+    }
+
+    // 登记一个已经启动的任务，返回任务 ID
+    pub fn track(&mut self, name: &str, chat_id: Option<String>, abort_handle: AbortHandle) -> u64 {
+        self.next_id += 1;
+        debug!("登记后台任务 #{}: {}", self.next_id, name);
+        self.entries.push(TaskEntry {
+            id: self.next_id,
+            name: name.to_string(),
+            chat_id,
+            started_at: Instant::now(),
+            abort_handle,
+        });
+        self.next_id
+    }
+
+    // 移除已经结束的任务
+    pub fn prune_finished(&mut self) {
+        self.entries
+            .retain(|entry| !entry.abort_handle.is_finished());
+    }
+
+    pub fn running(&self) -> &[TaskEntry] {
+        &self.entries
+    }
+
+    pub fn abort(&mut self, id: u64) {
+        self.entries.retain(|entry| {
+            if entry.id == id {
+                debug!("取消后台任务 #{}: {}", entry.id, entry.name);
+                entry.abort_handle.abort();
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    // 取消与指定对话相关的所有任务，返回取消的数量
+    pub fn abort_chat(&mut self, chat_id: &str) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|entry| {
+            if entry.chat_id.as_deref() == Some(chat_id) {
+                debug!(
+                    "取消对话 {} 的后台任务 #{}: {}",
+                    chat_id, entry.id, entry.name
+                );
+                entry.abort_handle.abort();
+                false
+            } else {
+                true
+            }
+        });
+        before - self.entries.len()
+    }
+
+    pub fn abort_all(&mut self) {
+        for entry in self.entries.drain(..) {
+            debug!("取消后台任务 #{}: {}", entry.id, entry.name);
+            entry.abort_handle.abort();
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use chrono::Local;
+use log::error;
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+
+// 每条回复之间的分隔线
+pub const REPLY_SEPARATOR: &str = "\n\n---\n";
+
+// 将回复内容追加写入外部文件
+#[derive(Clone)]
+pub struct TeeWriter {
+    sender: mpsc::UnboundedSender<(PathBuf, String)>,
+}
+
+impl TeeWriter {
+    // 所有写入经由同一个后台任务，保证实时片段按到达顺序落盘
+    pub fn spawn(handle: &Handle) -> Self {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<(PathBuf, String)>();
+        handle.spawn(async move {
+            while let Some((path, text)) = receiver.recv().await {
+                if let Err(e) = append(&path, &text).await {
+                    error!("写入 {} 失败: {}", path.display(), e);
+                }
+            }
+        });
+        Self { sender }
+    }
+
+    pub fn write(&self, path: &str, text: impl Into<String>) {
+        let _ = self.sender.send((PathBuf::from(path), text.into()));
+    }
+}
+
+async fn append(path: &PathBuf, text: &str) -> std::io::Result<()> {
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(text.as_bytes()).await?;
+    file.flush().await
+}
+
+// 每条回复前的标题，记录时间和模型
+pub fn reply_header(model: &str) -> String {
+    format!(
+        "\n## {} · {}\n\n",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        model
+    )
+}
@@ -0,0 +1,208 @@
+use crate::config::{FollowUpAction, Snippet};
+use crate::models::{Chat, ChatConfig};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::error::Error as StdError;
+use std::path::Path;
+use tokio::fs;
+
+// 模板包格式版本，不兼容的修改需要递增
+pub const TEMPLATE_FORMAT_VERSION: u32 = 1;
+
+// 可分享的模板包：包含角色、文本缩写和快捷追问
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TemplateBundle {
+    pub version: u32,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub roles: Vec<RoleTemplate>,
+    #[serde(default)]
+    pub snippets: Vec<Snippet>,
+    #[serde(default)]
+    pub follow_up_actions: Vec<FollowUpAction>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RoleTemplate {
+    pub name: String,
+    pub system_prompt: String,
+    // 为空时使用全局模型
+    #[serde(default)]
+    pub model_name: String,
+    #[serde(default = "default_temperature")]
+    pub temperature: f32,
+    #[serde(default)]
+    pub greeting: Option<String>,
+    #[serde(default)]
+    pub greeting_in_context: bool,
+}
+
+fn default_temperature() -> f32 {
+    0.7
+}
+
+impl RoleTemplate {
+    // 从已有的角色对话生成模板
+    pub fn from_chat(chat: &Chat) -> Option<Self> {
+        let config = chat.config.as_ref()?;
+        Some(Self {
+            name: chat.name.trim_start_matches('\u{f544}').trim().to_string(),
+            system_prompt: config.system_prompt.clone(),
+            model_name: config.model_name.clone(),
+            temperature: config.temperature,
+            greeting: config.greeting.clone(),
+            greeting_in_context: config.greeting_in_context,
+        })
+    }
+
+    // 安装为角色对话
+    pub fn to_chat(&self) -> Chat {
+        let mut chat = Chat::new(format!("\u{f544} {}", self.name.trim()));
+        chat.has_been_renamed = true;
+        chat.config = Some(ChatConfig {
+            model_name: self.model_name.clone(),
+            system_prompt: self.system_prompt.clone(),
+            temperature: self.temperature,
+            top_p: 1.0,
+            frequency_penalty: 0.0,
+            presence_penalty: 0.0,
+            greeting: self.greeting.clone().filter(|g| !g.trim().is_empty()),
+            greeting_in_context: self.greeting_in_context,
+            provider: None,
+            filter_mode: None,
+            filter_terms: Vec::new(),
+            profile: None,
+        });
+        chat
+    }
+}
+
+#[derive(Debug)]
+pub enum TemplateError {
+    RequestError(reqwest::Error),
+    IoError(std::io::Error),
+    ParseError(serde_json::Error),
+    Invalid(String),
+}
+
+impl From<reqwest::Error> for TemplateError {
+    fn from(err: reqwest::Error) -> Self {
+        TemplateError::RequestError(err)
+    }
+}
+
+impl From<std::io::Error> for TemplateError {
+    fn from(err: std::io::Error) -> Self {
+        TemplateError::IoError(err)
+    }
+}
+
+impl From<serde_json::Error> for TemplateError {
+    fn from(err: serde_json::Error) -> Self {
+        TemplateError::ParseError(err)
+    }
+}
+
+impl std::fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TemplateError::RequestError(e) => write!(f, "下载失败: {}", e),
+            TemplateError::IoError(e) => write!(f, "IO错误: {}", e),
+            TemplateError::ParseError(e) => write!(f, "JSON解析错误: {}", e),
+            TemplateError::Invalid(msg) => write!(f, "模板无效: {}", msg),
+        }
+    }
+}
+
+impl StdError for TemplateError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            TemplateError::RequestError(e) => Some(e),
+            TemplateError::IoError(e) => Some(e),
+            TemplateError::ParseError(e) => Some(e),
+            TemplateError::Invalid(_) => None,
+        }
+    }
+}
+
+impl TemplateBundle {
+    // 检查版本和必填字段
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        if self.version == 0 || self.version > TEMPLATE_FORMAT_VERSION {
+            return Err(TemplateError::Invalid(format!(
+                "不支持的版本 {}",
+                self.version
+            )));
+        }
+        if self.name.trim().is_empty() {
+            return Err(TemplateError::Invalid("缺少模板名称".to_string()));
+        }
+        if self.roles.is_empty() && self.snippets.is_empty() && self.follow_up_actions.is_empty() {
+            return Err(TemplateError::Invalid("模板内容为空".to_string()));
+        }
+        if let Some(role) = self
+            .roles
+            .iter()
+            .find(|r| r.name.trim().is_empty() || r.system_prompt.trim().is_empty())
+        {
+            return Err(TemplateError::Invalid(format!(
+                "角色 \"{}\" 缺少名称或提示词",
+                role.name
+            )));
+        }
+        if self
+            .roles
+            .iter()
+            .any(|r| !(0.0..=2.0).contains(&r.temperature))
+        {
+            return Err(TemplateError::Invalid(
+                "temperature 需在 0 到 2 之间".to_string(),
+            ));
+        }
+        if self.snippets.iter().any(|s| s.trigger.trim().is_empty()) {
+            return Err(TemplateError::Invalid("文本缩写缺少触发词".to_string()));
+        }
+        if self
+            .follow_up_actions
+            .iter()
+            .any(|a| a.label.trim().is_empty())
+        {
+            return Err(TemplateError::Invalid("快捷追问缺少名称".to_string()));
+        }
+        Ok(())
+    }
+}
+
+pub fn parse_bundle(text: &str) -> Result<TemplateBundle, TemplateError> {
+    let bundle: TemplateBundle = serde_json::from_str(text)?;
+    bundle.validate()?;
+    Ok(bundle)
+}
+
+// 从 URL 下载模板包并校验
+pub async fn fetch_bundle(client: &Client, url: &str) -> Result<TemplateBundle, TemplateError> {
+    let url = url.trim();
+    if !(url.starts_with("https://") || url.starts_with("http://")) {
+        return Err(TemplateError::Invalid(
+            "URL 需以 http:// 或 https:// 开头".to_string(),
+        ));
+    }
+    let text = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    parse_bundle(&text)
+}
+
+pub async fn export_bundle(bundle: &TemplateBundle, path: &Path) -> Result<(), TemplateError> {
+    let json = serde_json::to_string_pretty(bundle)?;
+    fs::write(path, json).await?;
+    Ok(())
+}
@@ -0,0 +1,249 @@
+use crate::language;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+
+// 本地生成标题时去掉的常见停用词
+const CHINESE_STOP_WORDS: &[&str] = &[
+    "请问",
+    "请你",
+    "请帮我",
+    "帮我",
+    "帮忙",
+    "能不能",
+    "可不可以",
+    "可以",
+    "能否",
+    "麻烦",
+    "一下",
+    "我想",
+    "我要",
+    "你好",
+    "您好",
+    "告诉我",
+    "请",
+];
+
+const ENGLISH_STOP_WORDS: &[&str] = &[
+    "a", "an", "the", "please", "can", "could", "would", "you", "me", "i", "to", "of", "for", "is",
+    "are", "what", "how", "hi", "hello", "help",
+];
+
+// 本地标题的最大字符数
+const MAX_FALLBACK_TITLE_CHARS: usize = 16;
+
+// 根据第一条用户消息生成标题，用于 API 生成标题失败或被禁用时
+pub fn fallback_title(first_user_message: &str) -> Option<String> {
+    // 只取第一行有意义的文本，跳过代码块
+    let line = first_user_message
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with("```"))
+        .find(|line| !line.is_empty())?;
+
+    let mut text = line
+        .trim_start_matches(['#', '>', '-', '*'])
+        .trim()
+        .to_string();
+    for stop_word in CHINESE_STOP_WORDS {
+        if let Some(rest) = text.strip_prefix(stop_word) {
+            text = rest.trim_start().to_string();
+        }
+    }
+
+    let words: Vec<&str> = text
+        .split_whitespace()
+        .filter(|word| {
+            let lower = word
+                .trim_matches(|c: char| !c.is_alphanumeric())
+                .to_lowercase();
+            !ENGLISH_STOP_WORDS.contains(&lower.as_str())
+        })
+        .collect();
+    let text = if words.is_empty() {
+        text
+    } else {
+        words.join(" ")
+    };
+
+    let title: String = text
+        .trim_matches(|c: char| c.is_ascii_punctuation() || "，。？！：；、".contains(c))
+        .chars()
+        .take(MAX_FALLBACK_TITLE_CHARS)
+        .collect();
+    let title = title.trim().to_string();
+
+    if title.is_empty() {
+        None
+    } else {
+        Some(title)
+    }
+}
+
+// 生成标题使用的语言：跟随第一条消息，或固定为中文 / 日文 / 英文
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TitleLanguage {
+    #[default]
+    Auto,
+    Chinese,
+    Japanese,
+    English,
+}
+
+impl TitleLanguage {
+    pub const ALL: [TitleLanguage; 4] = [
+        TitleLanguage::Auto,
+        TitleLanguage::Chinese,
+        TitleLanguage::Japanese,
+        TitleLanguage::English,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TitleLanguage::Auto => "跟随对话",
+            TitleLanguage::Chinese => "中文",
+            TitleLanguage::Japanese => "日本語",
+            TitleLanguage::English => "English",
+        }
+    }
+}
+
+// 某种语言的标题提示词和长度限制；提示词中的 {max} 会替换为长度上限
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TitleLocale {
+    pub prompt: String,
+    // 中文和日文按字数，英文按单词数
+    pub max_length: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TitleConfig {
+    // 关闭时只用本地规则生成标题
+    pub enabled: bool,
+    // 为空时使用对话的模型
+    pub model: String,
+    pub language: TitleLanguage,
+    pub chinese: TitleLocale,
+    pub japanese: TitleLocale,
+    pub english: TitleLocale,
+}
+
+impl Default for TitleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            model: String::new(),
+            language: TitleLanguage::Auto,
+            chinese: TitleLocale {
+                prompt: "请根据上面的对话生成一个简短的标题，不超过 {max} 个字，直接返回标题，不要包含任何解释和标点符号。标题应该概括对话的主要内容或主题。".to_string(),
+                max_length: 10,
+            },
+            japanese: TitleLocale {
+                prompt: "上の会話の内容を表す短いタイトルを日本語で {max} 文字以内で付けてください。タイトルだけを返し、説明や句読点は付けないでください。".to_string(),
+                max_length: 15,
+            },
+            english: TitleLocale {
+                prompt: "Write a short title in English for the conversation above, at most {max} words. Reply with the title only, without quotes, explanations or trailing punctuation.".to_string(),
+                max_length: 6,
+            },
+        }
+    }
+}
+
+impl TitleConfig {
+    // 跟随对话时，第一条消息主要是中文或日文就用对应的语言，否则用英文
+    pub fn resolve(&self, first_user_message: &str) -> TitleLanguage {
+        match self.language {
+            TitleLanguage::Auto => match language::detect_language(first_user_message) {
+                Some("Chinese") => TitleLanguage::Chinese,
+                Some("Japanese") => TitleLanguage::Japanese,
+                _ => TitleLanguage::English,
+            },
+            language => language,
+        }
+    }
+
+    pub fn locale(&self, language: TitleLanguage) -> &TitleLocale {
+        match language {
+            TitleLanguage::English => &self.english,
+            TitleLanguage::Japanese => &self.japanese,
+            _ => &self.chinese,
+        }
+    }
+
+    pub fn locale_mut(&mut self, language: TitleLanguage) -> &mut TitleLocale {
+        match language {
+            TitleLanguage::English => &mut self.english,
+            TitleLanguage::Japanese => &mut self.japanese,
+            _ => &mut self.chinese,
+        }
+    }
+
+    // 标题生成请求：先放入第一轮对话，再要求按所选语言总结
+    pub fn payload(&self, model: &str, user_input: &str, assistant_response: &str) -> JsonValue {
+        let locale = self.locale(self.resolve(user_input));
+        let prompt = locale
+            .prompt
+            .replace("{max}", &locale.max_length.to_string());
+        json!({
+            "model": model,
+            "messages": [
+                { "role": "user", "content": user_input },
+                { "role": "assistant", "content": assistant_response },
+                { "role": "user", "content": prompt }
+            ],
+            "temperature": 0.7,
+            "max_tokens": 60
+        })
+    }
+
+    // 去掉模型常加的引号和句末标点，并按长度上限截断
+    pub fn finish(&self, title: &str, user_input: &str) -> Option<String> {
+        let language = self.resolve(user_input);
+        let max_length = self.locale(language).max_length.max(1);
+        let title = title
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .trim_matches(|c: char| "\"'“”‘’「」《》".contains(c))
+            .trim_end_matches(|c: char| c.is_ascii_punctuation() || "，。？！：；、".contains(c))
+            .trim();
+        let title = match language {
+            TitleLanguage::English => title
+                .split_whitespace()
+                .take(max_length)
+                .collect::<Vec<_>>()
+                .join(" "),
+            _ => title.chars().take(max_length).collect(),
+        };
+        if title.is_empty() {
+            None
+        } else {
+            Some(title)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_language_follows_the_first_message() {
+        let config = TitleConfig::default();
+        assert_eq!(config.resolve("帮我写一个排序函数"), TitleLanguage::Chinese);
+        assert_eq!(
+            config.resolve("この関数の使い方を教えてください"),
+            TitleLanguage::Japanese
+        );
+        assert_eq!(
+            config.resolve("How do I sort a list?"),
+            TitleLanguage::English
+        );
+        let payload = config.payload("gpt-4o", "この関数の使い方を教えてください", "はい");
+        let prompt = payload["messages"][2]["content"].as_str().unwrap();
+        assert!(prompt.contains("日本語で 15 文字以内"));
+    }
+}
@@ -0,0 +1,103 @@
+// 本地 token 计数：用 tiktoken 估算输入框和整个上下文的 token 数，非 OpenAI 模型按 o200k 编码近似
+use crate::models::Message;
+use tiktoken_rs::{bpe_for_model, model::get_context_size, o200k_base_singleton, CoreBPE};
+
+// 每条消息的格式开销（角色标记、分隔符），以及回复开头的固定开销
+const TOKENS_PER_MESSAGE: usize = 3;
+const REPLY_PRIMING: usize = 3;
+// 识别不出的模型按这个窗口估算
+const DEFAULT_CONTEXT_WINDOW: usize = 128_000;
+// 超过窗口的这个比例时开始提醒
+pub const WARN_RATIO: f32 = 0.9;
+
+fn encoder(model: &str) -> &'static CoreBPE {
+    bpe_for_model(model).unwrap_or_else(|_| o200k_base_singleton())
+}
+
+pub fn count(model: &str, text: &str) -> usize {
+    if text.is_empty() {
+        return 0;
+    }
+    encoder(model).encode_ordinary(text).len()
+}
+
+pub fn context_window(model: &str) -> usize {
+    get_context_size(model).unwrap_or(DEFAULT_CONTEXT_WINDOW)
+}
+
+pub fn format_count(count: usize) -> String {
+    if count >= 10_000 {
+        format!("{:.1}k", count as f64 / 1000.0)
+    } else {
+        count.to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ContextKey {
+    model: String,
+    chat_id: Option<String>,
+    messages: usize,
+    chars: usize,
+    system_prompt_chars: usize,
+}
+
+// 缓存计数结果，输入和对话没有变化时不重新编码
+#[derive(Default, Clone)]
+pub struct TokenCounter {
+    input_key: Option<(String, String)>,
+    context_key: Option<ContextKey>,
+    pub input: usize,
+    pub context: usize,
+}
+
+impl TokenCounter {
+    pub fn update_input(&mut self, model: &str, input: &str) {
+        if self
+            .input_key
+            .as_ref()
+            .is_some_and(|(m, text)| m == model && text == input)
+        {
+            return;
+        }
+        self.input = count(model, input)
+            + if input.is_empty() {
+                0
+            } else {
+                TOKENS_PER_MESSAGE
+            };
+        self.input_key = Some((model.to_string(), input.to_string()));
+    }
+
+    pub fn update_context(
+        &mut self,
+        model: &str,
+        chat_id: Option<&str>,
+        system_prompt: &str,
+        messages: &[Message],
+    ) {
+        let key = ContextKey {
+            model: model.to_string(),
+            chat_id: chat_id.map(str::to_string),
+            messages: messages.len(),
+            chars: messages.iter().map(|m| m.content.len()).sum(),
+            system_prompt_chars: system_prompt.len(),
+        };
+        if self.context_key.as_ref() == Some(&key) {
+            return;
+        }
+        let mut total = REPLY_PRIMING;
+        if !system_prompt.is_empty() {
+            total += count(model, system_prompt) + TOKENS_PER_MESSAGE;
+        }
+        for message in messages {
+            total += count(model, &message.text_with_attachments()) + TOKENS_PER_MESSAGE;
+        }
+        self.context = total;
+        self.context_key = Some(key);
+    }
+
+    pub fn total(&self) -> usize {
+        self.context + self.input
+    }
+}
@@ -0,0 +1,9 @@
+use crate::reply_stream::ReplyStream;
+
+// 用不同参数重新生成某一轮回复，完成后替换原来的助手消息
+pub struct TurnRetry {
+    pub chat_id: String,
+    // 被替换的助手消息位置
+    pub index: usize,
+    pub stream: ReplyStream,
+}
@@ -1,55 +1,507 @@
+mod components;
+
 use crate::api;
+use crate::app_lock::{self, LockConfig};
+use crate::audio::{AudioPlayer, StreamingSource};
+use crate::backup::{self, BackupConfig};
+use crate::cache;
+use crate::capabilities::{self, Capabilities};
+use crate::checklist;
+use crate::code_paste;
+use crate::compare::{self, CompareColumn, Comparison};
 use crate::config;
-use crate::models::{Chat, ChatConfig, ChatHistory, ChatList, Message};
+use crate::content_filter::{self, ContentFilter, FilterMode};
+use crate::context::{self, ContextStrategy, SummaryCache};
+use crate::costs::{self, CostLedger, ModelPrice};
+use crate::deep_link;
+use crate::diagnostics;
+use crate::dictation;
+use crate::diff::{self, DiffOp};
+use crate::emoji;
+use crate::events::UiEvent;
+use crate::providers::{ApiTarget, ProviderKind};
+use crate::quick_ask::{self, QuickAsk};
+use crate::replay::Replay;
+use crate::reply_stream::{self, ReplyStream};
+use crate::retrieval::Retrieval;
+use crate::review;
+use crate::ghost_text::{self, GhostTextState};
+use crate::group_chat::{self, GroupReply, GroupTurn};
+use crate::health::EndpointHealth;
+use crate::image_cache::ImageCache;
+use crate::imagegen;
+use crate::importers::{self, ImportedChat, SourceFormat};
+use crate::knowledge;
+use crate::entities::{self, Entity, EntityKind};
+use crate::inbox::{self, InboxMessage};
+use crate::journal::{self, JournalState};
+use crate::inspector::RequestInspector;
+use crate::export;
+use crate::features::{self, Feature, FeatureFlags};
+use crate::language;
+use crate::memory;
+use crate::memory_store::{self, MemoryEvent, MemoryStore};
+use crate::metrics::{self, Metrics, MetricsData};
+use crate::notifications::Notifications;
+use crate::outline;
+use crate::path_complete;
+use crate::paths;
+use crate::keybindings::{self, ImeState, ModalState};
+use crate::platform::{self, PlatformIntegration};
+use crate::postprocess::{Pipeline, PostProcessor, Processed, StepKind};
+use crate::models::{self, CHAT_LIST_FILE, ChatMemory, ManifestEntry, MessageKind, RequestManifest, RetryOverrides,
+    Chat, ChatConfig, ChatHistory, ChatList, ChatTask, EffectiveConfig, GroupMember, Message, ParamPreset,
+    SamplingParams, TeeTarget, TextAttachment,
+};
+use crate::secrets;
+use crate::snippets;
+use crate::speech;
+use crate::suggestions;
+use crate::task_registry::TaskRegistry;
+use crate::tee::{self, TeeWriter};
+use crate::templates::{self, TemplateBundle};
+use crate::titles::{self, TitleLanguage};
+use crate::tokens::{self, TokenCounter};
+use crate::turn_retry::TurnRetry;
 use crate::utils::{self, ImageError};
+use crate::webhook;
+use components::{
+    attachment_chip, azure_config_rows, chat_list_item, composer_edit, failover_config_rows, ghost_suffix, header_grid,
+    histogram_rows, large_text_preview, message_header, network_config_rows, profile_rows, provider_config_rows, proxy_config_rows,
+    secret_warning, status_message, usage_line, DraggedChat, SidebarClick,
+};
 use chrono::Utc;
 use eframe::egui::{self, RichText, ScrollArea, TextEdit};
 use egui_commonmark::{CommonMarkCache, CommonMarkViewer};
 use log::{debug, error};
 use reqwest::Client;
 use rfd::FileDialog;
-use serde_json::{json, Value as JsonValue};
-use std::path::PathBuf;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 use tokio::runtime::Runtime;
 use tokio::sync::mpsc;
 use uuid::Uuid;
 
+// 长回复大纲栏的宽度
+const OUTLINE_WIDTH: f32 = 140.0;
+
+// 超过该行数或字符数的粘贴内容会提示作为附件发送
+const LARGE_PASTE_LINES: usize = 200;
+const LARGE_PASTE_CHARS: usize = 20_000;
+
+// 输入区的最小高度和最大占比；内容高度之外还要留出按钮行和边距
+const MIN_INPUT_HEIGHT: f32 = 120.0;
+const MAX_INPUT_HEIGHT_RATIO: f32 = 0.6;
+const INPUT_CHROME_HEIGHT: f32 = 90.0;
+
+// 代码输入模式可选的语言（syntect 语法名或扩展名）
+const CODE_LANGUAGES: &[&str] = &[
+    "rs", "py", "js", "ts", "go", "java", "c", "cpp", "cs", "sh", "sql", "json", "toml", "yaml",
+    "html", "css", "md",
+];
+
+fn is_large_paste(text: &str) -> bool {
+    text.len() > LARGE_PASTE_CHARS || text.lines().count() > LARGE_PASTE_LINES
+}
+
+// 切换对话的请求；当前对话仍在生成回复时需要确认
+#[derive(Clone)]
+enum ChatSwitch {
+    Open(String),
+    New,
+    NewIncognito,
+    NewFromRole(String),
+}
+
+// 从其他客户端导入的对话，确认前先预览
+#[derive(Clone)]
+struct ChatImport {
+    format: SourceFormat,
+    chats: Vec<ImportedChat>,
+    selected: Vec<bool>,
+}
+
+// 粘贴时自动包进代码块的内容，用于撤销
+#[derive(Clone)]
+struct CodePasteUndo {
+    fenced: String,
+    original: String,
+    language: Option<&'static str>,
+}
+
+// 命中内容过滤规则、等待用户处理的发送请求
+#[derive(Clone)]
+struct FilterHold {
+    mode: FilterMode,
+    matches: Vec<String>,
+    // 为空时表示输入框中的内容
+    prompt: Option<String>,
+    // 编辑后重新发送的用户消息位置
+    edit_index: Option<usize>,
+}
+
+// 对多个对话执行的批量操作
+#[derive(Clone)]
+pub enum BatchAction {
+    Delete,
+    Archive,
+    Tag(String),
+    Export(PathBuf),
+}
+
+impl BatchAction {
+    fn label(&self) -> &'static str {
+        match self {
+            BatchAction::Delete => "删除",
+            BatchAction::Archive => "归档",
+            BatchAction::Tag(_) => "添加标签",
+            BatchAction::Export(_) => "导出",
+        }
+    }
+}
+
+// 设置窗口的分页
+#[derive(Clone, Copy, PartialEq, Default)]
+enum SettingsTab {
+    #[default]
+    General,
+    Experimental,
+}
+
+// 加密导出时输入的密码
+pub struct EncryptedExport {
+    pub chat_ids: Vec<String>,
+    pub password: String,
+    pub confirm: String,
+}
+
+// 逐帧处理的批量任务，便于显示进度
+pub struct BatchJob {
+    pub action: BatchAction,
+    pub pending: Vec<String>,
+    pub total: usize,
+    pub failed: usize,
+}
+
+// 将缓存中的图片另存到用户选择的位置
+fn save_image_as(path: &str) {
+    let source = PathBuf::from(path);
+    let mut dialog = FileDialog::new();
+    if let Some(name) = source.file_name() {
+        dialog = dialog.set_file_name(name.to_string_lossy());
+    }
+    if let Some(target) = dialog.save_file() {
+        match std::fs::copy(&source, &target) {
+            Ok(_) => debug!("图片已保存到: {}", target.display()),
+            Err(e) => error!("保存图片失败: {}", e),
+        }
+    }
+}
+
+// 去掉 --safe-mode 参数重新启动程序，然后关闭当前窗口
+fn restart_without_safe_mode(ctx: &egui::Context) {
+    let args = std::env::args().skip(1).filter(|arg| arg != "--safe-mode");
+    match std::env::current_exe().and_then(|exe| std::process::Command::new(exe).args(args).spawn()) {
+        Ok(_) => ctx.send_viewport_cmd(egui::ViewportCommand::Close),
+        Err(e) => error!("重新启动失败: {}", e),
+    }
+}
+
+// 按代理和网络设置创建客户端；设置无效时退回默认客户端并返回错误信息
+fn client_with_network(proxy: &config::ProxyConfig, network: &config::NetworkConfig) -> (Client, Option<String>) {
+    match api::build_client(proxy, network) {
+        Ok(client) => (client, None),
+        Err(e) => {
+            error!("网络设置无效: {}", e);
+            let client = api::build_client(&config::ProxyConfig::default(), &config::NetworkConfig::default()).unwrap();
+            (client, Some(e))
+        }
+    }
+}
+
 pub struct ChatApp {
     pub input_text: String,
     pub chat_history: ChatHistory,
     pub api_key: String,
     pub runtime: Runtime,
     pub runtime_handle: tokio::runtime::Handle,
-    pub receiver: Option<mpsc::UnboundedReceiver<String>>,
+    pub receiver: Option<mpsc::UnboundedReceiver<UiEvent>>,
     pub show_settings: bool,
     pub api_endpoint: String,
+    pub api_provider: ProviderKind,
+    pub claude_config: config::ProviderConfig,
+    pub gemini_config: config::ProviderConfig,
+    pub azure_config: config::AzureConfig,
+    pub proxy_config: config::ProxyConfig,
+    pub network_config: config::NetworkConfig,
+    pub failover: config::FailoverConfig,
+    pub endpoint_health: EndpointHealth,
+    // 当前代理设置无效时的错误信息
+    pub network_error: Option<String>,
+    pub proxy_testing: bool,
+    pub proxy_test_result: Option<Result<String, String>>,
+    pub proxy_test_sender: mpsc::UnboundedSender<Result<String, String>>,
+    pub proxy_test_receiver: mpsc::UnboundedReceiver<Result<String, String>>,
     pub model_name: String,
     pub system_prompt: String,
     pub temperature: f32,
+    pub top_p: f32,
+    pub frequency_penalty: f32,
+    pub presence_penalty: f32,
     pub client: Client,
     pub chat_list: ChatList,
     pub previous_show_settings: bool,
     pub retry_enabled: bool,
     pub max_retries: i32,
+    pub stall_timeout_secs: u64,
+    pub webhook_url: String,
+    pub incognito_minutes: u64,
+    pub smart_code_paste: bool,
+    pub long_term_memory: bool,
+    pub memory_store: MemoryStore,
+    pub long_memory_sender: mpsc::UnboundedSender<MemoryEvent>,
+    pub long_memory_receiver: mpsc::UnboundedReceiver<MemoryEvent>,
+    // 正在提炼或嵌入的记忆数量
+    memory_pending: usize,
+    show_memory_manager: bool,
+    // 正在编辑的记忆：(ID, 文字)
+    memory_editing: Option<(String, String)>,
+    code_paste_undo: Option<CodePasteUndo>,
+    // 当前回复已经停滞的秒数
+    stream_stalled: Option<u64>,
     pub selected_image: Option<PathBuf>,
     pub processing_image: Option<tokio::task::JoinHandle<Result<PathBuf, ImageError>>>,
     pub dark_mode: bool,
     pub available_models: Vec<String>,
     pub input_focus: bool,
     pub markdown_cache: CommonMarkCache,
+    pub image_cache: ImageCache,
     pub new_model_input: String,
     pub show_role_creator: bool,
     pub role_name_input: String,
     pub role_prompt_input: String,
     pub role_model_name: String,
     pub role_temperature: f32,
+    pub role_greeting_input: String,
+    pub role_greeting_in_context: bool,
+    pub role_provider: Option<ProviderKind>,
+    pub role_profile: Option<String>,
+    pub profiles: Vec<config::ApiProfile>,
+    pub active_profile: Option<String>,
     pub clear_chat_mode: bool,
-    pub input_height: f32,
+    pub input_height_ratio: f32,
+    // 上一帧输入内容（含自动换行）的实际高度，用于让输入区随内容增高
+    pub input_content_height: f32,
     pub dragging_input: bool,
     pub search_query: String,
     pub is_loading: bool,
     pub loading_dots: String,
     pub loading_animation_timer: f32,
+    pub pending_config_reset: Option<config::ConfigSection>,
+    pub emoji_search: String,
+    pub move_input_cursor_to_end: bool,
+    pub show_input_preview: bool,
+    // 输入内容过大时仍然使用编辑框
+    pub edit_large_input: bool,
+    pub snippets: Vec<config::Snippet>,
+    pub text_attachments: Vec<TextAttachment>,
+    pub pending_large_paste: Option<String>,
+    pub code_input_mode: bool,
+    pub code_language: String,
+    pub keybinding_mode: config::KeybindingMode,
+    pub send_key: config::SendKey,
+    pub ime_state: ImeState,
+    pub reasoning_effort: config::ReasoningEffort,
+    pub modal_state: ModalState,
+    pub follow_up_actions: Vec<config::FollowUpAction>,
+    pub post_processors: Vec<PostProcessor>,
+    post_process_sender: mpsc::UnboundedSender<Processed>,
+    post_process_receiver: mpsc::UnboundedReceiver<Processed>,
+    pub pricing: std::collections::BTreeMap<String, ModelPrice>,
+    pub cost_ledger: CostLedger,
+    pub queued_prompt: Option<String>,
+    // 正在进行的请求的上下文清单，回复完成后挂到助手消息上
+    pending_manifest: Option<RequestManifest>,
+    // 正在编辑的用户消息（位置和草稿），以及重新发送时要记录的原文
+    editing_message: Option<(usize, String)>,
+    pending_edit_original: Option<String>,
+    viewing_manifest: Option<RequestManifest>,
+    // 正在进行的单条回复重试，以及重试弹窗中编辑的参数（对应消息位置）
+    turn_retry: Option<TurnRetry>,
+    retry_draft: Option<(usize, RetryOverrides)>,
+    // 用其他模型重放：设置窗口中的原对话和模型，以及正在进行的重放
+    replay_setup: Option<(String, String)>,
+    replay: Option<Replay>,
+    // 对比模式：开启时为选中的模型，以及正在进行的对比
+    compare_models: Option<Vec<String>>,
+    comparison: Option<Comparison>,
+    // 新建群聊窗口中的名称和选中的角色，以及正在进行的群聊回复
+    group_setup: Option<(String, Vec<String>)>,
+    group_turn: Option<GroupTurn>,
+    // 输入框和上下文的 token 计数缓存
+    token_counter: TokenCounter,
+    // 上下文裁剪时各对话已生成的摘要
+    context_summaries: SummaryCache,
+    // 后台更新的对话记忆，以及正在更新的对话
+    memory_sender: mpsc::UnboundedSender<(String, Option<ChatMemory>)>,
+    memory_receiver: mpsc::UnboundedReceiver<(String, Option<ChatMemory>)>,
+    memory_updating: HashSet<String>,
+    // 日记摘要的后台结果：对话 ID、摘要包含到的日期和新的摘要
+    journal_sender: mpsc::UnboundedSender<(String, chrono::NaiveDate, Option<String>)>,
+    journal_receiver: mpsc::UnboundedReceiver<(String, chrono::NaiveDate, Option<String>)>,
+    journal_updating: bool,
+    // 日历中显示的月份和需要滚动到的日期
+    journal_month: Option<chrono::NaiveDate>,
+    journal_jump: Option<chrono::NaiveDate>,
+    pending_chat_switch: Option<ChatSwitch>,
+    queued_chat_switch: Option<ChatSwitch>,
+    filter_hold: Option<FilterHold>,
+    pub show_task_panel: bool,
+    pub new_task_input: String,
+    pub auto_extract_tasks: bool,
+    pub group_by_role: bool,
+    pub ghost_text_enabled: bool,
+    pub ghost_text_model: String,
+    pub smart_replies_enabled: bool,
+    pub mask_secrets_in_exports: bool,
+    pub auto_export_dir: String,
+    // 自动导出后对话的指纹，指纹变化时重新导出
+    auto_exported: HashMap<String, u64>,
+    last_auto_export: Option<Instant>,
+    pub content_filter_mode: FilterMode,
+    // 每行一条过滤规则
+    pub content_filter_terms: String,
+    pub role_filter_mode: Option<FilterMode>,
+    pub role_filter_terms: String,
+    pub suggestion_model: String,
+    // 最新回复下的建议问题，按对话 ID 记录
+    pub reply_suggestions: Option<(String, Vec<String>)>,
+    pub suggestion_sender: mpsc::UnboundedSender<(String, Vec<String>)>,
+    pub suggestion_receiver: mpsc::UnboundedReceiver<(String, Vec<String>)>,
+    pub dictation_bridge_enabled: bool,
+    pub match_reply_language: bool,
+    pub context_strategy: ContextStrategy,
+    pub context_keep_last: usize,
+    pub auto_memory: bool,
+    pub entity_actions: bool,
+    // 等待用户确认运行的命令，以及命令的运行结果
+    pending_command: Option<String>,
+    command_sender: mpsc::UnboundedSender<(String, Result<String, String>)>,
+    command_receiver: mpsc::UnboundedReceiver<(String, Result<String, String>)>,
+    command_running: bool,
+    command_output: Option<(String, Result<String, String>)>,
+    pub capabilities: std::collections::BTreeMap<String, Capabilities>,
+    // 正在探测能力的模型
+    capability_probe: Option<String>,
+    capability_sender: mpsc::UnboundedSender<(String, Result<Capabilities, String>)>,
+    capability_receiver: mpsc::UnboundedReceiver<(String, Result<Capabilities, String>)>,
+    // 当前模型不支持图片输入时，发送前的提醒
+    image_unsupported_hold: bool,
+    pub tts_model: String,
+    pub tts_voice: String,
+    pub audio_player: AudioPlayer,
+    // 正在合成的音频缓存路径
+    pub speech_pending: Option<PathBuf>,
+    pub speech_sender: mpsc::UnboundedSender<Result<PathBuf, String>>,
+    pub speech_receiver: mpsc::UnboundedReceiver<Result<PathBuf, String>>,
+    pub image_model: String,
+    // 正在生成图片的对话
+    pub image_pending: Option<String>,
+    pub image_sender: mpsc::UnboundedSender<(String, String, Result<PathBuf, String>)>,
+    pub image_receiver: mpsc::UnboundedReceiver<(String, String, Result<PathBuf, String>)>,
+    pub embedding_model: String,
+    pub knowledge_top_k: usize,
+    pub metrics_enabled: bool,
+    pub metrics: Metrics,
+    pub show_metrics: bool,
+    // 正在建立知识库索引的对话
+    pub knowledge_indexing: Option<String>,
+    pub knowledge_sender: mpsc::UnboundedSender<(String, PathBuf, Result<usize, String>)>,
+    pub knowledge_receiver: mpsc::UnboundedReceiver<(String, PathBuf, Result<usize, String>)>,
+    // 路径补全使用的文件夹内容：(文件夹, 列出的时间, 相对路径)
+    workspace_paths: Option<(String, Instant, Vec<String>)>,
+    workspace_loading: Option<String>,
+    workspace_sender: mpsc::UnboundedSender<(String, Result<Vec<String>, String>)>,
+    workspace_receiver: mpsc::UnboundedReceiver<(String, Result<Vec<String>, String>)>,
+    pub quick_ask_prompt: String,
+    // 主选区快速提问的悬浮窗
+    quick_ask: Option<QuickAsk>,
+    // 正在运行的听写桥接任务
+    dictation_task: Option<u64>,
+    pub dictation_sender: mpsc::UnboundedSender<String>,
+    pub dictation_receiver: mpsc::UnboundedReceiver<String>,
+    // 接收其他程序发送文本的后台任务
+    inbox_task: Option<u64>,
+    platform: PlatformIntegration,
+    pub inbox_sender: mpsc::UnboundedSender<InboxMessage>,
+    pub inbox_receiver: mpsc::UnboundedReceiver<InboxMessage>,
+    pub ghost_text: GhostTextState,
+    pub ghost_sender: mpsc::UnboundedSender<(String, Option<String>)>,
+    pub ghost_receiver: mpsc::UnboundedReceiver<(String, Option<String>)>,
+    pub show_template_import: bool,
+    pub template_url_input: String,
+    pub template_loading: bool,
+    pub template_error: Option<String>,
+    pub template_preview: Option<TemplateBundle>,
+    // 分享窗口：模板名称、dream:// 链接和二维码
+    share_link: Option<(String, String)>,
+    share_qr: Option<Result<egui::TextureHandle, String>>,
+    chat_import: Option<ChatImport>,
+    // 分屏中对照查看的对话
+    split_chat_id: Option<String>,
+    pub template_sender: mpsc::UnboundedSender<Result<TemplateBundle, String>>,
+    pub template_receiver: mpsc::UnboundedReceiver<Result<TemplateBundle, String>>,
+    pub task_sender: mpsc::UnboundedSender<(String, Vec<String>)>,
+    pub task_receiver: mpsc::UnboundedReceiver<(String, Vec<String>)>,
+    pub title_sender: mpsc::UnboundedSender<(String, String)>,
+    tee_writer: TeeWriter,
+    pub title_receiver: mpsc::UnboundedReceiver<(String, String)>,
+    pub task_registry: TaskRegistry,
+    pub show_task_debug: bool,
+    // 最近一次对话请求的原始数据
+    pub inspector: RequestInspector,
+    // 最近一次出错的请求，生成诊断包时使用
+    failed_request: Option<RequestInspector>,
+    pub show_inspector: bool,
+    // 后台任务发来的通知
+    pub notifications: Notifications,
+    pub show_notifications: bool,
+    pub notification_sender: mpsc::UnboundedSender<String>,
+    pub notification_receiver: mpsc::UnboundedReceiver<String>,
+    pub cache_config: config::CacheConfig,
+    pub title_config: titles::TitleConfig,
+    pub backup_config: BackupConfig,
+    // 备份列表窗口，打开时读取
+    backups: Option<Vec<backup::Snapshot>>,
+    pub lock_config: LockConfig,
+    // 应用锁：是否已锁定、解锁时输入的密码和最近一次操作的时间
+    locked: bool,
+    lock_input: String,
+    lock_error: Option<String>,
+    last_activity: Instant,
+    // 设置密码窗口中的两次输入
+    lock_setup: Option<(String, String)>,
+    // 最小化时显示在窗口标题中的回复进度，以及上次更新的时间
+    title_progress: Option<(Instant, String)>,
+    last_cache_eviction: Option<Instant>,
+    pub selected_chat_ids: Vec<String>,
+    pub selection_anchor: Option<String>,
+    pub batch_tag_input: String,
+    // 侧边栏只显示带有该标签的对话
+    tag_filter: Option<String>,
+    folder_input: String,
+    pub batch_job: Option<BatchJob>,
+    pub confirm_batch_delete: bool,
+    pub encrypted_export: Option<EncryptedExport>,
+    // 以 --safe-mode 启动：不读取也不写入配置、对话和费用记录
+    pub safe_mode: bool,
+    // 安全模式下从备份恢复后，需要重新启动才能加载恢复的文件
+    restored_in_safe_mode: bool,
+    pub features: FeatureFlags,
+    settings_tab: SettingsTab,
+    show_changelog: bool,
 }
 
 impl Default for ChatApp {
@@ -58,16 +510,32 @@ impl Default for ChatApp {
         let runtime = Runtime::new().unwrap();
         let runtime_handle = runtime.handle().clone();
 
-        // 修复 timeout 的类型问题
-        let client = Client::builder()
-            .pool_idle_timeout(std::time::Duration::from_secs(30))
-            .tcp_keepalive(std::time::Duration::from_secs(60))
-            .timeout(std::time::Duration::from_secs(30))
-            .build()
-            .unwrap();
-
         // 读取配置文件并等待结果
         let config = runtime_handle.block_on(async { config::load_config().await });
+        let (client, network_error) = client_with_network(&config.proxy, &config.network);
+        let (task_sender, task_receiver) = mpsc::unbounded_channel();
+        let (title_sender, title_receiver) = mpsc::unbounded_channel();
+        let (template_sender, template_receiver) = mpsc::unbounded_channel();
+        let (ghost_sender, ghost_receiver) = mpsc::unbounded_channel();
+        let (suggestion_sender, suggestion_receiver) = mpsc::unbounded_channel();
+        let (dictation_sender, dictation_receiver) = mpsc::unbounded_channel();
+        let (inbox_sender, inbox_receiver) = mpsc::unbounded_channel();
+        let (notification_sender, notification_receiver) = mpsc::unbounded_channel();
+        let (memory_sender, memory_receiver) = mpsc::unbounded_channel();
+        let (journal_sender, journal_receiver) = mpsc::unbounded_channel();
+        let (command_sender, command_receiver) = mpsc::unbounded_channel();
+        let (capability_sender, capability_receiver) = mpsc::unbounded_channel();
+        let (proxy_test_sender, proxy_test_receiver) = mpsc::unbounded_channel();
+        let (speech_sender, speech_receiver) = mpsc::unbounded_channel();
+        let (image_sender, image_receiver) = mpsc::unbounded_channel();
+        let (knowledge_sender, knowledge_receiver) = mpsc::unbounded_channel();
+        let (workspace_sender, workspace_receiver) = mpsc::unbounded_channel();
+        let (post_process_sender, post_process_receiver) = mpsc::unbounded_channel();
+        let (long_memory_sender, long_memory_receiver) = mpsc::unbounded_channel();
+        let tee_writer = TeeWriter::spawn(&runtime_handle);
+        let cost_ledger = runtime_handle.block_on(costs::load_ledger());
+        let memory_store = runtime_handle.block_on(memory_store::load());
+        let metrics = Metrics::new(config.chat.metrics_enabled, runtime_handle.block_on(metrics::load()));
 
         let mut app = Self {
             input_text: String::new(),
@@ -78,33 +546,236 @@ impl Default for ChatApp {
             receiver: None,
             show_settings: false,
             api_endpoint: config.api.endpoint,
+            api_provider: config.api.provider,
+            claude_config: config.claude.clone(),
+            gemini_config: config.gemini.clone(),
+            azure_config: config.azure.clone(),
+            proxy_config: config.proxy.clone(),
+            network_config: config.network.clone(),
+            failover: config.failover.clone(),
+            endpoint_health: EndpointHealth::default(),
+            network_error,
+            proxy_testing: false,
+            proxy_test_result: None,
+            proxy_test_sender,
+            proxy_test_receiver,
             model_name: config.api.model,
             system_prompt: config.chat.system_prompt,
             temperature: config.chat.temperature as f32,
+            top_p: config.chat.top_p as f32,
+            frequency_penalty: config.chat.frequency_penalty as f32,
+            presence_penalty: config.chat.presence_penalty as f32,
             client,
             chat_list: ChatList::default(),
             previous_show_settings: false,
             retry_enabled: config.chat.retry_enabled,
             max_retries: config.chat.max_retries as i32,
+            stall_timeout_secs: config.chat.stall_timeout_secs,
+            webhook_url: config.chat.webhook_url,
+            incognito_minutes: config.chat.incognito_minutes,
+            smart_code_paste: config.chat.smart_code_paste,
+            long_term_memory: config.chat.long_term_memory,
+            memory_store,
+            long_memory_sender,
+            long_memory_receiver,
+            memory_pending: 0,
+            show_memory_manager: false,
+            memory_editing: None,
+            code_paste_undo: None,
+            stream_stalled: None,
             selected_image: None,
             processing_image: None,
             dark_mode: config.chat.dark_mode,
             available_models: config.api.available_models,
             input_focus: true,
             markdown_cache: CommonMarkCache::default(),
+            image_cache: ImageCache::default(),
             new_model_input: String::new(),
             show_role_creator: false,
             role_name_input: String::new(),
             role_prompt_input: String::new(),
             role_model_name: "gpt-4".to_string(),
             role_temperature: 0.7,
+            role_greeting_input: String::new(),
+            role_greeting_in_context: false,
+            role_provider: None,
+            role_profile: None,
+            profiles: config.profiles.clone(),
+            active_profile: config.api.active_profile.clone(),
             clear_chat_mode: true,
-            input_height: 120.0,
+            input_height_ratio: config.chat.input_height_ratio,
+            input_content_height: 0.0,
             dragging_input: false,
             search_query: String::new(),
             is_loading: false,
             loading_dots: String::new(),
             loading_animation_timer: 0.0,
+            pending_config_reset: None,
+            emoji_search: String::new(),
+            move_input_cursor_to_end: false,
+            show_input_preview: false,
+            edit_large_input: false,
+            snippets: config.snippets,
+            text_attachments: Vec::new(),
+            pending_large_paste: None,
+            code_input_mode: false,
+            code_language: "rs".to_string(),
+            keybinding_mode: config.chat.keybinding_mode,
+            send_key: config.chat.send_key,
+            ime_state: ImeState::default(),
+            reasoning_effort: config.chat.reasoning_effort,
+            modal_state: ModalState::default(),
+            follow_up_actions: config.follow_up_actions,
+            post_processors: config.post_processors,
+            post_process_sender,
+            post_process_receiver,
+            pricing: config.pricing,
+            capabilities: config.capabilities,
+            capability_probe: None,
+            image_unsupported_hold: false,
+            cost_ledger,
+            queued_prompt: None,
+            pending_manifest: None,
+            editing_message: None,
+            pending_edit_original: None,
+            viewing_manifest: None,
+            turn_retry: None,
+            retry_draft: None,
+            replay_setup: None,
+            replay: None,
+            compare_models: None,
+            comparison: None,
+            group_setup: None,
+            group_turn: None,
+            token_counter: TokenCounter::default(),
+            context_summaries: SummaryCache::default(),
+            memory_sender,
+            memory_receiver,
+            memory_updating: HashSet::new(),
+            journal_sender,
+            journal_receiver,
+            journal_updating: false,
+            journal_month: None,
+            journal_jump: None,
+            pending_chat_switch: None,
+            queued_chat_switch: None,
+            filter_hold: None,
+            show_task_panel: false,
+            new_task_input: String::new(),
+            auto_extract_tasks: config.chat.auto_extract_tasks,
+            group_by_role: config.chat.group_by_role,
+            ghost_text_enabled: config.chat.ghost_text_enabled,
+            ghost_text_model: config.chat.ghost_text_model.clone(),
+            smart_replies_enabled: config.chat.smart_replies_enabled,
+            dictation_bridge_enabled: config.chat.dictation_bridge_enabled,
+            match_reply_language: config.chat.match_reply_language,
+            context_strategy: config.chat.context_strategy,
+            context_keep_last: config.chat.context_keep_last,
+            auto_memory: config.chat.auto_memory,
+            entity_actions: config.chat.entity_actions,
+            pending_command: None,
+            command_sender,
+            command_receiver,
+            capability_sender,
+            capability_receiver,
+            command_running: false,
+            command_output: None,
+            tts_model: config.chat.tts_model.clone(),
+            tts_voice: config.chat.tts_voice.clone(),
+            image_model: config.chat.image_model.clone(),
+            embedding_model: config.chat.embedding_model.clone(),
+            knowledge_top_k: config.chat.knowledge_top_k,
+            metrics_enabled: config.chat.metrics_enabled,
+            metrics,
+            show_metrics: false,
+            audio_player: AudioPlayer::default(),
+            speech_pending: None,
+            speech_sender,
+            speech_receiver,
+            image_pending: None,
+            image_sender,
+            image_receiver,
+            knowledge_indexing: None,
+            knowledge_sender,
+            knowledge_receiver,
+            workspace_paths: None,
+            workspace_loading: None,
+            workspace_sender,
+            workspace_receiver,
+            quick_ask_prompt: config.chat.quick_ask_prompt.clone(),
+            quick_ask: None,
+            mask_secrets_in_exports: config.chat.mask_secrets_in_exports,
+            auto_export_dir: config.chat.auto_export_dir,
+            auto_exported: HashMap::new(),
+            last_auto_export: None,
+            content_filter_mode: config.content_filter.mode,
+            content_filter_terms: config.content_filter.terms.join("\n"),
+            role_filter_mode: None,
+            role_filter_terms: String::new(),
+            suggestion_model: config.chat.suggestion_model.clone(),
+            reply_suggestions: None,
+            suggestion_sender,
+            suggestion_receiver,
+            dictation_sender,
+            dictation_receiver,
+            dictation_task: None,
+            inbox_sender,
+            inbox_receiver,
+            inbox_task: None,
+            platform: PlatformIntegration::default(),
+            ghost_text: GhostTextState::default(),
+            ghost_sender,
+            ghost_receiver,
+            task_sender,
+            task_receiver,
+            title_sender,
+            tee_writer,
+            title_receiver,
+            task_registry: TaskRegistry::default(),
+            show_task_debug: false,
+            inspector: RequestInspector::default(),
+            failed_request: None,
+            show_inspector: false,
+            notifications: Notifications::default(),
+            show_notifications: false,
+            notification_sender,
+            notification_receiver,
+            cache_config: config.cache.clone(),
+            title_config: config.titles.clone(),
+            backup_config: config.backup.clone(),
+            backups: None,
+            locked: config.lock.is_active(),
+            lock_config: config.lock.clone(),
+            lock_input: String::new(),
+            lock_error: None,
+            last_activity: Instant::now(),
+            lock_setup: None,
+            title_progress: None,
+            last_cache_eviction: None,
+            selected_chat_ids: Vec::new(),
+            selection_anchor: None,
+            batch_tag_input: String::new(),
+            tag_filter: None,
+            folder_input: String::new(),
+            batch_job: None,
+            confirm_batch_delete: false,
+            encrypted_export: None,
+            safe_mode: false,
+            restored_in_safe_mode: false,
+            features: config.features.clone(),
+            settings_tab: SettingsTab::General,
+            show_changelog: false,
+            show_template_import: false,
+            template_url_input: String::new(),
+            template_loading: false,
+            template_error: None,
+            template_preview: None,
+            share_link: None,
+            share_qr: None,
+            chat_import: None,
+            split_chat_id: None,
+            template_sender,
+            template_receiver,
         };
 
         // 先尝试加载聊天列表
@@ -123,6 +794,21 @@ impl Default for ChatApp {
                 config: None,
                 created_at: Utc::now(),
                 updated_at: Utc::now(),
+                tasks: Vec::new(),
+                tags: Vec::new(),
+                archived: false,
+                role_id: None,
+                tee: None,
+                knowledge_folder: None,
+                group_members: Vec::new(),
+                memory: None,
+                journal: None,
+                model_override: None,
+                webhook_url: None,
+                incognito: false,
+                folder: None,
+                pinned: false,
+                disabled_post_processors: Vec::new(),
             };
             app.chat_list.chats.insert(0, new_chat);
             app.chat_list.current_chat_id = Some(id);
@@ -137,23 +823,55 @@ impl Default for ChatApp {
 }
 
 impl ChatApp {
-    pub fn new(runtime: Runtime) -> Self {
+    pub fn new(runtime: Runtime, safe_mode: bool) -> Self {
         debug!("创建新的 ChatApp 实");
         let handle = runtime.handle().clone();
 
-        // 修复 timeout 的类型问题
-        debug!("初始化 HTTP 客户端");
-        let client = Client::builder()
-            .pool_idle_timeout(std::time::Duration::from_secs(30))
-            .tcp_keepalive(std::time::Duration::from_secs(60))
-            .timeout(std::time::Duration::from_secs(30))
-            .build()
-            .unwrap();
-
-        // 读取配置文件并等待结果
+        // 读取配置文件并等待结果；安全模式下使用默认配置
         debug!("加载配置文件");
-        let config = handle.block_on(async { config::load_config().await });
+        let config = if safe_mode {
+            config::Config::default()
+        } else {
+            handle.block_on(async { config::load_config().await })
+        };
+        debug!("初始化 HTTP 客户端");
+        let (client, network_error) = client_with_network(&config.proxy, &config.network);
         debug!("配置加载完成");
+        let (task_sender, task_receiver) = mpsc::unbounded_channel();
+        let (title_sender, title_receiver) = mpsc::unbounded_channel();
+        let (template_sender, template_receiver) = mpsc::unbounded_channel();
+        let (ghost_sender, ghost_receiver) = mpsc::unbounded_channel();
+        let (suggestion_sender, suggestion_receiver) = mpsc::unbounded_channel();
+        let (dictation_sender, dictation_receiver) = mpsc::unbounded_channel();
+        let (inbox_sender, inbox_receiver) = mpsc::unbounded_channel();
+        let (notification_sender, notification_receiver) = mpsc::unbounded_channel();
+        let (memory_sender, memory_receiver) = mpsc::unbounded_channel();
+        let (journal_sender, journal_receiver) = mpsc::unbounded_channel();
+        let (command_sender, command_receiver) = mpsc::unbounded_channel();
+        let (capability_sender, capability_receiver) = mpsc::unbounded_channel();
+        let (proxy_test_sender, proxy_test_receiver) = mpsc::unbounded_channel();
+        let (speech_sender, speech_receiver) = mpsc::unbounded_channel();
+        let (image_sender, image_receiver) = mpsc::unbounded_channel();
+        let (knowledge_sender, knowledge_receiver) = mpsc::unbounded_channel();
+        let (workspace_sender, workspace_receiver) = mpsc::unbounded_channel();
+        let (post_process_sender, post_process_receiver) = mpsc::unbounded_channel();
+        let (long_memory_sender, long_memory_receiver) = mpsc::unbounded_channel();
+        let tee_writer = TeeWriter::spawn(&handle);
+        let cost_ledger = if safe_mode {
+            CostLedger::default()
+        } else {
+            handle.block_on(costs::load_ledger())
+        };
+        let memory_store = if safe_mode {
+            MemoryStore::default()
+        } else {
+            handle.block_on(memory_store::load())
+        };
+        let metrics = if safe_mode {
+            Metrics::new(false, MetricsData::default())
+        } else {
+            Metrics::new(config.chat.metrics_enabled, handle.block_on(metrics::load()))
+        };
 
         let mut app = Self {
             input_text: String::new(),
@@ -164,38 +882,253 @@ impl ChatApp {
             receiver: None,
             show_settings: false,
             api_endpoint: config.api.endpoint,
+            api_provider: config.api.provider,
+            claude_config: config.claude.clone(),
+            gemini_config: config.gemini.clone(),
+            azure_config: config.azure.clone(),
+            proxy_config: config.proxy.clone(),
+            network_config: config.network.clone(),
+            failover: config.failover.clone(),
+            endpoint_health: EndpointHealth::default(),
+            network_error,
+            proxy_testing: false,
+            proxy_test_result: None,
+            proxy_test_sender,
+            proxy_test_receiver,
             model_name: config.api.model,
             system_prompt: config.chat.system_prompt,
             temperature: config.chat.temperature as f32,
+            top_p: config.chat.top_p as f32,
+            frequency_penalty: config.chat.frequency_penalty as f32,
+            presence_penalty: config.chat.presence_penalty as f32,
             client,
             chat_list: ChatList::default(),
             previous_show_settings: false,
             retry_enabled: config.chat.retry_enabled,
             max_retries: config.chat.max_retries as i32,
+            stall_timeout_secs: config.chat.stall_timeout_secs,
+            webhook_url: config.chat.webhook_url,
+            incognito_minutes: config.chat.incognito_minutes,
+            smart_code_paste: config.chat.smart_code_paste,
+            long_term_memory: config.chat.long_term_memory,
+            memory_store,
+            long_memory_sender,
+            long_memory_receiver,
+            memory_pending: 0,
+            show_memory_manager: false,
+            memory_editing: None,
+            code_paste_undo: None,
+            stream_stalled: None,
             selected_image: None,
             processing_image: None,
             dark_mode: config.chat.dark_mode,
             available_models: config.api.available_models,
             input_focus: true,
             markdown_cache: CommonMarkCache::default(),
+            image_cache: ImageCache::default(),
             new_model_input: String::new(),
             show_role_creator: false,
             role_name_input: String::new(),
             role_prompt_input: String::new(),
             role_model_name: "gpt-4".to_string(),
             role_temperature: 0.7,
+            role_greeting_input: String::new(),
+            role_greeting_in_context: false,
+            role_provider: None,
+            role_profile: None,
+            profiles: config.profiles.clone(),
+            active_profile: config.api.active_profile.clone(),
             clear_chat_mode: true,
-            input_height: 120.0,
+            input_height_ratio: config.chat.input_height_ratio,
+            input_content_height: 0.0,
             dragging_input: false,
             search_query: String::new(),
             is_loading: false,
             loading_dots: String::new(),
             loading_animation_timer: 0.0,
+            pending_config_reset: None,
+            emoji_search: String::new(),
+            move_input_cursor_to_end: false,
+            show_input_preview: false,
+            edit_large_input: false,
+            snippets: config.snippets,
+            text_attachments: Vec::new(),
+            pending_large_paste: None,
+            code_input_mode: false,
+            code_language: "rs".to_string(),
+            keybinding_mode: config.chat.keybinding_mode,
+            send_key: config.chat.send_key,
+            ime_state: ImeState::default(),
+            reasoning_effort: config.chat.reasoning_effort,
+            modal_state: ModalState::default(),
+            follow_up_actions: config.follow_up_actions,
+            post_processors: config.post_processors,
+            post_process_sender,
+            post_process_receiver,
+            pricing: config.pricing,
+            capabilities: config.capabilities,
+            capability_probe: None,
+            image_unsupported_hold: false,
+            cost_ledger,
+            queued_prompt: None,
+            pending_manifest: None,
+            editing_message: None,
+            pending_edit_original: None,
+            viewing_manifest: None,
+            turn_retry: None,
+            retry_draft: None,
+            replay_setup: None,
+            replay: None,
+            compare_models: None,
+            comparison: None,
+            group_setup: None,
+            group_turn: None,
+            token_counter: TokenCounter::default(),
+            context_summaries: SummaryCache::default(),
+            memory_sender,
+            memory_receiver,
+            memory_updating: HashSet::new(),
+            journal_sender,
+            journal_receiver,
+            journal_updating: false,
+            journal_month: None,
+            journal_jump: None,
+            pending_chat_switch: None,
+            queued_chat_switch: None,
+            filter_hold: None,
+            show_task_panel: false,
+            new_task_input: String::new(),
+            auto_extract_tasks: config.chat.auto_extract_tasks,
+            group_by_role: config.chat.group_by_role,
+            ghost_text_enabled: config.chat.ghost_text_enabled,
+            ghost_text_model: config.chat.ghost_text_model.clone(),
+            smart_replies_enabled: config.chat.smart_replies_enabled,
+            dictation_bridge_enabled: config.chat.dictation_bridge_enabled,
+            match_reply_language: config.chat.match_reply_language,
+            context_strategy: config.chat.context_strategy,
+            context_keep_last: config.chat.context_keep_last,
+            auto_memory: config.chat.auto_memory,
+            entity_actions: config.chat.entity_actions,
+            pending_command: None,
+            command_sender,
+            command_receiver,
+            capability_sender,
+            capability_receiver,
+            command_running: false,
+            command_output: None,
+            tts_model: config.chat.tts_model.clone(),
+            tts_voice: config.chat.tts_voice.clone(),
+            image_model: config.chat.image_model.clone(),
+            embedding_model: config.chat.embedding_model.clone(),
+            knowledge_top_k: config.chat.knowledge_top_k,
+            metrics_enabled: config.chat.metrics_enabled,
+            metrics,
+            show_metrics: false,
+            audio_player: AudioPlayer::default(),
+            speech_pending: None,
+            speech_sender,
+            speech_receiver,
+            image_pending: None,
+            image_sender,
+            image_receiver,
+            knowledge_indexing: None,
+            knowledge_sender,
+            knowledge_receiver,
+            workspace_paths: None,
+            workspace_loading: None,
+            workspace_sender,
+            workspace_receiver,
+            quick_ask_prompt: config.chat.quick_ask_prompt.clone(),
+            quick_ask: None,
+            mask_secrets_in_exports: config.chat.mask_secrets_in_exports,
+            auto_export_dir: config.chat.auto_export_dir,
+            auto_exported: HashMap::new(),
+            last_auto_export: None,
+            content_filter_mode: config.content_filter.mode,
+            content_filter_terms: config.content_filter.terms.join("\n"),
+            role_filter_mode: None,
+            role_filter_terms: String::new(),
+            suggestion_model: config.chat.suggestion_model.clone(),
+            reply_suggestions: None,
+            suggestion_sender,
+            suggestion_receiver,
+            dictation_sender,
+            dictation_receiver,
+            dictation_task: None,
+            inbox_sender,
+            inbox_receiver,
+            inbox_task: None,
+            platform: PlatformIntegration::default(),
+            ghost_text: GhostTextState::default(),
+            ghost_sender,
+            ghost_receiver,
+            task_sender,
+            task_receiver,
+            title_sender,
+            tee_writer,
+            title_receiver,
+            task_registry: TaskRegistry::default(),
+            show_task_debug: false,
+            inspector: RequestInspector::default(),
+            failed_request: None,
+            show_inspector: false,
+            notifications: Notifications::default(),
+            show_notifications: false,
+            notification_sender,
+            notification_receiver,
+            cache_config: config.cache.clone(),
+            title_config: config.titles.clone(),
+            backup_config: config.backup.clone(),
+            backups: None,
+            locked: config.lock.is_active(),
+            lock_config: config.lock.clone(),
+            lock_input: String::new(),
+            lock_error: None,
+            last_activity: Instant::now(),
+            lock_setup: None,
+            title_progress: None,
+            last_cache_eviction: None,
+            selected_chat_ids: Vec::new(),
+            selection_anchor: None,
+            batch_tag_input: String::new(),
+            tag_filter: None,
+            folder_input: String::new(),
+            batch_job: None,
+            confirm_batch_delete: false,
+            encrypted_export: None,
+            safe_mode: false,
+            restored_in_safe_mode: false,
+            features: config.features.clone(),
+            settings_tab: SettingsTab::General,
+            show_changelog: false,
+            show_template_import: false,
+            template_url_input: String::new(),
+            template_loading: false,
+            template_error: None,
+            template_preview: None,
+            share_link: None,
+            share_qr: None,
+            chat_import: None,
+            split_chat_id: None,
+            template_sender,
+            template_receiver,
         };
 
-        // 先尝试加载聊天列表
-        if let Err(e) = app.load_chat_list() {
+        // 先尝试加载聊天列表，安全模式下从空列表开始
+        app.safe_mode = safe_mode;
+        if safe_mode {
+            debug!("安全模式启动，跳过加载聊天列表");
+        } else if let Err(e) = app.load_chat_list() {
             eprintln!("加载聊天列表失败: {}", e);
+        } else {
+            let backup_config = app.backup_config.clone();
+            app.task_registry.spawn(&app.runtime_handle, "自动备份", None, async move {
+                match backup::run_scheduled(&backup_config).await {
+                    Ok(Some(snapshot)) => debug!("自动备份完成: {}", snapshot.path.display()),
+                    Ok(None) => {}
+                    Err(e) => error!("自动备份失败: {}", e),
+                }
+            });
         }
 
         // 只有在加载后聊天列表仍为空时，才创建默认对话
@@ -209,6 +1142,21 @@ impl ChatApp {
                 config: None,
                 created_at: Utc::now(),
                 updated_at: Utc::now(),
+                tasks: Vec::new(),
+                tags: Vec::new(),
+                archived: false,
+                role_id: None,
+                tee: None,
+                knowledge_folder: None,
+                group_members: Vec::new(),
+                memory: None,
+                journal: None,
+                model_override: None,
+                webhook_url: None,
+                incognito: false,
+                folder: None,
+                pinned: false,
+                disabled_post_processors: Vec::new(),
             };
             app.chat_list.chats.insert(0, new_chat);
             app.chat_list.current_chat_id = Some(id);
@@ -222,23 +1170,157 @@ impl ChatApp {
         app
     }
 
-    fn save_config(&self, _frame: &mut eframe::Frame) -> Result<(), Box<dyn std::error::Error>> {
-        debug!("正在保存配置...");
-        let config = config::Config {
+    // 由当前界面状态构建配置
+    fn current_config(&self) -> config::Config {
+        config::Config {
             api_key: self.api_key.clone(),
             api: config::ApiConfig {
                 endpoint: self.api_endpoint.clone(),
                 model: self.model_name.clone(),
                 available_models: self.available_models.clone(),
+                provider: self.api_provider,
+                active_profile: self.active_profile.clone(),
             },
             chat: config::ChatConfig {
                 system_prompt: self.system_prompt.clone(),
                 temperature: self.temperature as f64,
+                top_p: self.top_p as f64,
+                frequency_penalty: self.frequency_penalty as f64,
+                presence_penalty: self.presence_penalty as f64,
                 retry_enabled: self.retry_enabled,
                 max_retries: self.max_retries as i64,
+                stall_timeout_secs: self.stall_timeout_secs,
+                webhook_url: self.webhook_url.clone(),
+                incognito_minutes: self.incognito_minutes,
+                smart_code_paste: self.smart_code_paste,
+                long_term_memory: self.long_term_memory,
                 dark_mode: self.dark_mode,
+                keybinding_mode: self.keybinding_mode,
+                send_key: self.send_key,
+                input_height_ratio: self.input_height_ratio,
+                reasoning_effort: self.reasoning_effort,
+                auto_extract_tasks: self.auto_extract_tasks,
+                group_by_role: self.group_by_role,
+                ghost_text_enabled: self.ghost_text_enabled,
+                ghost_text_model: self.ghost_text_model.clone(),
+                smart_replies_enabled: self.smart_replies_enabled,
+                dictation_bridge_enabled: self.dictation_bridge_enabled,
+                match_reply_language: self.match_reply_language,
+                context_strategy: self.context_strategy,
+                context_keep_last: self.context_keep_last,
+                auto_memory: self.auto_memory,
+                entity_actions: self.entity_actions,
+                tts_model: self.tts_model.clone(),
+                tts_voice: self.tts_voice.clone(),
+                image_model: self.image_model.clone(),
+                embedding_model: self.embedding_model.clone(),
+                knowledge_top_k: self.knowledge_top_k,
+                metrics_enabled: self.metrics_enabled,
+                quick_ask_prompt: self.quick_ask_prompt.clone(),
+                mask_secrets_in_exports: self.mask_secrets_in_exports,
+                auto_export_dir: self.auto_export_dir.clone(),
+                suggestion_model: self.suggestion_model.clone(),
             },
-        };
+            snippets: self.snippets.clone(),
+            follow_up_actions: self.follow_up_actions.clone(),
+            post_processors: self.post_processors.clone(),
+            pricing: self.pricing.clone(),
+            capabilities: self.capabilities.clone(),
+            claude: self.claude_config.clone(),
+            gemini: self.gemini_config.clone(),
+            azure: self.azure_config.clone(),
+            proxy: self.proxy_config.clone(),
+            network: self.network_config.clone(),
+            profiles: self.profiles.clone(),
+            features: self.features.clone(),
+            cache: self.cache_config.clone(),
+            titles: self.title_config.clone(),
+            backup: self.backup_config.clone(),
+            lock: self.lock_config.clone(),
+            failover: self.failover.clone(),
+            content_filter: config::ContentFilterConfig {
+                mode: self.content_filter_mode,
+                terms: content_filter::parse_terms(&self.content_filter_terms),
+            },
+        }
+    }
+
+    // 将配置应用到界面状态
+    fn apply_config(&mut self, config: config::Config) {
+        self.api_key = config.api_key;
+        self.api_endpoint = config.api.endpoint;
+        self.api_provider = config.api.provider;
+        self.active_profile = config.api.active_profile;
+        self.profiles = config.profiles;
+        self.features = config.features;
+        self.cache_config = config.cache;
+        self.title_config = config.titles;
+        self.backup_config = config.backup;
+        self.lock_config = config.lock;
+        self.claude_config = config.claude;
+        self.gemini_config = config.gemini;
+        self.azure_config = config.azure;
+        self.proxy_config = config.proxy;
+        self.network_config = config.network;
+        self.failover = config.failover;
+        self.rebuild_client();
+        self.model_name = config.api.model;
+        self.available_models = config.api.available_models;
+        self.system_prompt = config.chat.system_prompt;
+        self.temperature = config.chat.temperature as f32;
+        self.top_p = config.chat.top_p as f32;
+        self.frequency_penalty = config.chat.frequency_penalty as f32;
+        self.presence_penalty = config.chat.presence_penalty as f32;
+        self.retry_enabled = config.chat.retry_enabled;
+        self.max_retries = config.chat.max_retries as i32;
+        self.stall_timeout_secs = config.chat.stall_timeout_secs;
+        self.webhook_url = config.chat.webhook_url;
+        self.incognito_minutes = config.chat.incognito_minutes;
+        self.smart_code_paste = config.chat.smart_code_paste;
+        self.long_term_memory = config.chat.long_term_memory;
+        self.dark_mode = config.chat.dark_mode;
+        self.keybinding_mode = config.chat.keybinding_mode;
+        self.send_key = config.chat.send_key;
+        self.input_height_ratio = config.chat.input_height_ratio;
+        self.reasoning_effort = config.chat.reasoning_effort;
+        self.auto_extract_tasks = config.chat.auto_extract_tasks;
+        self.group_by_role = config.chat.group_by_role;
+        self.ghost_text_enabled = config.chat.ghost_text_enabled;
+        self.ghost_text_model = config.chat.ghost_text_model;
+        self.smart_replies_enabled = config.chat.smart_replies_enabled;
+        self.dictation_bridge_enabled = config.chat.dictation_bridge_enabled;
+        self.match_reply_language = config.chat.match_reply_language;
+        self.context_strategy = config.chat.context_strategy;
+        self.context_keep_last = config.chat.context_keep_last;
+        self.auto_memory = config.chat.auto_memory;
+        self.entity_actions = config.chat.entity_actions;
+        self.tts_model = config.chat.tts_model;
+        self.tts_voice = config.chat.tts_voice;
+        self.image_model = config.chat.image_model;
+        self.embedding_model = config.chat.embedding_model;
+        self.knowledge_top_k = config.chat.knowledge_top_k;
+        self.metrics_enabled = config.chat.metrics_enabled;
+        self.metrics.set_enabled(self.metrics_enabled);
+        self.quick_ask_prompt = config.chat.quick_ask_prompt;
+        self.mask_secrets_in_exports = config.chat.mask_secrets_in_exports;
+        self.auto_export_dir = config.chat.auto_export_dir;
+        self.suggestion_model = config.chat.suggestion_model;
+        self.snippets = config.snippets;
+        self.follow_up_actions = config.follow_up_actions;
+        self.post_processors = config.post_processors;
+        self.pricing = config.pricing;
+        self.capabilities = config.capabilities;
+        self.content_filter_mode = config.content_filter.mode;
+        self.content_filter_terms = config.content_filter.terms.join("\n");
+    }
+
+    fn save_config(&self, _frame: &mut eframe::Frame) -> Result<(), Box<dyn std::error::Error>> {
+        if self.safe_mode {
+            debug!("安全模式，跳过保存配置");
+            return Ok(());
+        }
+        debug!("正在保存配置...");
+        let config = self.current_config();
 
         // 使用 block_on 等待异步保存完成
         self.runtime_handle
@@ -246,13 +1328,596 @@ impl ChatApp {
             .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
     }
 
+    fn image_cache_dir(&self) -> PathBuf {
+        PathBuf::from(&self.cache_config.image_dir)
+    }
+
+    fn audio_cache_dir(&self) -> PathBuf {
+        PathBuf::from(&self.cache_config.audio_dir)
+    }
+
+    // 自动导出：定期检查对话内容是否有变化，把有变化的对话重新导出为 Markdown，删除的对话同时删除导出文件
+    fn schedule_auto_export(&mut self) {
+        const AUTO_EXPORT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+        if self.safe_mode
+            || self.auto_export_dir.trim().is_empty()
+            || self.last_auto_export.is_some_and(|last| last.elapsed() < AUTO_EXPORT_INTERVAL)
+        {
+            return;
+        }
+        self.last_auto_export = Some(Instant::now());
+        // 正在生成的回复完成后再导出
+        let loading_id = self.chat_list.current_chat_id.clone().filter(|_| self.is_loading);
+        let mut changed = Vec::new();
+        for chat in &self.chat_list.chats {
+            if chat.incognito || chat.messages.is_empty() || loading_id.as_ref() == Some(&chat.id) {
+                continue;
+            }
+            let fingerprint = export::fingerprint(chat);
+            if self.auto_exported.get(&chat.id) != Some(&fingerprint) {
+                self.auto_exported.insert(chat.id.clone(), fingerprint);
+                changed.push(chat.clone());
+            }
+        }
+        let removed: Vec<String> = self
+            .auto_exported
+            .keys()
+            .filter(|id| !self.chat_list.chats.iter().any(|chat| &chat.id == *id))
+            .cloned()
+            .collect();
+        for id in &removed {
+            self.auto_exported.remove(id);
+        }
+        if changed.is_empty() && removed.is_empty() {
+            return;
+        }
+
+        let dir = PathBuf::from(self.auto_export_dir.trim());
+        let mask_secrets = self.mask_secrets_in_exports;
+        self.task_registry.spawn(&self.runtime_handle, "自动导出", None, async move {
+            if let Err(e) = tokio::fs::create_dir_all(&dir).await {
+                error!("创建自动导出目录失败: {}", e);
+                return;
+            }
+            for chat in &changed {
+                if let Err(e) = export::mirror_chat_markdown(chat, &dir, mask_secrets).await {
+                    error!("自动导出对话失败: {} - {}", chat.name, e);
+                }
+            }
+            for id in &removed {
+                if let Err(e) = export::remove_mirrored(&dir, id, None).await {
+                    error!("删除自动导出的文件失败: {}", e);
+                }
+            }
+            debug!("自动导出了 {} 个对话，删除了 {} 个", changed.len(), removed.len());
+        });
+    }
+
+    // 定期在后台清理缓存：图片缓存超出容量上限时删除最久未使用且不再被对话引用的文件，
+    // 语音缓存删除超过保留天数的文件后再按容量上限清理
+    fn schedule_cache_eviction(&mut self) {
+        const EVICTION_INTERVAL: std::time::Duration = std::time::Duration::from_secs(600);
+        if self.safe_mode || self.last_cache_eviction.is_some_and(|last| last.elapsed() < EVICTION_INTERVAL) {
+            return;
+        }
+        self.last_cache_eviction = Some(Instant::now());
+
+        let audio_dir = self.audio_cache_dir();
+        let audio_max_bytes = self.cache_config.audio_max_size_mb * 1024 * 1024;
+        let audio_max_age = self.cache_config.audio_max_age_days;
+        self.task_registry.spawn(&self.runtime_handle, "清理语音缓存", None, async move {
+            if audio_max_age > 0 {
+                let max_age = std::time::Duration::from_secs(audio_max_age * 24 * 3600);
+                match cache::evict_older_than(&audio_dir, max_age).await {
+                    Ok(report) if report.removed > 0 => debug!("删除了过期的语音缓存: {:?}", report),
+                    Ok(_) => {}
+                    Err(e) => error!("清理语音缓存失败: {}", e),
+                }
+            }
+            if audio_max_bytes > 0 {
+                match cache::evict(&audio_dir, audio_max_bytes, &HashSet::new()).await {
+                    Ok(report) if report.removed > 0 => debug!("语音缓存超出上限: {:?}", report),
+                    Ok(_) => {}
+                    Err(e) => error!("清理语音缓存失败: {}", e),
+                }
+            }
+        });
+
+        if self.cache_config.max_size_mb == 0 {
+            return;
+        }
+        let referenced: HashSet<String> = self
+            .chat_list
+            .chats
+            .iter()
+            .flat_map(|chat| &chat.messages)
+            .chain(&self.chat_history.0)
+            .filter_map(|msg| msg.image_path.as_deref())
+            .filter_map(cache::file_name)
+            .map(str::to_string)
+            .collect();
+        let dir = self.image_cache_dir();
+        let max_bytes = self.cache_config.max_size_mb * 1024 * 1024;
+        let sender = self.notification_sender.clone();
+        self.task_registry.spawn(&self.runtime_handle, "清理图片缓存", None, async move {
+            match cache::evict(&dir, max_bytes, &referenced).await {
+                Ok(report) if report.removed > 0 => {
+                    debug!("图片缓存清理完成: {:?}", report);
+                    let _ = sender.send(format!(
+                        "图片缓存超出上限，已删除 {} 个不再使用的文件，释放 {}",
+                        report.removed,
+                        cache::format_size(report.freed_bytes)
+                    ));
+                }
+                Ok(_) => {}
+                Err(e) => error!("清理图片缓存失败: {}", e),
+            }
+        });
+    }
+
+    // 把图片缓存移动到新目录，并更新对话中引用的图片路径
+    fn move_image_cache(&mut self, new_dir: PathBuf) {
+        let old_dir = self.image_cache_dir();
+        if new_dir == old_dir {
+            return;
+        }
+        debug!("迁移图片缓存: {:?} -> {:?}", old_dir, new_dir);
+        let moved = match self.runtime_handle.block_on(cache::migrate(&old_dir, &new_dir)) {
+            Ok(moved) => moved,
+            Err(e) => {
+                error!("迁移图片缓存失败: {}", e);
+                self.notifications.push(format!("迁移图片缓存失败: {}", e));
+                return;
+            }
+        };
+        let messages = self
+            .chat_list
+            .chats
+            .iter_mut()
+            .flat_map(|chat| &mut chat.messages)
+            .chain(&mut self.chat_history.0);
+        for msg in messages {
+            let Some(path) = msg.image_path.as_deref() else {
+                continue;
+            };
+            if let Some(new_path) = cache::file_name(path).and_then(|name| moved.get(name)) {
+                self.image_cache.forget(path);
+                msg.image_path = Some(new_path.to_string_lossy().to_string());
+            }
+        }
+        self.cache_config.image_dir = new_dir.to_string_lossy().to_string();
+        self.notifications.push(format!("已将 {} 个缓存文件迁移到 {}", moved.len(), self.cache_config.image_dir));
+        if let Err(e) = self.save_chat_list() {
+            error!("保存聊天列表失败: {}", e);
+        }
+    }
+
+    // 输入区高度：不低于保存的窗口比例，随输入内容增高，但不超过窗口的最大占比
+    fn input_area_height(&self, window_height: f32) -> f32 {
+        (window_height * self.input_height_ratio)
+            .max(self.input_content_height + INPUT_CHROME_HEIGHT)
+            .min(window_height * MAX_INPUT_HEIGHT_RATIO)
+            .max(MIN_INPUT_HEIGHT)
+    }
+
+    fn export_config(&self) {
+        if let Some(path) = FileDialog::new()
+            .add_filter("TOML", &["toml"])
+            .set_file_name("dream.toml")
+            .save_file()
+        {
+            debug!("导出配置到: {:?}", path);
+            let config = self.current_config();
+            if let Err(e) = self
+                .runtime_handle
+                .block_on(async { config::export_config(&config, &path).await })
+            {
+                error!("导出配置失败: {}", e);
+            }
+        }
+    }
+
+    fn import_config(&mut self, frame: &mut eframe::Frame) {
+        if let Some(path) = FileDialog::new().add_filter("TOML", &["toml"]).pick_file() {
+            debug!("从文件导入配置: {:?}", path);
+            let current = self.current_config();
+            match self
+                .runtime_handle
+                .block_on(async { config::import_config(&path, &current).await })
+            {
+                Ok(config) => {
+                    self.apply_config(config);
+                    if let Err(e) = self.save_config(frame) {
+                        error!("保存配置失败: {}", e);
+                    }
+                }
+                Err(e) => error!("导入配置失败: {}", e),
+            }
+        }
+    }
+
+    // 导入其他电脑上导出的对话包
+    fn import_chat_bundle(&mut self) {
+        let Some(path) = FileDialog::new().add_filter("JSON", &["json"]).pick_file() else {
+            return;
+        };
+        debug!("导入对话包: {:?}", path);
+        let image_dir = self.image_cache_dir();
+        match self
+            .runtime_handle
+            .block_on(export::import_chat_bundle(&path, &image_dir))
+        {
+            Ok(chat) => {
+                self.notifications.push(format!("已导入对话: {}", chat.name));
+                let id = chat.id.clone();
+                self.chat_list.chats.insert(0, chat);
+                if let Err(e) = self.save_chat_list() {
+                    error!("保存聊天列表失败: {}", e);
+                }
+                self.request_chat_switch(ChatSwitch::Open(id));
+            }
+            Err(e) => {
+                error!("导入对话包失败: {}", e);
+                self.notifications.push(format!("导入对话包失败: {}", e));
+            }
+        }
+    }
+
+    // 选择其他客户端导出的 JSON 文件，识别格式后打开预览窗口
+    fn pick_chat_import(&mut self) {
+        let Some(path) = FileDialog::new().add_filter("JSON", &["json"]).pick_file() else {
+            return;
+        };
+        debug!("从文件导入对话: {:?}", path);
+        let parsed = std::fs::read_to_string(&path)
+            .map_err(|e| format!("读取文件失败: {}", e))
+            .and_then(|text| importers::parse(&text));
+        match parsed {
+            Ok((format, chats)) => {
+                self.chat_import = Some(ChatImport {
+                    format,
+                    selected: vec![true; chats.len()],
+                    chats,
+                });
+            }
+            Err(e) => {
+                error!("导入对话失败: {}", e);
+                self.notifications.push(format!("导入对话失败: {}", e));
+            }
+        }
+    }
+
+    fn display_chat_import(&mut self, ctx: &egui::Context) {
+        let Some(import) = self.chat_import.as_mut() else {
+            return;
+        };
+        let mut open = true;
+        let mut confirm = false;
+        egui::Window::new(format!("从 {} 导入对话", import.format.label()))
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(460.0)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("共 {} 个对话", import.chats.len()));
+                    if ui.small_button("全选").clicked() {
+                        import.selected.iter_mut().for_each(|selected| *selected = true);
+                    }
+                    if ui.small_button("全不选").clicked() {
+                        import.selected.iter_mut().for_each(|selected| *selected = false);
+                    }
+                });
+                ui.separator();
+                ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+                    for (chat, selected) in import.chats.iter().zip(import.selected.iter_mut()) {
+                        ui.horizontal(|ui| {
+                            ui.checkbox(selected, "");
+                            let name = if chat.name.trim().is_empty() { "导入的对话" } else { chat.name.as_str() };
+                            ui.collapsing(format!("{} ({} 条消息)", name, chat.messages.len()), |ui| {
+                                if let Some(model) = &chat.model {
+                                    ui.label(format!("模型: {}", model));
+                                }
+                                if let Some(temperature) = chat.temperature {
+                                    ui.label(format!("Temperature: {:.1}", temperature));
+                                }
+                                if let Some(prompt) = &chat.system_prompt {
+                                    ui.label(RichText::new(format!("系统提示词: {}", prompt)).small());
+                                }
+                                for (role, content) in chat.messages.iter().take(4) {
+                                    let speaker = if role == "user" { "You" } else { "AI" };
+                                    let preview: String = content.chars().take(120).collect();
+                                    ui.label(RichText::new(format!("{}: {}", speaker, preview)).small().weak());
+                                }
+                                if chat.messages.len() > 4 {
+                                    ui.label(RichText::new("……").small().weak());
+                                }
+                            });
+                        });
+                    }
+                });
+                ui.separator();
+                ui.label(RichText::new("模型和系统提示词保存为对话设置，没有的项跟随全局设置").small().weak());
+                let count = import.selected.iter().filter(|selected| **selected).count();
+                if ui.add_enabled(count > 0, egui::Button::new(format!("导入 {} 个对话", count))).clicked() {
+                    confirm = true;
+                }
+            });
+
+        if confirm {
+            if let Some(import) = self.chat_import.take() {
+                let defaults = self.global_effective_config().params;
+                let chats: Vec<Chat> = import
+                    .chats
+                    .into_iter()
+                    .zip(import.selected)
+                    .filter(|(_, selected)| *selected)
+                    .map(|(chat, _)| chat.into_chat(defaults))
+                    .collect();
+                let count = chats.len();
+                for chat in chats.into_iter().rev() {
+                    self.chat_list.chats.insert(0, chat);
+                }
+                if let Err(e) = self.save_chat_list() {
+                    error!("保存聊天列表失败: {}", e);
+                }
+                self.notifications.push(format!("已从 {} 导入 {} 个对话", import.format.label(), count));
+            }
+        }
+        if !open {
+            self.chat_import = None;
+        }
+    }
+
+    // 应用锁的状态：记录最近的操作，空闲超时后锁定；返回 true 时本帧只显示锁定界面
+    fn update_lock(&mut self, ctx: &egui::Context) -> bool {
+        if !self.lock_config.is_active() {
+            self.locked = false;
+            return false;
+        }
+        if !self.locked {
+            if ctx.input(|i| !i.events.is_empty() || i.pointer.is_moving()) {
+                self.last_activity = Instant::now();
+            }
+            let idle = std::time::Duration::from_secs(self.lock_config.idle_minutes * 60);
+            if self.lock_config.idle_minutes > 0 && self.last_activity.elapsed() >= idle {
+                debug!("空闲超时，锁定应用");
+                self.lock();
+            } else {
+                // 没有操作时界面不会刷新，定时检查是否空闲超时
+                ctx.request_repaint_after(std::time::Duration::from_secs(30));
+                return false;
+            }
+        }
+        self.display_lock_screen(ctx);
+        true
+    }
+
+    fn lock(&mut self) {
+        self.locked = true;
+        self.lock_input.clear();
+        self.lock_error = None;
+    }
+
+    fn display_lock_screen(&mut self, ctx: &egui::Context) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                ui.add_space(ui.available_height() / 3.0);
+                ui.label(RichText::new("\u{f023}").size(48.0));
+                ui.add_space(8.0);
+                ui.heading("Dream 已锁定");
+                ui.add_space(12.0);
+                let response = ui.add(
+                    TextEdit::singleline(&mut self.lock_input)
+                        .password(true)
+                        .hint_text("输入密码解锁")
+                        .desired_width(240.0),
+                );
+                response.request_focus();
+                let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                if let Some(error) = &self.lock_error {
+                    ui.label(RichText::new(error).color(egui::Color32::RED));
+                }
+                if ui.button("解锁").clicked() || submitted {
+                    if self.lock_config.verify(&self.lock_input) {
+                        debug!("应用已解锁");
+                        if self.lock_config.needs_upgrade() {
+                            // 旧版本的摘要换成当前算法
+                            self.lock_config.set_passphrase(&self.lock_input);
+                            let config = self.current_config();
+                            if let Err(e) = self.runtime_handle.block_on(config::save_config(&config)) {
+                                error!("保存配置失败: {}", e);
+                            }
+                        }
+                        self.locked = false;
+                        self.last_activity = Instant::now();
+                        self.lock_error = None;
+                    } else {
+                        self.lock_error = Some("密码错误".to_string());
+                    }
+                    self.lock_input.clear();
+                }
+            });
+        });
+    }
+
+    // 窗口最小化时在标题中显示回复进度，便于在任务栏查看；回复结束或恢复窗口后还原标题
+    fn update_window_title(&mut self, ctx: &egui::Context) {
+        const REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+        let minimized = ctx.input(|i| i.viewport().minimized).unwrap_or(false);
+        if !(self.is_loading && minimized) {
+            if self.title_progress.take().is_some() {
+                ctx.send_viewport_cmd(egui::ViewportCommand::Title(crate::APP_NAME.to_string()));
+            }
+            return;
+        }
+        if self.title_progress.as_ref().is_some_and(|(updated, _)| updated.elapsed() < REFRESH_INTERVAL) {
+            return;
+        }
+        let model = self.effective_config(self.chat_list.current_chat_id.as_deref()).model_name;
+        let received = self
+            .chat_history
+            .0
+            .last()
+            .filter(|msg| msg.is_reply())
+            .map_or(0, |msg| tokens::count(&model, &msg.content));
+        let status = if self.stream_stalled.is_some() { "回复停滞" } else { "正在回复…" };
+        let title = format!("{} — {} {} tokens", crate::APP_NAME, status, tokens::format_count(received));
+        if self.title_progress.as_ref().map(|(_, current)| current) != Some(&title) {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(title.clone()));
+        }
+        self.title_progress = Some((Instant::now(), title));
+    }
+
+    // 设置或修改应用锁的密码
+    fn display_lock_setup(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let Some((mut passphrase, mut confirm)) = self.lock_setup.take() else {
+            return;
+        };
+        let mut open = true;
+        let mut saved = false;
+        egui::Window::new("设置应用锁密码")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                egui::Grid::new("lock_setup_grid").num_columns(2).show(ui, |ui| {
+                    ui.label("新密码:");
+                    ui.add(TextEdit::singleline(&mut passphrase).password(true));
+                    ui.end_row();
+                    ui.label("确认密码:");
+                    ui.add(TextEdit::singleline(&mut confirm).password(true));
+                    ui.end_row();
+                });
+                let long_enough = passphrase.chars().count() >= app_lock::MIN_PASSPHRASE_CHARS;
+                if !passphrase.is_empty() && !long_enough {
+                    ui.label(RichText::new(format!("密码至少 {} 个字符", app_lock::MIN_PASSPHRASE_CHARS)).small().color(egui::Color32::YELLOW));
+                } else if !confirm.is_empty() && passphrase != confirm {
+                    ui.label(RichText::new("两次输入的密码不一致").small().color(egui::Color32::YELLOW));
+                }
+                ui.label(RichText::new("密码只以加盐摘要的形式保存在配置中，忘记密码时需要删除 dream.toml 中的 [lock] 部分").small().weak());
+                if ui.add_enabled(long_enough && passphrase == confirm, egui::Button::new("保存")).clicked() {
+                    saved = true;
+                }
+            });
+        if saved {
+            self.lock_config.set_passphrase(&passphrase);
+            self.lock_config.enabled = true;
+            self.last_activity = Instant::now();
+            if let Err(e) = self.save_config(frame) {
+                error!("保存配置失败: {}", e);
+            }
+            self.notifications.push("已启用应用锁".to_string());
+        } else if open {
+            self.lock_setup = Some((passphrase, confirm));
+        }
+    }
+
+    // 备份列表：可以立即备份，或者从某个备份恢复对话记录和配置
+    fn display_backups(&mut self, ctx: &egui::Context) {
+        let Some(snapshots) = self.backups.clone() else {
+            return;
+        };
+        let mut open = true;
+        let mut refresh = false;
+        let mut restore = None;
+        egui::Window::new("备份")
+            .open(&mut open)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                ui.label(RichText::new(format!("备份保存在 {} 中，恢复前会先备份当前的数据", backup::backup_dir().display()))
+                    .small()
+                    .color(egui::Color32::GRAY));
+                if ui.button("立即备份").clicked() {
+                    match self.runtime_handle.block_on(backup::create()) {
+                        Ok(_) => refresh = true,
+                        Err(e) => error!("备份失败: {}", e),
+                    }
+                }
+                ui.separator();
+                if snapshots.is_empty() {
+                    ui.label(RichText::new("还没有备份").color(egui::Color32::GRAY));
+                }
+                ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+                    egui::Grid::new("backup_list").num_columns(3).striped(true).show(ui, |ui| {
+                        for snapshot in &snapshots {
+                            ui.label(snapshot.label());
+                            ui.label(RichText::new(format!("{} KB", snapshot.size.div_ceil(1024))).small().color(egui::Color32::GRAY));
+                            if ui.small_button("恢复").clicked() {
+                                restore = Some(snapshot.path.clone());
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+        if let Some(path) = restore {
+            self.restore_backup(&path);
+            refresh = true;
+        }
+        if !open {
+            self.backups = None;
+        } else if refresh {
+            self.backups = Some(self.runtime_handle.block_on(backup::list()));
+        }
+    }
+
+    // 安全模式下也可以恢复，用于修复损坏的配置和对话记录；恢复后需要以普通模式重新启动
+    fn restore_backup(&mut self, path: &Path) {
+        debug!("从备份恢复: {}", path.display());
+        if let Err(e) = self.runtime_handle.block_on(backup::restore(path)) {
+            error!("从备份恢复失败: {}", e);
+            self.notifications.push(format!("从备份恢复失败: {}", e));
+            return;
+        }
+        if self.safe_mode {
+            self.restored_in_safe_mode = true;
+            self.notifications.push(format!("已从备份恢复: {}，重新启动后生效", path.display()));
+            return;
+        }
+        self.stop_stream();
+        let config = self.runtime_handle.block_on(config::load_config());
+        self.apply_config(config);
+        if let Err(e) = self.load_chat_list() {
+            error!("加载聊天列表失败: {}", e);
+        }
+        self.chat_history.0 = self
+            .chat_list
+            .current_chat_id
+            .as_ref()
+            .and_then(|id| self.chat_list.chats.iter().find(|c| &c.id == id))
+            .map(|chat| chat.messages.clone())
+            .unwrap_or_default();
+        self.notifications.push(format!("已从备份恢复: {}", path.display()));
+    }
+
+    fn reset_config(&mut self, section: config::ConfigSection, frame: &mut eframe::Frame) {
+        debug!("重置配置: {}", section.label());
+        let mut config = self.current_config();
+        config.reset_section(section);
+        self.apply_config(config);
+        if let Err(e) = self.save_config(frame) {
+            error!("保存配置失败: {}", e);
+        }
+    }
+
     async fn save_chat_list_async(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.safe_mode {
+            debug!("安全模式，跳过保存聊天列表");
+            return Ok(());
+        }
         debug!("正在保存聊天列表...");
         // 在保存之前先克隆并反转列表，这样保存的顺序就和加载时的顺序一致
         let mut save_list = self.chat_list.clone();
+        // 无痕对话不写入磁盘
+        save_list.chats.retain(|chat| !chat.incognito);
+        if save_list.current_chat_id.as_ref().is_some_and(|id| !save_list.chats.iter().any(|c| &c.id == id)) {
+            save_list.current_chat_id = None;
+        }
         save_list.chats.reverse();
         let json = serde_json::to_string_pretty(&save_list)?;
-        tokio::fs::write("chat_list.json", json).await?;
+        utils::write_atomic(&paths::data_file(CHAT_LIST_FILE), json).await?;
         debug!("聊天列表保存成功");
         Ok(())
     }
@@ -265,8 +1930,8 @@ impl ChatApp {
     async fn load_chat_list_async(
         chat_list: &mut ChatList,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        if let Ok(content) = tokio::fs::read_to_string("chat_list.json").await {
-            *chat_list = serde_json::from_str(&content)?;
+        if let Some(loaded) = utils::load_with_backup(&paths::data_file(CHAT_LIST_FILE), |content| serde_json::from_str::<ChatList>(content)).await {
+            *chat_list = loaded?;
             // 加载后反转列表顺序，使其与显示顺序一致
             chat_list.chats.reverse();
         }
@@ -281,38 +1946,508 @@ impl ChatApp {
         Ok(())
     }
 
-    fn new_chat(&mut self) {
-        debug!("创建新对话");
-        let chat_count = self.chat_list.chats.len();
-        let name = format!("新对话 {}", chat_count + 1);
+    // 获取当前选中聊天的角色配置
+    fn current_chat_config(&self) -> Option<&ChatConfig> {
+        let current_id = self.chat_list.current_chat_id.as_ref()?;
+        self.chat_list
+            .chats
+            .iter()
+            .find(|c| &c.id == current_id)
+            .and_then(|chat| chat.config.as_ref())
+    }
 
-        // 使用 new 方法创建新对话，它会自动设置 config 为 None
-        let new_chat = Chat::new(name);
-        let id = new_chat.id.clone();
+    fn global_effective_config(&self) -> EffectiveConfig {
+        EffectiveConfig {
+            model_name: self.model_name.clone(),
+            system_prompt: self.system_prompt.clone(),
+            params: SamplingParams {
+                temperature: self.temperature,
+                top_p: self.top_p,
+                frequency_penalty: self.frequency_penalty,
+                presence_penalty: self.presence_penalty,
+            },
+            provider: self
+                .find_profile(self.active_profile.as_deref())
+                .map_or(self.api_provider, |profile| profile.provider),
+            profile: self.active_profile.clone(),
+        }
+    }
 
-        // 将新对话添加到列表开头，而不是末尾
-        self.chat_list.chats.insert(0, new_chat);
-        self.chat_list.current_chat_id = Some(id);
-        self.chat_history.0.clear();
-        self.input_focus = true;
+    fn find_profile(&self, name: Option<&str>) -> Option<&config::ApiProfile> {
+        let name = name?;
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
 
-        if let Err(e) = self.save_chat_list() {
-            error!("保存聊天列表失败: {}", e);
+    // 配置档案的常用模型，未选择档案或档案没有填写模型时使用设置中的常用模型
+    fn model_choices(&self, profile: Option<&str>) -> Vec<String> {
+        match self.find_profile(profile) {
+            Some(profile) if !profile.models.is_empty() => profile.models.clone(),
+            _ => self.available_models.clone(),
         }
     }
 
+    // 请求实际使用的地址和密钥：配置档案优先，否则按服务商取设置中的连接信息
+    fn effective_target(&self, effective: &EffectiveConfig) -> ApiTarget {
+        match self.find_profile(effective.profile.as_deref()) {
+            Some(profile) => profile.target(),
+            None => self.api_target(effective.provider),
+        }
+    }
+
+    // 切换全局配置档案；当前模型不在档案的常用模型中时改用档案的第一个模型
+    fn switch_profile(&mut self, profile: Option<String>, frame: &mut eframe::Frame) {
+        debug!("切换配置档案: {:?}", profile);
+        self.active_profile = profile;
+        let models = self.model_choices(self.active_profile.as_deref());
+        if !models.contains(&self.model_name) {
+            if let Some(model) = models.first() {
+                self.model_name = model.clone();
+            }
+        }
+        if let Err(e) = self.save_config(frame) {
+            error!("保存配置失败: {}", e);
+        }
+    }
+
+    // 指定服务商对应的请求地址和密钥
+    fn api_target(&self, provider: ProviderKind) -> ApiTarget {
+        let (endpoint, api_key) = match provider {
+            ProviderKind::OpenAi => (self.api_endpoint.clone(), &self.api_key),
+            ProviderKind::Claude => (self.claude_config.endpoint.clone(), &self.claude_config.api_key),
+            ProviderKind::Gemini => (self.gemini_config.endpoint.clone(), &self.gemini_config.api_key),
+            ProviderKind::Azure => (self.azure_config.endpoint(), &self.azure_config.api_key),
+        };
+        ApiTarget {
+            provider,
+            endpoint,
+            api_key: api_key.clone(),
+        }
+    }
+
+    // 主服务商处于故障状态且备用服务商可用时，返回备用服务商和模型
+    fn failover_target(&self, primary: &ApiTarget) -> Option<(ApiTarget, String)> {
+        let failover = &self.failover;
+        if !failover.enabled || failover.model.trim().is_empty() {
+            return None;
+        }
+        let fallback = self.api_target(failover.provider);
+        if fallback.url() == primary.url() {
+            return None;
+        }
+        let primary_down = self.endpoint_health.is_down(primary.url());
+        let fallback_down = self.endpoint_health.is_down(fallback.url());
+        (primary_down && !fallback_down).then(|| (fallback, failover.model.trim().to_string()))
+    }
+
+    // 当前服务商连续失败时，在聊天区域顶部显示服务状态提示
+    fn display_outage_banner(&mut self, ui: &mut egui::Ui) {
+        let target = self.effective_target(&self.effective_config(self.chat_list.current_chat_id.as_deref()));
+        let Some(recovery_in) = self.endpoint_health.recovery_in(target.url()) else {
+            return;
+        };
+        ui.ctx().request_repaint_after(std::time::Duration::from_secs(1));
+
+        let mut text = format!(
+            "\u{f071} {} 连续 {} 次请求失败，服务可能暂时不可用，{} 秒后恢复尝试",
+            target.provider.label(),
+            self.endpoint_health.failures(target.url()),
+            recovery_in.as_secs()
+        );
+        if let Some((fallback, model)) = self.failover_target(&target) {
+            text.push_str(&format!("，期间使用 {}（{}）", fallback.provider.label(), model));
+        }
+        ui.horizontal_wrapped(|ui| {
+            ui.colored_label(egui::Color32::from_rgb(230, 140, 0), text);
+            if ui.small_button("立即重试").clicked() {
+                self.endpoint_health.reset(target.url());
+            }
+        });
+        ui.separator();
+    }
+
+    // 解析指定聊天实际生效的配置，主请求、重试和标题等辅助请求统一使用
+    fn effective_config(&self, chat_id: Option<&str>) -> EffectiveConfig {
+        let chat = chat_id.and_then(|id| self.chat_list.chats.iter().find(|c| c.id == id));
+        let mut effective = EffectiveConfig::resolve(chat.and_then(|chat| chat.config.as_ref()), self.global_effective_config());
+        if let Some(profile) = self.find_profile(effective.profile.as_deref()) {
+            effective.provider = profile.provider;
+        }
+        if let Some(model) = chat.and_then(|chat| chat.model_override.clone()) {
+            effective.model_name = model;
+        }
+        effective
+    }
+
+    // 输入框上方的模型切换：只影响当前对话之后发送的消息，不修改全局设置和角色配置
+    fn display_model_switcher(&mut self, ui: &mut egui::Ui) {
+        let Some(current_id) = self.chat_list.current_chat_id.clone() else {
+            return;
+        };
+        if self.current_chat_is_group() {
+            return;
+        }
+        let effective = self.effective_config(Some(&current_id));
+        let models = self.model_choices(effective.profile.as_deref());
+        let global = self.global_effective_config();
+        let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == current_id) else {
+            return;
+        };
+        let configured = EffectiveConfig::resolve(chat.config.as_ref(), global).model_name;
+        let mut selected = chat.model_override.clone();
+        let text = if selected.is_some() {
+            RichText::new(&effective.model_name).small().strong()
+        } else {
+            RichText::new(&effective.model_name).small()
+        };
+        egui::ComboBox::from_id_salt("quick_model_switcher")
+            .selected_text(text)
+            .width(140.0)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut selected, None, format!("默认（{}）", configured));
+                for model in &models {
+                    ui.selectable_value(&mut selected, Some(model.clone()), model);
+                }
+            })
+            .response
+            .on_hover_text("下一条消息使用的模型；只影响这个对话之后发送的消息，每条回复使用的模型记录在上下文详情中");
+        if selected != chat.model_override {
+            debug!("对话切换模型: {:?}", selected);
+            chat.model_override = selected;
+            if let Err(e) = self.save_chat_list() {
+                error!("保存聊天列表失败: {}", e);
+            }
+        }
+    }
+
+    // 当前聊天实际生效的采样参数（角色配置优先）
+    fn current_sampling_params(&self) -> SamplingParams {
+        self.effective_config(self.chat_list.current_chat_id.as_deref())
+            .params
+    }
+
+    // 应用参数预设：角色聊天修改角色配置，普通聊天修改全局配置
+    fn apply_preset(&mut self, preset: ParamPreset, frame: &mut eframe::Frame) {
+        debug!("应用参数预设: {}", preset.label());
+        let params = preset.params();
+        let current_id = self.chat_list.current_chat_id.clone();
+        let role_config = current_id.and_then(|id| {
+            self.chat_list
+                .chats
+                .iter_mut()
+                .find(|c| c.id == id)
+                .and_then(|chat| chat.config.as_mut())
+        });
+
+        if let Some(config) = role_config {
+            config.set_sampling_params(params);
+            if let Err(e) = self.save_chat_list() {
+                error!("保存聊天列表失败: {}", e);
+            }
+        } else {
+            self.temperature = params.temperature;
+            self.top_p = params.top_p;
+            self.frequency_penalty = params.frequency_penalty;
+            self.presence_penalty = params.presence_penalty;
+            if let Err(e) = self.save_config(frame) {
+                error!("保存配置失败: {}", e);
+            }
+        }
+    }
+
+    fn new_chat(&mut self) {
+        debug!("创建新对话");
+        let chat_count = self.chat_list.chats.len();
+        let name = format!("新对话 {}", chat_count + 1);
+
+        // 使用 new 方法创建新对话，它会自动设置 config 为 None
+        let new_chat = Chat::new(name);
+        let id = new_chat.id.clone();
+
+        // 将新对话添加到列表开头，而不是末尾
+        self.chat_list.chats.insert(0, new_chat);
+        self.chat_list.current_chat_id = Some(id);
+        self.chat_history.0.clear();
+        self.input_focus = true;
+
+        if let Err(e) = self.save_chat_list() {
+            error!("保存聊天列表失败: {}", e);
+        }
+    }
+
+    // 基于角色新建对话，沿用角色的配置并记录来源
+    fn new_chat_from_role(&mut self, role_id: &str) {
+        let Some(role) = self.chat_list.chats.iter().find(|c| c.id == role_id) else {
+            return;
+        };
+        debug!("基于角色创建新对话: {}", role.name);
+        let mut new_chat = Chat::new(format!("新对话 {}", self.chat_list.chats.len() + 1));
+        new_chat.config = role.config.clone();
+        new_chat.role_id = Some(role.id.clone());
+        let id = new_chat.id.clone();
+
+        self.chat_list.chats.insert(0, new_chat);
+        self.chat_list.current_chat_id = Some(id);
+        self.chat_history.0.clear();
+        self.input_focus = true;
+
+        if let Err(e) = self.save_chat_list() {
+            error!("保存聊天列表失败: {}", e);
+        }
+    }
+
+    // 当前对话生效的内容过滤器，角色可以覆盖过滤方式并追加规则
+    fn content_filter(&self) -> ContentFilter {
+        let role_config = self.current_chat_config();
+        let mode = role_config
+            .and_then(|config| config.filter_mode)
+            .unwrap_or(self.content_filter_mode);
+        let mut terms = content_filter::parse_terms(&self.content_filter_terms);
+        if let Some(config) = role_config {
+            terms.extend(config.filter_terms.iter().cloned());
+        }
+        ContentFilter::new(mode, &terms)
+    }
+
+    // 检查即将发送的文本，命中规则时返回需要用户处理的请求
+    fn check_content_filter(&self, text: &str, prompt: Option<String>) -> Option<FilterHold> {
+        let filter = self.content_filter();
+        if filter.mode == FilterMode::Off {
+            return None;
+        }
+        let matches = filter.matches(text);
+        if matches.is_empty() {
+            return None;
+        }
+        Some(FilterHold { mode: filter.mode, matches, prompt, edit_index: None })
+    }
+
+    // 发送输入框中的内容
     fn send_message(&mut self) {
+        let mut outgoing = self.input_text.clone();
+        for attachment in &self.text_attachments {
+            outgoing.push('\n');
+            outgoing.push_str(&attachment.content);
+        }
+        if let Some(hold) = self.check_content_filter(&outgoing, None) {
+            self.filter_hold = Some(hold);
+            return;
+        }
+        if self.selected_image.is_some() && self.current_capabilities().is_some_and(|caps| !caps.vision) {
+            self.image_unsupported_hold = true;
+            return;
+        }
+        self.send_input();
+    }
+
+    // 输入内容过大时的只读预览：可以直接发送、转为附件或仍然编辑
+    fn display_large_input(&mut self, ui: &mut egui::Ui, max_height: f32) {
+        ui.horizontal_wrapped(|ui| {
+            ui.label(RichText::new(format!(
+                "\u{f071} 输入内容共 {} 行、{} 个字符，为避免卡顿以只读方式显示",
+                self.input_text.lines().count(),
+                self.input_text.chars().count()
+            ))
+            .color(egui::Color32::GRAY));
+            if ui.small_button("发送").clicked() {
+                self.send_message();
+            }
+            if ui.small_button("作为附件").clicked() {
+                let name = format!("输入内容-{}.txt", chrono::Local::now().format("%H%M%S"));
+                let content = std::mem::take(&mut self.input_text);
+                self.text_attachments.push(TextAttachment { name, content });
+                self.input_focus = true;
+            }
+            if ui.small_button("仍然编辑").on_hover_text("内容较大时编辑可能卡顿").clicked() {
+                self.edit_large_input = true;
+                self.input_focus = true;
+            }
+            if ui.small_button("清空").clicked() {
+                self.input_text.clear();
+                self.input_focus = true;
+            }
+        });
+        large_text_preview(ui, &self.input_text, max_height);
+    }
+
+    // 当前对话的模型能力，无法判断时返回 None
+    fn current_capabilities(&self) -> Option<Capabilities> {
+        let effective = self.effective_config(self.chat_list.current_chat_id.as_deref());
+        capabilities::resolve(&self.capabilities, effective.provider, &effective.model_name)
+    }
+
+    // 输入框上方的模型能力标记，OpenAI 兼容接口可以发送请求探测
+    fn display_capabilities(&mut self, ui: &mut egui::Ui, frame: &mut eframe::Frame) {
+        if self.chat_list.current_chat_id.is_none() || self.current_chat_is_group() {
+            return;
+        }
+        let effective = self.effective_config(self.chat_list.current_chat_id.as_deref());
+        let model = effective.model_name.clone();
+        if self.capability_probe.as_ref() == Some(&model) {
+            ui.spinner().on_hover_text("正在探测模型能力...");
+            return;
+        }
+        let caps = capabilities::resolve(&self.capabilities, effective.provider, &model);
+        let source = if self.capabilities.contains_key(&model) {
+            "已记录的能力"
+        } else {
+            "按模型名称判断"
+        };
+        let response = match caps {
+            Some(caps) => {
+                let color = |supported: bool| if supported { egui::Color32::LIGHT_GREEN } else { egui::Color32::GRAY };
+                let mut job = egui::text::LayoutJob::default();
+                for (icon, supported) in [("\u{f03e} ", caps.vision), ("\u{f0ad} ", caps.tools), ("{}", caps.json_mode)] {
+                    RichText::new(icon)
+                        .small()
+                        .color(color(supported))
+                        .append_to(&mut job, ui.style(), egui::FontSelection::Default, egui::Align::Center);
+                }
+                ui.add(egui::Label::new(job).sense(egui::Sense::click()))
+                    .on_hover_text(format!("{}（{}）", caps.summary(), source))
+            }
+            None => ui
+                .add(egui::Label::new(RichText::new("\u{f128}").small().weak()).sense(egui::Sense::click()))
+                .on_hover_text("无法判断这个模型的能力"),
+        };
+        let can_probe = matches!(effective.provider, ProviderKind::OpenAi | ProviderKind::Azure);
+        response.context_menu(|ui| {
+            if ui.add_enabled(can_probe, egui::Button::new("发送请求探测能力"))
+                .on_disabled_hover_text("只支持 OpenAI 兼容接口")
+                .clicked()
+            {
+                self.probe_capabilities(&effective);
+                ui.close_menu();
+            }
+            if self.capabilities.contains_key(&model) && ui.button("清除记录的能力").clicked() {
+                self.capabilities.remove(&model);
+                if let Err(e) = self.save_config(frame) {
+                    error!("保存配置失败: {}", e);
+                }
+                ui.close_menu();
+            }
+        });
+    }
+
+    fn probe_capabilities(&mut self, effective: &EffectiveConfig) {
+        let model = effective.model_name.clone();
+        debug!("探测模型能力: {}", model);
+        self.capability_probe = Some(model.clone());
+        let client = self.client.clone();
+        let target = self.effective_target(effective);
+        let sender = self.capability_sender.clone();
+        self.task_registry.spawn(&self.runtime_handle, "探测模型能力", None, async move {
+            let result = capabilities::probe(&client, &target, &model).await;
+            let _ = sender.send((model, result));
+        });
+    }
+
+    fn receive_capabilities(&mut self, frame: &mut eframe::Frame) {
+        while let Ok((model, result)) = self.capability_receiver.try_recv() {
+            self.capability_probe = None;
+            match result {
+                Ok(caps) => {
+                    self.notifications.push(format!("{}: {}", model, caps.summary()));
+                    self.capabilities.insert(model, caps);
+                    if let Err(e) = self.save_config(frame) {
+                        error!("保存配置失败: {}", e);
+                    }
+                }
+                Err(e) => {
+                    error!("探测模型能力失败 {}: {}", model, e);
+                    self.notifications.push(format!("探测模型能力失败: {}", e));
+                }
+            }
+        }
+    }
+
+    // 当前模型不支持图片输入时的提醒窗口
+    fn display_image_unsupported_hold(&mut self, ctx: &egui::Context) {
+        if !self.image_unsupported_hold {
+            return;
+        }
+        let model = self.effective_config(self.chat_list.current_chat_id.as_deref()).model_name;
+        egui::Window::new("模型不支持图片")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.label(format!("{} 可能不支持图片输入，带图片发送时接口可能会拒绝请求。", model));
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    if ui.button("去掉图片发送").clicked() {
+                        self.image_unsupported_hold = false;
+                        self.selected_image = None;
+                        self.send_input();
+                    }
+                    if ui.button("仍然发送").clicked() {
+                        self.image_unsupported_hold = false;
+                        self.send_input();
+                    }
+                    if ui.button("返回修改").clicked() {
+                        self.image_unsupported_hold = false;
+                    }
+                });
+            });
+    }
+
+    // 跳过内容过滤直接发送输入框中的内容
+    fn send_input(&mut self) {
+        let mut user_input = std::mem::take(&mut self.input_text);
+        // 代码模式下自动包裹为代码块
+        if self.code_input_mode && !user_input.trim().is_empty() {
+            user_input = format!("```{}\n{}\n```", self.code_language, user_input.trim_end());
+        }
+        if let Some(prompt) = imagegen::parse_command(&user_input) {
+            if prompt.is_empty() {
+                self.input_text = user_input;
+                return;
+            }
+            let prompt = prompt.to_string();
+            self.generate_image(prompt);
+            return;
+        }
+        if self.compare_models.is_some() {
+            self.start_comparison(user_input);
+            return;
+        }
+        let image_path = self.selected_image.take();
+        let text_attachments = std::mem::take(&mut self.text_attachments);
+        if let Some(id) = self.ghost_text.clear() {
+            self.task_registry.abort(id);
+        }
+        self.send_user_message(user_input, image_path, text_attachments);
+    }
+
+    // 直接发送一段文本，不影响输入框中的草稿
+    fn send_prompt(&mut self, prompt: String) {
+        if let Some(hold) = self.check_content_filter(&prompt, Some(prompt.clone())) {
+            self.filter_hold = Some(hold);
+            return;
+        }
+        self.send_user_message(prompt, None, Vec::new());
+    }
+
+    fn send_user_message(
+        &mut self,
+        user_input: String,
+        image_path: Option<PathBuf>,
+        text_attachments: Vec<TextAttachment>,
+    ) {
+        if self.current_chat_is_group() {
+            self.send_group_message(user_input, text_attachments);
+            return;
+        }
         debug!("开始发送消息");
         self.is_loading = true; // 设置加载状态
         self.loading_dots.clear();
-        let user_input = std::mem::take(&mut self.input_text);
-        let image_path = self.selected_image.take();
+        self.reply_suggestions = None;
 
         // 如果没有选中的聊天，创建一个新的
         if self.chat_list.current_chat_id.is_none() {
             debug!("没有选中的聊天，创建新对话");
             self.new_chat();
         }
+        self.rotate_journal();
 
         // 更新当前聊天的时间戳
         if let Some(current_id) = &self.chat_list.current_chat_id {
@@ -326,45 +2461,35 @@ impl ChatApp {
             }
         }
 
-        // 获取当前聊天的配置
-        let (current_model, current_prompt, current_temp) =
-            if let Some(current_id) = &self.chat_list.current_chat_id {
-                if let Some(chat) = self.chat_list.chats.iter().find(|c| &c.id == current_id) {
-                    if let Some(config) = &chat.config {
-                        (
-                            config.model_name.clone(),
-                            config.system_prompt.clone(),
-                            config.temperature,
-                        )
-                    } else {
-                        (
-                            self.model_name.clone(),
-                            self.system_prompt.clone(),
-                            self.temperature,
-                        )
-                    }
-                } else {
-                    (
-                        self.model_name.clone(),
-                        self.system_prompt.clone(),
-                        self.temperature,
-                    )
-                }
-            } else {
-                (
-                    self.model_name.clone(),
-                    self.system_prompt.clone(),
-                    self.temperature,
-                )
-            };
+        // 获取当前聊天的配置，主服务商故障时临时使用备用服务商
+        let mut effective = self.effective_config(self.chat_list.current_chat_id.as_deref());
+        let mut target = self.effective_target(&effective);
+        if let Some((fallback, model)) = self.failover_target(&target) {
+            debug!("{} 暂时不可用，改用备用服务商 {} ({})", target.url(), fallback.provider.label(), model);
+            effective.provider = fallback.provider;
+            effective.profile = None;
+            effective.model_name = model;
+            target = fallback;
+        }
+        let current_model = effective.model_name.clone();
+        let current_prompt = effective.system_prompt.clone();
+        let current_params = effective.params;
+
+        // 需要加入上下文的角色开场白
+        let greeting_context = self
+            .current_chat_config()
+            .filter(|config| config.greeting_in_context)
+            .and_then(|config| config.greeting_text())
+            .map(str::to_string);
 
         // 处理图片
-        let processed_image = if let Some(processing) = self.processing_image.take() {
+        let processed_image = if image_path.is_none() {
+            None
+        } else if let Some(processing) = self.processing_image.take() {
             match self.runtime_handle.block_on(async {
                 match processing.await {
                     Ok(result) => result,
-                    Err(_) => Err(ImageError::IoError(std::io::Error::new(
-                        std::io::ErrorKind::Other,
+                    Err(_) => Err(ImageError::IoError(std::io::Error::other(
                         "图片处理任务被取消",
                     ))),
                 }
@@ -378,7 +2503,7 @@ impl ChatApp {
         } else if let Some(ref path) = image_path {
             match self
                 .runtime_handle
-                .block_on(async { utils::copy_to_cache(path).await })
+                .block_on(async { utils::copy_to_cache(path, &self.image_cache_dir()).await })
             {
                 Ok(path) => Some(path),
                 Err(e) => {
@@ -395,46 +2520,70 @@ impl ChatApp {
             user_input.clone(),
             processed_image.map(|p| p.to_string_lossy().to_string()),
         );
-
-        debug!("检查是否需要生成标题");
-        let should_generate_title = if let Some(current_id) = &self.chat_list.current_chat_id {
-            self.chat_list
-                .chats
-                .iter()
-                .find(|c| &c.id == current_id)
-                .map(|chat| !chat.has_been_renamed && chat.messages.is_empty())
-                .unwrap_or(false)
-        } else {
-            false
-        };
+        new_message.text_attachments = text_attachments;
+        new_message.original_content = self.pending_edit_original.take();
 
         debug!("准备发消息，是���包含图片: {}", image_path.is_some());
 
         // 创建通道
         let (tx, rx) = mpsc::unbounded_channel();
         self.receiver = Some(rx);
+        self.inspector = RequestInspector::default();
+
+        // 历史消息不包含本次的新消息，新消息在构建请求时单独添加；按上下文策略裁剪时把新消息算在内
+        let mut history_messages: Vec<Message> = models::context_messages(&self.chat_history.0).cloned().collect();
+        history_messages.push(new_message.clone());
+        let (mut history_messages, summarized, current_prompt) = self.prepare_history(
+            self.chat_list.current_chat_id.as_deref(),
+            &current_model,
+            &current_prompt,
+            history_messages,
+        );
+        history_messages.pop();
+
+        // 记录本次请求实际发送的上下文
+        let mut entries = Vec::new();
+        if !summarized.is_empty() {
+            entries.push(ManifestEntry::from_text("system", &context::summary_note(summarized.len())));
+        }
+        if let Some(greeting) = &greeting_context {
+            entries.push(ManifestEntry::from_text("assistant", greeting));
+        }
+        entries.extend(history_messages.iter().map(ManifestEntry::from_message));
+        entries.push(ManifestEntry::from_message(&new_message));
+        self.pending_manifest = Some(RequestManifest {
+            sent_at: Utc::now(),
+            provider: effective.provider,
+            model: current_model.clone(),
+            system_prompt: current_prompt.clone(),
+            params: current_params,
+            entries,
+            overrides: None,
+        });
 
         // 立即创建并添加用户消息
         self.chat_history.add_message(new_message.clone());
 
         // 启动异步任务
         let client = self.client.clone();
-        let api_key = self.api_key.clone();
-        let api_endpoint = self.api_endpoint.clone();
-        let model_name = self.model_name.clone();
-        let temperature = self.temperature;
-        let system_prompt = self.system_prompt.clone();
-        let retry_enabled = self.retry_enabled;
-        let max_retries = self.max_retries;
-        let history_messages = self.chat_history.0.clone();
+        let retry_policy = self.retry_policy();
+        let endpoint_health = self.endpoint_health.clone();
+        let metrics = self.metrics.clone();
         let chat_id = self.chat_list.current_chat_id.clone();
-        let should_generate_title = should_generate_title;
         let tx_clone = tx.clone(); // 克隆通道发送端
 
         // 在 spawn 之前克隆需要的数据
-        let chat_history = self.chat_history.0.clone();
-
-        self.runtime.spawn(async move {
+        let reasoning_effort = self.reasoning_effort;
+        let language_directive = self
+            .match_reply_language
+            .then(|| language::reply_directive(&user_input));
+        let review_directive = review::message_diff(&new_message).map(|_| review::REVIEW_DIRECTIVE.to_string());
+        let retrieval = self.retrieval(self.chat_list.current_chat_id.as_deref().unwrap_or_default());
+        let cache_dir = self.image_cache_dir();
+        let context_summaries = self.context_summaries.clone();
+
+        let stream_chat_id = self.chat_list.current_chat_id.clone();
+        self.task_registry.spawn(&self.runtime_handle, "消息流", stream_chat_id, async move {
             // 先处理图片（如果有）
             let cached_image_path = if let Some(path) = image_path {
                 // 如果已经有理的图片路径，直接使用它
@@ -443,7 +2592,7 @@ impl ChatApp {
                     Some(PathBuf::from(processed_path))
                 } else {
                     // 否则才进行处理
-                    match utils::copy_to_cache(&path).await {
+                    match utils::copy_to_cache(&path, &cache_dir).await {
                         Ok(cache_path) => {
                             debug!("图片已复制到缓存: {:?}", cache_path);
                             Some(cache_path)
@@ -463,18 +2612,31 @@ impl ChatApp {
                 if let Some(path) = cached_image_path.clone() {
                     new_message.image_path = Some(path.to_string_lossy().to_string());
                     // 送消息更通
-                    let _ = tx_clone.send(format!("__UPDATE_MESSAGE_IMAGE__:{}", path.to_string_lossy()));
+                    let _ = tx_clone.send(UiEvent::ImageResolved(path.to_string_lossy().to_string()));
                 }
             }
 
+            // 较早的消息被裁剪时，把它们的摘要附加到系统提示词
+            let current_prompt = context_summaries
+                .summarized_prompt(&client, &target, &current_model, chat_id.as_deref().unwrap_or_default(), &current_prompt, &summarized)
+                .await;
+
             // 构建消息数组时使用当前配置
             let mut messages = vec![
                 json!({
                     "role": "system",
-                    "content": system_prompt
+                    "content": current_prompt
                 })
             ];
 
+            // 添加角色开场白
+            if let Some(greeting) = greeting_context {
+                messages.push(json!({
+                    "role": "assistant",
+                    "content": greeting
+                }));
+            }
+
             // 添加历史消息
             for msg in history_messages {
                 if let Ok(content) = msg.to_api_content().await {
@@ -485,6 +2647,22 @@ impl ChatApp {
                 }
             }
 
+            // 从关联的知识库和长期记忆中检索与问题相关的内容
+            for context in retrieval.retrieve(&client, &target, &new_message, &tx_clone).await {
+                messages.push(json!({
+                    "role": "system",
+                    "content": context
+                }));
+            }
+
+            // 回复语言和代码审查指令紧挨着新消息，不显示在系统提示词中
+            for directive in [language_directive, review_directive].into_iter().flatten() {
+                messages.push(json!({
+                    "role": "system",
+                    "content": directive
+                }));
+            }
+
             // 添加新消息（包含处理后的图片）
             if let Ok(content) = new_message.to_api_content().await {
                 messages.push(json!({
@@ -497,107 +2675,52 @@ impl ChatApp {
             let payload = json!({
                 "model": current_model,
                 "messages": messages,
-                "temperature": current_temp,
-                "stream": true
+                "temperature": current_params.temperature,
+                "top_p": current_params.top_p,
+                "frequency_penalty": current_params.frequency_penalty,
+                "presence_penalty": current_params.presence_penalty,
+                "reasoning_effort": reasoning_effort.api_value(),
+                "stream": true,
+                "stream_options": { "include_usage": true }
             });
 
             // 发送请求
-            if let Err(e) = api::send_request(
+            let started = Instant::now();
+            let result = api::send_request(
                 &client,
-                &api_endpoint,
-                &api_key,
+                &target,
                 &payload,
-                retry_enabled,
-                max_retries,
+                retry_policy,
+                &endpoint_health,
                 &tx_clone
-            ).await {
+            ).await;
+            metrics.finish_request(started, result.is_ok()).await;
+            if let Err(e) = result {
                 error!("发送请求失败: {:?}", e);
-                let _ = tx_clone.send(format!("错误: {}", e));
-                let _ = tx_clone.send("__STREAM_DONE__".to_string());
-            }
-
-            // 在等待助手回复完成后再生成标题
-            if should_generate_title {
-                debug!("需要生成标题，当对话ID: {:?}", chat_id);
-                // 使克隆的 chat_history 而不是 self.chat_history
-                let assistant_response = chat_history.last()
-                    .filter(|msg| msg.role == "assistant")
-                    .map(|msg| msg.content.clone())
-                    .unwrap_or_default();
-
-                debug!("开始生成标题，用户输入: {}", user_input);
-                debug!("助手回复: {}", assistant_response);
-
-                let title_payload = json!({
-                    "model": model_name.clone(),
-                    "messages": vec![
-                        json!({
-                            "role": "system",
-                            "content": "请根据用户的输入AI的回复生成一个简短的对话标题(不超过20个字),直接返回标题即可,不需要任何解释或额外的标点符号。标题应该概括对话的主要内容或主题。"
-                        }),
-                        json!({
-                            "role": "user",
-                            "content": user_input
-                        }),
-                        json!({
-                            "role": "assistant",
-                            "content": assistant_response
-                        })
-                    ],
-                    "temperature": 0.7,
-                    "max_tokens": 60
-                });
-
-                debug!("发送标题生成请求: {}", title_payload);
-                // 发送标题生成请
-                match client
-                    .post(&api_endpoint)
-                    .header("Authorization", format!("Bearer {}", api_key))
-                    .header("Content-Type", "application/json")
-                    .json(&title_payload)
-                    .send()
-                    .await
-                {
-                    Ok(response) => {
-                        debug!("收到标题生成响应: {:?}", response.status());
-                        match response.json::<JsonValue>().await {
-                            Ok(json) => {
-                                debug!("标题生成响应JSON: {:?}", json);
-                                if let Some(title) = json["choices"][0]["message"]["content"]
-                                    .as_str()
-                                    .map(|s| s.trim().to_string())
-                                {
-                                    debug!("成功生成标题: {}", title);
-                                    if let Some(chat_id) = chat_id {
-                                        let title_message = format!("__TITLE_UPDATE__{}:{}", chat_id, title);
-                                        debug!("发送标题更新消息: {}", title_message);
-                                        if let Err(e) = tx_clone.send(title_message) {
-                                            error!("发送标题更新消息失败: {}", e);
-                                        }
-                                    } else {
-                                        debug!("没有找到对话ID，无法更新标题");
-                                    }
-                                } else {
-                                    error!("无法从响应中提取标题");
-                                }
-                            }
-                            Err(e) => {
-                                error!("解析标题生成响应失败: {}", e);
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        error!("标题生成请求失败: {}", e);
-                    }
-                }
-            } else {
-                debug!("不需要生成题");
+                let _ = tx_clone.send(UiEvent::Error(e.to_string()));
+                let _ = tx_clone.send(UiEvent::Done);
             }
+
         });
     }
 
-    fn handle_message_selection(&mut self, messages: Vec<Message>) {
+    // 载入对话消息；重复选择当前对话不会重新载入，避免覆盖内存中的分割线和未保存的回复
+    fn handle_message_selection(&mut self, chat_id: &str) {
+        if self.chat_list.current_chat_id.as_deref() == Some(chat_id) {
+            debug!("对话已选中，跳过重新载入: {}", chat_id);
+            return;
+        }
+        let Some(messages) = self
+            .chat_list
+            .chats
+            .iter()
+            .find(|c| c.id == chat_id)
+            .map(|chat| chat.messages.clone())
+        else {
+            return;
+        };
         debug!("选择消息: {} ", messages.len());
+        self.chat_list.current_chat_id = Some(chat_id.to_string());
         self.chat_history.0 = messages;
 
         // 不再在这里修改全配置
@@ -605,77 +2728,4449 @@ impl ChatApp {
         // 发送消息时会自动使用角色的配置
     }
 
-    fn handle_response(&mut self, response: String) {
-        debug!("处理响应: {}", response);
-        if self.chat_history.last_message_is_assistant() {
-            if let Some(last_msg) = self.chat_history.0.last_mut() {
-                last_msg.content.push_str(&response);
+    // 切换对话的统一入口：正在生成回复时先询问用户
+    fn request_chat_switch(&mut self, switch: ChatSwitch) {
+        if let ChatSwitch::Open(id) = &switch {
+            if self.chat_list.current_chat_id.as_ref() == Some(id) {
+                return;
             }
-        } else {
-            debug!("加新的助手消息");
-            self.chat_history
-                .add_message(Message::new_assistant(response));
         }
+        if self.is_loading {
+            debug!("当前对话正在生成回复，等待用户确认切换");
+            self.pending_chat_switch = Some(switch);
+            return;
+        }
+        self.perform_chat_switch(switch);
     }
 
-    fn display_message(&mut self, ui: &mut egui::Ui, msg: &Message) {
-        match msg.role.as_str() {
-            "user" => {
-                ui.horizontal(|ui| {
-                    ui.label(RichText::new("You:").strong().size(16.0));
-                    ui.add_space(8.0);
-                });
-                ui.add_space(4.0);
-
-                // 构建包含图片的 markdown 内
-                let content = if let Some(path) = &msg.image_path {
-                    // 直接使用 markdown 图片法
-                    format!("{}\n\n![image]({})", msg.content, path)
-                } else {
-                    msg.content.clone()
-                };
-
-                // 使用 CommonMarkViewer 渲染完整内容
-                ui.ctx().set_theme(egui::Theme::Light);
-                let viewer = if self.dark_mode {
-                    CommonMarkViewer::new().syntax_theme_dark("fuck")
-                } else {
-                    CommonMarkViewer::new().syntax_theme_light("fuck")
-                };
-                viewer.show(ui, &mut self.markdown_cache, &content);
-            }
-            "assistant" => {
-                ui.horizontal(|ui| {
-                    ui.label(RichText::new("AI:").strong().size(16.0));
-                    ui.add_space(8.0);
-                });
-                ui.add_space(4.0);
-
-                let viewer = if self.dark_mode {
-                    CommonMarkViewer::new().syntax_theme_dark("fuck")
-                } else {
-                    CommonMarkViewer::new().syntax_theme_light("fuck")
-                };
-                viewer.show(ui, &mut self.markdown_cache, &msg.content);
+    fn perform_chat_switch(&mut self, switch: ChatSwitch) {
+        let previous_id = self.chat_list.current_chat_id.clone();
+        match switch {
+            ChatSwitch::Open(id) => self.handle_message_selection(&id),
+            ChatSwitch::New => self.new_chat(),
+            ChatSwitch::NewIncognito => self.new_incognito_chat(),
+            ChatSwitch::NewFromRole(role_id) => self.new_chat_from_role(&role_id),
+        }
+        // 离开无痕对话后立即清除
+        if let Some(previous_id) = previous_id.filter(|id| self.chat_list.current_chat_id.as_ref() != Some(id)) {
+            if self.chat_list.chats.iter().any(|c| c.id == previous_id && c.incognito) {
+                debug!("关闭无痕对话: {}", previous_id);
+                self.delete_chat(&previous_id);
             }
-            _ => {}
         }
     }
 
-    // 添加创建角色的函数
-    fn create_role(&mut self) {
-        let new_chat = Chat {
-            id: Uuid::new_v4().to_string(),
-            name: format!("\u{f544} {}", self.role_name_input.trim()),
-            messages: Vec::new(),
-            has_been_renamed: true,
-            config: Some(ChatConfig {
+    // 新建无痕对话：消息只保存在内存中，不写入对话记录和费用统计
+    fn new_incognito_chat(&mut self) {
+        debug!("创建无痕对话");
+        let mut chat = Chat::new("\u{f21b} 无痕对话".to_string());
+        chat.has_been_renamed = true;
+        chat.incognito = true;
+        let id = chat.id.clone();
+        self.chat_list.chats.insert(0, chat);
+        self.chat_list.current_chat_id = Some(id);
+        self.chat_history.0.clear();
+        self.input_focus = true;
+    }
+
+    fn current_chat_is_incognito(&self) -> bool {
+        let Some(current_id) = self.chat_list.current_chat_id.as_ref() else {
+            return false;
+        };
+        self.chat_list.chats.iter().any(|c| &c.id == current_id && c.incognito)
+    }
+
+    // 超过设置的时间没有新消息的无痕对话自动清除
+    fn expire_incognito_chats(&mut self) {
+        if self.incognito_minutes == 0 {
+            return;
+        }
+        let deadline = Utc::now() - chrono::Duration::minutes(self.incognito_minutes as i64);
+        let expired: Vec<String> = self
+            .chat_list
+            .chats
+            .iter()
+            .filter(|c| c.incognito && c.updated_at < deadline)
+            .filter(|c| !(self.is_loading && self.chat_list.current_chat_id.as_ref() == Some(&c.id)))
+            .map(|c| c.id.clone())
+            .collect();
+        if expired.is_empty() {
+            return;
+        }
+        for id in expired {
+            debug!("无痕对话已过期: {}", id);
+            self.delete_chat(&id);
+        }
+        self.notifications.push("无痕对话已超时清除".to_string());
+        self.select_fallback_chat();
+    }
+
+    // 停止当前对话的生成，保留已收到的内容
+    fn stop_stream(&mut self) {
+        let Some(current_id) = self.chat_list.current_chat_id.clone() else {
+            return;
+        };
+        debug!("停止生成回复: {}", current_id);
+        self.task_registry.abort_chat(&current_id);
+        self.is_loading = false;
+        self.stream_stalled = None;
+        self.loading_dots.clear();
+        self.receiver = None;
+        if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == current_id) {
+            chat.messages = self.chat_history.0.clone();
+        }
+        if let Err(e) = self.save_chat_list() {
+            error!("保存聊天列表失败: {}", e);
+        }
+    }
+
+    fn retry_policy(&self) -> api::RetryPolicy {
+        api::RetryPolicy {
+            enabled: self.retry_enabled,
+            max_retries: self.max_retries,
+            stall_timeout: std::time::Duration::from_secs(self.stall_timeout_secs),
+        }
+    }
+
+    // 停滞后重试：停止当前回复，删除最后一条用户消息之后的内容并重新发送
+    fn retry_stalled_stream(&mut self) {
+        self.stop_stream();
+        let Some(index) = self
+            .chat_history
+            .0
+            .iter()
+            .rposition(|msg| msg.role == "user" && msg.kind.is_chat())
+        else {
+            return;
+        };
+        let message = self.chat_history.0[index].clone();
+        debug!("回复停滞，重新发送第 {} 条消息", index + 1);
+        self.chat_history.0.truncate(index);
+        if let Some(current_id) = self.chat_list.current_chat_id.clone() {
+            if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == current_id) {
+                chat.messages = self.chat_history.0.clone();
+            }
+        }
+        self.send_user_message(message.content, message.image_path.map(PathBuf::from), message.text_attachments);
+    }
+
+    // 回复停滞时的提示：继续等待、重试或取消
+    fn display_stall_indicator(&mut self, ui: &mut egui::Ui) {
+        let Some(seconds) = self.stream_stalled else {
+            return;
+        };
+        ui.horizontal(|ui| {
+            ui.label(RichText::new(format!("\u{f071} 响应停滞（已 {} 秒没有新内容）", seconds))
+                .color(egui::Color32::from_rgb(230, 160, 0)));
+            if ui.small_button("继续等待").clicked() {
+                self.stream_stalled = None;
+            }
+            if ui.small_button("重试").clicked() {
+                self.retry_stalled_stream();
+            }
+            if ui.small_button("取消").clicked() {
+                self.stop_stream();
+            }
+        });
+    }
+
+    // 显示某条回复对应请求实际发送的上下文
+    fn display_manifest_window(&mut self, ctx: &egui::Context) {
+        let Some(manifest) = &self.viewing_manifest else {
+            return;
+        };
+        let mut open = true;
+        egui::Window::new("本次请求的上下文")
+            .open(&mut open)
+            .default_width(480.0)
+            .show(ctx, |ui| {
+                egui::Grid::new("manifest_grid").num_columns(2).show(ui, |ui| {
+                    ui.label("发送时间:");
+                    ui.label(manifest.sent_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string());
+                    ui.end_row();
+                    ui.label("服务商:");
+                    ui.label(manifest.provider.label());
+                    ui.end_row();
+                    ui.label("模型:");
+                    ui.label(&manifest.model);
+                    ui.end_row();
+                    ui.label("参数:");
+                    ui.label(format!(
+                        "temperature {:.1} · top_p {:.2} · 频率惩罚 {:.1} · 存在惩罚 {:.1}",
+                        manifest.params.temperature,
+                        manifest.params.top_p,
+                        manifest.params.frequency_penalty,
+                        manifest.params.presence_penalty
+                    ));
+                    ui.end_row();
+                    if let Some(overrides) = &manifest.overrides {
+                        ui.label("重试参数:");
+                        ui.label(format!("模型 {} · temperature {:.1}", overrides.model, overrides.temperature));
+                        ui.end_row();
+                        if !overrides.instruction.is_empty() {
+                            ui.label("附加指令:");
+                            ui.label(&overrides.instruction);
+                            ui.end_row();
+                        }
+                    }
+                });
+
+                ui.separator();
+                ui.label(RichText::new("系统提示词").strong());
+                ui.label(&manifest.system_prompt);
+
+                ui.separator();
+                ui.label(RichText::new(format!("消息 ({} 条)", manifest.entries.len())).strong());
+                ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+                    for (index, entry) in manifest.entries.iter().enumerate() {
+                        let speaker = match entry.role.as_str() {
+                            "user" => "You",
+                            "system" => "系统",
+                            _ => "AI",
+                        };
+                        egui::CollapsingHeader::new(format!("{}. {} · {} 字", index + 1, speaker, entry.char_count))
+                            .id_salt(("manifest_entry", index))
+                            .show(ui, |ui| {
+                                let mut excerpt = entry.excerpt.clone();
+                                if entry.is_truncated() {
+                                    excerpt.push('…');
+                                }
+                                ui.label(excerpt);
+                                if entry.has_image {
+                                    ui.label(RichText::new("\u{f03e} 包含图片").small().color(egui::Color32::GRAY));
+                                }
+                                for name in &entry.attachments {
+                                    ui.label(RichText::new(format!("\u{f15c} {}", name)).small().color(egui::Color32::GRAY));
+                                }
+                            });
+                    }
+                });
+            });
+        if !open {
+            self.viewing_manifest = None;
+        }
+    }
+
+    // 提示词命中内容过滤规则时的提醒窗口
+    fn display_filter_hold(&mut self, ctx: &egui::Context) {
+        let Some(hold) = self.filter_hold.clone() else {
+            return;
+        };
+        let title = match hold.mode {
+            FilterMode::Block => "无法发送",
+            _ => "内容提醒",
+        };
+        egui::Window::new(title)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.label(format!("消息包含过滤列表中的内容：{}", hold.matches.join("、")));
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    if hold.mode == FilterMode::Warn && ui.button("仍然发送").clicked() {
+                        self.filter_hold = None;
+                        match (hold.edit_index, hold.prompt.clone()) {
+                            (Some(index), _) => self.resend_edited_message(index),
+                            (None, Some(prompt)) => self.send_user_message(prompt, None, Vec::new()),
+                            (None, None) => self.send_input(),
+                        }
+                    }
+                    if ui.button("返回修改").clicked() {
+                        self.filter_hold = None;
+                    }
+                });
+            });
+    }
+
+    // 生成回复期间切换对话的确认窗口
+    fn display_switch_confirmation(&mut self, ctx: &egui::Context) {
+        if self.pending_chat_switch.is_none() {
+            return;
+        }
+        egui::Window::new("正在生成回复")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.label("当前对话仍在生成回复，切换后回复将无法继续写入。");
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    if ui.button("完成后切换").clicked() {
+                        self.queued_chat_switch = self.pending_chat_switch.take();
+                    }
+                    if ui.button("停止并切换").clicked() {
+                        if let Some(switch) = self.pending_chat_switch.take() {
+                            self.stop_stream();
+                            self.perform_chat_switch(switch);
+                        }
+                    }
+                    if ui.button("取消").clicked() {
+                        self.pending_chat_switch = None;
+                    }
+                });
+            });
+    }
+
+    // 按当前单价计算最新回复的费用，并计入当天的累计费用
+    fn record_reply_cost(&mut self, model: &str) {
+        let Some(last_msg) = self.chat_history.0.last_mut().filter(|msg| msg.is_reply()) else {
+            return;
+        };
+        let (Some(usage), None) = (last_msg.usage, last_msg.cost) else {
+            return;
+        };
+        let Some(price) = costs::price_for(&self.pricing, model) else {
+            debug!("模型 {} 没有配置单价，跳过费用统计", model);
+            return;
+        };
+        let cost = price.cost(usage);
+        last_msg.cost = Some(cost);
+        // 无痕对话不计入费用记录
+        if self.current_chat_is_incognito() {
+            return;
+        }
+        self.cost_ledger.add(cost);
+        if self.safe_mode {
+            return;
+        }
+        if let Err(e) = self.runtime_handle.block_on(costs::save_ledger(&self.cost_ledger)) {
+            error!("保存费用记录失败: {}", e);
+        }
+    }
+
+    // 当前对话的外部输出文件
+    fn current_tee(&self) -> Option<TeeTarget> {
+        let current_id = self.chat_list.current_chat_id.as_ref()?;
+        self.chat_list
+            .chats
+            .iter()
+            .find(|c| &c.id == current_id)
+            .and_then(|chat| chat.tee.clone())
+    }
+
+    fn set_current_tee(&mut self, tee: Option<TeeTarget>) {
+        let Some(current_id) = self.chat_list.current_chat_id.clone() else {
+            return;
+        };
+        if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == current_id) {
+            chat.tee = tee;
+        }
+        if let Err(e) = self.save_chat_list() {
+            error!("保存聊天列表失败: {}", e);
+        }
+    }
+
+    // 实时模式下将流式片段同步写入外部文件
+    fn tee_stream_chunk(&self, chunk: &str, starts_reply: bool) {
+        let Some(tee) = self.current_tee().filter(|tee| tee.live) else {
+            return;
+        };
+        if starts_reply {
+            let model = self.effective_config(self.chat_list.current_chat_id.as_deref()).model_name;
+            self.tee_writer.write(&tee.path, tee::reply_header(&model));
+        }
+        self.tee_writer.write(&tee.path, chunk);
+    }
+
+    // 回复完成后写入外部文件；实时模式下只需补上分隔线
+    fn tee_finished_reply(&self, model: &str) {
+        let Some(tee) = self.current_tee() else {
+            return;
+        };
+        if tee.live {
+            self.tee_writer.write(&tee.path, tee::REPLY_SEPARATOR);
+            return;
+        }
+        if let Some(last_msg) = self.chat_history.0.last().filter(|msg| msg.is_reply()) {
+            let text = format!("{}{}{}", tee::reply_header(model), last_msg.content, tee::REPLY_SEPARATOR);
+            self.tee_writer.write(&tee.path, text);
+        }
+    }
+
+    // 当前对话的回复后处理流水线：全局启用的步骤中去掉对话关闭的步骤
+    fn post_process_pipeline(&self, chat_id: &str) -> Pipeline {
+        let disabled = self
+            .chat_list
+            .chats
+            .iter()
+            .find(|c| c.id == chat_id)
+            .map(|chat| chat.disabled_post_processors.as_slice())
+            .unwrap_or_default();
+        Pipeline::new(&self.post_processors, disabled, self.features.is_enabled(Feature::Plugins))
+    }
+
+    // 回复完成后处理最后一条回复；只有正则替换时立即完成，有插件钩子时在后台运行
+    fn post_process_reply(&mut self) {
+        let Some(chat_id) = self.chat_list.current_chat_id.clone() else {
+            return;
+        };
+        let pipeline = self.post_process_pipeline(&chat_id);
+        if pipeline.is_empty() {
+            return;
+        }
+        let Some(index) = self.chat_history.0.len().checked_sub(1) else {
+            return;
+        };
+        let Some(raw) = self.chat_history.0.get(index).filter(|msg| msg.is_reply()).map(|msg| msg.content.clone()) else {
+            return;
+        };
+        if raw.trim().is_empty() {
+            return;
+        }
+        if pipeline.has_hooks() {
+            let sender = self.post_process_sender.clone();
+            self.task_registry.spawn(&self.runtime_handle, "回复后处理", Some(chat_id.clone()), async move {
+                let (text, errors) = pipeline.run(&raw).await;
+                let _ = sender.send(Processed { chat_id, index, raw, text, errors });
+            });
+        } else {
+            let (text, errors) = self.runtime_handle.block_on(pipeline.run(&raw));
+            self.apply_processed(Processed { chat_id, index, raw, text, errors });
+        }
+    }
+
+    // 替换回复内容；回复在处理期间被编辑或重新生成时放弃结果
+    fn apply_processed(&mut self, processed: Processed) -> bool {
+        for e in processed.errors {
+            error!("{}", e);
+            self.notifications.push(e);
+        }
+        if processed.text == processed.raw {
+            return false;
+        }
+        let replace = |messages: &mut Vec<Message>| {
+            if let Some(msg) = messages.get_mut(processed.index).filter(|msg| msg.content == processed.raw) {
+                msg.raw_content = Some(processed.raw.clone());
+                msg.content = processed.text.clone();
+            }
+        };
+        if self.chat_list.current_chat_id.as_deref() == Some(processed.chat_id.as_str()) {
+            replace(&mut self.chat_history.0);
+        }
+        if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == processed.chat_id) {
+            replace(&mut chat.messages);
+        }
+        true
+    }
+
+    fn receive_post_processed(&mut self) {
+        while let Ok(processed) = self.post_process_receiver.try_recv() {
+            debug!("回复后处理完成: {}", processed.chat_id);
+            if self.apply_processed(processed) {
+                if let Err(e) = self.save_chat_list() {
+                    error!("保存聊天列表失败: {}", e);
+                }
+            }
+        }
+    }
+
+    // 当前对话关闭的后处理步骤
+    fn current_disabled_post_processors(&self) -> Vec<String> {
+        let Some(current_id) = self.chat_list.current_chat_id.as_ref() else {
+            return Vec::new();
+        };
+        self.chat_list
+            .chats
+            .iter()
+            .find(|c| &c.id == current_id)
+            .map(|chat| chat.disabled_post_processors.clone())
+            .unwrap_or_default()
+    }
+
+    fn set_current_disabled_post_processors(&mut self, disabled: Vec<String>) {
+        let Some(current_id) = self.chat_list.current_chat_id.clone() else {
+            return;
+        };
+        if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == current_id) {
+            chat.disabled_post_processors = disabled;
+        }
+    }
+
+    // 当前对话单独设置的 Webhook 地址
+    fn current_webhook(&self) -> Option<String> {
+        let current_id = self.chat_list.current_chat_id.as_ref()?;
+        self.chat_list
+            .chats
+            .iter()
+            .find(|c| &c.id == current_id)
+            .and_then(|chat| chat.webhook_url.clone())
+    }
+
+    fn set_current_webhook(&mut self, url: Option<String>) {
+        let Some(current_id) = self.chat_list.current_chat_id.clone() else {
+            return;
+        };
+        if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == current_id) {
+            chat.webhook_url = url.filter(|url| !url.trim().is_empty());
+        }
+    }
+
+    // 回复完成后通知 Webhook；对话的设置优先于全局设置
+    fn fire_webhook(&mut self, model: &str) {
+        if self.safe_mode || self.current_chat_is_incognito() {
+            return;
+        }
+        let Some(current_id) = self.chat_list.current_chat_id.clone() else {
+            return;
+        };
+        let Some(url) = self
+            .current_webhook()
+            .or_else(|| Some(self.webhook_url.clone()))
+            .filter(|url| webhook::is_valid_url(url))
+        else {
+            return;
+        };
+        let Some(answer) = self.chat_history.0.last().filter(|msg| msg.is_reply()) else {
+            return;
+        };
+        let user_message = self
+            .chat_history
+            .0
+            .iter()
+            .rev()
+            .find(|msg| msg.role == "user" && msg.kind.is_chat())
+            .map(|msg| msg.content.as_str())
+            .unwrap_or_default();
+        let chat_name = self
+            .chat_list
+            .chats
+            .iter()
+            .find(|c| c.id == current_id)
+            .map(|chat| chat.name.as_str())
+            .unwrap_or_default();
+        let payload = webhook::payload(&webhook::Completion {
+            chat_id: &current_id,
+            chat_name,
+            model,
+            user_message,
+            answer: &answer.content,
+            usage: answer.usage,
+        });
+        let client = self.client.clone();
+        self.task_registry.spawn(&self.runtime_handle, "Webhook", Some(current_id.clone()), async move {
+            match webhook::send(&client, &url, &payload).await {
+                Ok(()) => debug!("Webhook 已发送: {}", url),
+                Err(e) => error!("Webhook 发送失败 {}: {}", url, e),
+            }
+        });
+    }
+
+    // 思考过程先于正文到达，必要时先创建空的助手消息
+    fn handle_reasoning(&mut self, text: &str) {
+        if !self.chat_history.last_message_is_assistant() {
+            self.chat_history
+                .add_message(Message::new_assistant(String::new()));
+        }
+        if let Some(last_msg) = self.chat_history.0.last_mut() {
+            last_msg.reasoning.get_or_insert_with(String::new).push_str(text);
+        }
+    }
+
+    // 移除末尾的重试提示
+    fn pop_notices(&mut self) {
+        while self.chat_history.0.last().is_some_and(|msg| msg.kind == MessageKind::Notice) {
+            self.chat_history.0.pop();
+        }
+    }
+
+    // 新的提示或错误替换之前的重试提示
+    fn push_event(&mut self, kind: MessageKind, text: &str) {
+        self.pop_notices();
+        self.chat_history.add_message(Message::new_event(kind, text.to_string()));
+    }
+
+    fn handle_response(&mut self, response: String) {
+        debug!("处理响应: {}", response);
+        let starts_reply = self
+            .chat_history
+            .0
+            .last()
+            .is_none_or(|msg| !msg.is_reply() || msg.content.is_empty());
+        self.tee_stream_chunk(&response, starts_reply);
+        if self.chat_history.last_message_is_assistant() {
+            if let Some(last_msg) = self.chat_history.0.last_mut() {
+                last_msg.content.push_str(&response);
+            }
+        } else {
+            debug!("加新的助手消息");
+            self.chat_history
+                .add_message(Message::new_assistant(response));
+        }
+    }
+
+    // 使用与消息列表相同的 CommonMark 渲染管线
+    fn render_markdown(&mut self, ui: &mut egui::Ui, content: &str) {
+        let viewer = if self.dark_mode {
+            CommonMarkViewer::new().syntax_theme_dark("fuck")
+        } else {
+            CommonMarkViewer::new().syntax_theme_light("fuck")
+        };
+        viewer.show(ui, &mut self.markdown_cache, content);
+    }
+
+    // 渲染助手回复；标题较多的长回复在左侧显示大纲，点击后滚动到对应的段落
+    fn render_reply(&mut self, ui: &mut egui::Ui, content: &str) {
+        let headings = outline::headings(content);
+        if headings.len() < outline::MIN_HEADINGS {
+            self.render_markdown(ui, content);
+            return;
+        }
+        let min_level = headings.iter().map(|h| h.level).min().unwrap_or(1);
+        let (preamble, sections) = outline::split(content, &headings);
+        let mut target = None;
+        ui.horizontal_top(|ui| {
+            ui.vertical(|ui| {
+                ui.set_width(OUTLINE_WIDTH);
+                ui.label(RichText::new("\u{f0ca} 大纲").small().color(egui::Color32::GRAY));
+                for (index, heading) in headings.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.add_space((heading.level - min_level) as f32 * 8.0);
+                        let label = egui::Label::new(RichText::new(&heading.title).small())
+                            .sense(egui::Sense::click())
+                            .truncate();
+                        if ui.add(label).on_hover_text(&heading.title).clicked() {
+                            target = Some(index);
+                        }
+                    });
+                }
+            });
+            ui.separator();
+            ui.vertical(|ui| {
+                if !preamble.trim().is_empty() {
+                    self.render_markdown(ui, preamble);
+                }
+                for (index, section) in sections.into_iter().enumerate() {
+                    let response = ui.scope(|ui| self.render_markdown(ui, section)).response;
+                    if target == Some(index) {
+                        ui.scroll_to_rect(response.rect, Some(egui::Align::TOP));
+                    }
+                }
+            });
+        });
+    }
+
+    // 消息中的图片用图片控件显示，宽度不超过消息区域
+    fn display_image(&mut self, ui: &mut egui::Ui, path: &str) {
+        match self.image_cache.image(path) {
+            Some(image) => {
+                ui.add(image.max_width(ui.available_width().min(480.0)).max_height(480.0));
+            }
+            None => {
+                ui.label(RichText::new(format!("\u{f03e} 图片无法加载: {}", path))
+                    .small()
+                    .color(egui::Color32::GRAY));
+            }
+        }
+    }
+
+    // 代码审查视图：按文件显示差异，模型的意见显示在对应的行下方
+    fn display_review(&mut self, ui: &mut egui::Ui, index: usize, diff: &str, msg: &Message) {
+        egui::CollapsingHeader::new(RichText::new("\u{f002} 代码审查视图").small())
+            .id_salt(("review", index))
+            .default_open(true)
+            .show(ui, |ui| {
+                let files = review::parse_diff(diff);
+                let comments = review::parse_comments(&msg.content, &files);
+                if ui.small_button("导出审查摘要...").clicked() {
+                    if let Some(path) = FileDialog::new()
+                        .add_filter("Markdown", &["md"])
+                        .set_file_name("代码审查.md")
+                        .save_file()
+                    {
+                        match std::fs::write(&path, review::summary_markdown(&files, &comments, &msg.content)) {
+                            Ok(()) => debug!("已导出审查摘要: {}", path.display()),
+                            Err(e) => error!("导出审查摘要失败: {}", e),
+                        }
+                    }
+                }
+                let comment_frame = egui::Frame::none()
+                    .fill(ui.visuals().faint_bg_color)
+                    .stroke(egui::Stroke::new(1.0, egui::Color32::from_rgb(230, 160, 0)))
+                    .inner_margin(6.0)
+                    .rounding(4.0);
+                for (file_index, file) in files.iter().enumerate() {
+                    let file_comments: Vec<_> = comments.iter().filter(|c| c.file == file_index).collect();
+                    ui.add_space(6.0);
+                    ui.label(RichText::new(format!("\u{f15b} {}（{} 条意见）", file.path, file_comments.len())).strong());
+                    // 没有行号或行号不在差异中的意见显示在文件开头
+                    for comment in file_comments.iter().filter(|c| {
+                        c.line.is_none_or(|line| !file.lines.iter().any(|l| l.new == Some(line)))
+                    }) {
+                        comment_frame.show(ui, |ui| self.render_markdown(ui, &comment.text));
+                    }
+                    for line in &file.lines {
+                        let (prefix, color) = match line.kind {
+                            review::LineKind::Hunk => ("", egui::Color32::from_rgb(80, 140, 220)),
+                            review::LineKind::Added => ("+", egui::Color32::from_rgb(60, 160, 60)),
+                            review::LineKind::Removed => ("-", egui::Color32::RED),
+                            review::LineKind::Context => (" ", ui.visuals().text_color()),
+                        };
+                        let number = |n: Option<u32>| n.map_or("    ".to_string(), |n| format!("{:>4}", n));
+                        let text = if line.kind == review::LineKind::Hunk {
+                            line.text.clone()
+                        } else {
+                            format!("{} {} {}{}", number(line.old), number(line.new), prefix, line.text)
+                        };
+                        ui.label(RichText::new(text).monospace().color(color));
+                        for comment in file_comments.iter().filter(|c| line.new.is_some() && c.line == line.new) {
+                            comment_frame.show(ui, |ui| self.render_markdown(ui, &comment.text));
+                        }
+                    }
+                }
+            });
+    }
+
+    // 文本附件默认折叠，展开后按行虚拟滚动显示
+    fn display_text_attachments(&self, ui: &mut egui::Ui, msg: &Message) {
+        for attachment in &msg.text_attachments {
+            let line_count = attachment.line_count();
+            egui::CollapsingHeader::new(format!("\u{f15c} {} ({} 行)", attachment.name, line_count))
+                .id_salt(egui::Id::new(&attachment.name).with(msg.content.len()).with(attachment.content.len()))
+                .default_open(false)
+                .show(ui, |ui| {
+                    let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+                    ScrollArea::both()
+                        .max_height(300.0)
+                        .auto_shrink([false, true])
+                        .show_rows(ui, row_height, line_count, |ui, row_range| {
+                            for line in attachment.content.lines().skip(row_range.start).take(row_range.len()) {
+                                ui.label(RichText::new(line).monospace());
+                            }
+                        });
+                });
+        }
+    }
+
+    // 助手回复下方的快捷追问按钮
+    fn display_follow_up_chips(&mut self, ui: &mut egui::Ui, msg: &Message, is_latest: bool) {
+        // 最新回复下显示建议问题，点击后直接发送
+        let current_id = self.chat_list.current_chat_id.as_deref();
+        if let Some((_, items)) = self
+            .reply_suggestions
+            .as_ref()
+            .filter(|(chat_id, _)| is_latest && current_id == Some(chat_id.as_str()))
+        {
+            ui.horizontal_wrapped(|ui| {
+                for item in items {
+                    if ui.small_button(format!("\u{f059} {}", item)).clicked() {
+                        self.queued_prompt = Some(item.clone());
+                    }
+                }
+            });
+        }
+
+        if self.follow_up_actions.is_empty() {
+            return;
+        }
+        ui.horizontal_wrapped(|ui| {
+            for action in &self.follow_up_actions {
+                if ui.small_button(&action.label).on_hover_text(&action.prompt).clicked() {
+                    // 针对较早的回复时引用其开头，让模型知道指的是哪一段
+                    let prompt = if is_latest {
+                        action.prompt.clone()
+                    } else {
+                        let excerpt: String = msg.content.chars().take(200).collect();
+                        format!("> {}\n\n{}", excerpt.replace('\n', "\n> "), action.prompt)
+                    };
+                    self.queued_prompt = Some(prompt);
+                }
+            }
+        });
+    }
+
+    fn display_message(&mut self, ui: &mut egui::Ui, msg: &Message) {
+        if let Some(response) = status_message(ui, msg) {
+            let date = journal::section_date(msg);
+            if date.is_some() && self.journal_jump == date {
+                response.scroll_to_me(Some(egui::Align::TOP));
+                self.journal_jump = None;
+            }
+            return;
+        }
+        match msg.role.as_str() {
+            "user" => {
+                ui.horizontal(|ui| {
+                    message_header(ui, "You:", None);
+                });
+                ui.add_space(4.0);
+
+                // 使用 CommonMarkViewer 渲染文字内容
+                ui.ctx().set_theme(egui::Theme::Light);
+                self.render_markdown(ui, &msg.content);
+                if let Some(path) = &msg.image_path {
+                    self.display_image(ui, path);
+                }
+                self.display_text_attachments(ui, msg);
+            }
+            "assistant" => {
+                ui.horizontal(|ui| {
+                    let label = msg.speaker.as_deref().map_or("AI:".to_string(), |name| format!("{}:", name));
+                    let model = msg.request_manifest.as_ref().map(|manifest| manifest.model.as_str());
+                    if message_header(ui, &label, model) {
+                        self.viewing_manifest = msg.request_manifest.clone();
+                    }
+                    self.display_speech_button(ui, &msg.content);
+                });
+                ui.add_space(4.0);
+
+                // 推理模型的思考过程默认折叠
+                if let Some(reasoning) = msg.reasoning.as_deref().filter(|r| !r.is_empty()) {
+                    egui::CollapsingHeader::new(RichText::new("\u{f0eb} 思考过程").color(egui::Color32::GRAY))
+                        .id_salt(ui.next_auto_id())
+                        .default_open(false)
+                        .show(ui, |ui| {
+                            ui.label(RichText::new(reasoning).color(egui::Color32::GRAY));
+                        });
+                }
+
+                self.render_reply(ui, &msg.content);
+                if let Some(path) = &msg.image_path {
+                    self.display_image(ui, path);
+                    if ui.small_button("另存为...").clicked() {
+                        save_image_as(path);
+                    }
+                }
+
+                // 标记回复中疑似泄露的密钥或凭据
+                let found = secrets::find_secrets(&msg.content);
+                if !found.is_empty() {
+                    secret_warning(ui, &msg.content, &found);
+                }
+
+                if self.entity_actions {
+                    self.display_entities(ui, msg);
+                }
+
+                if let Some(usage) = &msg.usage {
+                    usage_line(ui, usage);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // 渲染后的标注层：回复中识别出的实体显示为一行标签，点击展开快捷操作
+    fn display_entities(&mut self, ui: &mut egui::Ui, msg: &Message) {
+        let found = entities::find_entities(&msg.content);
+        if found.is_empty() {
+            return;
+        }
+        ui.horizontal_wrapped(|ui| {
+            for entity in &found {
+                let preview: String = entity.text.chars().take(40).collect();
+                let text = RichText::new(format!("{} {}", entity.kind.icon(), preview)).monospace().small();
+                ui.menu_button(text, |ui| self.display_entity_actions(ui, entity))
+                    .response
+                    .on_hover_text(format!("{}: {}", entity.kind.label(), entity.text));
+            }
+        });
+    }
+
+    fn display_entity_actions(&mut self, ui: &mut egui::Ui, entity: &Entity) {
+        if ui.button("\u{f0c5} 复制").clicked() {
+            ui.ctx().copy_text(entity.text.clone());
+            ui.close_menu();
+        }
+        match entity.kind {
+            EntityKind::Url => {
+                if ui.button("\u{f08e} 在浏览器中打开").clicked() {
+                    ui.ctx().open_url(egui::OpenUrl::new_tab(&entity.text));
+                    ui.close_menu();
+                }
+            }
+            EntityKind::Path => {
+                let url = entities::file_url(&entity.text);
+                let response = ui.add_enabled(url.is_some(), egui::Button::new("\u{f07c} 打开文件"))
+                    .on_disabled_hover_text("本地不存在这个路径");
+                if response.clicked() {
+                    if let Some(url) = url {
+                        ui.ctx().open_url(egui::OpenUrl::new_tab(url));
+                    }
+                    ui.close_menu();
+                }
+            }
+            EntityKind::Command => {
+                if ui.add_enabled(!self.command_running, egui::Button::new("\u{f04b} 运行...")).clicked() {
+                    self.pending_command = Some(entity.text.clone());
+                    ui.close_menu();
+                }
+            }
+            EntityKind::Identifier => {}
+        }
+        if ui.button("\u{f002} 在对话中搜索").clicked() {
+            self.search_query = entity.text.clone();
+            ui.close_menu();
+        }
+    }
+
+    // 运行回复中的命令前的确认窗口，以及运行结果
+    fn display_command_runner(&mut self, ctx: &egui::Context) {
+        if let Some(command) = self.pending_command.clone() {
+            let mut decided = false;
+            egui::Window::new("运行命令")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    ui.label(RichText::new("\u{f071} 命令将在本机的 shell 中运行，请确认内容安全:")
+                        .color(egui::Color32::from_rgb(230, 160, 0)));
+                    ui.add_space(4.0);
+                    ui.label(RichText::new(&command).monospace());
+                    ui.add_space(8.0);
+                    ui.horizontal(|ui| {
+                        if ui.button("运行").clicked() {
+                            self.run_command(command.clone());
+                            decided = true;
+                        }
+                        if ui.button("取消").clicked() {
+                            decided = true;
+                        }
+                    });
+                });
+            if decided {
+                self.pending_command = None;
+            }
+        }
+
+        if self.command_output.is_none() && !self.command_running {
+            return;
+        }
+        let mut open = true;
+        egui::Window::new("命令输出")
+            .open(&mut open)
+            .default_width(520.0)
+            .show(ctx, |ui| {
+                match &self.command_output {
+                    None => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label("运行中...");
+                        });
+                    }
+                    Some((command, result)) => {
+                        ui.label(RichText::new(format!("$ {}", command)).monospace().strong());
+                        let (text, color) = match result {
+                            Ok(output) => (output.as_str(), ui.visuals().text_color()),
+                            Err(e) => (e.as_str(), egui::Color32::RED),
+                        };
+                        ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+                            ui.label(RichText::new(text).monospace().color(color));
+                        });
+                        if ui.button("\u{f0c5} 复制输出").clicked() {
+                            ui.ctx().copy_text(text.to_string());
+                        }
+                    }
+                }
+            });
+        if !open {
+            self.command_output = None;
+        }
+    }
+
+    fn run_command(&mut self, command: String) {
+        debug!("运行回复中的命令: {}", command);
+        self.command_running = true;
+        self.command_output = None;
+        let sender = self.command_sender.clone();
+        self.task_registry.spawn(&self.runtime_handle, "运行命令", None, async move {
+            let result = entities::run_command(&command).await;
+            let _ = sender.send((command, result));
+        });
+    }
+
+    fn receive_command_output(&mut self) {
+        while let Ok(output) = self.command_receiver.try_recv() {
+            self.command_running = false;
+            self.command_output = Some(output);
+        }
+    }
+
+    // 回复旁的朗读按钮：合成中显示进度，播放中可以停止
+    fn display_speech_button(&mut self, ui: &mut egui::Ui, text: &str) {
+        let path = speech::cache_path(&self.audio_cache_dir(), &self.tts_model, &self.tts_voice, text);
+        if self.audio_player.playing() == Some(path.as_path()) {
+            ui.ctx().request_repaint_after(std::time::Duration::from_millis(500));
+            if ui.small_button("\u{f04d}").on_hover_text("停止朗读").clicked() {
+                self.audio_player.stop();
+            }
+        } else if self.speech_pending.as_ref() == Some(&path) {
+            ui.spinner();
+        } else if ui.small_button("\u{f028}").on_hover_text("朗读").clicked() {
+            self.speak(text);
+        }
+    }
+
+    // 朗读文本，优先使用缓存的音频；没有缓存时边合成边播放
+    fn speak(&mut self, text: &str) {
+        let path = speech::cache_path(&self.audio_cache_dir(), &self.tts_model, &self.tts_voice, text);
+        if path.exists() {
+            if let Err(e) = self.audio_player.play(&path) {
+                error!("播放音频失败: {}", e);
+            }
+            return;
+        }
+
+        let (samples_sender, samples_receiver) = std::sync::mpsc::channel();
+        let source = StreamingSource::new(samples_receiver, speech::SAMPLE_RATE);
+        if let Err(e) = self.audio_player.play_stream(&path, source) {
+            error!("播放音频失败: {}", e);
+            return;
+        }
+        self.speech_pending = Some(path.clone());
+        let client = self.client.clone();
+        let target = self.effective_target(&self.effective_config(self.chat_list.current_chat_id.as_deref()));
+        let model = self.tts_model.clone();
+        let voice = self.tts_voice.clone();
+        let text = text.to_string();
+        let sender = self.speech_sender.clone();
+        let chat_id = self.chat_list.current_chat_id.clone();
+        self.task_registry.spawn(&self.runtime_handle, "语音合成", chat_id, async move {
+            let result = speech::synthesize(&client, &target, &model, &voice, &text, &path, samples_sender).await;
+            let _ = sender.send(result);
+        });
+    }
+
+    fn receive_speech(&mut self) {
+        while let Ok(result) = self.speech_receiver.try_recv() {
+            self.speech_pending = None;
+            match result {
+                Ok(path) => debug!("语音已缓存: {:?}", path),
+                Err(e) => error!("{}", e),
+            }
+        }
+    }
+
+    // 设置中的“实验功能”页：功能开关和更新日志入口，返回开关是否有变化
+    fn display_experimental_settings(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        ui.label(RichText::new("实验功能仍在开发中，可能不稳定").small().color(egui::Color32::GRAY));
+        ui.add_space(4.0);
+        for feature in Feature::ALL {
+            let mut enabled = self.features.is_enabled(feature);
+            if ui.checkbox(&mut enabled, feature.label()).on_hover_text(feature.description()).changed() {
+                self.features.set(feature, enabled);
+                changed = true;
+            }
+        }
+        ui.separator();
+        ui.horizontal(|ui| {
+            if ui.button("查看更新日志").clicked() {
+                self.show_changelog = true;
+            }
+            if ui.button("生成诊断包...").clicked() {
+                self.save_diagnostics_bundle();
+            }
+        });
+        changed
+    }
+
+    // 本地诊断统计面板
+    fn display_metrics(&mut self, ctx: &egui::Context) {
+        if !self.show_metrics {
+            return;
+        }
+        let data = self.metrics.snapshot();
+        let mut open = true;
+        egui::Window::new("诊断面板")
+            .open(&mut open)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                if !self.metrics.is_enabled() {
+                    ui.label(RichText::new("未开启诊断统计，可在设置中开启").color(egui::Color32::GRAY));
+                }
+                ui.label(format!("统计开始于 {}", data.since));
+                ui.separator();
+
+                egui::Grid::new("metrics_summary").num_columns(2).show(ui, |ui| {
+                    ui.label("请求次数:");
+                    ui.label(data.requests.to_string());
+                    ui.end_row();
+                    ui.label("失败次数:");
+                    match data.error_rate() {
+                        Some(rate) => ui.label(format!("{} ({:.1}%)", data.errors, rate * 100.0)),
+                        None => ui.label("0"),
+                    };
+                    ui.end_row();
+                    ui.label("平均耗时:");
+                    ui.label(data.request_latency.mean_ms().map_or("-".to_string(), |ms| metrics::format_ms(ms as u64)));
+                    ui.end_row();
+                    ui.label("P50 / P95:");
+                    let p50 = data.request_latency.percentile(&metrics::LATENCY_BOUNDS_MS, 0.5);
+                    let p95 = data.request_latency.percentile(&metrics::LATENCY_BOUNDS_MS, 0.95);
+                    ui.label(match (p50, p95) {
+                        (Some(p50), Some(p95)) => format!("≤ {} / ≤ {}", metrics::format_ms(p50), metrics::format_ms(p95)),
+                        _ => "-".to_string(),
+                    });
+                    ui.end_row();
+                    ui.label("平均帧耗时:");
+                    ui.label(data.frame_time.mean_ms().map_or("-".to_string(), |ms| format!("{:.1}ms", ms)));
+                    ui.end_row();
+                });
+
+                ui.separator();
+                ui.label(RichText::new("请求耗时分布").strong());
+                histogram_rows(ui, &data.request_latency, &metrics::LATENCY_BOUNDS_MS);
+                ui.add_space(4.0);
+                ui.label(RichText::new("帧耗时分布").strong());
+                histogram_rows(ui, &data.frame_time, &metrics::FRAME_BOUNDS_MS);
+
+                ui.separator();
+                if ui.button("清空统计").clicked() {
+                    self.metrics.reset();
+                    let metrics = self.metrics.clone();
+                    self.runtime_handle.spawn(async move { metrics.save().await });
+                }
+            });
+        self.show_metrics = open;
+    }
+
+    // 后台任务的通知列表，打开时视为已读
+    fn display_notifications(&mut self, ctx: &egui::Context) {
+        if !self.show_notifications {
+            return;
+        }
+        self.notifications.mark_read();
+        let mut open = true;
+        let mut clear = false;
+        egui::Window::new("通知")
+            .open(&mut open)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                if self.notifications.items().next().is_none() {
+                    ui.label(RichText::new("暂无通知").italics().color(egui::Color32::GRAY));
+                }
+                ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    for notification in self.notifications.items() {
+                        ui.horizontal_wrapped(|ui| {
+                            ui.label(RichText::new(notification.time.format("%m-%d %H:%M").to_string())
+                                .small()
+                                .color(egui::Color32::GRAY));
+                            ui.label(&notification.text);
+                        });
+                    }
+                });
+                if ui.button("清空").clicked() {
+                    clear = true;
+                }
+            });
+        if clear {
+            self.notifications.clear();
+        }
+        self.show_notifications = open;
+    }
+
+    // 生成诊断包：选择保存位置后写入脱敏的日志、配置、系统信息和最近一次失败的请求
+    fn save_diagnostics_bundle(&mut self) {
+        let Some(path) = FileDialog::new()
+            .add_filter("ZIP", &["zip"])
+            .set_file_name(diagnostics::default_file_name())
+            .save_file()
+        else {
+            return;
+        };
+        let request = match &self.failed_request {
+            Some(request) => (request, true),
+            None => (&self.inspector, false),
+        };
+        let entries = diagnostics::bundle_entries(&self.current_config(), self.safe_mode, request);
+        match self.runtime_handle.block_on(diagnostics::write_bundle(&path, entries)) {
+            Ok(()) => {
+                debug!("已生成诊断包: {}", path.display());
+                self.notifications.push(format!("诊断包已保存到 {}", path.display()));
+            }
+            Err(e) => {
+                error!("生成诊断包失败: {}", e);
+                self.notifications.push(format!("生成诊断包失败: {}", e));
+            }
+        }
+    }
+
+    // 请求检查器：最近一次对话请求的请求体、响应头和原始 SSE 数据
+    fn display_inspector(&mut self, ctx: &egui::Context) {
+        if !self.show_inspector {
+            return;
+        }
+        let mut open = true;
+        let mut save_bundle = false;
+        egui::Window::new("请求检查器")
+            .open(&mut open)
+            .default_width(560.0)
+            .default_height(520.0)
+            .show(ctx, |ui| {
+                if ui.button("生成诊断包...")
+                    .on_hover_text("打包脱敏后的日志、配置、系统信息和最近一次失败的请求，用于反馈问题")
+                    .clicked()
+                {
+                    save_bundle = true;
+                }
+                let inspector = &self.inspector;
+                if inspector.is_empty() {
+                    ui.label(RichText::new("还没有发送过请求").italics().color(egui::Color32::GRAY));
+                    return;
+                }
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new(format!("{} {}", inspector.method, inspector.url)).monospace());
+                    if inspector.attempts > 1 {
+                        ui.label(RichText::new(format!("（第 {} 次尝试）", inspector.attempts)).color(egui::Color32::GRAY));
+                    }
+                });
+                ui.label(match &inspector.status {
+                    Some(status) => format!("状态: {}", status),
+                    None => "状态: 等待响应...".to_string(),
+                });
+                ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| {
+                    egui::CollapsingHeader::new("请求头").default_open(false).show(ui, |ui| {
+                        header_grid(ui, "inspector_request_headers", &inspector.request_headers);
+                    });
+                    egui::CollapsingHeader::new("请求体").default_open(true).show(ui, |ui| {
+                        if ui.small_button("复制").clicked() {
+                            ui.ctx().copy_text(inspector.payload.clone());
+                        }
+                        ui.label(RichText::new(&inspector.payload).monospace());
+                    });
+                    egui::CollapsingHeader::new("响应头").default_open(false).show(ui, |ui| {
+                        header_grid(ui, "inspector_response_headers", &inspector.response_headers);
+                    });
+                    egui::CollapsingHeader::new("原始响应").default_open(true).show(ui, |ui| {
+                        if ui.small_button("复制").clicked() {
+                            ui.ctx().copy_text(inspector.raw.clone());
+                        }
+                        if inspector.truncated {
+                            ui.label(RichText::new("响应过长，后续内容未记录").color(egui::Color32::GRAY));
+                        }
+                        ui.label(RichText::new(&inspector.raw).monospace());
+                    });
+                });
+            });
+        self.show_inspector = open;
+        if save_bundle {
+            self.save_diagnostics_bundle();
+        }
+    }
+
+    // 显示编译时嵌入的更新日志
+    fn display_changelog(&mut self, ctx: &egui::Context) {
+        if !self.show_changelog {
+            return;
+        }
+        let mut open = true;
+        egui::Window::new("更新日志")
+            .open(&mut open)
+            .default_width(480.0)
+            .default_height(420.0)
+            .show(ctx, |ui| {
+                ScrollArea::vertical().show(ui, |ui| {
+                    self.render_markdown(ui, features::CHANGELOG);
+                });
+            });
+        self.show_changelog = open;
+    }
+
+    // 助手回复下方的“用不同参数重试”弹窗
+    fn display_retry_menu(&mut self, ui: &mut egui::Ui, index: usize, msg: &Message) {
+        let current_id = self.chat_list.current_chat_id.as_deref();
+        if self
+            .turn_retry
+            .as_ref()
+            .is_some_and(|retry| retry.index == index && current_id == Some(retry.chat_id.as_str()))
+        {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(RichText::new("正在用不同参数重试...").italics().color(egui::Color32::GRAY));
+            });
+            return;
+        }
+        // 只有紧跟在用户消息之后的回复可以重试
+        let retryable = index > 0 && self.chat_history.0[index - 1].role == "user";
+        if !retryable {
+            return;
+        }
+
+        let effective = self.effective_config(current_id);
+        let models = self.model_choices(effective.profile.as_deref());
+        let defaults = match &msg.request_manifest {
+            Some(manifest) => RetryOverrides {
+                model: manifest.model.clone(),
+                temperature: manifest.params.temperature,
+                instruction: String::new(),
+            },
+            None => RetryOverrides {
+                model: effective.model_name,
+                temperature: effective.params.temperature,
+                instruction: String::new(),
+            },
+        };
+
+        let mut submit = None;
+        // 最后一条回复可以直接重新生成，下拉菜单中可以换一个模型
+        if index + 1 == self.chat_history.0.len() {
+            ui.add_enabled_ui(self.turn_retry.is_none(), |ui| {
+                ui.horizontal(|ui| {
+                    if ui.small_button("\u{f021} 重新生成").clicked() {
+                        submit = Some(defaults.clone());
+                    }
+                    ui.menu_button(RichText::new("\u{f0d7}").small(), |ui| {
+                        for model in &models {
+                            if ui.button(model).clicked() {
+                                submit = Some(RetryOverrides {
+                                    model: model.clone(),
+                                    ..defaults.clone()
+                                });
+                                ui.close_menu();
+                            }
+                        }
+                    })
+                    .response
+                    .on_hover_text("换一个模型重新生成");
+                });
+            });
+        }
+        ui.add_enabled_ui(self.turn_retry.is_none(), |ui| {
+            ui.menu_button(RichText::new("\u{f2f9} 用不同参数重试").small(), |ui| {
+                if self.retry_draft.as_ref().is_none_or(|(draft_index, _)| *draft_index != index) {
+                    self.retry_draft = Some((index, defaults));
+                }
+                let Some((_, draft)) = self.retry_draft.as_mut() else {
+                    return;
+                };
+                egui::Grid::new(("retry_grid", index)).num_columns(2).show(ui, |ui| {
+                    ui.label("模型:");
+                    ui.horizontal(|ui| {
+                        ui.add(TextEdit::singleline(&mut draft.model).desired_width(140.0));
+                        egui::ComboBox::from_id_salt(("retry_model", index))
+                            .selected_text("")
+                            .width(20.0)
+                            .show_ui(ui, |ui| {
+                                for model in &models {
+                                    ui.selectable_value(&mut draft.model, model.clone(), model);
+                                }
+                            });
+                    });
+                    ui.end_row();
+                    ui.label("Temperature:");
+                    ui.add(egui::Slider::new(&mut draft.temperature, 0.0..=2.0).step_by(0.1));
+                    ui.end_row();
+                    ui.label("附加指令:");
+                    ui.add(TextEdit::multiline(&mut draft.instruction)
+                        .desired_rows(2)
+                        .desired_width(200.0)
+                        .hint_text("例如：回答更简洁一些"));
+                    ui.end_row();
+                });
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(!draft.model.trim().is_empty(), egui::Button::new("重试")).clicked() {
+                        submit = Some(draft.clone());
+                        ui.close_menu();
+                    }
+                    if ui.button("取消").clicked() {
+                        ui.close_menu();
+                    }
+                });
+            });
+        });
+        if let Some(overrides) = submit {
+            self.retry_draft = None;
+            self.start_turn_retry(index, overrides);
+        }
+    }
+
+    // 编辑用户消息：保存后从该消息处重新发送
+    fn display_message_editor(&mut self, ui: &mut egui::Ui, index: usize) {
+        let is_loading = self.is_loading;
+        let Some((_, draft)) = self.editing_message.as_mut() else {
+            return;
+        };
+        ui.label(RichText::new("You:").strong().size(16.0));
+        ui.add_space(4.0);
+        ui.add(TextEdit::multiline(draft).desired_rows(3).desired_width(f32::INFINITY));
+        let ready = !is_loading && !draft.trim().is_empty();
+        let mut submit = false;
+        let mut cancel = false;
+        ui.horizontal(|ui| {
+            if ui.add_enabled(ready, egui::Button::new("保存并重新发送")).clicked() {
+                submit = true;
+            }
+            if ui.button("取消").clicked() {
+                cancel = true;
+            }
+            ui.label(RichText::new("之后的消息将被删除").small().color(egui::Color32::GRAY));
+        });
+        if cancel {
+            self.editing_message = None;
+        } else if submit {
+            let text = draft.clone();
+            match self.check_content_filter(&text, Some(text.clone())) {
+                Some(hold) => self.filter_hold = Some(FilterHold { edit_index: Some(index), ..hold }),
+                None => self.resend_edited_message(index),
+            }
+        }
+    }
+
+    // 删除被编辑的消息及之后的消息，用新文本重新发送，并保留最初的文本用于对比
+    fn resend_edited_message(&mut self, index: usize) {
+        let Some((_, text)) = self.editing_message.take() else {
+            return;
+        };
+        let Some(original) = self.chat_history.0.get(index).cloned() else {
+            return;
+        };
+        if text == original.content {
+            return;
+        }
+        debug!("编辑第 {} 条消息并重新发送", index + 1);
+        self.chat_history.0.truncate(index);
+        if let Some(current_id) = self.chat_list.current_chat_id.clone() {
+            if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == current_id) {
+                chat.messages = self.chat_history.0.clone();
+            }
+        }
+        self.pending_edit_original = Some(original.original_content.unwrap_or(original.content));
+        self.send_user_message(text, original.image_path.map(PathBuf::from), original.text_attachments);
+    }
+
+    // 编辑过的消息下方的差异对比：删除的文字标红划线，新增的文字标绿
+    fn display_edit_diff(&self, ui: &mut egui::Ui, index: usize, msg: &Message) {
+        let Some(original) = &msg.original_content else {
+            return;
+        };
+        egui::CollapsingHeader::new(RichText::new("已编辑，查看差异").small().color(egui::Color32::GRAY))
+            .id_salt(("edit_diff", index))
+            .default_open(false)
+            .show(ui, |ui| {
+                let font_id = egui::TextStyle::Body.resolve(ui.style());
+                let text_color = ui.visuals().text_color();
+                let mut job = egui::text::LayoutJob::default();
+                for op in diff::word_diff(original, &msg.content) {
+                    let (text, format) = match op {
+                        DiffOp::Same(text) => (text, egui::TextFormat::simple(font_id.clone(), text_color)),
+                        DiffOp::Removed(text) => (text, egui::TextFormat {
+                            strikethrough: egui::Stroke::new(1.0, egui::Color32::RED),
+                            ..egui::TextFormat::simple(font_id.clone(), egui::Color32::RED)
+                        }),
+                        DiffOp::Added(text) => (text, egui::TextFormat {
+                            background: egui::Color32::from_rgba_unmultiplied(0, 160, 0, 40),
+                            ..egui::TextFormat::simple(font_id.clone(), egui::Color32::from_rgb(0, 140, 0))
+                        }),
+                    };
+                    job.append(&text, 0.0, format);
+                }
+                job.wrap.max_width = ui.available_width();
+                ui.label(job);
+            });
+    }
+
+    // 重新生成前的历史版本，可以换回其中任意一个
+    fn display_reply_versions(&mut self, ui: &mut egui::Ui, index: usize, msg: &Message) {
+        let mut restore = None;
+        egui::CollapsingHeader::new(RichText::new(format!("\u{f1da} 历史版本 ({})", msg.previous_versions.len()))
+            .small()
+            .color(egui::Color32::GRAY))
+            .id_salt(("reply_versions", index))
+            .default_open(false)
+            .show(ui, |ui| {
+                for (version, previous) in msg.previous_versions.iter().enumerate().rev() {
+                    ui.horizontal(|ui| {
+                        let model = previous.request_manifest.as_ref().map_or("未知模型", |manifest| manifest.model.as_str());
+                        ui.label(RichText::new(format!("#{} · {}", version + 1, model)).small().strong());
+                        if ui.small_button("换回此版本").clicked() {
+                            restore = Some(version);
+                        }
+                    });
+                    let preview: String = previous.content.chars().take(200).collect();
+                    ui.label(RichText::new(preview).small().color(egui::Color32::GRAY));
+                    ui.add_space(4.0);
+                }
+            });
+        let Some(version) = restore else {
+            return;
+        };
+        if let Some(msg) = self.chat_history.0.get_mut(index) {
+            msg.restore_version(version);
+        }
+        if let Some(current_id) = self.chat_list.current_chat_id.clone() {
+            if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == current_id) {
+                chat.messages = self.chat_history.0.clone();
+            }
+        }
+        if let Err(e) = self.save_chat_list() {
+            error!("保存聊天列表失败: {}", e);
+        }
+    }
+
+    // 使用覆盖后的参数重新请求第 index 条回复，只发送它之前的消息
+    fn start_turn_retry(&mut self, index: usize, mut overrides: RetryOverrides) {
+        let Some(chat_id) = self.chat_list.current_chat_id.clone() else {
+            return;
+        };
+        let history: Vec<Message> = models::context_messages(&self.chat_history.0[..index]).cloned().collect();
+        let Some(user_message) = history.last().filter(|msg| msg.role == "user") else {
+            return;
+        };
+        overrides.model = overrides.model.trim().to_string();
+        overrides.instruction = overrides.instruction.trim().to_string();
+
+        let mut effective = self.effective_config(Some(&chat_id));
+        effective.model_name = overrides.model.clone();
+        effective.params.temperature = overrides.temperature;
+        let target = self.effective_target(&effective);
+        let greeting_context = self
+            .current_chat_config()
+            .filter(|config| config.greeting_in_context)
+            .and_then(|config| config.greeting_text())
+            .map(str::to_string);
+        let language_directive = self
+            .match_reply_language
+            .then(|| language::reply_directive(&user_message.content));
+        let review_directive = review::message_diff(user_message).map(|_| review::REVIEW_DIRECTIVE.to_string());
+        let question = user_message.clone();
+        let retrieval = self.retrieval(&chat_id);
+        let (history, summarized, system_prompt) = self.prepare_history(
+            Some(&chat_id),
+            &overrides.model,
+            &effective.system_prompt,
+            history,
+        );
+
+        let mut entries = Vec::new();
+        if !summarized.is_empty() {
+            entries.push(ManifestEntry::from_text("system", &context::summary_note(summarized.len())));
+        }
+        if let Some(greeting) = &greeting_context {
+            entries.push(ManifestEntry::from_text("assistant", greeting));
+        }
+        entries.extend(history.iter().map(ManifestEntry::from_message));
+        let manifest = RequestManifest {
+            sent_at: Utc::now(),
+            provider: effective.provider,
+            model: overrides.model.clone(),
+            system_prompt: system_prompt.clone(),
+            params: effective.params,
+            entries,
+            overrides: Some(overrides.clone()),
+        };
+        debug!("用不同参数重试第 {} 条消息: {:?}", index + 1, overrides);
+
+        let client = self.client.clone();
+        let retry_policy = self.retry_policy();
+        let endpoint_health = self.endpoint_health.clone();
+        let metrics = self.metrics.clone();
+        let reasoning_effort = self.reasoning_effort;
+        let params = effective.params;
+        let context_summaries = self.context_summaries.clone();
+        let summary_key = chat_id.clone();
+        let (tx, rx) = mpsc::unbounded_channel();
+        let task_chat_id = Some(chat_id.clone());
+        self.task_registry.spawn(&self.runtime_handle, "重试回复", task_chat_id, async move {
+            let system_prompt = context_summaries
+                .summarized_prompt(&client, &target, &overrides.model, &summary_key, &system_prompt, &summarized)
+                .await;
+            // 检索到的上下文、附加指令、回复语言和代码审查指令紧挨着被重试的用户消息
+            let mut directives = retrieval.retrieve(&client, &target, &question, &tx).await;
+            directives.extend([Some(overrides.instruction.clone()), language_directive, review_directive].into_iter().flatten());
+            let messages = reply_stream::build_messages(&system_prompt, greeting_context.as_deref(), &history, &directives).await;
+            let payload = reply_stream::stream_payload(&overrides.model, messages, params, reasoning_effort);
+            let started = Instant::now();
+            let result = api::send_request(&client, &target, &payload, retry_policy, &endpoint_health, &tx).await;
+            metrics.finish_request(started, result.is_ok()).await;
+            if let Err(e) = result {
+                error!("重试回复失败: {:?}", e);
+                let _ = tx.send(UiEvent::Error(e.to_string()));
+                let _ = tx.send(UiEvent::Done);
+            }
+        });
+        self.turn_retry = Some(TurnRetry {
+            chat_id,
+            index,
+            stream: ReplyStream::new(manifest, rx),
+        });
+    }
+
+    // 发送请求前需要检索的补充上下文
+    fn retrieval(&self, chat_id: &str) -> Retrieval {
+        let knowledge_chat_id = self
+            .chat_list
+            .chats
+            .iter()
+            .find(|c| c.id == chat_id)
+            .filter(|chat| chat.knowledge_folder.is_some())
+            .map(|chat| chat.id.clone());
+        let memories = if self.long_term_memory {
+            self.memory_store.entries.clone()
+        } else {
+            Vec::new()
+        };
+        Retrieval {
+            knowledge_chat_id,
+            knowledge_top_k: self.knowledge_top_k,
+            memories,
+            embedding_model: self.embedding_model.clone(),
+        }
+    }
+
+    fn current_chat_is_group(&self) -> bool {
+        self.chat_list
+            .current_chat_id
+            .as_ref()
+            .and_then(|id| self.chat_list.chats.iter().find(|c| &c.id == id))
+            .is_some_and(Chat::is_group)
+    }
+
+    // 新建群聊：选择参与的角色，按选择的顺序依次回复
+    fn display_group_setup(&mut self, ctx: &egui::Context) {
+        let roles: Vec<(String, String)> = self
+            .chat_list
+            .chats
+            .iter()
+            .filter(|chat| chat.is_role())
+            .map(|chat| (chat.id.clone(), chat.name.clone()))
+            .collect();
+        let Some((name, selected)) = self.group_setup.as_mut() else {
+            return;
+        };
+        let mut create = false;
+        let mut close = false;
+        egui::Window::new("新建群聊")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("名称:");
+                    ui.text_edit_singleline(name);
+                });
+                ui.add_space(4.0);
+                ui.label("参与的角色（按选择顺序依次回复）:");
+                if roles.is_empty() {
+                    ui.label(RichText::new("还没有角色，请先创建角色").italics().color(egui::Color32::GRAY));
+                }
+                for (id, role_name) in &roles {
+                    let mut checked = selected.contains(id);
+                    let label = match selected.iter().position(|item| item == id) {
+                        Some(position) => format!("{} (#{})", role_name, position + 1),
+                        None => role_name.clone(),
+                    };
+                    if ui.checkbox(&mut checked, label).changed() {
+                        if checked {
+                            selected.push(id.clone());
+                        } else {
+                            selected.retain(|item| item != id);
+                        }
+                    }
+                }
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    let ready = selected.len() >= 2 && !name.trim().is_empty();
+                    if ui.add_enabled(ready, egui::Button::new("创建")).clicked() {
+                        create = true;
+                    }
+                    if ui.button("取消").clicked() {
+                        close = true;
+                    }
+                });
+            });
+        if create {
+            if let Some((name, role_ids)) = self.group_setup.take() {
+                self.create_group_chat(name.trim(), role_ids);
+            }
+        } else if close {
+            self.group_setup = None;
+        }
+    }
+
+    fn create_group_chat(&mut self, name: &str, role_ids: Vec<String>) {
+        debug!("创建群聊: {} ({} 个角色)", name, role_ids.len());
+        let mut chat = Chat::new(format!("\u{f0c0} {}", name));
+        chat.has_been_renamed = true;
+        chat.group_members = role_ids
+            .into_iter()
+            .map(|role_id| GroupMember { role_id, muted: false })
+            .collect();
+        let id = chat.id.clone();
+        self.chat_list.chats.insert(0, chat);
+        if let Err(e) = self.save_chat_list() {
+            error!("保存聊天列表失败: {}", e);
+        }
+        self.request_chat_switch(ChatSwitch::Open(id));
+    }
+
+    // 打开日记对话，还没有时新建
+    fn open_journal(&mut self) {
+        let id = match self.chat_list.chats.iter().find(|c| c.is_journal()) {
+            Some(chat) => chat.id.clone(),
+            None => {
+                let mut chat = Chat::new(journal::JOURNAL_NAME.to_string());
+                chat.has_been_renamed = true;
+                chat.journal = Some(JournalState::default());
+                let id = chat.id.clone();
+                self.chat_list.chats.insert(0, chat);
+                if let Err(e) = self.save_chat_list() {
+                    error!("保存聊天列表失败: {}", e);
+                }
+                id
+            }
+        };
+        self.request_chat_switch(ChatSwitch::Open(id));
+    }
+
+    // 日记对话每天第一次发送时开始新的分段，并在后台把之前几天并入滚动摘要
+    fn rotate_journal(&mut self) {
+        let Some(current_id) = self.chat_list.current_chat_id.clone() else {
+            return;
+        };
+        let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == current_id && c.is_journal()) else {
+            return;
+        };
+        let today = journal::today();
+        if journal::current_section(&self.chat_history.0) == Some(today) {
+            return;
+        }
+        debug!("日记开始新的一天: {}", today);
+        self.chat_history.add_message(journal::section(today));
+        chat.messages = self.chat_history.0.clone();
+        self.summarize_journal(&current_id);
+    }
+
+    fn summarize_journal(&mut self, chat_id: &str) {
+        if self.journal_updating {
+            return;
+        }
+        let Some(chat) = self.chat_list.chats.iter().find(|c| c.id == chat_id) else {
+            return;
+        };
+        let Some(state) = &chat.journal else {
+            return;
+        };
+        let days = journal::pending_days(&chat.messages, state, journal::today());
+        let Some(through) = days.last().map(|(date, _)| *date) else {
+            return;
+        };
+        debug!("更新日记摘要: {} 天", days.len());
+        let previous = state.summary.clone();
+        let effective = self.effective_config(Some(chat_id));
+        let target = self.effective_target(&effective);
+        let client = self.client.clone();
+        let sender = self.journal_sender.clone();
+        let key = chat_id.to_string();
+        self.journal_updating = true;
+        self.task_registry.spawn(&self.runtime_handle, "日记摘要", Some(key.clone()), async move {
+            let summary = match journal::summarize(&client, &target, &effective.model_name, &previous, &days).await {
+                Ok(summary) => Some(summary),
+                Err(e) => {
+                    error!("更新日记摘要失败: {}", e);
+                    None
+                }
+            };
+            let _ = sender.send((key, through, summary));
+        });
+    }
+
+    fn receive_journal_updates(&mut self) {
+        let mut changed = false;
+        while let Ok((chat_id, through, summary)) = self.journal_receiver.try_recv() {
+            self.journal_updating = false;
+            let Some(summary) = summary else {
+                continue;
+            };
+            if let Some(state) = self
+                .chat_list
+                .chats
+                .iter_mut()
+                .find(|c| c.id == chat_id)
+                .and_then(|chat| chat.journal.as_mut())
+            {
+                state.summary = summary;
+                state.summarized_through = Some(through);
+                changed = true;
+            }
+        }
+        if changed {
+            if let Err(e) = self.save_chat_list() {
+                error!("保存聊天列表失败: {}", e);
+            }
+        }
+    }
+
+    // 日记的月历：有记录的日期可以点击，跳转到当天的分段
+    fn display_journal_calendar(&mut self, ui: &mut egui::Ui) {
+        let is_journal = self
+            .chat_list
+            .current_chat_id
+            .as_ref()
+            .and_then(|id| self.chat_list.chats.iter().find(|c| &c.id == id))
+            .is_some_and(Chat::is_journal);
+        if !is_journal {
+            return;
+        }
+        ui.menu_button("\u{f073}", |ui| {
+            let dates = journal::dates(&self.chat_history.0);
+            let month = *self
+                .journal_month
+                .get_or_insert_with(|| journal::month_start(dates.last().copied().unwrap_or_else(journal::today)));
+            ui.horizontal(|ui| {
+                if ui.small_button("\u{f053}").clicked() {
+                    self.journal_month = Some(journal::shift_month(month, false));
+                }
+                ui.label(RichText::new(month.format("%Y年%m月").to_string()).strong());
+                if ui.small_button("\u{f054}").clicked() {
+                    self.journal_month = Some(journal::shift_month(month, true));
+                }
+            });
+            let (offset, days) = journal::month_layout(month);
+            egui::Grid::new("journal_calendar").spacing([4.0, 4.0]).show(ui, |ui| {
+                for weekday in ["一", "二", "三", "四", "五", "六", "日"] {
+                    ui.label(RichText::new(weekday).small().color(egui::Color32::GRAY));
+                }
+                ui.end_row();
+                for _ in 0..offset {
+                    ui.label("");
+                }
+                for day in 1..=days {
+                    let Some(date) = journal::day_in_month(month, day) else {
+                        continue;
+                    };
+                    let text = RichText::new(format!("{:>2}", day)).monospace();
+                    if dates.contains(&date) {
+                        if ui.small_button(text).on_hover_text(journal::format_date(date)).clicked() {
+                            self.journal_jump = Some(date);
+                            ui.close_menu();
+                        }
+                    } else {
+                        ui.label(text.color(egui::Color32::GRAY));
+                    }
+                    if (offset + day) % 7 == 0 {
+                        ui.end_row();
+                    }
+                }
+            });
+        }).response.on_hover_text("按日期跳转");
+    }
+
+    // 整理要发送的历史：开启对话记忆时先用记忆代替较早的消息，再按上下文策略裁剪
+    // 返回（发送的历史，需要摘要的消息，附加了记忆的系统提示词）
+    fn prepare_history(
+        &self,
+        chat_id: Option<&str>,
+        model: &str,
+        system_prompt: &str,
+        history: Vec<Message>,
+    ) -> (Vec<Message>, Vec<Message>, String) {
+        let chat = chat_id.and_then(|id| self.chat_list.chats.iter().find(|c| c.id == id));
+        let system_prompt = match chat.and_then(|chat| chat.journal.as_ref()) {
+            Some(state) => journal::prompt_with(system_prompt, state),
+            None => system_prompt.to_string(),
+        };
+        let chat_memory = chat
+            .filter(|_| self.auto_memory)
+            .and_then(|chat| chat.memory.as_ref());
+        let (history, system_prompt) = memory::apply(chat_memory, &system_prompt, history);
+        let (history, summarized) = context::trim(self.context_strategy, self.context_keep_last, model, &system_prompt, history);
+        (history, summarized, system_prompt)
+    }
+
+    // 对话足够长时在后台把较早的消息并入对话记忆
+    fn schedule_memory_update(&mut self, chat_id: &str) {
+        if self.memory_updating.contains(chat_id) {
+            return;
+        }
+        let Some(chat) = self.chat_list.chats.iter().find(|c| c.id == chat_id && !c.is_group()) else {
+            return;
+        };
+        let Some((covered, messages, previous)) = memory::pending(chat) else {
+            return;
+        };
+        debug!("更新对话记忆: {} 新增 {} 条消息", chat_id, messages.len());
+        let chat_messages = chat.messages.clone();
+        let effective = self.effective_config(Some(chat_id));
+        let target = self.effective_target(&effective);
+        let client = self.client.clone();
+        let sender = self.memory_sender.clone();
+        let key = chat_id.to_string();
+        self.memory_updating.insert(key.clone());
+        self.task_registry.spawn(&self.runtime_handle, "更新对话记忆", Some(key.clone()), async move {
+            let chat_memory = match context::summarize(&client, &target, &effective.model_name, previous.as_deref(), &messages).await {
+                Ok(text) => Some(memory::build(&chat_messages, covered, text)),
+                Err(e) => {
+                    error!("更新对话记忆失败: {}", e);
+                    None
+                }
+            };
+            let _ = sender.send((key, chat_memory));
+        });
+    }
+
+    fn receive_memory_updates(&mut self) {
+        let mut changed = false;
+        while let Ok((chat_id, chat_memory)) = self.memory_receiver.try_recv() {
+            self.memory_updating.remove(&chat_id);
+            let Some(chat_memory) = chat_memory else {
+                continue;
+            };
+            if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == chat_id) {
+                debug!("对话记忆已更新: {} 覆盖 {} 条消息", chat_id, chat_memory.covered);
+                chat.memory = Some(chat_memory);
+                changed = true;
+            }
+        }
+        if changed {
+            if let Err(e) = self.save_chat_list() {
+                error!("保存聊天列表失败: {}", e);
+            }
+        }
+    }
+
+    // 当前对话的记忆：查看内容或清除
+    fn display_chat_memory(&mut self, ui: &mut egui::Ui) {
+        if !self.auto_memory {
+            return;
+        }
+        let Some(current_id) = self.chat_list.current_chat_id.clone() else {
+            return;
+        };
+        let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == current_id) else {
+            return;
+        };
+        let Some(chat_memory) = &chat.memory else {
+            return;
+        };
+        let mut clear = false;
+        ui.menu_button("\u{f0eb}", |ui| {
+            ui.label(RichText::new(format!(
+                "较早的 {} 条消息已并入记忆（{}）",
+                chat_memory.covered,
+                chat_memory.updated_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")
+            )).small().color(egui::Color32::GRAY));
+            ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+                ui.set_max_width(360.0);
+                ui.label(&chat_memory.text);
+            });
+            if ui.button("清除记忆").on_hover_text("之后的请求重新发送全部消息，对话足够长时会重新生成").clicked() {
+                clear = true;
+                ui.close_menu();
+            }
+        }).response.on_hover_text("对话记忆");
+        if clear {
+            chat.memory = None;
+            if let Err(e) = self.save_chat_list() {
+                error!("保存聊天列表失败: {}", e);
+            }
+        }
+    }
+
+    // 输入框的 token 计数，加上历史和系统提示词接近或超出模型上下文窗口时提醒
+    fn display_token_count(&mut self, ui: &mut egui::Ui) {
+        let chat_id = self.chat_list.current_chat_id.clone();
+        let effective = self.effective_config(chat_id.as_deref());
+        let model = effective.model_name;
+        let mut draft = self.input_text.clone();
+        for attachment in &self.text_attachments {
+            draft.push('\n');
+            draft.push_str(&attachment.content);
+        }
+        self.token_counter.update_input(&model, &draft);
+        // 流式输出时历史每帧都在变，等回复结束后再重新计数
+        if !self.is_loading {
+            let history: Vec<Message> = models::context_messages(&self.chat_history.0).cloned().collect();
+            self.token_counter.update_context(&model, chat_id.as_deref(), &effective.system_prompt, &history);
+        }
+
+        let window = tokens::context_window(&model);
+        let total = self.token_counter.total();
+        let text = format!("{} tokens", tokens::format_count(self.token_counter.input));
+        let label = if total > window {
+            RichText::new(format!("\u{f071} {}", text)).color(egui::Color32::LIGHT_RED)
+        } else if total as f32 > window as f32 * tokens::WARN_RATIO {
+            RichText::new(format!("\u{f071} {}", text)).color(egui::Color32::from_rgb(255, 165, 0))
+        } else {
+            RichText::new(text).color(egui::Color32::GRAY)
+        };
+        let mut hover = format!(
+            "本条消息: {}\n上下文（含系统提示词）: {}\n合计: {} / {}（{}）",
+            self.token_counter.input,
+            self.token_counter.context,
+            total,
+            window,
+            model
+        );
+        if total > window {
+            if self.context_strategy == ContextStrategy::Off {
+                hover.push_str("\n已超出模型的上下文窗口，请求可能失败，可以在设置中开启上下文裁剪");
+            } else {
+                hover.push_str(&format!("\n已超出模型的上下文窗口，发送时将按“{}”处理较早的消息", self.context_strategy.label()));
+            }
+        }
+        ui.separator();
+        ui.label(label).on_hover_text(hover);
+    }
+
+    // 群聊成员开关：点击静音或恢复，回复进行中可以停止
+    fn display_group_members(&mut self, ui: &mut egui::Ui) {
+        let Some(current_id) = self.chat_list.current_chat_id.clone() else {
+            return;
+        };
+        let Some(chat) = self.chat_list.chats.iter().find(|c| c.id == current_id) else {
+            return;
+        };
+        let names: Vec<String> = chat
+            .group_members
+            .iter()
+            .map(|member| {
+                self.chat_list
+                    .chats
+                    .iter()
+                    .find(|role| role.id == member.role_id)
+                    .map_or("（已删除）".to_string(), |role| group_chat::speaker_name(&role.name))
+            })
+            .collect();
+        if names.is_empty() {
+            return;
+        }
+        let mut changed = false;
+        ui.label(RichText::new("\u{f0c0}").color(egui::Color32::GRAY));
+        if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == current_id) {
+            for (member, name) in chat.group_members.iter_mut().zip(&names) {
+                let hint = if member.muted { "已静音，点击恢复" } else { "点击静音" };
+                if ui.selectable_label(!member.muted, name).on_hover_text(hint).clicked() {
+                    member.muted = !member.muted;
+                    changed = true;
+                }
+            }
+        }
+        let running = self.group_turn.as_ref().is_some_and(|turn| turn.chat_id == current_id);
+        if running && ui.small_button("\u{f04d}").on_hover_text("停止群聊回复").clicked() {
+            self.task_registry.abort_chat(&current_id);
+            self.group_turn = None;
+        }
+        if changed {
+            if let Err(e) = self.save_chat_list() {
+                error!("保存聊天列表失败: {}", e);
+            }
+        }
+    }
+
+    // 群聊中发送消息：加入用户消息后，未静音的角色依次回复
+    fn send_group_message(&mut self, user_input: String, text_attachments: Vec<TextAttachment>) {
+        let Some(chat_id) = self.chat_list.current_chat_id.clone() else {
+            return;
+        };
+        if self.group_turn.is_some() {
+            debug!("上一轮群聊回复尚未完成");
+            self.input_text = user_input;
+            return;
+        }
+        let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == chat_id) else {
+            return;
+        };
+        let role_ids: Vec<String> = chat
+            .group_members
+            .iter()
+            .filter(|member| !member.muted)
+            .map(|member| member.role_id.clone())
+            .collect();
+        let mut message = Message::new_user(user_input, None);
+        message.text_attachments = text_attachments;
+        message.original_content = self.pending_edit_original.take();
+        self.chat_history.add_message(message);
+        chat.messages = self.chat_history.0.clone();
+        chat.update_time();
+        if let Err(e) = self.save_chat_list() {
+            error!("保存聊天列表失败: {}", e);
+        }
+        self.group_turn = Some(GroupTurn::new(chat_id, role_ids));
+    }
+
+    // 推进群聊的一轮：上一个角色回复完成后，请下一个角色发言
+    fn process_group_turn(&mut self, ctx: &egui::Context) {
+        let Some(mut turn) = self.group_turn.take() else {
+            return;
+        };
+        ctx.request_repaint_after(std::time::Duration::from_millis(16));
+        let is_current = self.chat_list.current_chat_id.as_ref() == Some(&turn.chat_id);
+
+        if let Some(reply) = turn.current.as_mut() {
+            if !reply.stream.poll() {
+                self.group_turn = Some(turn);
+                return;
+            }
+            let Some(GroupReply { name, stream }) = turn.current.take() else {
+                return;
+            };
+            let mut message = stream.reply;
+            if message.content.is_empty() {
+                message.kind = MessageKind::Error;
+                message.content = format!(
+                    "{} 回复失败: {}",
+                    name,
+                    stream.error.unwrap_or_else(|| "没有收到回复".to_string())
+                );
+            }
+            message.speaker = Some(name);
+            self.charge_reply(&mut message);
+            if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == turn.chat_id) {
+                chat.messages.push(message);
+                chat.update_time();
+                if is_current {
+                    self.chat_history.0 = chat.messages.clone();
+                }
+            }
+            if let Err(e) = self.save_chat_list() {
+                error!("保存聊天列表失败: {}", e);
+            }
+        }
+
+        let Some(role_id) = turn.pending.pop_front() else {
+            debug!("群聊本轮回复完成");
+            return;
+        };
+        let Some(chat) = self.chat_list.chats.iter().find(|c| c.id == turn.chat_id) else {
+            debug!("群聊已被删除，停止回复");
+            return;
+        };
+        let role_name = |id: &str| {
+            self.chat_list
+                .chats
+                .iter()
+                .find(|role| role.id == id)
+                .map(|role| group_chat::speaker_name(&role.name))
+        };
+        let Some(speaker) = role_name(&role_id) else {
+            // 角色已被删除，跳过
+            self.group_turn = Some(turn);
+            return;
+        };
+        let members: Vec<String> = chat
+            .group_members
+            .iter()
+            .filter_map(|member| role_name(&member.role_id))
+            .collect();
+        let history = group_chat::history_for(&chat.messages, &speaker);
+        let directives = vec![group_chat::directive(&speaker, &members)];
+        let question = history.iter().rev().find(|msg| msg.role == "user").cloned();
+        let retrieval = self.retrieval(&turn.chat_id);
+
+        let effective = self.effective_config(Some(&role_id));
+        let target = self.effective_target(&effective);
+        let (history, summarized) = context::trim(
+            self.context_strategy,
+            self.context_keep_last,
+            &effective.model_name,
+            &effective.system_prompt,
+            history,
+        );
+        let mut entries = Vec::new();
+        if !summarized.is_empty() {
+            entries.push(ManifestEntry::from_text("system", &context::summary_note(summarized.len())));
+        }
+        entries.extend(history.iter().map(ManifestEntry::from_message));
+        let manifest = RequestManifest {
+            sent_at: Utc::now(),
+            provider: effective.provider,
+            model: effective.model_name.clone(),
+            system_prompt: effective.system_prompt.clone(),
+            params: effective.params,
+            entries,
+            overrides: None,
+        };
+        debug!("群聊中由 {} 回复", speaker);
+
+        let client = self.client.clone();
+        let retry_policy = self.retry_policy();
+        let endpoint_health = self.endpoint_health.clone();
+        let metrics = self.metrics.clone();
+        let reasoning_effort = self.reasoning_effort;
+        let (tx, rx) = mpsc::unbounded_channel();
+        // 每个成员看到的历史不同，摘要分开缓存
+        let context_summaries = self.context_summaries.clone();
+        let summary_key = format!("{}:{}", turn.chat_id, role_id);
+        let task_chat_id = Some(turn.chat_id.clone());
+        self.task_registry.spawn(&self.runtime_handle, "群聊回复", task_chat_id, async move {
+            let system_prompt = context_summaries
+                .summarized_prompt(&client, &target, &effective.model_name, &summary_key, &effective.system_prompt, &summarized)
+                .await;
+            let mut contexts = match &question {
+                Some(question) => retrieval.retrieve(&client, &target, question, &tx).await,
+                None => Vec::new(),
+            };
+            contexts.extend(directives);
+            let messages = reply_stream::build_messages(&system_prompt, None, &history, &contexts).await;
+            let payload = reply_stream::stream_payload(&effective.model_name, messages, effective.params, reasoning_effort);
+            let started = Instant::now();
+            let result = api::send_request(&client, &target, &payload, retry_policy, &endpoint_health, &tx).await;
+            metrics.finish_request(started, result.is_ok()).await;
+            if let Err(e) = result {
+                error!("群聊回复失败: {:?}", e);
+                let _ = tx.send(UiEvent::Error(e.to_string()));
+                let _ = tx.send(UiEvent::Done);
+            }
+        });
+        turn.current = Some(GroupReply {
+            name: speaker,
+            stream: ReplyStream::new(manifest, rx),
+        });
+        self.group_turn = Some(turn);
+    }
+
+    // 对比模式下选择参与对比的模型
+    fn display_compare_models(&mut self, ui: &mut egui::Ui) {
+        let profile = self.effective_config(self.chat_list.current_chat_id.as_deref()).profile;
+        let choices = self.model_choices(profile.as_deref());
+        let Some(selected) = self.compare_models.as_mut() else {
+            return;
+        };
+        ui.menu_button(format!("{}/{} 个模型", selected.len(), compare::MAX_MODELS), |ui| {
+            for choice in &choices {
+                let mut checked = selected.contains(choice);
+                let enabled = checked || selected.len() < compare::MAX_MODELS;
+                if ui.add_enabled(enabled, egui::Checkbox::new(&mut checked, choice)).changed() {
+                    if checked {
+                        selected.push(choice.clone());
+                    } else {
+                        selected.retain(|model| model != choice);
+                    }
+                }
+            }
+        });
+    }
+
+    // 把同一条消息并发发送给选中的每个模型，各自的回复在对比窗口中显示
+    fn start_comparison(&mut self, prompt: String) {
+        let Some(models) = self.compare_models.clone() else {
+            return;
+        };
+        if prompt.trim().is_empty()
+            || models.len() < compare::MIN_MODELS
+            || self.comparison.as_ref().is_some_and(Comparison::is_running) {
+            self.input_text = prompt;
+            return;
+        }
+        if self.chat_list.current_chat_id.is_none() {
+            self.new_chat();
+        }
+        let Some(chat_id) = self.chat_list.current_chat_id.clone() else {
+            return;
+        };
+        let user_message = Message::new_user(prompt, None);
+        let mut history: Vec<Message> = models::context_messages(&self.chat_history.0).cloned().collect();
+        history.push(user_message.clone());
+        let greeting_context = self
+            .current_chat_config()
+            .filter(|config| config.greeting_in_context)
+            .and_then(|config| config.greeting_text())
+            .map(str::to_string);
+        let directives: Vec<String> = self
+            .match_reply_language
+            .then(|| language::reply_directive(&user_message.content))
+            .into_iter()
+            .collect();
+        let base = self.effective_config(Some(&chat_id));
+        let retrieval = self.retrieval(&chat_id);
+        debug!("对比 {} 个模型: {:?}", models.len(), models);
+
+        let mut comparison = Comparison::new(chat_id.clone(), user_message);
+        for model in models {
+            let mut effective = base.clone();
+            effective.model_name = model.clone();
+            let target = self.effective_target(&effective);
+            let (history, summarized, system_prompt) = self.prepare_history(
+                Some(&chat_id),
+                &model,
+                &effective.system_prompt,
+                history.clone(),
+            );
+            let mut entries = Vec::new();
+            if !summarized.is_empty() {
+                entries.push(ManifestEntry::from_text("system", &context::summary_note(summarized.len())));
+            }
+            if let Some(greeting) = &greeting_context {
+                entries.push(ManifestEntry::from_text("assistant", greeting));
+            }
+            entries.extend(history.iter().map(ManifestEntry::from_message));
+            let manifest = RequestManifest {
+                sent_at: Utc::now(),
+                provider: effective.provider,
+                model: model.clone(),
+                system_prompt: system_prompt.clone(),
+                params: effective.params,
+                entries,
+                overrides: None,
+            };
+
+            let client = self.client.clone();
+            let retry_policy = self.retry_policy();
+            let endpoint_health = self.endpoint_health.clone();
+            let metrics = self.metrics.clone();
+            let reasoning_effort = self.reasoning_effort;
+            let greeting_context = greeting_context.clone();
+            let directives = directives.clone();
+            let retrieval = retrieval.clone();
+            let question = comparison.prompt.clone();
+            let context_summaries = self.context_summaries.clone();
+            let summary_key = chat_id.clone();
+            let (tx, rx) = mpsc::unbounded_channel();
+            let handle = self.runtime_handle.spawn(async move {
+                let system_prompt = context_summaries
+                    .summarized_prompt(&client, &target, &effective.model_name, &summary_key, &system_prompt, &summarized)
+                    .await;
+                let mut contexts = retrieval.retrieve(&client, &target, &question, &tx).await;
+                contexts.extend(directives);
+                let messages = reply_stream::build_messages(&system_prompt, greeting_context.as_deref(), &history, &contexts).await;
+                let payload = reply_stream::stream_payload(&effective.model_name, messages, effective.params, reasoning_effort);
+                let started = Instant::now();
+                let result = api::send_request(&client, &target, &payload, retry_policy, &endpoint_health, &tx).await;
+                metrics.finish_request(started, result.is_ok()).await;
+                if let Err(e) = result {
+                    error!("模型对比请求失败 ({}): {:?}", effective.model_name, e);
+                    let _ = tx.send(UiEvent::Error(e.to_string()));
+                    let _ = tx.send(UiEvent::Done);
+                }
+            });
+            let task_id = self.task_registry.track("模型对比", Some(chat_id.clone()), handle.abort_handle());
+            comparison.columns.push(CompareColumn {
+                model,
+                stream: ReplyStream::new(manifest, rx),
+                task_id,
+                done: false,
+            });
+        }
+        self.comparison = Some(comparison);
+    }
+
+    // 接收对比中各模型的回复，每条回复完成时单独计费
+    fn poll_comparison(&mut self, ctx: &egui::Context) {
+        let Some(mut comparison) = self.comparison.take() else {
+            return;
+        };
+        if comparison.is_running() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(16));
+        }
+        for index in comparison.poll() {
+            self.charge_reply(&mut comparison.columns[index].stream.reply);
+        }
+        self.comparison = Some(comparison);
+    }
+
+    // 对比窗口：各模型的回复并排显示，采用其中一条后连同用户消息写入对话
+    fn display_comparison(&mut self, ctx: &egui::Context) {
+        let Some(comparison) = self.comparison.take() else {
+            return;
+        };
+        let mut open = true;
+        let mut adopted = None;
+        egui::Window::new("模型对比")
+            .open(&mut open)
+            .default_size([900.0, 520.0])
+            .show(ctx, |ui| {
+                let prompt: String = comparison.prompt.content.chars().take(120).collect();
+                ui.label(RichText::new(prompt).color(egui::Color32::GRAY));
+                ui.separator();
+                ui.columns(comparison.columns.len(), |columns| {
+                    for (index, (ui, column)) in columns.iter_mut().zip(&comparison.columns).enumerate() {
+                        let reply = &column.stream.reply;
+                        ui.horizontal(|ui| {
+                            ui.strong(&column.model);
+                            if !column.done {
+                                ui.spinner();
+                            }
+                        });
+                        if let Some(usage) = &reply.usage {
+                            ui.label(RichText::new(format!(
+                                "输入 {} · 输出 {} tokens",
+                                usage.prompt_tokens, usage.completion_tokens
+                            ))
+                            .small()
+                            .color(egui::Color32::GRAY));
+                        }
+                        if let Some(error) = &column.stream.error {
+                            ui.label(RichText::new(error).small().color(egui::Color32::RED));
+                        }
+                        ScrollArea::vertical()
+                            .id_salt(("compare_column", index))
+                            .max_height(ui.available_height() - 32.0)
+                            .stick_to_bottom(!column.done)
+                            .show(ui, |ui| {
+                                self.render_markdown(ui, &reply.content);
+                            });
+                        let ready = column.done && !reply.content.is_empty();
+                        if ui.add_enabled(ready, egui::Button::new("采用此回复")).clicked() {
+                            adopted = Some(index);
+                        }
+                    }
+                });
+            });
+        if let Some(index) = adopted {
+            self.adopt_comparison(comparison, index);
+        } else if open {
+            self.comparison = Some(comparison);
+        } else {
+            for column in comparison.columns.iter().filter(|column| !column.done) {
+                self.task_registry.abort(column.task_id);
+            }
+        }
+    }
+
+    // 把对比中选中的回复连同用户消息写入发起对比的对话，其余回复丢弃
+    fn adopt_comparison(&mut self, comparison: Comparison, index: usize) {
+        let Comparison { chat_id, prompt, mut columns } = comparison;
+        for column in columns.iter().filter(|column| !column.done) {
+            self.task_registry.abort(column.task_id);
+        }
+        let reply = columns.swap_remove(index).stream.reply;
+        debug!("采用对比回复: {:?}", reply.request_manifest.as_ref().map(|manifest| &manifest.model));
+        let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == chat_id) else {
+            return;
+        };
+        chat.messages.push(prompt);
+        chat.messages.push(reply);
+        chat.update_time();
+        if self.chat_list.current_chat_id.as_ref() == Some(&chat_id) {
+            self.chat_history.0 = chat.messages.clone();
+        }
+        if let Err(e) = self.save_chat_list() {
+            error!("保存聊天列表失败: {}", e);
+        }
+    }
+
+    // 按请求清单中的模型计算单独接收的回复的费用
+    fn charge_reply(&mut self, reply: &mut Message) {
+        let Some(model) = reply.request_manifest.as_ref().map(|manifest| manifest.model.clone()) else {
+            return;
+        };
+        let (Some(usage), Some(price)) = (reply.usage, costs::price_for(&self.pricing, &model)) else {
+            return;
+        };
+        let cost = price.cost(usage);
+        reply.cost = Some(cost);
+        if self.current_chat_is_incognito() {
+            return;
+        }
+        self.cost_ledger.add(cost);
+        if self.safe_mode {
+            return;
+        }
+        if let Err(e) = self.runtime_handle.block_on(costs::save_ledger(&self.cost_ledger)) {
+            error!("保存费用记录失败: {}", e);
+        }
+    }
+
+    // 重试完成后用新回复替换原来的消息，失败时保留原回复
+    fn poll_turn_retry(&mut self, ctx: &egui::Context) {
+        let Some(retry) = self.turn_retry.as_mut() else {
+            return;
+        };
+        ctx.request_repaint_after(std::time::Duration::from_millis(16));
+        if !retry.stream.poll() {
+            return;
+        }
+        let Some(TurnRetry { chat_id, index, stream }) = self.turn_retry.take() else {
+            return;
+        };
+        let mut reply = stream.reply;
+        if reply.content.is_empty() {
+            error!("重试回复失败: {}", stream.error.unwrap_or_else(|| "没有收到回复".to_string()));
+            return;
+        }
+        self.charge_reply(&mut reply);
+
+        let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == chat_id) else {
+            return;
+        };
+        if self.chat_list.current_chat_id.as_ref() == Some(&chat_id) {
+            if let Some(msg) = self.chat_history.0.get_mut(index).filter(|msg| msg.role == "assistant") {
+                msg.replace_reply(reply);
+            }
+            chat.messages = self.chat_history.0.clone();
+        } else if let Some(msg) = chat.messages.get_mut(index).filter(|msg| msg.role == "assistant") {
+            msg.replace_reply(reply);
+        }
+        chat.update_time();
+        if let Err(e) = self.save_chat_list() {
+            error!("保存聊天列表失败: {}", e);
+        }
+    }
+
+    // 选择重放使用的模型
+    fn display_replay_setup(&mut self, ctx: &egui::Context) {
+        let Some(source_id) = self.replay_setup.as_ref().map(|(id, _)| id.clone()) else {
+            return;
+        };
+        let Some(source) = self.chat_list.chats.iter().find(|c| c.id == source_id) else {
+            self.replay_setup = None;
+            return;
+        };
+        let source_name = source.name.clone();
+        let turns = source.messages.iter().filter(|msg| msg.role == "user").count();
+        let models = self.model_choices(self.effective_config(Some(&source_id)).profile.as_deref());
+        let replay_running = self.replay.is_some();
+        let Some((_, model)) = self.replay_setup.as_mut() else {
+            return;
+        };
+        let mut start = false;
+        let mut close = false;
+        egui::Window::new("用其他模型重放")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.label(format!("将「{}」中的 {} 条用户消息逐条发送给新模型，回复保存到新对话", source_name, turns));
+                ui.add_space(4.0);
+                ui.horizontal(|ui| {
+                    ui.label("模型:");
+                    ui.add(TextEdit::singleline(model).desired_width(160.0));
+                    egui::ComboBox::from_id_salt("replay_model")
+                        .selected_text("")
+                        .width(20.0)
+                        .show_ui(ui, |ui| {
+                            for choice in &models {
+                                ui.selectable_value(model, choice.clone(), choice);
+                            }
+                        });
+                });
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    let ready = turns > 0 && !model.trim().is_empty() && !replay_running;
+                    if ui.add_enabled(ready, egui::Button::new("开始")).clicked() {
+                        start = true;
+                    }
+                    if ui.button("取消").clicked() {
+                        close = true;
+                    }
+                });
+                if replay_running {
+                    ui.label(RichText::new("已有正在进行的重放").small().color(egui::Color32::GRAY));
+                }
+            });
+        if start {
+            if let Some((source_id, model)) = self.replay_setup.take() {
+                self.start_replay(&source_id, model.trim().to_string());
+            }
+        } else if close {
+            self.replay_setup = None;
+        }
+    }
+
+    // 新建对话并沿用原对话的配置，只替换模型
+    fn start_replay(&mut self, source_id: &str, model: String) {
+        let Some(source) = self.chat_list.chats.iter().find(|c| c.id == source_id) else {
+            return;
+        };
+        let effective = self.effective_config(Some(source_id));
+        let mut config = source.config.clone().unwrap_or_else(|| ChatConfig {
+            model_name: String::new(),
+            system_prompt: effective.system_prompt.clone(),
+            temperature: effective.params.temperature,
+            top_p: effective.params.top_p,
+            frequency_penalty: effective.params.frequency_penalty,
+            presence_penalty: effective.params.presence_penalty,
+            greeting: None,
+            greeting_in_context: false,
+            provider: Some(effective.provider),
+            filter_mode: None,
+            filter_terms: Vec::new(),
+            profile: effective.profile.clone(),
+        });
+        config.model_name = model.clone();
+
+        let mut new_chat = Chat::new(format!("{} ({})", source.name, model));
+        new_chat.has_been_renamed = true;
+        new_chat.config = Some(config);
+        new_chat.role_id = source.role_id.clone();
+        new_chat.tags = source.tags.clone();
+        debug!("开始用 {} 重放对话: {}", model, source.name);
+        let replay = Replay::new(source.name.clone(), new_chat.id.clone(), model, &source.messages);
+
+        self.chat_list.chats.insert(0, new_chat);
+        self.replay = Some(replay);
+        if let Err(e) = self.save_chat_list() {
+            error!("保存聊天列表失败: {}", e);
+        }
+    }
+
+    // 每帧推进重放：上一轮回复完成后再发送下一条用户消息
+    fn process_replay(&mut self, ctx: &egui::Context) {
+        let Some(mut replay) = self.replay.take() else {
+            return;
+        };
+        ctx.request_repaint_after(std::time::Duration::from_millis(16));
+        let is_current = self.chat_list.current_chat_id.as_ref() == Some(&replay.chat_id);
+
+        if let Some(stream) = replay.stream.as_mut() {
+            if !stream.poll() {
+                self.replay = Some(replay);
+                return;
+            }
+            let Some(stream) = replay.stream.take() else {
+                return;
+            };
+            let mut reply = stream.reply;
+            if reply.content.is_empty() {
+                replay.failed += 1;
+                reply.kind = MessageKind::Error;
+                reply.content = stream.error.unwrap_or_else(|| "没有收到回复".to_string());
+            }
+            self.charge_reply(&mut reply);
+            if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == replay.chat_id) {
+                chat.messages.push(reply);
+                chat.update_time();
+                if is_current {
+                    self.chat_history.0 = chat.messages.clone();
+                }
+            }
+            if let Err(e) = self.save_chat_list() {
+                error!("保存聊天列表失败: {}", e);
+            }
+        }
+
+        let Some(turn) = replay.turns.pop_front() else {
+            debug!("重放完成: {} 轮，{} 轮失败", replay.total, replay.failed);
+            return;
+        };
+        let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == replay.chat_id) else {
+            debug!("重放的对话已被删除，停止重放");
+            return;
+        };
+        chat.messages.push(turn.clone());
+        if is_current {
+            self.chat_history.0 = chat.messages.clone();
+        }
+        let history: Vec<Message> = models::context_messages(&chat.messages).cloned().collect();
+
+        let effective = self.effective_config(Some(&replay.chat_id));
+        let target = self.effective_target(&effective);
+        let greeting_context = self
+            .chat_list
+            .chats
+            .iter()
+            .find(|c| c.id == replay.chat_id)
+            .and_then(|chat| chat.config.as_ref())
+            .filter(|config| config.greeting_in_context)
+            .and_then(|config| config.greeting_text())
+            .map(str::to_string);
+        let directives: Vec<String> = self
+            .match_reply_language
+            .then(|| language::reply_directive(&turn.content))
+            .into_iter()
+            .collect();
+        let retrieval = self.retrieval(&replay.chat_id);
+        let (history, summarized, system_prompt) = self.prepare_history(
+            Some(&replay.chat_id),
+            &effective.model_name,
+            &effective.system_prompt,
+            history,
+        );
+
+        let mut entries = Vec::new();
+        if !summarized.is_empty() {
+            entries.push(ManifestEntry::from_text("system", &context::summary_note(summarized.len())));
+        }
+        if let Some(greeting) = &greeting_context {
+            entries.push(ManifestEntry::from_text("assistant", greeting));
+        }
+        entries.extend(history.iter().map(ManifestEntry::from_message));
+        let manifest = RequestManifest {
+            sent_at: Utc::now(),
+            provider: effective.provider,
+            model: effective.model_name.clone(),
+            system_prompt: system_prompt.clone(),
+            params: effective.params,
+            entries,
+            overrides: None,
+        };
+
+        let client = self.client.clone();
+        let retry_policy = self.retry_policy();
+        let endpoint_health = self.endpoint_health.clone();
+        let metrics = self.metrics.clone();
+        let reasoning_effort = self.reasoning_effort;
+        let (tx, rx) = mpsc::unbounded_channel();
+        let context_summaries = self.context_summaries.clone();
+        let summary_key = replay.chat_id.clone();
+        let task_chat_id = Some(replay.chat_id.clone());
+        self.task_registry.spawn(&self.runtime_handle, "重放对话", task_chat_id, async move {
+            let system_prompt = context_summaries
+                .summarized_prompt(&client, &target, &effective.model_name, &summary_key, &system_prompt, &summarized)
+                .await;
+            let mut contexts = retrieval.retrieve(&client, &target, &turn, &tx).await;
+            contexts.extend(directives);
+            let messages = reply_stream::build_messages(&system_prompt, greeting_context.as_deref(), &history, &contexts).await;
+            let payload = reply_stream::stream_payload(&effective.model_name, messages, effective.params, reasoning_effort);
+            let started = Instant::now();
+            let result = api::send_request(&client, &target, &payload, retry_policy, &endpoint_health, &tx).await;
+            metrics.finish_request(started, result.is_ok()).await;
+            if let Err(e) = result {
+                error!("重放对话失败: {:?}", e);
+                let _ = tx.send(UiEvent::Error(e.to_string()));
+                let _ = tx.send(UiEvent::Done);
+            }
+        });
+        replay.stream = Some(ReplyStream::new(manifest, rx));
+        self.replay = Some(replay);
+    }
+
+    // 重放进度窗口，显示正在接收的回复
+    fn display_replay_progress(&mut self, ctx: &egui::Context) {
+        let Some(replay) = &self.replay else {
+            return;
+        };
+        let mut stop = false;
+        let mut open_chat = false;
+        egui::Window::new("重放对话")
+            .collapsible(true)
+            .resizable(false)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                ui.label(format!("「{}」→ {}", replay.source_name, replay.model));
+                let done = replay.completed();
+                ui.add(egui::ProgressBar::new(done as f32 / replay.total.max(1) as f32)
+                    .text(format!("{}/{}", done, replay.total)));
+                if let Some(stream) = &replay.stream {
+                    ScrollArea::vertical()
+                        .max_height(160.0)
+                        .stick_to_bottom(true)
+                        .show(ui, |ui| {
+                            ui.label(RichText::new(&stream.reply.content).color(egui::Color32::GRAY));
+                        });
+                }
+                if replay.failed > 0 {
+                    ui.label(RichText::new(format!("{} 轮失败，详见对话", replay.failed)).color(egui::Color32::RED));
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("打开对话").clicked() {
+                        open_chat = true;
+                    }
+                    if ui.button("停止").clicked() {
+                        stop = true;
+                    }
+                });
+            });
+        if open_chat {
+            let chat_id = replay.chat_id.clone();
+            self.request_chat_switch(ChatSwitch::Open(chat_id));
+        }
+        if stop {
+            if let Some(replay) = self.replay.take() {
+                debug!("停止重放: {}", replay.chat_id);
+                self.task_registry.abort_chat(&replay.chat_id);
+            }
+        }
+    }
+
+    // 在后台读取文件夹并嵌入所有文本块
+    fn index_knowledge(&mut self, chat_id: String, folder: PathBuf) {
+        if self.knowledge_indexing.is_some() {
+            debug!("已有正在建立的知识库索引，忽略: {}", folder.display());
+            return;
+        }
+        debug!("开始建立知识库索引: {}", folder.display());
+        self.knowledge_indexing = Some(chat_id.clone());
+        let client = self.client.clone();
+        let target = self.effective_target(&self.effective_config(Some(&chat_id)));
+        let model = self.embedding_model.clone();
+        let sender = self.knowledge_sender.clone();
+        self.task_registry.spawn(&self.runtime_handle, "建立知识库索引", None, async move {
+            let result = knowledge::build_index(&client, &target, &model, &chat_id, &folder).await;
+            let _ = sender.send((chat_id, folder, result));
+        });
+    }
+
+    // 当前对话关联文件夹中的路径，用于输入框的路径补全；没有加载或已过期时在后台重新列出
+    fn workspace_paths(&mut self) -> Option<&[String]> {
+        const REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+        let current_id = self.chat_list.current_chat_id.as_ref()?;
+        let folder = self
+            .chat_list
+            .chats
+            .iter()
+            .find(|c| &c.id == current_id)
+            .and_then(|chat| chat.knowledge_folder.clone())?;
+        let fresh = self
+            .workspace_paths
+            .as_ref()
+            .is_some_and(|(loaded, at, _)| loaded == &folder && at.elapsed() < REFRESH_INTERVAL);
+        if !fresh && self.workspace_loading.as_ref() != Some(&folder) {
+            self.workspace_loading = Some(folder.clone());
+            let sender = self.workspace_sender.clone();
+            let folder = folder.clone();
+            self.task_registry.spawn(&self.runtime_handle, "列出文件夹路径", None, async move {
+                let result = path_complete::list_paths(Path::new(&folder)).await.map_err(|e| e.to_string());
+                let _ = sender.send((folder, result));
+            });
+        }
+        self.workspace_paths
+            .as_ref()
+            .filter(|(loaded, _, _)| loaded == &folder)
+            .map(|(_, _, paths)| paths.as_slice())
+    }
+
+    fn receive_workspace_paths(&mut self) {
+        while let Ok((folder, result)) = self.workspace_receiver.try_recv() {
+            if self.workspace_loading.as_ref() == Some(&folder) {
+                self.workspace_loading = None;
+            }
+            match result {
+                Ok(paths) => {
+                    debug!("已列出 {} 中的 {} 个路径", folder, paths.len());
+                    self.workspace_paths = Some((folder, Instant::now(), paths));
+                }
+                Err(e) => error!("列出文件夹 {} 失败: {}", folder, e),
+            }
+        }
+    }
+
+    fn receive_knowledge_index(&mut self) {
+        while let Ok((chat_id, folder, result)) = self.knowledge_receiver.try_recv() {
+            self.knowledge_indexing = None;
+            match result {
+                Ok(count) => {
+                    debug!("知识库索引完成: {} 个文本块", count);
+                    if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == chat_id) {
+                        chat.knowledge_folder = Some(folder.to_string_lossy().to_string());
+                    }
+                    if let Err(e) = self.save_chat_list() {
+                        error!("保存聊天列表失败: {}", e);
+                    }
+                }
+                Err(e) => error!("建立知识库索引失败: {}", e),
+            }
+        }
+    }
+
+    // “记住”一条消息：在后台提炼成一条事实并嵌入
+    fn remember_message(&mut self, text: String) {
+        let chat_id = self.chat_list.current_chat_id.clone();
+        let effective = self.effective_config(chat_id.as_deref());
+        let target = self.effective_target(&effective);
+        let client = self.client.clone();
+        let embedding_model = self.embedding_model.clone();
+        let sender = self.long_memory_sender.clone();
+        self.memory_pending += 1;
+        self.task_registry.spawn(&self.runtime_handle, "提炼记忆", chat_id.clone(), async move {
+            let event = match memory_store::remember(&client, &target, &effective.model_name, &embedding_model, &text, chat_id).await {
+                Ok(entry) => MemoryEvent::Added(entry),
+                Err(e) => MemoryEvent::Failed(e),
+            };
+            let _ = sender.send(event);
+        });
+    }
+
+    // 用当前的嵌入模型重新生成记忆的向量
+    fn embed_memory(&mut self, id: String, text: String) {
+        let target = self.effective_target(&self.global_effective_config());
+        let client = self.client.clone();
+        let model = self.embedding_model.clone();
+        let sender = self.long_memory_sender.clone();
+        self.memory_pending += 1;
+        self.task_registry.spawn(&self.runtime_handle, "嵌入记忆", None, async move {
+            let event = match memory_store::embed_entry(&client, &target, &model, &text).await {
+                Ok(vector) => MemoryEvent::Embedded { id, model, vector },
+                Err(e) => MemoryEvent::Failed(format!("记忆嵌入失败: {}", e)),
+            };
+            let _ = sender.send(event);
+        });
+    }
+
+    fn receive_memories(&mut self) {
+        let mut changed = false;
+        while let Ok(event) = self.long_memory_receiver.try_recv() {
+            self.memory_pending = self.memory_pending.saturating_sub(1);
+            match event {
+                MemoryEvent::Added(entry) => {
+                    debug!("已记住: {}", entry.text);
+                    self.notifications.push(format!("已记住: {}", entry.text));
+                    self.memory_store.entries.push(entry);
+                    changed = true;
+                }
+                MemoryEvent::Embedded { id, model, vector } => {
+                    if let Some(entry) = self.memory_store.entries.iter_mut().find(|entry| entry.id == id) {
+                        entry.model = model;
+                        entry.vector = vector;
+                        changed = true;
+                    }
+                }
+                MemoryEvent::Failed(e) => {
+                    error!("{}", e);
+                    self.notifications.push(e);
+                }
+            }
+        }
+        if changed {
+            self.save_memories();
+        }
+    }
+
+    fn save_memories(&self) {
+        if self.safe_mode {
+            return;
+        }
+        if let Err(e) = self.runtime_handle.block_on(memory_store::save(&self.memory_store)) {
+            error!("{}", e);
+        }
+    }
+
+    // 长期记忆管理窗口：查看、编辑和删除记忆
+    fn display_memory_manager(&mut self, ctx: &egui::Context) {
+        if !self.show_memory_manager {
+            return;
+        }
+        let mut open = true;
+        let mut to_remove = None;
+        let mut to_save = None;
+        let mut to_embed = Vec::new();
+        egui::Window::new("长期记忆")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(420.0)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                if self.memory_store.entries.is_empty() {
+                    ui.label("还没有记忆。点击消息下方的 \u{f02e} 即可记住其中的信息。");
+                }
+                let stale: Vec<_> = self
+                    .memory_store
+                    .entries
+                    .iter()
+                    .filter(|entry| !entry.is_embedded(&self.embedding_model))
+                    .map(|entry| (entry.id.clone(), entry.text.clone()))
+                    .collect();
+                if !stale.is_empty() {
+                    ui.horizontal_wrapped(|ui| {
+                        ui.label(RichText::new(format!("{} 条记忆没有当前嵌入模型的向量，不会被检索", stale.len()))
+                            .color(egui::Color32::from_rgb(230, 160, 0)));
+                        if ui.add_enabled(self.memory_pending == 0, egui::Button::new("重新嵌入")).clicked() {
+                            to_embed = stale;
+                        }
+                    });
+                }
+                if self.memory_pending > 0 {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label(RichText::new("正在处理记忆...").italics().color(egui::Color32::GRAY));
+                    });
+                }
+                ui.separator();
+
+                ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    for entry in &self.memory_store.entries {
+                        ui.group(|ui| {
+                            ui.set_min_width(ui.available_width());
+                            match &mut self.memory_editing {
+                                Some((id, text)) if id == &entry.id => {
+                                    ui.add(TextEdit::multiline(text).desired_rows(2).desired_width(f32::INFINITY));
+                                    ui.horizontal(|ui| {
+                                        if ui.button("保存").clicked() && !text.trim().is_empty() {
+                                            to_save = Some((id.clone(), text.trim().to_string()));
+                                        }
+                                        if ui.button("取消").clicked() {
+                                            to_save = Some((id.clone(), entry.text.clone()));
+                                        }
+                                    });
+                                }
+                                _ => {
+                                    ui.label(&entry.text);
+                                    ui.horizontal(|ui| {
+                                        let source = entry
+                                            .source_chat
+                                            .as_ref()
+                                            .and_then(|id| self.chat_list.chats.iter().find(|chat| &chat.id == id))
+                                            .map_or(String::new(), |chat| format!(" · 来自 {}", chat.name));
+                                        ui.label(RichText::new(format!(
+                                            "{}{}",
+                                            entry.created_at.with_timezone(&chrono::Local).format("%Y-%m-%d"),
+                                            source
+                                        ))
+                                        .small()
+                                        .color(egui::Color32::GRAY));
+                                        if ui.small_button("\u{f044}").on_hover_text("编辑").clicked() {
+                                            self.memory_editing = Some((entry.id.clone(), entry.text.clone()));
+                                        }
+                                        if ui.small_button("\u{f1f8}").on_hover_text("删除").clicked() {
+                                            to_remove = Some(entry.id.clone());
+                                        }
+                                    });
+                                }
+                            }
+                        });
+                    }
+                });
+            });
+        self.show_memory_manager = open;
+
+        if let Some(id) = to_remove {
+            self.memory_store.entries.retain(|entry| entry.id != id);
+            self.save_memories();
+        }
+        if let Some((id, text)) = to_save {
+            self.memory_editing = None;
+            if let Some(entry) = self.memory_store.entries.iter_mut().find(|entry| entry.id == id) {
+                if entry.text != text {
+                    // 文字变了，旧向量不再可用
+                    entry.text = text.clone();
+                    entry.vector.clear();
+                    self.save_memories();
+                    self.embed_memory(id, text);
+                }
+            }
+        }
+        for (id, text) in to_embed {
+            self.embed_memory(id, text);
+        }
+    }
+
+    // 处理 /image 命令：记录用户消息，在后台生成图片
+    fn generate_image(&mut self, prompt: String) {
+        if self.chat_list.current_chat_id.is_none() {
+            self.new_chat();
+        }
+        let Some(chat_id) = self.chat_list.current_chat_id.clone() else {
+            return;
+        };
+        self.chat_history.add_message(Message::new_user(format!("{} {}", imagegen::COMMAND, prompt), None));
+        if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == chat_id) {
+            chat.messages = self.chat_history.0.clone();
+            chat.update_time();
+        }
+        if let Err(e) = self.save_chat_list() {
+            error!("保存聊天列表失败: {}", e);
+        }
+
+        self.image_pending = Some(chat_id.clone());
+        let client = self.client.clone();
+        let target = self.effective_target(&self.effective_config(Some(&chat_id)));
+        let model = self.image_model.clone();
+        let sender = self.image_sender.clone();
+        let cache_dir = self.image_cache_dir();
+        let task_chat_id = Some(chat_id.clone());
+        self.task_registry.spawn(&self.runtime_handle, "生成图片", task_chat_id, async move {
+            let result = imagegen::generate(&client, &target, &model, &prompt, &cache_dir).await;
+            let _ = sender.send((chat_id, prompt, result));
+        });
+    }
+
+    // 将生成的图片作为助手消息加入对应的对话
+    fn receive_generated_image(&mut self) {
+        while let Ok((chat_id, prompt, result)) = self.image_receiver.try_recv() {
+            if self.image_pending.as_ref() == Some(&chat_id) {
+                self.image_pending = None;
+            }
+            let reply = match result {
+                Ok(path) => {
+                    let mut reply = Message::new_assistant(format!("已生成图片：{}", prompt));
+                    reply.image_path = Some(path.to_string_lossy().to_string());
+                    reply
+                }
+                Err(e) => {
+                    error!("{}", e);
+                    Message::new_event(MessageKind::Error, e)
+                }
+            };
+            let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == chat_id) else {
+                continue;
+            };
+            if self.chat_list.current_chat_id.as_ref() == Some(&chat_id) {
+                self.chat_history.add_message(reply);
+                chat.messages = self.chat_history.0.clone();
+            } else {
+                chat.messages.push(reply);
+            }
+            if let Err(e) = self.save_chat_list() {
+                error!("保存聊天列表失败: {}", e);
+            }
+        }
+    }
+
+    // 在后台让模型从对话中提取任务清单
+    fn extract_tasks(&mut self, chat_id: &str) {
+        let Some(chat) = self.chat_list.chats.iter().find(|c| c.id == chat_id) else {
+            return;
+        };
+        if chat.messages.is_empty() {
+            return;
+        }
+
+        let effective = self.effective_config(Some(chat_id));
+        let payload = checklist::build_payload(&effective.model_name, &chat.messages);
+        let client = self.client.clone();
+        let target = self.effective_target(&effective);
+        let chat_id = chat_id.to_string();
+        let sender = self.task_sender.clone();
+
+        debug!("开始提取任务清单: {}", chat_id);
+        let task_chat_id = Some(chat_id.clone());
+        self.task_registry.spawn(&self.runtime_handle, "提取任务清单", task_chat_id, async move {
+            match api::complete(&client, &target, &payload).await {
+                Ok(content) => {
+                    let tasks = checklist::parse_tasks(&content);
+                    debug!("提取到 {} 个任务", tasks.len());
+                    let _ = sender.send((chat_id, tasks));
+                }
+                Err(e) => error!("提取任务清单失败: {}", e),
+            }
+        });
+    }
+
+    // 在后台为最新回复生成后续问题建议，失败时从回复中提取
+    fn suggest_replies(&mut self, chat_id: &str) {
+        let Some(answer) = self
+            .chat_history
+            .0
+            .last()
+            .filter(|msg| msg.is_reply())
+            .map(|msg| msg.content.clone())
+        else {
+            return;
+        };
+
+        let payload = suggestions::build_payload(&self.suggestion_model, &self.chat_history.0);
+        let client = self.client.clone();
+        let target = self.effective_target(&self.global_effective_config());
+        let chat_id = chat_id.to_string();
+        let sender = self.suggestion_sender.clone();
+
+        let task_chat_id = Some(chat_id.clone());
+        self.task_registry.spawn(&self.runtime_handle, "生成回复建议", task_chat_id, async move {
+            let mut items = match api::complete(&client, &target, &payload).await {
+                Ok(content) => suggestions::parse_suggestions(&content),
+                Err(e) => {
+                    error!("生成回复建议失败: {}", e);
+                    Vec::new()
+                }
+            };
+            if items.is_empty() {
+                items = suggestions::extract_from_answer(&answer);
+            }
+            debug!("生成 {} 条回复建议", items.len());
+            let _ = sender.send((chat_id, items));
+        });
+    }
+
+    // 读取主选区并在悬浮窗中提问
+    // 在后台读取主选区，读取完成后由悬浮窗发起提问
+    pub fn start_quick_ask(&mut self) {
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        self.task_registry.spawn(&self.runtime_handle, "读取主选区", None, async move {
+            let _ = sender.send(quick_ask::read_primary_selection().await);
+        });
+        self.quick_ask = Some(QuickAsk::reading(receiver));
+    }
+
+    fn ask_selection(&mut self, selection: Result<String, String>) {
+        let selection = match selection {
+            Ok(selection) => selection,
+            Err(e) => {
+                self.quick_ask = Some(QuickAsk::failed(e));
+                return;
+            }
+        };
+        let prompt = format!("{}\n\n{}", self.quick_ask_prompt, selection);
+        if let Some(hold) = self.check_content_filter(&prompt, None) {
+            self.quick_ask = Some(QuickAsk::failed(format!("内容命中过滤规则：{}", hold.matches.join("、"))));
+            return;
+        }
+
+        let effective = self.global_effective_config();
+        let target = self.effective_target(&effective);
+        let payload = quick_ask::build_payload(&effective.model_name, &self.quick_ask_prompt, &selection);
+        let client = self.client.clone();
+        let retry_policy = self.retry_policy();
+        let endpoint_health = self.endpoint_health.clone();
+        let metrics = self.metrics.clone();
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.task_registry.spawn(&self.runtime_handle, "快速提问", None, async move {
+            let started = Instant::now();
+            let result = api::send_request(&client, &target, &payload, retry_policy, &endpoint_health, &tx).await;
+            metrics.finish_request(started, result.is_ok()).await;
+            if let Err(e) = result {
+                error!("快速提问失败: {:?}", e);
+                let _ = tx.send(UiEvent::Error(e.to_string()));
+                let _ = tx.send(UiEvent::Done);
+            }
+        });
+        self.quick_ask = Some(QuickAsk {
+            selection,
+            answer: String::new(),
+            error: None,
+            receiver: Some(rx),
+            selection_receiver: None,
+        });
+    }
+
+    // 快速提问的置顶悬浮窗
+    fn display_quick_ask(&mut self, ctx: &egui::Context) {
+        if let Some(selection) = self.quick_ask.as_mut().and_then(QuickAsk::take_selection) {
+            self.ask_selection(selection);
+        }
+        let Some(quick_ask) = self.quick_ask.as_mut() else {
+            return;
+        };
+        quick_ask.poll();
+        if quick_ask.is_loading() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(16));
+        }
+
+        let markdown_cache = &mut self.markdown_cache;
+        let dark_mode = self.dark_mode;
+        let mut close = false;
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("quick_ask"),
+            egui::ViewportBuilder::default()
+                .with_title("快速提问")
+                .with_inner_size([420.0, 320.0])
+                .with_always_on_top(),
+            |ctx, _class| {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    if !quick_ask.selection.is_empty() {
+                        let preview: String = quick_ask.selection.chars().take(120).collect();
+                        ui.label(RichText::new(preview).small().italics().color(egui::Color32::GRAY));
+                        ui.separator();
+                    }
+                    ScrollArea::vertical().max_height(ui.available_height() - 32.0).show(ui, |ui| {
+                        if let Some(error) = &quick_ask.error {
+                            ui.colored_label(egui::Color32::RED, error);
+                        }
+                        if quick_ask.answer.is_empty() && quick_ask.is_loading() {
+                            ui.spinner();
+                        }
+                        let viewer = if dark_mode {
+                            CommonMarkViewer::new().syntax_theme_dark("fuck")
+                        } else {
+                            CommonMarkViewer::new().syntax_theme_light("fuck")
+                        };
+                        viewer.show(ui, markdown_cache, &quick_ask.answer);
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.button("复制").clicked() {
+                            ui.ctx().copy_text(quick_ask.answer.clone());
+                        }
+                        if ui.button("关闭").clicked() {
+                            close = true;
+                        }
+                    });
+                });
+                if ctx.input(|i| i.viewport().close_requested() || i.key_pressed(egui::Key::Escape)) {
+                    close = true;
+                }
+            },
+        );
+        if close {
+            self.quick_ask = None;
+        }
+    }
+
+    // 启动或停止听写桥接，使其与设置保持一致
+    fn sync_dictation_bridge(&mut self, ctx: &egui::Context) {
+        if let Some(id) = self.dictation_task {
+            if !self.dictation_bridge_enabled {
+                self.task_registry.abort(id);
+                self.dictation_task = None;
+            }
+            return;
+        }
+        if !self.dictation_bridge_enabled {
+            return;
+        }
+        let sender = self.dictation_sender.clone();
+        let ctx = ctx.clone();
+        let join_handle = self.runtime_handle.spawn(async move {
+            if let Err(e) = dictation::serve(sender, ctx).await {
+                error!("听写桥接启动失败: {}", e);
+            }
+        });
+        self.dictation_task = Some(self.task_registry.track("听写桥接", None, join_handle.abort_handle()));
+    }
+
+    fn dictation_running(&self) -> bool {
+        self.dictation_task
+            .is_some_and(|id| self.task_registry.running().iter().any(|task| task.id == id))
+    }
+
+    // 启动接收外部文本的本地通道，只尝试一次
+    fn start_inbox(&mut self, ctx: &egui::Context) {
+        if self.inbox_task.is_some() {
+            return;
+        }
+        let sender = self.inbox_sender.clone();
+        let ctx = ctx.clone();
+        let join_handle = self.runtime_handle.spawn(async move {
+            if let Err(e) = inbox::serve(sender, ctx).await {
+                error!("外部文本通道启动失败: {}", e);
+            }
+        });
+        self.inbox_task = Some(self.task_registry.track("外部文本通道", None, join_handle.abort_handle()));
+    }
+
+    // 新建对话，并将外部发送的文本作为附件放入输入框
+    pub fn open_with_selection(&mut self, text: String) {
+        self.request_chat_switch(ChatSwitch::New);
+        self.text_attachments.push(TextAttachment {
+            name: "选中的文本".to_string(),
+            content: text,
+        });
+        self.input_focus = true;
+    }
+
+    // 打开指定对话，对话不存在时忽略
+    pub fn open_chat(&mut self, id: String) {
+        if self.chat_list.chats.iter().any(|c| c.id == id) {
+            self.request_chat_switch(ChatSwitch::Open(id));
+        }
+    }
+
+    // 导入 dream:// 链接中的模板，先在导入窗口中预览
+    pub fn open_link(&mut self, link: &str) {
+        debug!("打开链接: {}", link);
+        self.show_template_import = true;
+        match deep_link::decode(link) {
+            Ok(bundle) => {
+                self.template_preview = Some(bundle);
+                self.template_error = None;
+            }
+            Err(e) => {
+                error!("解析链接失败: {}", e);
+                self.template_preview = None;
+                self.template_error = Some(e.to_string());
+            }
+        }
+    }
+
+    fn receive_inbox(&mut self, ctx: &egui::Context) {
+        while let Ok(message) = self.inbox_receiver.try_recv() {
+            match message {
+                InboxMessage::Selection(text) => self.open_with_selection(text),
+                InboxMessage::OpenChat(id) => self.open_chat(id),
+                InboxMessage::OpenLink(link) => self.open_link(&link),
+                // 结果显示在置顶的悬浮窗中，不切换到主窗口
+                InboxMessage::QuickAsk => {
+                    self.start_quick_ask();
+                    ctx.request_repaint();
+                    continue;
+                }
+            }
+            ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+        }
+    }
+
+    // 同步任务栏进度和跳转列表中的最近对话
+    fn sync_platform(&mut self, frame: &eframe::Frame) {
+        self.platform.set_streaming(frame, self.is_loading);
+        let mut recent: Vec<&Chat> = self
+            .chat_list
+            .chats
+            .iter()
+            .filter(|chat| !chat.is_role() && !chat.archived && !chat.messages.is_empty())
+            .collect();
+        recent.sort_by_key(|chat| std::cmp::Reverse(chat.updated_at));
+        let recent = recent
+            .into_iter()
+            .take(platform::RECENT_CHAT_LIMIT)
+            .map(|chat| (chat.id.clone(), chat.name.clone()))
+            .collect();
+        self.platform.update_recent_chats(recent);
+    }
+
+    // 将外部工具写入的文本追加到输入框
+    fn receive_dictation(&mut self) {
+        while let Ok(text) = self.dictation_receiver.try_recv() {
+            self.input_text.push_str(&text);
+            self.move_input_cursor_to_end = true;
+        }
+    }
+
+    fn receive_reply_suggestions(&mut self) {
+        while let Ok((chat_id, items)) = self.suggestion_receiver.try_recv() {
+            if self.chat_list.current_chat_id.as_deref() == Some(chat_id.as_str()) && !items.is_empty() {
+                self.reply_suggestions = Some((chat_id, items));
+            }
+        }
+    }
+
+    // 合并后台提取到的任务
+    fn receive_extracted_tasks(&mut self) {
+        let mut changed = false;
+        while let Ok((chat_id, tasks)) = self.task_receiver.try_recv() {
+            if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == chat_id) {
+                checklist::merge_tasks(&mut chat.tasks, tasks);
+                changed = true;
+            }
+        }
+        if changed {
+            if let Err(e) = self.save_chat_list() {
+                error!("保存聊天列表失败: {}", e);
+            }
+        }
+    }
+
+    // 对话收到回复后生成标题，已命名的对话跳过；关闭自动标题或请求失败时使用本地规则
+    fn generate_title(&mut self, chat_id: &str) {
+        let Some(chat) = self.chat_list.chats.iter().find(|c| c.id == chat_id && !c.has_been_renamed) else {
+            return;
+        };
+        let first_content = |reply: bool| {
+            chat.messages
+                .iter()
+                .find(|msg| if reply { msg.is_reply() } else { msg.role == "user" })
+                .map(|msg| msg.content.clone())
+                .unwrap_or_default()
+        };
+        let user_input = first_content(false);
+        let assistant_response = first_content(true);
+        if user_input.trim().is_empty() {
+            return;
+        }
+        let chat_id = chat_id.to_string();
+        let title_sender = self.title_sender.clone();
+        if !self.title_config.enabled {
+            if let Some(title) = titles::fallback_title(&user_input) {
+                let _ = title_sender.send((chat_id, title));
+            }
+            return;
+        }
+
+        let mut effective = self.effective_config(Some(&chat_id));
+        if !self.title_config.model.trim().is_empty() {
+            effective.model_name = self.title_config.model.trim().to_string();
+        }
+        let target = self.effective_target(&effective);
+        let title_payload = self.title_config.payload(&effective.model_name, &user_input, &assistant_response);
+        let title_config = self.title_config.clone();
+        let client = self.client.clone();
+        let task_chat_id = Some(chat_id.clone());
+        // 标题通过独立通道返回，避免影响正在进行的消息流
+        self.task_registry.spawn(&self.runtime_handle, "生成标题", task_chat_id, async move {
+            debug!("发送标题生成请求: {}", title_payload);
+            let title = match api::complete(&client, &target, &title_payload).await {
+                Ok(title) => match title_config.finish(&title, &user_input) {
+                    Some(title) => Some(title),
+                    None => {
+                        error!("无法从响应中提取标题");
+                        titles::fallback_title(&user_input)
+                    }
+                },
+                Err(e) => {
+                    error!("标题生成请求失败: {}", e);
+                    titles::fallback_title(&user_input)
+                }
+            };
+            if let Some(title) = title {
+                debug!("成功生成标题: {}", title);
+                if let Err(e) = title_sender.send((chat_id, title)) {
+                    error!("发送标题更新消息失败: {}", e);
+                }
+            }
+        });
+    }
+
+    // 应用后台生成的标题；已命名的对话不再被覆盖，避免多个标题请求互相竞争
+    fn receive_title_updates(&mut self) {
+        let mut changed = false;
+        while let Ok((chat_id, title)) = self.title_receiver.try_recv() {
+            debug!("正在更新标题 - chat_id: {}, title: {}", chat_id, title);
+            if let Some(chat) = self
+                .chat_list
+                .chats
+                .iter_mut()
+                .find(|c| c.id == chat_id && !c.has_been_renamed)
+            {
+                chat.name = title;
+                chat.has_been_renamed = true;
+                changed = true;
+            }
+        }
+        if changed {
+            if let Err(e) = self.save_chat_list() {
+                error!("保存聊天列表失败: {}", e);
+            }
+        }
+    }
+
+    // 在后台下载模板包，结果通过专用通道返回
+    fn fetch_template(&mut self) {
+        let url = self.template_url_input.trim().to_string();
+        // dream:// 链接自带内容，不需要下载
+        if deep_link::is_link(&url) {
+            self.open_link(&url);
+            return;
+        }
+        debug!("从 URL 导入模板: {}", url);
+        self.template_loading = true;
+        self.template_error = None;
+        self.template_preview = None;
+
+        let client = self.client.clone();
+        let sender = self.template_sender.clone();
+        self.task_registry.spawn(&self.runtime_handle, "下载模板", None, async move {
+            let result = templates::fetch_bundle(&client, &url)
+                .await
+                .map_err(|e| e.to_string());
+            if let Err(e) = sender.send(result) {
+                error!("发送模板下载结果失败: {}", e);
+            }
+        });
+    }
+
+    // 请求当前草稿的续写建议
+    fn request_ghost_text(&mut self) {
+        let draft = self.input_text.clone();
+        let payload = ghost_text::build_payload(&self.ghost_text_model, &draft);
+        let client = self.client.clone();
+        let target = self.effective_target(&self.global_effective_config());
+        let sender = self.ghost_sender.clone();
+        let task_draft = draft.clone();
+
+        let handle = self.runtime_handle.spawn(async move {
+            let suggestion = match api::complete(&client, &target, &payload).await {
+                Ok(completion) => ghost_text::clean_completion(&task_draft, &completion),
+                Err(e) => {
+                    debug!("获取输入补全失败: {}", e);
+                    None
+                }
+            };
+            let _ = sender.send((task_draft, suggestion));
+        });
+        let id = self.task_registry.track("输入补全", None, handle.abort_handle());
+        self.ghost_text.start_request(&draft, id);
+    }
+
+    fn receive_ghost_text(&mut self) {
+        while let Ok((draft, suggestion)) = self.ghost_receiver.try_recv() {
+            self.ghost_text.receive(draft, suggestion, &self.input_text);
+        }
+    }
+
+    // 代理或网络设置变化后重新创建 HTTP 客户端
+    fn rebuild_client(&mut self) {
+        let (client, network_error) = client_with_network(&self.proxy_config, &self.network_config);
+        self.client = client;
+        self.network_error = network_error;
+    }
+
+    // 用当前客户端访问所选服务商的端点，检查代理和网络是否可用
+    fn test_proxy_connection(&mut self) {
+        let endpoint = self.effective_target(&self.global_effective_config()).url().to_string();
+        debug!("测试连接: {}", endpoint);
+        self.proxy_testing = true;
+        self.proxy_test_result = None;
+
+        let client = self.client.clone();
+        let sender = self.proxy_test_sender.clone();
+        self.task_registry.spawn(&self.runtime_handle, "测试连接", None, async move {
+            let _ = sender.send(api::test_connection(&client, &endpoint).await);
+        });
+    }
+
+    fn receive_proxy_test(&mut self) {
+        while let Ok(result) = self.proxy_test_receiver.try_recv() {
+            self.proxy_testing = false;
+            self.proxy_test_result = Some(result);
+        }
+    }
+
+    fn receive_template(&mut self) {
+        while let Ok(result) = self.template_receiver.try_recv() {
+            self.template_loading = false;
+            match result {
+                Ok(bundle) => self.template_preview = Some(bundle),
+                Err(e) => {
+                    error!("导入模板失败: {}", e);
+                    self.template_error = Some(e);
+                }
+            }
+        }
+    }
+
+    // 安装模板：同名角色、相同触发词和同名追问会被跳过
+    fn install_template(&mut self, bundle: TemplateBundle, frame: &mut eframe::Frame) {
+        debug!("安装模板: {}", bundle.name);
+        for role in &bundle.roles {
+            let chat = role.to_chat();
+            if !self.chat_list.chats.iter().any(|c| c.name == chat.name) {
+                self.chat_list.chats.insert(0, chat);
+            }
+        }
+        for snippet in bundle.snippets {
+            if !self.snippets.iter().any(|s| s.trigger == snippet.trigger) {
+                self.snippets.push(snippet);
+            }
+        }
+        for action in bundle.follow_up_actions {
+            if !self.follow_up_actions.iter().any(|a| a.label == action.label) {
+                self.follow_up_actions.push(action);
+            }
+        }
+
+        if let Err(e) = self.save_chat_list() {
+            error!("保存聊天列表失败: {}", e);
+        }
+        if let Err(e) = self.save_config(frame) {
+            error!("保存配置失败: {}", e);
+        }
+    }
+
+    // 当前的角色、文本缩写和快捷追问组成的模板包
+    fn current_template(&self) -> TemplateBundle {
+        TemplateBundle {
+            version: templates::TEMPLATE_FORMAT_VERSION,
+            name: "我的模板".to_string(),
+            description: String::new(),
+            author: String::new(),
+            roles: self
+                .chat_list
+                .chats
+                .iter()
+                .filter(|c| c.is_role())
+                .filter_map(templates::RoleTemplate::from_chat)
+                .collect(),
+            snippets: self.snippets.clone(),
+            follow_up_actions: self.follow_up_actions.clone(),
+        }
+    }
+
+    // 将当前的角色、文本缩写和快捷追问导出为模板包
+    fn export_template(&self) {
+        let bundle = self.current_template();
+        if let Some(path) = FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .set_file_name("dream-template.json")
+            .save_file()
+        {
+            debug!("导出模板到: {:?}", path);
+            if let Err(e) = self
+                .runtime_handle
+                .block_on(async { templates::export_bundle(&bundle, &path).await })
+            {
+                error!("导出模板失败: {}", e);
+            }
+        }
+    }
+
+    fn share_bundle(&mut self, bundle: &TemplateBundle) {
+        match deep_link::encode(bundle) {
+            Ok(link) => {
+                self.share_link = Some((bundle.name.clone(), link));
+                self.share_qr = None;
+            }
+            Err(e) => error!("生成分享链接失败: {}", e),
+        }
+    }
+
+    // 分享窗口：显示 dream:// 链接和对应的二维码
+    fn display_share_link(&mut self, ctx: &egui::Context) {
+        let Some((name, link)) = self.share_link.clone() else {
+            return;
+        };
+        let qr = self.share_qr.get_or_insert_with(|| {
+            deep_link::qr_image(&link).map(|image| ctx.load_texture("share_qr", image, egui::TextureOptions::NEAREST))
+        });
+        let mut open = true;
+        egui::Window::new(format!("分享: {}", name))
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(360.0)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                match qr {
+                    Ok(texture) => {
+                        ui.vertical_centered(|ui| {
+                            ui.add(egui::Image::new(&*texture).fit_to_exact_size(egui::vec2(240.0, 240.0)));
+                        });
+                    }
+                    Err(e) => {
+                        ui.label(RichText::new(e.as_str()).color(egui::Color32::GRAY));
+                    }
+                }
+                ui.add_space(8.0);
+                let mut text = link.clone();
+                ui.add(TextEdit::multiline(&mut text)
+                    .font(egui::TextStyle::Monospace)
+                    .desired_rows(3)
+                    .desired_width(f32::INFINITY)
+                    .interactive(false));
+                ui.horizontal(|ui| {
+                    if ui.button("\u{f0c5} 复制链接").clicked() {
+                        ui.ctx().copy_text(link.clone());
+                    }
+                    ui.label(RichText::new(format!("{} 个字符", link.len())).small().color(egui::Color32::GRAY));
+                });
+                ui.label(RichText::new("对方在“从 URL 导入”中粘贴链接，或直接点击已注册的链接即可导入")
+                    .small()
+                    .color(egui::Color32::GRAY));
+            });
+        if !open {
+            self.share_link = None;
+            self.share_qr = None;
+        }
+    }
+
+    // 模板导入窗口：下载后先预览内容，确认后再安装
+    fn display_template_import(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let mut open = true;
+        let mut install = None;
+        egui::Window::new("导入模板")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(420.0)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(TextEdit::singleline(&mut self.template_url_input)
+                        .desired_width(300.0)
+                        .hint_text("https://... 或 dream://import?..."));
+                    let can_fetch = !self.template_loading && !self.template_url_input.trim().is_empty();
+                    if ui.add_enabled(can_fetch, egui::Button::new("获取")).clicked() {
+                        self.fetch_template();
+                    }
+                });
+
+                if self.template_loading {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("正在下载...");
+                    });
+                }
+                if let Some(error) = &self.template_error {
+                    ui.label(RichText::new(error).color(egui::Color32::RED));
+                }
+                if ui.small_button("用本程序打开 dream:// 链接")
+                    .on_hover_text("在系统中注册链接类型，点击分享链接时直接导入")
+                    .clicked()
+                {
+                    match deep_link::register_handler() {
+                        Ok(location) => self.notifications.push(format!("已注册 dream:// 链接: {}", location)),
+                        Err(e) => {
+                            error!("注册链接类型失败: {}", e);
+                            self.template_error = Some(format!("注册失败: {}", e));
+                        }
+                    }
+                }
+
+                if let Some(bundle) = &self.template_preview {
+                    ui.separator();
+                    ui.label(RichText::new(&bundle.name).strong());
+                    if !bundle.author.is_empty() {
+                        ui.label(RichText::new(format!("作者: {}", bundle.author)).small());
+                    }
+                    if !bundle.description.is_empty() {
+                        ui.label(&bundle.description);
+                    }
+
+                    ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+                        if !bundle.roles.is_empty() {
+                            ui.label(RichText::new(format!("角色 ({})", bundle.roles.len())).strong());
+                            for role in &bundle.roles {
+                                ui.collapsing(&role.name, |ui| {
+                                    if !role.model_name.is_empty() {
+                                        ui.label(format!("模型: {}", role.model_name));
+                                    }
+                                    ui.label(format!("Temperature: {:.1}", role.temperature));
+                                    ui.label(&role.system_prompt);
+                                });
+                            }
+                        }
+                        if !bundle.snippets.is_empty() {
+                            ui.label(RichText::new(format!("文本缩写 ({})", bundle.snippets.len())).strong());
+                            for snippet in &bundle.snippets {
+                                ui.label(format!("{} → {}", snippet.trigger, snippet.expansion));
+                            }
+                        }
+                        if !bundle.follow_up_actions.is_empty() {
+                            ui.label(RichText::new(format!("快捷追问 ({})", bundle.follow_up_actions.len())).strong());
+                            for action in &bundle.follow_up_actions {
+                                ui.label(format!("{}: {}", action.label, action.prompt));
+                            }
+                        }
+                    });
+
+                    ui.add_space(8.0);
+                    if ui.button("安装").clicked() {
+                        install = Some(bundle.clone());
+                    }
+                }
+            });
+
+        if let Some(bundle) = install {
+            self.install_template(bundle, frame);
+            self.template_preview = None;
+            open = false;
+        }
+        if !open {
+            self.show_template_import = false;
+            self.template_error = None;
+        }
+    }
+
+    // 分屏：在右侧只读显示另一个对话，便于一边参考一边输入；分隔条可以拖动调整宽度
+    fn display_split_view(&mut self, ctx: &egui::Context) {
+        let Some(split_id) = self.split_chat_id.clone() else {
+            return;
+        };
+        if self.chat_list.current_chat_id.as_ref() == Some(&split_id) {
+            self.split_chat_id = None;
+            return;
+        }
+        let Some(split_chat) = self.chat_list.chats.iter().find(|c| c.id == split_id) else {
+            self.split_chat_id = None;
+            return;
+        };
+        let name = split_chat.name.clone();
+        let messages = split_chat.messages.clone();
+        let choices: Vec<(String, String)> = self
+            .chat_list
+            .chats
+            .iter()
+            .filter(|chat| !chat.is_role() && self.chat_list.current_chat_id.as_ref() != Some(&chat.id))
+            .map(|chat| (chat.id.clone(), chat.name.clone()))
+            .collect();
+        let window_width = ctx.available_rect().width();
+        let mut selected = split_id.clone();
+        let mut swap = false;
+        let mut close = false;
+
+        egui::SidePanel::right("split_panel")
+            .resizable(true)
+            .default_width(window_width * 0.4)
+            .width_range(240.0..=(window_width * 0.7).max(240.0))
+            .show(ctx, |ui| {
+                ui.add_space(6.0);
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_salt("split_chat_selector")
+                        .selected_text(&name)
+                        .width(ui.available_width() - 60.0)
+                        .show_ui(ui, |ui| {
+                            for (id, name) in &choices {
+                                ui.selectable_value(&mut selected, id.clone(), name);
+                            }
+                        });
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.small_button("\u{f00d}").on_hover_text("关闭分屏").clicked() {
+                            close = true;
+                        }
+                        if ui.small_button("\u{f0ec}").on_hover_text("与当前对话交换").clicked() {
+                            swap = true;
+                        }
+                    });
+                });
+                ui.separator();
+                ScrollArea::vertical()
+                    .id_salt("split_scroll")
+                    .auto_shrink([false; 2])
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for msg in &messages {
+                            self.display_message(ui, msg);
+                            ui.add_space(8.0);
+                        }
+                    });
+            });
+
+        if close {
+            self.split_chat_id = None;
+        } else if swap {
+            self.split_chat_id = self.chat_list.current_chat_id.clone();
+            self.request_chat_switch(ChatSwitch::Open(split_id));
+        } else if selected != split_id {
+            self.split_chat_id = Some(selected);
+        }
+    }
+
+    // 打开或关闭分屏，打开时默认对照最近更新的另一个对话
+    fn toggle_split_view(&mut self) {
+        if self.split_chat_id.take().is_some() {
+            return;
+        }
+        self.split_chat_id = self
+            .chat_list
+            .chats
+            .iter()
+            .filter(|chat| !chat.is_role() && self.chat_list.current_chat_id.as_ref() != Some(&chat.id))
+            .max_by_key(|chat| chat.updated_at)
+            .map(|chat| chat.id.clone());
+    }
+
+    // 任务清单面板
+    fn display_task_panel(&mut self, ctx: &egui::Context) {
+        let Some(current_id) = self.chat_list.current_chat_id.clone() else {
+            return;
+        };
+
+        egui::SidePanel::right("task_panel")
+            .default_width(220.0)
+            .show(ctx, |ui| {
+                ui.add_space(6.0);
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("任务清单").strong());
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.small_button("\u{f021}").on_hover_text("从对话中提取任务").clicked() {
+                            self.extract_tasks(&current_id);
+                        }
+                    });
+                });
+                ui.separator();
+
+                let mut changed = false;
+                if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == current_id) {
+                    ScrollArea::vertical().auto_shrink([false, true]).show(ui, |ui| {
+                        let mut task_to_remove = None;
+                        for (index, task) in chat.tasks.iter_mut().enumerate() {
+                            ui.horizontal(|ui| {
+                                let text = if task.done {
+                                    RichText::new(&task.text).strikethrough().color(egui::Color32::GRAY)
+                                } else {
+                                    RichText::new(&task.text)
+                                };
+                                if ui.checkbox(&mut task.done, text).changed() {
+                                    changed = true;
+                                }
+                                if ui.small_button("\u{f00d}").clicked() {
+                                    task_to_remove = Some(index);
+                                }
+                            });
+                        }
+                        if let Some(index) = task_to_remove {
+                            chat.tasks.remove(index);
+                            changed = true;
+                        }
+                        if chat.tasks.is_empty() {
+                            ui.label(RichText::new("暂无任务").italics().color(egui::Color32::GRAY));
+                        }
+                    });
+
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        let response = ui.add(TextEdit::singleline(&mut self.new_task_input)
+                            .desired_width(ui.available_width() - 30.0)
+                            .hint_text("添加任务"));
+                        let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                        if (ui.small_button("\u{f067}").clicked() || submitted)
+                            && !self.new_task_input.trim().is_empty()
+                        {
+                            chat.tasks.push(ChatTask {
+                                text: self.new_task_input.trim().to_string(),
+                                done: false,
+                            });
+                            self.new_task_input.clear();
+                            changed = true;
+                        }
+                    });
+                }
+
+                if changed {
+                    if let Err(e) = self.save_chat_list() {
+                        error!("保存聊天列表失败: {}", e);
+                    }
+                }
+            });
+    }
+
+    // 删除对话：取消相关后台任务并清理缓存图片
+    fn delete_chat(&mut self, chat_id: &str) {
+        debug!("开始删除对话: {}", chat_id);
+
+        // 获取要删除的对话
+        if let Some(chat) = self.chat_list.chats.iter().find(|c| c.id == chat_id) {
+            debug!("找到要删除的对话: {} ({})", chat.name, chat.id);
+            // 删除所有相关的缓存图片
+            let messages = chat.messages.clone();
+            for path in messages.iter().filter_map(|msg| msg.image_path.as_deref()) {
+                self.image_cache.forget(path);
+            }
+            debug!("开始清理对话的图片缓存，消数: {}", messages.len());
+            self.context_summaries.forget(chat_id);
+
+            // 取消该对话仍在运行的后台任务
+            if self.task_registry.abort_chat(chat_id) > 0 {
+                self.is_loading = false;
+                self.loading_dots.clear();
+                self.receiver = None;
+            }
+
+            // 清理任务不关联对话，避免被上面的取消操作影响
+            let knowledge_chat_id = chat.knowledge_folder.as_ref().map(|_| chat.id.clone());
+            let cache_dir = self.image_cache_dir();
+            self.task_registry.spawn(&self.runtime_handle, "清理图片缓存", None, async move {
+                if let Some(id) = knowledge_chat_id {
+                    knowledge::remove_index(&id).await;
+                }
+                for (index, msg) in messages.iter().enumerate() {
+                    if let Some(image_path) = &msg.image_path {
+                        debug!("处理第 {} 条消息的图片: {}", index + 1, image_path);
+                        if let Err(e) = utils::remove_cached_image(image_path, &cache_dir).await {
+                            error!(
+                                "删除第 {} 条消的缓存图片失败: {} - {}",
+                                index + 1,
+                                image_path,
+                                e
+                            );
+                        }
+                    }
+                }
+                debug!("图片缓存清理完成");
+            });
+        } else {
+            debug!("未找到要删除的对话: {}", chat_id);
+        }
+
+        // 如果删除的是当前选中的对话，清空聊天历史
+        if self.chat_list.current_chat_id.as_deref() == Some(chat_id) {
+            self.chat_history.0.clear();
+            self.chat_list.current_chat_id = None;
+        }
+
+        // 从列表中移除对话
+        self.chat_list.chats.retain(|chat| chat.id != chat_id);
+        self.selected_chat_ids.retain(|id| id != chat_id);
+        debug!("对话删除完成");
+    }
+
+    // 当前没有选中的对话时，选中第一个；列表为空时创建新对话
+    fn select_fallback_chat(&mut self) {
+        if self.chat_list.chats.is_empty() {
+            self.new_chat();
+        } else if self.chat_list.current_chat_id.is_none() {
+            if let Some(first_id) = self.chat_list.chats.first().map(|chat| chat.id.clone()) {
+                self.handle_message_selection(&first_id);
+            }
+        }
+    }
+
+    // 处理侧边栏点击：普通点击打开对话，Ctrl/Cmd 切换选中，Shift 范围选择
+    fn handle_sidebar_click(&mut self, click: SidebarClick, display_order: &[String]) {
+        match click {
+            SidebarClick::Open(id) => {
+                self.selected_chat_ids.clear();
+                self.selection_anchor = Some(id.clone());
+                self.request_chat_switch(ChatSwitch::Open(id));
+            }
+            SidebarClick::Toggle(id) => {
+                if let Some(index) = self.selected_chat_ids.iter().position(|selected| selected == &id) {
+                    self.selected_chat_ids.remove(index);
+                } else {
+                    self.selected_chat_ids.push(id.clone());
+                }
+                self.selection_anchor = Some(id);
+            }
+            SidebarClick::NewFromRole(role_id) => self.request_chat_switch(ChatSwitch::NewFromRole(role_id)),
+            SidebarClick::ExportCode(id) => {
+                let Some(chat) = self.chat_list.chats.iter().find(|c| c.id == id) else {
+                    return;
+                };
+                if let Some(dir) = FileDialog::new().pick_folder() {
+                    match self
+                        .runtime_handle
+                        .block_on(async { export::export_code_blocks(chat, &dir).await })
+                    {
+                        Ok(count) => debug!("已导出 {} 个代码块: {}", count, dir.display()),
+                        Err(e) => error!("导出代码块失败: {} - {}", chat.name, e),
+                    }
+                }
+            }
+            SidebarClick::ExportHtml(id) => {
+                let Some(chat) = self.chat_list.chats.iter().find(|c| c.id == id) else {
+                    return;
+                };
+                if let Some(path) = FileDialog::new()
+                    .add_filter("HTML", &["html"])
+                    .set_file_name(export::export_file_name(chat, "html"))
+                    .save_file()
+                {
+                    let mask_secrets = self.mask_secrets_in_exports;
+                    match self
+                        .runtime_handle
+                        .block_on(async { export::export_chat_html(chat, &path, mask_secrets).await })
+                    {
+                        Ok(()) => debug!("已导出 HTML: {}", path.display()),
+                        Err(e) => error!("导出 HTML 失败: {} - {}", chat.name, e),
+                    }
+                }
+            }
+            SidebarClick::ExportBundle(id) => {
+                let Some(chat) = self.chat_list.chats.iter().find(|c| c.id == id) else {
+                    return;
+                };
+                if let Some(path) = FileDialog::new()
+                    .add_filter("JSON", &["json"])
+                    .set_file_name(export::export_file_name(chat, "json"))
+                    .save_file()
+                {
+                    let mask_secrets = self.mask_secrets_in_exports;
+                    match self
+                        .runtime_handle
+                        .block_on(async { export::export_chat_bundle(chat, &path, mask_secrets).await })
+                    {
+                        Ok(()) => debug!("已导出对话包: {}", path.display()),
+                        Err(e) => error!("导出对话包失败: {} - {}", chat.name, e),
+                    }
+                }
+            }
+            SidebarClick::EncryptedExport(id) => self.begin_encrypted_export(vec![id]),
+            SidebarClick::AttachKnowledge(id) => {
+                if let Some(folder) = FileDialog::new().pick_folder() {
+                    self.index_knowledge(id, folder);
+                }
+            }
+            SidebarClick::ReindexKnowledge(id) => {
+                let folder = self
+                    .chat_list
+                    .chats
+                    .iter()
+                    .find(|c| c.id == id)
+                    .and_then(|chat| chat.knowledge_folder.clone());
+                if let Some(folder) = folder {
+                    self.index_knowledge(id, PathBuf::from(folder));
+                }
+            }
+            SidebarClick::DetachKnowledge(id) => {
+                if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == id) {
+                    chat.knowledge_folder = None;
+                }
+                self.runtime_handle.block_on(knowledge::remove_index(&id));
+                if let Err(e) = self.save_chat_list() {
+                    error!("保存聊天列表失败: {}", e);
+                }
+            }
+            SidebarClick::OpenSplit(id) => self.split_chat_id = Some(id),
+            SidebarClick::MoveToFolder(id, folder) => {
+                if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == id) {
+                    chat.folder = folder;
+                }
+                if let Err(e) = self.save_chat_list() {
+                    error!("保存聊天列表失败: {}", e);
+                }
+            }
+            SidebarClick::TogglePin(id) => {
+                if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == id) {
+                    chat.pinned = !chat.pinned;
+                }
+                if let Err(e) = self.save_chat_list() {
+                    error!("保存聊天列表失败: {}", e);
+                }
+            }
+            SidebarClick::ToggleArchive(id) => {
+                if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == id) {
+                    chat.archived = !chat.archived;
+                    debug!("{}对话: {}", if chat.archived { "已归档" } else { "已取消归档" }, chat.name);
+                }
+                if let Err(e) = self.save_chat_list() {
+                    error!("保存聊天列表失败: {}", e);
+                }
+            }
+            SidebarClick::DeleteFolder(name) => {
+                // 文件夹中的对话移回未分类，不会被删除
+                self.chat_list.folders.retain(|folder| folder != &name);
+                for chat in &mut self.chat_list.chats {
+                    if chat.folder.as_ref() == Some(&name) {
+                        chat.folder = None;
+                    }
+                }
+                if let Err(e) = self.save_chat_list() {
+                    error!("保存聊天列表失败: {}", e);
+                }
+            }
+            SidebarClick::Replay(id) => {
+                let model = self.effective_config(Some(&id)).model_name;
+                self.replay_setup = Some((id, model));
+            }
+            SidebarClick::ShareRole(id) => {
+                let role = self
+                    .chat_list
+                    .chats
+                    .iter()
+                    .find(|c| c.id == id)
+                    .and_then(templates::RoleTemplate::from_chat);
+                if let Some(role) = role {
+                    let bundle = TemplateBundle {
+                        version: templates::TEMPLATE_FORMAT_VERSION,
+                        name: role.name.clone(),
+                        description: String::new(),
+                        author: String::new(),
+                        roles: vec![role],
+                        snippets: Vec::new(),
+                        follow_up_actions: Vec::new(),
+                    };
+                    self.share_bundle(&bundle);
+                }
+            }
+            SidebarClick::Range(id) => {
+                let anchor = self
+                    .selection_anchor
+                    .clone()
+                    .or_else(|| self.chat_list.current_chat_id.clone())
+                    .unwrap_or_else(|| id.clone());
+                let anchor_index = display_order.iter().position(|item| item == &anchor);
+                let target_index = display_order.iter().position(|item| item == &id);
+                if let (Some(a), Some(b)) = (anchor_index, target_index) {
+                    let (start, end) = if a <= b { (a, b) } else { (b, a) };
+                    for item in &display_order[start..=end] {
+                        if !self.selected_chat_ids.contains(item) {
+                            self.selected_chat_ids.push(item.clone());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn start_batch_job(&mut self, action: BatchAction) {
+        let pending = std::mem::take(&mut self.selected_chat_ids);
+        debug!("开始批量{}: {} 个对话", action.label(), pending.len());
+        self.batch_job = Some(BatchJob {
+            action,
+            total: pending.len(),
+            pending,
+            failed: 0,
+        });
+    }
+
+    // 每帧处理一个对话，处理完成后统一保存
+    fn process_batch_job(&mut self) {
+        let Some(job) = self.batch_job.as_mut() else {
+            return;
+        };
+        let Some(chat_id) = job.pending.pop() else {
+            let finished = self.batch_job.take();
+            if matches!(finished.map(|job| job.action), Some(BatchAction::Delete)) {
+                self.select_fallback_chat();
+            }
+            if let Err(e) = self.save_chat_list() {
+                error!("保存聊天列表失败: {}", e);
+            }
+            return;
+        };
+
+        let action = job.action.clone();
+        match action {
+            BatchAction::Delete => self.delete_chat(&chat_id),
+            BatchAction::Archive => {
+                if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == chat_id) {
+                    chat.archived = true;
+                }
+            }
+            BatchAction::Tag(tag) => {
+                if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == chat_id) {
+                    if !chat.tags.contains(&tag) {
+                        chat.tags.push(tag);
+                    }
+                }
+            }
+            BatchAction::Export(dir) => {
+                let mask_secrets = self.mask_secrets_in_exports;
+                if let Some(chat) = self.chat_list.chats.iter().find(|c| c.id == chat_id) {
+                    if let Err(e) = self
+                        .runtime_handle
+                        .block_on(async { export::export_chat_markdown(chat, &dir, mask_secrets).await })
+                    {
+                        error!("导出对话失败: {} - {}", chat.name, e);
+                        if let Some(job) = self.batch_job.as_mut() {
+                            job.failed += 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // 搜索框下方的新建文件夹按钮和标签筛选行
+    fn display_list_filter(&mut self, ui: &mut egui::Ui) {
+        let mut tags: Vec<String> = self
+            .chat_list
+            .chats
+            .iter()
+            .flat_map(|chat| chat.tags.iter().cloned())
+            .collect();
+        tags.sort();
+        tags.dedup();
+        if self.tag_filter.as_ref().is_some_and(|tag| !tags.contains(tag)) {
+            self.tag_filter = None;
+        }
+
+        ui.horizontal_wrapped(|ui| {
+            // nf-fa-folder 新建文件夹
+            ui.menu_button("\u{f07b}+", |ui| {
+                ui.add(TextEdit::singleline(&mut self.folder_input)
+                    .desired_width(120.0)
+                    .hint_text("文件夹名称"));
+                let name = self.folder_input.trim().to_string();
+                if ui.button("新建文件夹").clicked() && !name.is_empty() {
+                    if !self.chat_list.folders.contains(&name) {
+                        self.chat_list.folders.push(name);
+                        if let Err(e) = self.save_chat_list() {
+                            error!("保存聊天列表失败: {}", e);
+                        }
+                    }
+                    self.folder_input.clear();
+                    ui.close_menu();
+                }
+            })
+            .response
+            .on_hover_text("新建文件夹，把对话拖到文件夹标题上即可移入");
+
+            if !tags.is_empty() {
+                if ui.selectable_label(self.tag_filter.is_none(), "全部").clicked() {
+                    self.tag_filter = None;
+                }
+                for tag in tags {
+                    let selected = self.tag_filter.as_ref() == Some(&tag);
+                    if ui.selectable_label(selected, format!("#{}", tag)).clicked() {
+                        self.tag_filter = if selected { None } else { Some(tag) };
+                    }
+                }
+            }
+        });
+    }
+
+    // 侧边栏顶部的批量操作栏
+    fn display_batch_bar(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal_wrapped(|ui| {
+            ui.label(format!("已选 {} 项", self.selected_chat_ids.len()));
+            if ui.small_button("删除").clicked() {
+                self.confirm_batch_delete = true;
+            }
+            if ui.small_button("归档").clicked() {
+                self.start_batch_job(BatchAction::Archive);
+            }
+            ui.menu_button("标签", |ui| {
+                ui.add(TextEdit::singleline(&mut self.batch_tag_input)
+                    .desired_width(120.0)
+                    .hint_text("标签名称"));
+                let tag = self.batch_tag_input.trim().to_string();
+                if ui.button("添加").clicked() && !tag.is_empty() {
+                    self.batch_tag_input.clear();
+                    self.start_batch_job(BatchAction::Tag(tag));
+                    ui.close_menu();
+                }
+            });
+            if ui.small_button("导出").clicked() {
+                if let Some(dir) = FileDialog::new().pick_folder() {
+                    self.start_batch_job(BatchAction::Export(dir));
+                }
+            }
+            if ui.small_button("加密导出").clicked() {
+                let chat_ids = std::mem::take(&mut self.selected_chat_ids);
+                self.begin_encrypted_export(chat_ids);
+            }
+            if ui.small_button("取消").clicked() {
+                self.selected_chat_ids.clear();
+            }
+        });
+    }
+
+    fn begin_encrypted_export(&mut self, chat_ids: Vec<String>) {
+        self.encrypted_export = Some(EncryptedExport {
+            chat_ids,
+            password: String::new(),
+            confirm: String::new(),
+        });
+    }
+
+    // 输入密码后选择保存位置，将选中的对话写入加密压缩包
+    fn display_encrypted_export(&mut self, ctx: &egui::Context) {
+        let Some(export) = self.encrypted_export.as_mut() else {
+            return;
+        };
+        let mut close = false;
+        let mut confirmed = false;
+        egui::Window::new("加密导出")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.label(format!("将 {} 个对话导出为 AES-256 加密的 ZIP 压缩包", export.chat_ids.len()));
+                ui.add_space(4.0);
+                egui::Grid::new("encrypted_export_grid").num_columns(2).show(ui, |ui| {
+                    ui.label("密码:");
+                    ui.add(TextEdit::singleline(&mut export.password).password(true));
+                    ui.end_row();
+                    ui.label("确认密码:");
+                    ui.add(TextEdit::singleline(&mut export.confirm).password(true));
+                    ui.end_row();
+                });
+                let mismatch = !export.confirm.is_empty() && export.password != export.confirm;
+                if mismatch {
+                    ui.label(RichText::new("两次输入的密码不一致").color(egui::Color32::RED));
+                }
+                ui.label(RichText::new("忘记密码将无法恢复压缩包中的内容").small().color(egui::Color32::GRAY));
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    let ready = !export.password.is_empty() && export.password == export.confirm;
+                    if ui.add_enabled(ready, egui::Button::new("导出...")).clicked() {
+                        confirmed = true;
+                    }
+                    if ui.button("取消").clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        if close {
+            self.encrypted_export = None;
+        }
+        if !confirmed {
+            return;
+        }
+        let Some(export) = self.encrypted_export.take() else {
+            return;
+        };
+        let Some(path) = FileDialog::new()
+            .add_filter("zip", &["zip"])
+            .set_file_name("对话导出.zip")
+            .save_file()
+        else {
+            return;
+        };
+        let chats: Vec<&Chat> = self
+            .chat_list
+            .chats
+            .iter()
+            .filter(|chat| export.chat_ids.contains(&chat.id))
+            .collect();
+        let mask_secrets = self.mask_secrets_in_exports;
+        match self.runtime_handle.block_on(export::export_encrypted_archive(
+            &chats,
+            &path,
+            &export.password,
+            mask_secrets,
+        )) {
+            Ok(()) => debug!("已加密导出 {} 个对话: {}", chats.len(), path.display()),
+            Err(e) => error!("加密导出失败: {}", e),
+        }
+    }
+
+    // 添加创建角色的函数
+    fn create_role(&mut self) {
+        let new_chat = Chat {
+            id: Uuid::new_v4().to_string(),
+            name: format!("\u{f544} {}", self.role_name_input.trim()),
+            messages: Vec::new(),
+            has_been_renamed: true,
+            config: Some(ChatConfig {
                 model_name: self.role_model_name.clone(),
                 system_prompt: self.role_prompt_input.clone(),
                 temperature: self.role_temperature,
+                top_p: 1.0,
+                frequency_penalty: 0.0,
+                presence_penalty: 0.0,
+                greeting: Some(self.role_greeting_input.trim().to_string())
+                    .filter(|greeting| !greeting.is_empty()),
+                greeting_in_context: self.role_greeting_in_context,
+                provider: self.role_provider,
+                filter_mode: self.role_filter_mode,
+                filter_terms: content_filter::parse_terms(&self.role_filter_terms),
+                profile: self.role_profile.clone(),
             }),
             created_at: Utc::now(),
             updated_at: Utc::now(),
+            tasks: Vec::new(),
+            tags: Vec::new(),
+            archived: false,
+            role_id: None,
+            tee: None,
+            knowledge_folder: None,
+            group_members: Vec::new(),
+            memory: None,
+            journal: None,
+            model_override: None,
+            webhook_url: None,
+            incognito: false,
+            folder: None,
+            pinned: false,
+            disabled_post_processors: Vec::new(),
         };
 
         // 将角色添加到列表最前面
@@ -689,6 +7184,12 @@ impl ChatApp {
         // 清空输入
         self.role_name_input.clear();
         self.role_prompt_input.clear();
+        self.role_greeting_input.clear();
+        self.role_greeting_in_context = false;
+        self.role_provider = None;
+        self.role_profile = None;
+        self.role_filter_mode = None;
+        self.role_filter_terms.clear();
         self.role_temperature = 0.7;
         self.show_role_creator = false;
     }
@@ -698,7 +7199,7 @@ impl ChatApp {
         if self.clear_chat_mode {
             // 完全清空模式：清空内存和保存的记录
             self.chat_history.0.clear();
-            if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| &c.id == chat_id) {
+            if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == chat_id) {
                 chat.messages.clear();
                 // 保存更新后的聊天列表
                 if let Err(e) = self.save_chat_list() {
@@ -707,16 +7208,32 @@ impl ChatApp {
             }
         } else {
             // 仅清空内存模式添加分隔线消息
-            self.chat_history.add_message(Message::new_assistant(
-                "--------------------------- 历史记录分割线 ---------------------------"
-                    .to_string(),
-            ));
+            self.chat_history.add_message(Message::new_event(MessageKind::Divider, String::new()));
         }
     }
 }
 
 impl Clone for ChatApp {
     fn clone(&self) -> Self {
+        let (task_sender, task_receiver) = mpsc::unbounded_channel();
+        let (title_sender, title_receiver) = mpsc::unbounded_channel();
+        let (template_sender, template_receiver) = mpsc::unbounded_channel();
+        let (ghost_sender, ghost_receiver) = mpsc::unbounded_channel();
+        let (suggestion_sender, suggestion_receiver) = mpsc::unbounded_channel();
+        let (dictation_sender, dictation_receiver) = mpsc::unbounded_channel();
+        let (inbox_sender, inbox_receiver) = mpsc::unbounded_channel();
+        let (notification_sender, notification_receiver) = mpsc::unbounded_channel();
+        let (memory_sender, memory_receiver) = mpsc::unbounded_channel();
+        let (journal_sender, journal_receiver) = mpsc::unbounded_channel();
+        let (command_sender, command_receiver) = mpsc::unbounded_channel();
+        let (capability_sender, capability_receiver) = mpsc::unbounded_channel();
+        let (proxy_test_sender, proxy_test_receiver) = mpsc::unbounded_channel();
+        let (speech_sender, speech_receiver) = mpsc::unbounded_channel();
+        let (image_sender, image_receiver) = mpsc::unbounded_channel();
+        let (knowledge_sender, knowledge_receiver) = mpsc::unbounded_channel();
+        let (workspace_sender, workspace_receiver) = mpsc::unbounded_channel();
+        let (post_process_sender, post_process_receiver) = mpsc::unbounded_channel();
+        let (long_memory_sender, long_memory_receiver) = mpsc::unbounded_channel();
         Self {
             input_text: self.input_text.clone(),
             chat_history: self.chat_history.clone(),
@@ -726,39 +7243,250 @@ impl Clone for ChatApp {
             receiver: None,
             show_settings: self.show_settings,
             api_endpoint: self.api_endpoint.clone(),
+            api_provider: self.api_provider,
+            claude_config: self.claude_config.clone(),
+            gemini_config: self.gemini_config.clone(),
+            azure_config: self.azure_config.clone(),
+            proxy_config: self.proxy_config.clone(),
+            network_config: self.network_config.clone(),
+            failover: self.failover.clone(),
+            endpoint_health: self.endpoint_health.clone(),
+            network_error: self.network_error.clone(),
+            proxy_testing: false,
+            proxy_test_result: None,
+            proxy_test_sender,
+            proxy_test_receiver,
             model_name: self.model_name.clone(),
             system_prompt: self.system_prompt.clone(),
             temperature: self.temperature,
+            top_p: self.top_p,
+            frequency_penalty: self.frequency_penalty,
+            presence_penalty: self.presence_penalty,
             client: self.client.clone(),
             chat_list: self.chat_list.clone(),
             previous_show_settings: self.previous_show_settings,
             retry_enabled: self.retry_enabled,
             max_retries: self.max_retries,
+            stall_timeout_secs: self.stall_timeout_secs,
+            webhook_url: self.webhook_url.clone(),
+            incognito_minutes: self.incognito_minutes,
+            smart_code_paste: self.smart_code_paste,
+            long_term_memory: self.long_term_memory,
+            memory_store: self.memory_store.clone(),
+            long_memory_sender,
+            long_memory_receiver,
+            memory_pending: self.memory_pending,
+            show_memory_manager: self.show_memory_manager,
+            memory_editing: self.memory_editing.clone(),
+            code_paste_undo: self.code_paste_undo.clone(),
+            stream_stalled: self.stream_stalled,
             selected_image: self.selected_image.clone(),
             processing_image: None,
             dark_mode: self.dark_mode,
             available_models: self.available_models.clone(),
             input_focus: self.input_focus,
             markdown_cache: CommonMarkCache::default(),
+            image_cache: ImageCache::default(),
             new_model_input: self.new_model_input.clone(),
             show_role_creator: self.show_role_creator,
             role_name_input: self.role_name_input.clone(),
             role_prompt_input: self.role_prompt_input.clone(),
             role_model_name: self.role_model_name.clone(),
             role_temperature: self.role_temperature,
+            role_greeting_input: self.role_greeting_input.clone(),
+            role_greeting_in_context: self.role_greeting_in_context,
+            role_provider: self.role_provider,
+            role_profile: self.role_profile.clone(),
+            profiles: self.profiles.clone(),
+            active_profile: self.active_profile.clone(),
             clear_chat_mode: self.clear_chat_mode,
-            input_height: self.input_height,
+            input_height_ratio: self.input_height_ratio,
+            input_content_height: self.input_content_height,
             dragging_input: self.dragging_input,
             search_query: self.search_query.clone(),
             is_loading: self.is_loading,
             loading_dots: self.loading_dots.clone(),
             loading_animation_timer: self.loading_animation_timer,
+            pending_config_reset: self.pending_config_reset,
+            emoji_search: self.emoji_search.clone(),
+            move_input_cursor_to_end: self.move_input_cursor_to_end,
+            show_input_preview: self.show_input_preview,
+            edit_large_input: self.edit_large_input,
+            snippets: self.snippets.clone(),
+            text_attachments: self.text_attachments.clone(),
+            pending_large_paste: self.pending_large_paste.clone(),
+            code_input_mode: self.code_input_mode,
+            code_language: self.code_language.clone(),
+            keybinding_mode: self.keybinding_mode,
+            send_key: self.send_key,
+            ime_state: self.ime_state.clone(),
+            reasoning_effort: self.reasoning_effort,
+            modal_state: self.modal_state.clone(),
+            follow_up_actions: self.follow_up_actions.clone(),
+            post_processors: self.post_processors.clone(),
+            post_process_sender,
+            post_process_receiver,
+            pricing: self.pricing.clone(),
+            capabilities: self.capabilities.clone(),
+            capability_probe: self.capability_probe.clone(),
+            image_unsupported_hold: self.image_unsupported_hold,
+            cost_ledger: self.cost_ledger.clone(),
+            queued_prompt: self.queued_prompt.clone(),
+            pending_manifest: None,
+            editing_message: None,
+            pending_edit_original: None,
+            viewing_manifest: self.viewing_manifest.clone(),
+            turn_retry: None,
+            retry_draft: None,
+            replay_setup: None,
+            replay: None,
+            compare_models: None,
+            comparison: None,
+            group_setup: None,
+            group_turn: None,
+            token_counter: TokenCounter::default(),
+            context_summaries: SummaryCache::default(),
+            memory_sender,
+            memory_receiver,
+            memory_updating: HashSet::new(),
+            journal_sender,
+            journal_receiver,
+            journal_updating: false,
+            journal_month: None,
+            journal_jump: None,
+            pending_chat_switch: self.pending_chat_switch.clone(),
+            queued_chat_switch: self.queued_chat_switch.clone(),
+            filter_hold: self.filter_hold.clone(),
+            show_task_panel: self.show_task_panel,
+            new_task_input: self.new_task_input.clone(),
+            auto_extract_tasks: self.auto_extract_tasks,
+            group_by_role: self.group_by_role,
+            ghost_text_enabled: self.ghost_text_enabled,
+            ghost_text_model: self.ghost_text_model.clone(),
+            smart_replies_enabled: self.smart_replies_enabled,
+            dictation_bridge_enabled: self.dictation_bridge_enabled,
+            match_reply_language: self.match_reply_language,
+            context_strategy: self.context_strategy,
+            context_keep_last: self.context_keep_last,
+            auto_memory: self.auto_memory,
+            entity_actions: self.entity_actions,
+            pending_command: None,
+            command_sender,
+            command_receiver,
+            capability_sender,
+            capability_receiver,
+            command_running: false,
+            command_output: None,
+            tts_model: self.tts_model.clone(),
+            tts_voice: self.tts_voice.clone(),
+            image_model: self.image_model.clone(),
+            embedding_model: self.embedding_model.clone(),
+            knowledge_top_k: self.knowledge_top_k,
+            metrics_enabled: self.metrics_enabled,
+            metrics: self.metrics.clone(),
+            show_metrics: self.show_metrics,
+            audio_player: AudioPlayer::default(),
+            speech_pending: None,
+            speech_sender,
+            speech_receiver,
+            image_pending: None,
+            image_sender,
+            image_receiver,
+            knowledge_indexing: None,
+            knowledge_sender,
+            knowledge_receiver,
+            workspace_paths: None,
+            workspace_loading: None,
+            workspace_sender,
+            workspace_receiver,
+            quick_ask_prompt: self.quick_ask_prompt.clone(),
+            quick_ask: None,
+            mask_secrets_in_exports: self.mask_secrets_in_exports,
+            auto_export_dir: self.auto_export_dir.clone(),
+            auto_exported: self.auto_exported.clone(),
+            last_auto_export: self.last_auto_export,
+            content_filter_mode: self.content_filter_mode,
+            content_filter_terms: self.content_filter_terms.clone(),
+            role_filter_mode: self.role_filter_mode,
+            role_filter_terms: self.role_filter_terms.clone(),
+            suggestion_model: self.suggestion_model.clone(),
+            reply_suggestions: self.reply_suggestions.clone(),
+            suggestion_sender,
+            suggestion_receiver,
+            dictation_sender,
+            dictation_receiver,
+            dictation_task: None,
+            inbox_sender,
+            inbox_receiver,
+            inbox_task: None,
+            platform: PlatformIntegration::default(),
+            ghost_text: GhostTextState::default(),
+            ghost_sender,
+            ghost_receiver,
+            task_sender,
+            task_receiver,
+            title_sender,
+            tee_writer: self.tee_writer.clone(),
+            title_receiver,
+            task_registry: TaskRegistry::default(),
+            show_task_debug: self.show_task_debug,
+            inspector: self.inspector.clone(),
+            failed_request: self.failed_request.clone(),
+            show_inspector: self.show_inspector,
+            notifications: self.notifications.clone(),
+            show_notifications: self.show_notifications,
+            notification_sender,
+            notification_receiver,
+            cache_config: self.cache_config.clone(),
+            title_config: self.title_config.clone(),
+            backup_config: self.backup_config.clone(),
+            backups: self.backups.clone(),
+            lock_config: self.lock_config.clone(),
+            locked: self.locked,
+            lock_input: String::new(),
+            lock_error: None,
+            last_activity: self.last_activity,
+            lock_setup: None,
+            title_progress: None,
+            last_cache_eviction: self.last_cache_eviction,
+            selected_chat_ids: self.selected_chat_ids.clone(),
+            selection_anchor: self.selection_anchor.clone(),
+            batch_tag_input: self.batch_tag_input.clone(),
+            tag_filter: self.tag_filter.clone(),
+            folder_input: self.folder_input.clone(),
+            batch_job: None,
+            confirm_batch_delete: false,
+            encrypted_export: None,
+            safe_mode: self.safe_mode,
+            restored_in_safe_mode: self.restored_in_safe_mode,
+            features: self.features.clone(),
+            settings_tab: self.settings_tab,
+            show_changelog: self.show_changelog,
+            show_template_import: self.show_template_import,
+            template_url_input: self.template_url_input.clone(),
+            template_loading: false,
+            template_error: self.template_error.clone(),
+            template_preview: self.template_preview.clone(),
+            share_link: self.share_link.clone(),
+            share_qr: self.share_qr.clone(),
+            chat_import: self.chat_import.clone(),
+            split_chat_id: self.split_chat_id.clone(),
+            template_sender,
+            template_receiver,
         }
     }
 }
 
 impl eframe::App for ChatApp {
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        debug!("应用退出，取消所有后台任务");
+        self.task_registry.abort_all();
+        self.runtime_handle.block_on(self.metrics.save());
+    }
+
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let frame_started = Instant::now();
+        self.image_cache.release_textures(ctx);
         // 如果正在接收消息流，设置较高的刷新率
         if self.receiver.is_some() {
             ctx.request_repaint_after(std::time::Duration::from_millis(16));
@@ -771,6 +7499,11 @@ impl eframe::App for ChatApp {
             ctx.set_visuals(egui::Visuals::light());
         }
 
+        self.update_window_title(ctx);
+        if self.update_lock(ctx) {
+            return;
+        }
+
         egui::SidePanel::left("chat_list_panel")
             .default_width(200.0)
             .show(ctx, |ui| {
@@ -800,21 +7533,30 @@ impl eframe::App for ChatApp {
                                         });
                                     });
                             });
+                            self.display_list_filter(ui);
                             ui.separator();
 
+                            // 多选时显示批量操作栏
+                            if !self.selected_chat_ids.is_empty() {
+                                self.display_batch_bar(ui);
+                                ui.separator();
+                            }
+
                             // 聊天列表区域 - 设置为充满剩余空间
                             ScrollArea::vertical()
                                 .auto_shrink([false; 2])
                                 .drag_to_scroll(false)
                                 .show(ui, |ui| {
-                                    let mut selected_messages = None;
-                                    let mut selected_id = None;
+                                    let mut click = None;
+                                    // 按显示顺序记录对话 ID，用于 Shift 范围选择
+                                    let mut display_order = Vec::new();
 
-                                    // 分别获取角色聊天和普通聊天
-                                    let (mut role_chats, mut normal_chats): (Vec<_>, Vec<_>) = self
+                                    // 分别获取角色聊天和普通聊天（已归档的单独显示）
+                                    let (archived_chats, active_chats): (Vec<_>, Vec<_>) = self
                                         .chat_list
                                         .chats
                                         .iter()
+                                        .filter(|chat| self.tag_filter.as_ref().is_none_or(|tag| chat.tags.contains(tag)))
                                         .filter(|chat| {
                                             if self.search_query.is_empty() {
                                                 return true;
@@ -824,77 +7566,168 @@ impl eframe::App for ChatApp {
                                             if chat.name.to_lowercase().contains(&query) {
                                                 return true;
                                             }
-                                            // 检查聊天内容
-                                            chat.messages.iter().any(|msg| 
+                                            // 检查聊天内容，无痕对话的内容不参与搜索
+                                            !chat.incognito && chat.messages.iter().any(|msg| 
                                                 msg.content.to_lowercase().contains(&query)
                                             )
                                         })
-                                        .partition(|chat| chat.name.starts_with("\u{f544}"));
+                                        .partition(|chat| chat.archived);
+                                    let (mut pinned_chats, active_chats): (Vec<_>, Vec<_>) = active_chats
+                                        .into_iter()
+                                        .partition(|chat| chat.pinned);
+                                    let (mut role_chats, mut normal_chats): (Vec<_>, Vec<_>) = active_chats
+                                        .into_iter()
+                                        .partition(|chat| chat.is_role());
+
+                                    // 置顶的对话按更新时间排序（新的在前）
+                                    pinned_chats.sort_by_key(|chat| std::cmp::Reverse(chat.updated_at));
 
                                     // 对普通聊天按更新时间排序（新的在前）
-                                    normal_chats.sort_by(|a, b| a.updated_at.cmp(&b.updated_at));
+                                    normal_chats.sort_by_key(|chat| chat.updated_at);
 
                                     // 对角色聊天按更新时间排序（新的在前）
-                                    role_chats.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
-
-                                    // 显示角色聊天
-                                    for chat in &role_chats {
-                                        let is_selected = self
-                                            .chat_list
-                                            .current_chat_id
-                                            .as_ref()
-                                            .map_or(false, |id| id == &chat.id);
-
-                                        ui.horizontal(|ui| {
-                                            ui.set_min_height(24.0);
-
-                                            let response = ui.selectable_label(
-                                                is_selected,
-                                                RichText::new(&chat.name),
-                                            );
+                                    role_chats.sort_by_key(|chat| std::cmp::Reverse(chat.updated_at));
+
+                                    let current_id = self.chat_list.current_chat_id.clone();
+                                    let mut show_item = |ui: &mut egui::Ui, chat: &Chat| {
+                                        display_order.push(chat.id.clone());
+                                        let is_current = current_id.as_ref() == Some(&chat.id);
+                                        let is_checked = self.selected_chat_ids.contains(&chat.id);
+                                        let folders = &self.chat_list.folders;
+                                        // 有文件夹时普通对话可以拖到文件夹标题上
+                                        let item_click = if folders.is_empty() || chat.is_role() || chat.archived {
+                                            chat_list_item(ui, chat, is_current, is_checked, folders)
+                                        } else {
+                                            ui.dnd_drag_source(egui::Id::new(("dragged_chat", &chat.id)), DraggedChat(chat.id.clone()), |ui| {
+                                                chat_list_item(ui, chat, is_current, is_checked, folders)
+                                            })
+                                            .inner
+                                        };
+                                        if let Some(item_click) = item_click {
+                                            click = Some(item_click);
+                                        }
+                                    };
+                                    let mut folder_click = None;
 
-                                            if response.clicked() {
-                                                selected_id = Some(chat.id.clone());
-                                                selected_messages = Some(chat.messages.clone());
-                                            }
-                                        });
+                                    // 置顶的对话始终显示在最上方
+                                    for chat in &pinned_chats {
+                                        show_item(ui, chat);
                                     }
-
-                                    // 添加分割线
-                                    if !role_chats.is_empty() && !normal_chats.is_empty() {
+                                    if !pinned_chats.is_empty() && (!role_chats.is_empty() || !normal_chats.is_empty()) {
                                         ui.separator();
                                     }
 
-                                    // 显示普通聊天（反转顺序）
-                                    for chat in normal_chats.iter().rev() {
-                                        // 这里添加 .rev()
-                                        let is_selected = self
-                                            .chat_list
-                                            .current_chat_id
-                                            .as_ref()
-                                            .map_or(false, |id| id == &chat.id);
+                                    if self.group_by_role {
+                                        // 按角色分组：每个角色下列出由它派生的对话
+                                        for role in &role_chats {
+                                            let children: Vec<_> = normal_chats
+                                                .iter()
+                                                .rev()
+                                                .filter(|chat| chat.role_id.as_ref() == Some(&role.id))
+                                                .collect();
+                                            egui::CollapsingHeader::new(format!("{} ({})", role.name, children.len()))
+                                                .id_salt(("role_group", &role.id))
+                                                .default_open(true)
+                                                .show(ui, |ui| {
+                                                    show_item(ui, role);
+                                                    for chat in children {
+                                                        show_item(ui, chat);
+                                                    }
+                                                });
+                                        }
 
-                                        ui.horizontal(|ui| {
-                                            ui.set_min_height(24.0);
+                                        // 没有来源角色（或角色不在列表中）的对话
+                                        let others: Vec<_> = normal_chats
+                                            .iter()
+                                            .rev()
+                                            .filter(|chat| {
+                                                chat.role_id
+                                                    .as_ref()
+                                                    .is_none_or(|role_id| !role_chats.iter().any(|role| &role.id == role_id))
+                                            })
+                                            .collect();
+                                        if !others.is_empty() {
+                                            egui::CollapsingHeader::new(format!("其他对话 ({})", others.len()))
+                                                .id_salt("role_group_others")
+                                                .default_open(true)
+                                                .show(ui, |ui| {
+                                                    for chat in others {
+                                                        show_item(ui, chat);
+                                                    }
+                                                });
+                                        }
+                                    } else {
+                                        // 显示角色聊天
+                                        for chat in &role_chats {
+                                            show_item(ui, chat);
+                                        }
 
-                                            let response = ui.selectable_label(
-                                                is_selected,
-                                                RichText::new(&chat.name),
-                                            );
+                                        // 添加分割线
+                                        if !role_chats.is_empty() && !normal_chats.is_empty() {
+                                            ui.separator();
+                                        }
 
-                                            if response.clicked() {
-                                                selected_id = Some(chat.id.clone());
-                                                selected_messages = Some(chat.messages.clone());
+                                        // 显示普通聊天（反转顺序），放入文件夹的对话显示在各自的文件夹下
+                                        let filtering = !self.search_query.is_empty() || self.tag_filter.is_some();
+                                        for folder in &self.chat_list.folders {
+                                            let children: Vec<_> = normal_chats
+                                                .iter()
+                                                .rev()
+                                                .filter(|chat| chat.folder.as_ref() == Some(folder))
+                                                .collect();
+                                            if filtering && children.is_empty() {
+                                                continue;
                                             }
-                                        });
-                                    }
+                                            let response = egui::CollapsingHeader::new(format!("\u{f07b} {} ({})", folder, children.len()))
+                                                .id_salt(("chat_folder", folder))
+                                                .default_open(true)
+                                                .show(ui, |ui| {
+                                                    for chat in children {
+                                                        show_item(ui, chat);
+                                                    }
+                                                })
+                                                .header_response;
+                                            if response.dnd_hover_payload::<DraggedChat>().is_some() {
+                                                ui.painter().rect_stroke(response.rect, 2.0, ui.visuals().selection.stroke);
+                                            }
+                                            if let Some(dragged) = response.dnd_release_payload::<DraggedChat>() {
+                                                folder_click = Some(SidebarClick::MoveToFolder(dragged.0.clone(), Some(folder.clone())));
+                                            }
+                                            response.context_menu(|ui| {
+                                                if ui.button("删除文件夹").clicked() {
+                                                    folder_click = Some(SidebarClick::DeleteFolder(folder.clone()));
+                                                    ui.close_menu();
+                                                }
+                                            });
+                                        }
 
-                                    if let Some(id) = selected_id {
-                                        self.chat_list.current_chat_id = Some(id);
-                                        if let Some(messages) = selected_messages {
-                                            self.handle_message_selection(messages);
+                                        // 未放入文件夹（或文件夹已不存在）的对话
+                                        for chat in normal_chats.iter().rev().filter(|chat| {
+                                            chat.folder
+                                                .as_ref()
+                                                .is_none_or(|folder| !self.chat_list.folders.contains(folder))
+                                        }) {
+                                            show_item(ui, chat);
                                         }
                                     }
+
+                                    // 已归档的对话折叠显示在最后；搜索时自动展开，便于找到匹配的归档对话
+                                    if !archived_chats.is_empty() {
+                                        ui.separator();
+                                        egui::CollapsingHeader::new(format!("已归档 ({})", archived_chats.len()))
+                                            .id_salt("archived_chats")
+                                            .default_open(false)
+                                            .open((!self.search_query.is_empty()).then_some(true))
+                                            .show(ui, |ui| {
+                                                for chat in &archived_chats {
+                                                    show_item(ui, chat);
+                                                }
+                                            });
+                                    }
+
+                                    if let Some(click) = click.or(folder_click) {
+                                        self.handle_sidebar_click(click, &display_order);
+                                    }
                                 });
 
                             // 底部齿轮按钮
@@ -911,6 +7744,50 @@ impl eframe::App for ChatApp {
                                         self.show_role_creator = !self.show_role_creator;
                                     }
 
+                                    if self.lock_config.is_active() && ui.small_button("\u{f023}").on_hover_text("立即锁定").clicked() {
+                                        // nf-fa-lock 锁定应用
+                                        self.lock();
+                                    }
+
+                                    if ui.small_button("\u{f073}").on_hover_text("日记").clicked() {
+                                        // nf-fa-calendar 每天自动分段的日记对话
+                                        self.open_journal();
+                                    }
+
+                                    if ui.small_button("\u{f0c0}").on_hover_text("新建群聊").clicked() {
+                                        // nf-fa-users 多个角色轮流回复的群聊
+                                        self.group_setup = Some((String::new(), Vec::new()));
+                                    }
+
+                                    if ui
+                                        .selectable_label(self.group_by_role, "\u{f0e8}")
+                                        .on_hover_text("按角色分组")
+                                        .clicked()
+                                    {
+                                        // nf-fa-sitemap 侧边栏按角色分组
+                                        self.group_by_role = !self.group_by_role;
+                                        if let Err(e) = self.save_config(frame) {
+                                            error!("保存配置失败: {}", e);
+                                        }
+                                    }
+
+                                    if ui.small_button("\u{f188}").on_hover_text("后台任务").clicked() {
+                                        // nf-fa-bug 后台任务调试视图
+                                        self.show_task_debug = !self.show_task_debug;
+                                    }
+
+                                    if ui.small_button("\u{f1c9}").on_hover_text("请求检查器").clicked() {
+                                        // nf-fa-file_code_o 最近一次请求的原始数据
+                                        self.show_inspector = !self.show_inspector;
+                                    }
+
+                                    let unread = self.notifications.unread();
+                                    let bell = if unread > 0 { format!("\u{f0f3} {}", unread) } else { "\u{f0f3}".to_string() };
+                                    if ui.small_button(bell).on_hover_text("通知").clicked() {
+                                        // nf-fa-bell 后台任务的通知
+                                        self.show_notifications = !self.show_notifications;
+                                    }
+
                                     // 主题切换按钮
                                     if ui
                                         .small_button(if self.dark_mode {
@@ -934,85 +7811,173 @@ impl eframe::App for ChatApp {
                 // 修改删除快捷键检查的部分
                 if ui.input(|i| i.modifiers.command && i.key_pressed(egui::Key::Backspace)) {
                     if let Some(current_id) = self.chat_list.current_chat_id.clone() {
-                        debug!("开始删除对话: {}", current_id);
-
-                        // 获取要删除的对话
-                        if let Some(chat) = self.chat_list.chats.iter().find(|c| c.id == current_id)
-                        {
-                            debug!("找到要删除的对话: {} ({})", chat.name, chat.id);
-                            // 删除所有相关的缓存图片
-                            let messages = chat.messages.clone();
-                            let runtime_handle = self.runtime_handle.clone();
-                            debug!("开始清理对话的图片缓存，消数: {}", messages.len());
-
-                            runtime_handle.spawn(async move {
-                                for (index, msg) in messages.iter().enumerate() {
-                                    if let Some(image_path) = &msg.image_path {
-                                        debug!("处理第 {} 条消息的图片: {}", index + 1, image_path);
-                                        if let Err(e) = utils::remove_cached_image(image_path).await
-                                        {
-                                            error!(
-                                                "删除第 {} 条消的缓存图片失败: {} - {}",
-                                                index + 1,
-                                                image_path,
-                                                e
-                                            );
-                                        }
-                                    }
-                                }
-                                debug!("图片缓存清理完成");
-                            });
-                        } else {
-                            debug!("未找到要删除的对话: {}", current_id);
-                        }
-
-                        // 如果删除的是当前选中的对话，清空聊天历史
-                        self.chat_history.0.clear();
-                        self.chat_list.current_chat_id = None;
-
-                        // 从列表中移除对话
-                        self.chat_list.chats.retain(|chat| chat.id != current_id);
-
-                        // 如果删除后没有对话了，创建一个新的
-                        if self.chat_list.chats.is_empty() {
-                            self.new_chat();
-                        } else {
-                            // 如果当前没有选中的对话，选中第一个
-                            if let Some(first_chat) = self.chat_list.chats.first() {
-                                self.chat_list.current_chat_id = Some(first_chat.id.clone());
-                                self.handle_message_selection(first_chat.messages.clone());
-                            }
-                        }
+                        self.delete_chat(&current_id);
+                        self.select_fallback_chat();
                         // 保存更改
                         let _ = self.save_chat_list();
-
-                        debug!("对话删除完成");
                     }
                 }
             });
 
-        egui::TopBottomPanel::top("top_panel")
-            .resizable(true)
-            .height_range(350.0..=500.0)
-            .default_height(500.0)
+        self.task_registry.prune_finished();
+        self.receive_title_updates();
+        self.receive_memory_updates();
+        self.receive_journal_updates();
+        self.receive_command_output();
+        self.receive_capabilities(frame);
+        self.expire_incognito_chats();
+        self.receive_extracted_tasks();
+        self.receive_template();
+        self.receive_proxy_test();
+        self.receive_speech();
+        self.receive_generated_image();
+        self.poll_turn_retry(ctx);
+        self.process_replay(ctx);
+        self.poll_comparison(ctx);
+        self.process_group_turn(ctx);
+        self.receive_knowledge_index();
+        self.receive_workspace_paths();
+        self.receive_memories();
+        self.receive_post_processed();
+        self.receive_ghost_text();
+        self.receive_reply_suggestions();
+        self.sync_dictation_bridge(ctx);
+        self.receive_dictation();
+        self.start_inbox(ctx);
+        self.receive_inbox(ctx);
+        self.schedule_cache_eviction();
+        self.schedule_auto_export();
+        while let Ok(text) = self.notification_receiver.try_recv() {
+            self.notifications.push(text);
+        }
+        self.sync_platform(frame);
+
+        // Ctrl+Shift+E：用主选区内容快速提问；只在窗口获得焦点时有效，其他程序中通过 quick-ask 命令触发
+        let quick_ask_shortcut = egui::KeyboardShortcut::new(
+            egui::Modifiers::COMMAND | egui::Modifiers::SHIFT,
+            egui::Key::E,
+        );
+        if ctx.input_mut(|i| i.consume_shortcut(&quick_ask_shortcut)) {
+            self.start_quick_ask();
+        }
+        self.display_quick_ask(ctx);
+        if self.show_task_panel {
+            self.display_task_panel(ctx);
+        }
+        self.display_split_view(ctx);
+
+        // 聊天记录在上、输入区在下：输入区高度按窗口比例计算并随内容增高，拖动分隔条时改为手动调整
+        let window_height = ctx.available_rect().height();
+        let resizing_input = ctx
+            .read_response(egui::Id::new("top_panel").with("__resize"))
+            .is_some_and(|response| response.dragged());
+        let top_panel = egui::TopBottomPanel::top("top_panel").resizable(true);
+        let top_panel = if resizing_input {
+            let min_height = window_height * (1.0 - MAX_INPUT_HEIGHT_RATIO);
+            top_panel.height_range(min_height..=(window_height - MIN_INPUT_HEIGHT).max(min_height))
+        } else {
+            top_panel.exact_height(window_height - self.input_area_height(window_height))
+        };
+        let top_panel_response = top_panel
             .show(ctx, |ui| {
                 // 添加顶部栏
                 ui.add_space(7.0);  // 在顶部添加空白
                 ui.horizontal(|ui| {
+                    // 配置档案快速切换
+                    if !self.profiles.is_empty() {
+                        let mut selected = self.active_profile.clone();
+                        egui::ComboBox::from_id_salt("profile_switcher")
+                            .selected_text(selected.as_deref().unwrap_or("默认配置"))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut selected, None, "默认配置");
+                                for profile in &self.profiles {
+                                    ui.selectable_value(&mut selected, Some(profile.name.clone()), &profile.name);
+                                }
+                            });
+                        if selected != self.active_profile {
+                            self.switch_profile(selected, frame);
+                        }
+                        ui.separator();
+                    }
+
+                    // 参数预设按钮
+                    let active_preset = ParamPreset::matching(self.current_sampling_params());
+                    for preset in ParamPreset::ALL {
+                        if ui
+                            .selectable_label(active_preset == Some(preset), preset.label())
+                            .on_hover_text(preset.description())
+                            .clicked()
+                        {
+                            self.apply_preset(preset, frame);
+                        }
+                    }
+
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                         if ui.small_button("\u{f067}").clicked() {
-                            self.new_chat();
+                            self.request_chat_switch(ChatSwitch::New);
+                        }
+                        if ui.small_button("\u{f21b}")
+                            .on_hover_text("新建无痕对话：只保存在内存中，关闭或长时间无操作后清除")
+                            .clicked()
+                        {
+                            self.request_chat_switch(ChatSwitch::NewIncognito);
+                        }
+                        if ui.selectable_label(self.show_task_panel, "\u{f0ae}")
+                            .on_hover_text("任务清单")
+                            .clicked()
+                        {
+                            self.show_task_panel = !self.show_task_panel;
+                        }
+                        if ui.selectable_label(self.split_chat_id.is_some(), "\u{f0db}")
+                            .on_hover_text("分屏对照另一个对话")
+                            .clicked()
+                        {
+                            self.toggle_split_view();
+                        }
+                        let chat_cost = costs::messages_cost(&self.chat_history.0);
+                        if chat_cost > 0.0 {
+                            ui.label(RichText::new(costs::format_cost(chat_cost))
+                                .small()
+                                .color(egui::Color32::GRAY))
+                                .on_hover_text("本对话累计费用");
                         }
                     });
                 });
                 ui.add_space(2.0); 
                 ui.separator();
+                if self.restored_in_safe_mode {
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new("\u{f071} 已从备份恢复，以普通模式重新启动后加载恢复的配置和对话记录")
+                            .color(egui::Color32::from_rgb(230, 160, 0)));
+                        if ui.small_button("立即重启").clicked() {
+                            restart_without_safe_mode(ui.ctx());
+                        }
+                    });
+                } else if self.safe_mode {
+                    ui.label(RichText::new("\u{f071} 安全模式：未加载配置和对话记录，本次运行中的修改不会保存")
+                        .color(egui::Color32::from_rgb(230, 160, 0)));
+                }
+                self.display_outage_banner(ui);
 
                 // 聊天历史记录区域
                 ScrollArea::vertical()
                     .auto_shrink([false; 2])
                     .stick_to_bottom(true)
                     .show(ui, |ui| {
+                        // 在所有消息之前显示角色开场白
+                        if let Some(greeting) = self
+                            .current_chat_config()
+                            .and_then(|config| config.greeting_text())
+                            .map(str::to_string)
+                        {
+                            self.display_message(ui, &Message::new_assistant(greeting));
+                            if !self.chat_history.0.is_empty() {
+                                ui.add_space(4.0);
+                                ui.separator();
+                                ui.add_space(4.0);
+                            }
+                        }
+
                         let messages = self.chat_history.0.clone();
                         for (i, msg) in messages.iter().enumerate() {
                             if i > 0 && i % 2 == 0 {
@@ -1020,7 +7985,66 @@ impl eframe::App for ChatApp {
                                 ui.separator();
                                 ui.add_space(4.0);
                             }
+                            if self.editing_message.as_ref().is_some_and(|(index, _)| *index == i) {
+                                self.display_message_editor(ui, i);
+                                continue;
+                            }
                             self.display_message(ui, msg);
+                            if msg.original_content.is_some() {
+                                self.display_edit_diff(ui, i, msg);
+                            }
+                            if let Some(raw) = &msg.raw_content {
+                                egui::CollapsingHeader::new(RichText::new("已后处理，查看原文").small().color(egui::Color32::GRAY))
+                                    .id_salt(("raw_reply", i))
+                                    .default_open(false)
+                                    .show(ui, |ui| {
+                                        ui.label(RichText::new(raw).weak());
+                                    });
+                            }
+                            if !self.is_loading {
+                                if msg.role == "user" && msg.kind.is_chat()
+                                    && ui.small_button("\u{f044}").on_hover_text("编辑并重新发送").clicked()
+                                {
+                                    self.editing_message = Some((i, msg.content.clone()));
+                                }
+                                if msg.kind.is_chat()
+                                    && !msg.content.trim().is_empty()
+                                    && ui.small_button("\u{f02e}").on_hover_text("记住：提炼成一条长期记忆，在之后的所有对话中参考").clicked()
+                                {
+                                    self.remember_message(msg.content.clone());
+                                }
+                                // 失败的回复也可以重试
+                                if msg.is_reply() || msg.kind == MessageKind::Error {
+                                    self.display_retry_menu(ui, i, msg);
+                                }
+                                if !msg.previous_versions.is_empty() {
+                                    self.display_reply_versions(ui, i, msg);
+                                }
+                                if msg.is_reply() {
+                                    if let Some(diff) = messages[..i].iter().rev().find(|m| m.role == "user").and_then(review::message_diff) {
+                                        self.display_review(ui, i, diff, msg);
+                                    }
+                                    let is_latest = i + 1 == messages.len();
+                                    self.display_follow_up_chips(ui, msg, is_latest);
+                                }
+                            }
+                        }
+
+                        // 群聊中正在回复的角色
+                        let group_reply = self
+                            .group_turn
+                            .as_ref()
+                            .filter(|turn| self.chat_list.current_chat_id.as_ref() == Some(&turn.chat_id))
+                            .and_then(|turn| turn.current.as_ref())
+                            .map(|reply| (reply.name.clone(), reply.stream.reply.content.clone()));
+                        if let Some((name, content)) = group_reply {
+                            ui.add_space(4.0);
+                            ui.separator();
+                            ui.horizontal(|ui| {
+                                message_header(ui, &format!("{}:", name), None);
+                                ui.spinner();
+                            });
+                            self.render_markdown(ui, &content);
                         }
 
                         // 在消息列表底部显示加载状态
@@ -1042,9 +8066,53 @@ impl eframe::App for ChatApp {
                                     .italics()
                                     .color(egui::Color32::GRAY));
                             });
+                            self.display_stall_indicator(ui);
+                        }
+                        if self.knowledge_indexing.is_some() && self.knowledge_indexing == self.chat_list.current_chat_id {
+                            ui.add_space(8.0);
+                            ui.horizontal(|ui| {
+                                ui.spinner();
+                                ui.label(RichText::new("正在建立知识库索引...").italics().color(egui::Color32::GRAY));
+                            });
+                        }
+                        if self.image_pending.is_some() && self.image_pending == self.chat_list.current_chat_id {
+                            ui.add_space(8.0);
+                            ui.horizontal(|ui| {
+                                ui.spinner();
+                                ui.label(RichText::new("正在生成图片...").italics().color(egui::Color32::GRAY));
+                            });
                         }
                     });
             });
+        if resizing_input {
+            let input_height = window_height - top_panel_response.response.rect.height();
+            self.input_height_ratio = (input_height / window_height).clamp(0.0, MAX_INPUT_HEIGHT_RATIO);
+            self.dragging_input = true;
+        } else if self.dragging_input {
+            // 拖动结束后保存比例，下次启动或窗口大小变化时按比例恢复
+            self.dragging_input = false;
+            if let Err(e) = self.save_config(frame) {
+                error!("保存配置失败: {}", e);
+            }
+        }
+
+        // 发送通过快捷按钮排队的追问
+        if let Some(prompt) = self.queued_prompt.take() {
+            self.send_prompt(prompt);
+        }
+
+        // 回复完成后执行排队的对话切换
+        if !self.is_loading {
+            if let Some(switch) = self.queued_chat_switch.take() {
+                self.perform_chat_switch(switch);
+            }
+        }
+        self.display_switch_confirmation(ctx);
+        self.display_command_runner(ctx);
+        self.display_memory_manager(ctx);
+        self.display_filter_hold(ctx);
+        self.display_image_unsupported_hold(ctx);
+        self.display_manifest_window(ctx);
 
         // 修改中央面板，移除顶部的连续聊天项
         egui::CentralPanel::default().show(ctx, |ui| {
@@ -1062,10 +8130,37 @@ impl eframe::App for ChatApp {
                         .show(ctx, |ui| {
                             let mut config_changed = false;
 
+                            ui.horizontal(|ui| {
+                                ui.selectable_value(&mut self.settings_tab, SettingsTab::General, "常规");
+                                ui.selectable_value(&mut self.settings_tab, SettingsTab::Experimental, "实验功能");
+                            });
+                            ui.separator();
+                            if self.settings_tab == SettingsTab::Experimental {
+                                if self.display_experimental_settings(ui) {
+                                    if let Err(e) = self.save_config(frame) {
+                                        error!("保存配置失败: {}", e);
+                                    }
+                                }
+                                return;
+                            }
+
                             egui::Grid::new("settings_grid")
                                 .num_columns(2)
                                 .spacing([8.0, 4.0])
                                 .show(ui, |ui| {
+                                    // 默认服务商
+                                    ui.label("服务商:");
+                                    egui::ComboBox::from_id_salt("provider_selector")
+                                        .selected_text(self.api_provider.label())
+                                        .show_ui(ui, |ui| {
+                                            for provider in ProviderKind::ALL {
+                                                if ui.selectable_value(&mut self.api_provider, provider, provider.label()).changed() {
+                                                    config_changed = true;
+                                                }
+                                            }
+                                        });
+                                    ui.end_row();
+
                                     // API Key 设置
                                     ui.label("API Key:");
                                     if ui.add(TextEdit::singleline(&mut self.api_key)
@@ -1083,13 +8178,184 @@ impl eframe::App for ChatApp {
                                     }
                                     ui.end_row();
 
+                                    // 其他服务商的连接设置
+                                    if provider_config_rows(ui, "Claude", ProviderKind::Claude, &mut self.claude_config) {
+                                        config_changed = true;
+                                    }
+                                    if provider_config_rows(ui, "Gemini", ProviderKind::Gemini, &mut self.gemini_config) {
+                                        config_changed = true;
+                                    }
+                                    if azure_config_rows(ui, &mut self.azure_config) {
+                                        config_changed = true;
+                                    }
+                                    if profile_rows(ui, &mut self.profiles) {
+                                        config_changed = true;
+                                    }
+
+                                    if failover_config_rows(ui, &mut self.failover) {
+                                        config_changed = true;
+                                    }
+
+                                    if proxy_config_rows(ui, &mut self.proxy_config) {
+                                        self.rebuild_client();
+                                        config_changed = true;
+                                    }
+                                    if network_config_rows(ui, &mut self.network_config) {
+                                        self.rebuild_client();
+                                        config_changed = true;
+                                    }
+                                    ui.label("");
+                                    ui.horizontal(|ui| {
+                                        if self.proxy_testing {
+                                            ui.spinner();
+                                        } else if ui.button("测试连接").clicked() {
+                                            self.test_proxy_connection();
+                                        }
+                                        if let Some(e) = &self.network_error {
+                                            ui.colored_label(egui::Color32::RED, e);
+                                        } else {
+                                            match &self.proxy_test_result {
+                                                Some(Ok(message)) => {
+                                                    ui.colored_label(egui::Color32::GREEN, message);
+                                                }
+                                                Some(Err(e)) => {
+                                                    ui.colored_label(egui::Color32::RED, e);
+                                                }
+                                                None => {}
+                                            }
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    // 配置和对话记录所在的目录，点击在文件管理器中打开
+                                    ui.label("数据目录:");
+                                    ui.vertical(|ui| {
+                                        for (label, dir) in [("配置", paths::config_dir()), ("对话", paths::data_dir())] {
+                                            ui.horizontal(|ui| {
+                                                ui.label(format!("{}:", label));
+                                                ui.label(RichText::new(dir.display().to_string()).monospace());
+                                                if ui.small_button("\u{f07c}").on_hover_text("打开").clicked() {
+                                                    if let Some(url) = entities::file_url(&dir.to_string_lossy()) {
+                                                        ui.ctx().open_url(egui::OpenUrl::new_tab(url));
+                                                    }
+                                                }
+                                            });
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    ui.label("图片缓存:");
+                                    ui.horizontal(|ui| {
+                                        ui.label(RichText::new(&self.cache_config.image_dir).monospace());
+                                        if ui.small_button("更改...").clicked() {
+                                            if let Some(dir) = FileDialog::new().pick_folder() {
+                                                self.move_image_cache(dir);
+                                                config_changed = true;
+                                            }
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    ui.label("缓存上限:");
+                                    ui.horizontal(|ui| {
+                                        let response = ui.add(egui::DragValue::new(&mut self.cache_config.max_size_mb)
+                                            .range(0..=102_400)
+                                            .suffix(" MB"));
+                                        if response.changed() {
+                                            config_changed = true;
+                                        }
+                                        if response.drag_stopped() || response.lost_focus() {
+                                            self.last_cache_eviction = None;
+                                        }
+                                        ui.label(RichText::new("0 表示不限制").small().color(egui::Color32::GRAY));
+                                    });
+                                    ui.end_row();
+
+                                    // 语音缓存可以重新合成，更改目录时不迁移旧文件
+                                    ui.label("语音缓存:");
+                                    ui.horizontal(|ui| {
+                                        ui.label(RichText::new(&self.cache_config.audio_dir).monospace());
+                                        if ui.small_button("更改...").clicked() {
+                                            if let Some(dir) = FileDialog::new().pick_folder() {
+                                                self.cache_config.audio_dir = dir.to_string_lossy().to_string();
+                                                config_changed = true;
+                                            }
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    ui.label("语音缓存上限:");
+                                    ui.horizontal(|ui| {
+                                        let size = ui.add(egui::DragValue::new(&mut self.cache_config.audio_max_size_mb)
+                                            .range(0..=102_400)
+                                            .suffix(" MB"));
+                                        let age = ui.add(egui::DragValue::new(&mut self.cache_config.audio_max_age_days)
+                                            .range(0..=3650)
+                                            .prefix("保留 ")
+                                            .suffix(" 天"));
+                                        if size.changed() || age.changed() {
+                                            config_changed = true;
+                                        }
+                                        if size.drag_stopped() || size.lost_focus() || age.drag_stopped() || age.lost_focus() {
+                                            self.last_cache_eviction = None;
+                                        }
+                                        ui.label(RichText::new("0 表示不限制").small().color(egui::Color32::GRAY));
+                                    });
+                                    ui.end_row();
+
+                                    // 自动备份
+                                    ui.label("自动备份:");
+                                    ui.horizontal(|ui| {
+                                        if ui.checkbox(&mut self.backup_config.enabled, "启动时备份").changed() {
+                                            config_changed = true;
+                                        }
+                                        ui.add_enabled_ui(self.backup_config.enabled, |ui| {
+                                            if ui.checkbox(&mut self.backup_config.daily, "每天一次").changed() {
+                                                config_changed = true;
+                                            }
+                                            if ui.add(egui::DragValue::new(&mut self.backup_config.keep).range(1..=100).prefix("保留 ").suffix(" 份")).changed() {
+                                                config_changed = true;
+                                            }
+                                        });
+                                        if ui.small_button("从备份恢复...").clicked() {
+                                            self.backups = Some(self.runtime_handle.block_on(backup::list()));
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    // 应用锁
+                                    ui.label("应用锁:");
+                                    ui.horizontal(|ui| {
+                                        let has_passphrase = self.lock_config.has_passphrase();
+                                        if ui.add_enabled(has_passphrase, egui::Checkbox::new(&mut self.lock_config.enabled, "启动时要求密码"))
+                                            .on_disabled_hover_text("请先设置密码")
+                                            .changed()
+                                        {
+                                            config_changed = true;
+                                        }
+                                        ui.add_enabled_ui(self.lock_config.is_active(), |ui| {
+                                            if ui.add(egui::DragValue::new(&mut self.lock_config.idle_minutes).range(0..=720).prefix("空闲 ").suffix(" 分钟后锁定"))
+                                                .on_hover_text("0 表示只在启动时要求密码")
+                                                .changed()
+                                            {
+                                                config_changed = true;
+                                            }
+                                        });
+                                        let label = if has_passphrase { "修改密码..." } else { "设置密码..." };
+                                        if ui.small_button(label).clicked() {
+                                            self.lock_setup = Some((String::new(), String::new()));
+                                        }
+                                    });
+                                    ui.end_row();
+
                                     // 默认模型设置 - 改为下拉选择
                                     ui.label("默认模型:");
+                                    let model_choices = self.model_choices(self.active_profile.as_deref());
                                     egui::ComboBox::from_id_salt("default_model_selector")
                                         .selected_text(&self.model_name)
                                         .width(ui.available_width() - 60.0)
                                         .show_ui(ui, |ui| {
-                                            for model in &self.available_models {
+                                            for model in &model_choices {
                                                 if ui.selectable_value(&mut self.model_name, model.clone(), model).changed() {
                                                     config_changed = true;
                                                 }
@@ -1114,6 +8380,30 @@ impl eframe::App for ChatApp {
                                     }
                                     ui.end_row();
 
+                                    // Top P 设置
+                                    ui.label("Top P:");
+                                    if ui.add(egui::Slider::new(&mut self.top_p, 0.0..=1.0)
+                                        .step_by(0.05)).changed() {
+                                        config_changed = true;
+                                    }
+                                    ui.end_row();
+
+                                    // 频率惩罚设置
+                                    ui.label("频率惩罚:");
+                                    if ui.add(egui::Slider::new(&mut self.frequency_penalty, -2.0..=2.0)
+                                        .step_by(0.1)).changed() {
+                                        config_changed = true;
+                                    }
+                                    ui.end_row();
+
+                                    // 存在惩罚设置
+                                    ui.label("存在惩罚:");
+                                    if ui.add(egui::Slider::new(&mut self.presence_penalty, -2.0..=2.0)
+                                        .step_by(0.1)).changed() {
+                                        config_changed = true;
+                                    }
+                                    ui.end_row();
+
                                     // 添加重试设置
                                     ui.label("启用重试:");
                                     if ui.checkbox(&mut self.retry_enabled, "").changed() {
@@ -1128,6 +8418,60 @@ impl eframe::App for ChatApp {
                                     }
                                     ui.end_row();
 
+                                    // 流式回复停滞检测
+                                    ui.label("停滞检测:");
+                                    if ui.add(egui::DragValue::new(&mut self.stall_timeout_secs).range(0..=api::MAX_STALL_TIMEOUT_SECS).suffix(" 秒"))
+                                        .on_hover_text("回复中途超过这个时间没有新内容时提示停滞，可以选择继续等待、重试或取消；0 表示不检测")
+                                        .changed()
+                                    {
+                                        config_changed = true;
+                                    }
+                                    ui.end_row();
+
+                                    // 长期记忆
+                                    ui.label("长期记忆:");
+                                    ui.horizontal(|ui| {
+                                        if ui.checkbox(&mut self.long_term_memory, "发送消息时附加相关的记忆")
+                                            .on_hover_text("按嵌入相似度取出最相关的几条记忆，需要服务商支持嵌入接口")
+                                            .changed()
+                                        {
+                                            config_changed = true;
+                                        }
+                                        if ui.button(format!("管理 ({})...", self.memory_store.entries.len())).clicked() {
+                                            self.show_memory_manager = true;
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    // 无痕对话的自动清除时间
+                                    ui.label("无痕对话:");
+                                    if ui.add(egui::DragValue::new(&mut self.incognito_minutes).range(0..=1440).prefix("无操作 ").suffix(" 分钟后清除"))
+                                        .on_hover_text("无痕对话不写入对话记录和费用统计，切换到其他对话或关闭程序时清除；0 表示不按时间清除")
+                                        .changed()
+                                    {
+                                        config_changed = true;
+                                    }
+                                    ui.end_row();
+
+                                    ui.label("智能粘贴:");
+                                    if ui.checkbox(&mut self.smart_code_paste, "粘贴的文本像代码时自动包进代码块")
+                                        .on_hover_text("根据缩进、括号和关键字判断并标注语言，猜错时可在输入框上方撤销")
+                                        .changed()
+                                    {
+                                        config_changed = true;
+                                    }
+                                    ui.end_row();
+
+                                    // 回复完成后的 Webhook
+                                    ui.label("Webhook:");
+                                    if ui.add(TextEdit::singleline(&mut self.webhook_url).hint_text("https://..."))
+                                        .on_hover_text("每条回复完成后把对话 ID、最后一条用户消息、最终回复和用量以 JSON 发送到这个地址；对话可以在输入框上方单独设置")
+                                        .lost_focus()
+                                    {
+                                        config_changed = true;
+                                    }
+                                    ui.end_row();
+
                                     // 添加模型管部分
                                     ui.label("常用模:");
                                     ui.vertical(|ui| {
@@ -1153,24 +8497,511 @@ impl eframe::App for ChatApp {
                                             if ui.text_edit_singleline(&mut self.new_model_input).lost_focus()
                                                 && ui.input(|i| i.key_pressed(egui::Key::Enter))
                                                 && !self.new_model_input.is_empty()
+                                                && !self.available_models.contains(&self.new_model_input)
                                             {
-                                                if !self.available_models.contains(&self.new_model_input) {
-                                                    self.available_models.push(self.new_model_input.clone());
-                                                    self.new_model_input.clear();
+                                                self.available_models.push(self.new_model_input.clone());
+                                                self.new_model_input.clear();
+                                                config_changed = true;
+                                            }
+                                            if ui.small_button("加").clicked()
+                                                && !self.new_model_input.is_empty()
+                                                && !self.available_models.contains(&self.new_model_input)
+                                            {
+                                                self.available_models.push(self.new_model_input.clone());
+                                                self.new_model_input.clear();
+                                                config_changed = true;
+                                            }
+                                        });
+                                    });
+                                    ui.end_row();
+
+                                    // 文本缩写设置
+                                    ui.label("文本缩写:");
+                                    ui.vertical(|ui| {
+                                        let mut snippet_to_remove = None;
+                                        for (index, snippet) in self.snippets.iter_mut().enumerate() {
+                                            ui.horizontal(|ui| {
+                                                if ui.add(TextEdit::singleline(&mut snippet.trigger)
+                                                    .desired_width(60.0)
+                                                    .hint_text(";sig")).changed() {
+                                                    config_changed = true;
+                                                }
+                                                if ui.add(TextEdit::multiline(&mut snippet.expansion)
+                                                    .desired_rows(1)
+                                                    .desired_width(200.0)
+                                                    .hint_text("展开后的内容")).changed() {
                                                     config_changed = true;
                                                 }
+                                                if ui.small_button("\u{f1f8}").clicked() {
+                                                    snippet_to_remove = Some(index);
+                                                }
+                                            });
+                                        }
+                                        if let Some(index) = snippet_to_remove {
+                                            self.snippets.remove(index);
+                                            config_changed = true;
+                                        }
+                                        if ui.small_button("添加缩写").clicked() {
+                                            self.snippets.push(config::Snippet::default());
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    // 快捷追问设置
+                                    ui.label("快捷追问:");
+                                    ui.vertical(|ui| {
+                                        let mut action_to_remove = None;
+                                        for (index, action) in self.follow_up_actions.iter_mut().enumerate() {
+                                            ui.horizontal(|ui| {
+                                                if ui.add(TextEdit::singleline(&mut action.label)
+                                                    .desired_width(60.0)
+                                                    .hint_text("按钮文字")).changed() {
+                                                    config_changed = true;
+                                                }
+                                                if ui.add(TextEdit::singleline(&mut action.prompt)
+                                                    .desired_width(200.0)
+                                                    .hint_text("发送的提示词")).changed() {
+                                                    config_changed = true;
+                                                }
+                                                if ui.small_button("\u{f1f8}").clicked() {
+                                                    action_to_remove = Some(index);
+                                                }
+                                            });
+                                        }
+                                        if let Some(index) = action_to_remove {
+                                            self.follow_up_actions.remove(index);
+                                            config_changed = true;
+                                        }
+                                        if ui.small_button("添加追问").clicked() {
+                                            self.follow_up_actions.push(config::FollowUpAction::default());
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    // 回复后处理步骤，按顺序应用于每条完成的回复
+                                    ui.label("回复后处理:");
+                                    ui.vertical(|ui| {
+                                        let hooks_allowed = self.features.is_enabled(Feature::Plugins);
+                                        let mut processor_to_remove = None;
+                                        let mut processor_to_raise = None;
+                                        for (index, processor) in self.post_processors.iter_mut().enumerate() {
+                                            ui.horizontal(|ui| {
+                                                if ui.checkbox(&mut processor.enabled, "").on_hover_text("启用").changed() {
+                                                    config_changed = true;
+                                                }
+                                                if ui.add(TextEdit::singleline(&mut processor.name)
+                                                    .desired_width(80.0)
+                                                    .hint_text("名称")).changed() {
+                                                    config_changed = true;
+                                                }
+                                                egui::ComboBox::from_id_salt(("post_processor_kind", index))
+                                                    .width(80.0)
+                                                    .selected_text(processor.kind.label())
+                                                    .show_ui(ui, |ui| {
+                                                        for kind in StepKind::ALL {
+                                                            if ui.selectable_value(&mut processor.kind, kind, kind.label()).changed() {
+                                                                config_changed = true;
+                                                            }
+                                                        }
+                                                    });
+                                                match processor.kind {
+                                                    StepKind::Regex => {
+                                                        if ui.add(TextEdit::singleline(&mut processor.pattern)
+                                                            .desired_width(120.0)
+                                                            .hint_text("正则表达式")).changed() {
+                                                            config_changed = true;
+                                                        }
+                                                        if ui.add(TextEdit::singleline(&mut processor.replacement)
+                                                            .desired_width(100.0)
+                                                            .hint_text("替换为，可用 $1")).changed() {
+                                                            config_changed = true;
+                                                        }
+                                                    }
+                                                    StepKind::Hook => {
+                                                        if ui.add(TextEdit::singleline(&mut processor.command)
+                                                            .desired_width(224.0)
+                                                            .hint_text("命令，从标准输入读取回复")).changed() {
+                                                            config_changed = true;
+                                                        }
+                                                    }
+                                                }
+                                                if index > 0 && ui.small_button("\u{f062}").on_hover_text("上移").clicked() {
+                                                    processor_to_raise = Some(index);
+                                                }
+                                                if ui.small_button("\u{f1f8}").clicked() {
+                                                    processor_to_remove = Some(index);
+                                                }
+                                            });
+                                            if let Err(e) = processor.validate() {
+                                                ui.label(RichText::new(e).small().color(egui::Color32::RED));
+                                            } else if processor.kind == StepKind::Hook && !hooks_allowed {
+                                                ui.label(RichText::new("需要在实验功能中开启“插件”后才会运行").small().color(egui::Color32::YELLOW));
+                                            }
+                                        }
+                                        if let Some(index) = processor_to_raise {
+                                            self.post_processors.swap(index - 1, index);
+                                            config_changed = true;
+                                        }
+                                        if let Some(index) = processor_to_remove {
+                                            self.post_processors.remove(index);
+                                            config_changed = true;
+                                        }
+                                        if ui.small_button("添加步骤").clicked() {
+                                            self.post_processors.push(PostProcessor::default());
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    // 任务清单自动提取
+                                    ui.label("自动提取任务:");
+                                    if ui.checkbox(&mut self.auto_extract_tasks, "每次回复后更新任务清单").changed() {
+                                        config_changed = true;
+                                    }
+                                    ui.end_row();
+
+                                    // 导出时隐藏密钥
+                                    // 费用统计
+                                    ui.label("费用:");
+                                    ui.vertical(|ui| {
+                                        ui.label(format!(
+                                            "今日 {} · 累计 {}",
+                                            costs::format_cost(self.cost_ledger.today()),
+                                            costs::format_cost(self.cost_ledger.total())
+                                        ));
+                                        ui.label(RichText::new("单价在 dream.toml 的 [pricing] 中按模型配置")
+                                            .small()
+                                            .color(egui::Color32::GRAY));
+                                    });
+                                    ui.end_row();
+
+                                    ui.label("隐藏密钥:");
+                                    if ui.checkbox(&mut self.mask_secrets_in_exports, "导出对话时自动隐藏疑似密钥").changed() {
+                                        config_changed = true;
+                                    }
+                                    ui.end_row();
+
+                                    // 对话有变化时自动导出 Markdown 到指定目录
+                                    ui.label("自动导出:");
+                                    ui.horizontal(|ui| {
+                                        if self.auto_export_dir.is_empty() {
+                                            ui.label(RichText::new("未开启").color(egui::Color32::GRAY));
+                                        } else {
+                                            ui.label(RichText::new(&self.auto_export_dir).monospace());
+                                        }
+                                        if ui.small_button("选择文件夹...").clicked() {
+                                            if let Some(dir) = FileDialog::new().pick_folder() {
+                                                self.auto_export_dir = dir.to_string_lossy().to_string();
+                                                // 新目录中还没有任何文件，全部重新导出
+                                                self.auto_exported.clear();
+                                                config_changed = true;
+                                            }
+                                        }
+                                        if !self.auto_export_dir.is_empty() && ui.small_button("关闭").clicked() {
+                                            self.auto_export_dir.clear();
+                                            config_changed = true;
+                                        }
+                                    }).response.on_hover_text("对话更新后把 Markdown 记录同步到该文件夹，可以选择同步盘中的目录");
+                                    ui.end_row();
+
+                                    // 发送前的内容过滤
+                                    ui.label("内容过滤:");
+                                    ui.vertical(|ui| {
+                                        egui::ComboBox::from_id_salt("content_filter_selector")
+                                            .selected_text(self.content_filter_mode.label())
+                                            .show_ui(ui, |ui| {
+                                                for mode in FilterMode::ALL {
+                                                    if ui.selectable_value(&mut self.content_filter_mode, mode, mode.label()).changed() {
+                                                        config_changed = true;
+                                                    }
+                                                }
+                                            });
+                                        if ui.add(TextEdit::multiline(&mut self.content_filter_terms)
+                                            .hint_text("每行一条，/…/ 包围的按正则匹配")
+                                            .desired_rows(3)
+                                            .desired_width(ui.available_width() - 60.0)).changed() {
+                                            config_changed = true;
+                                        }
+                                        let terms = content_filter::parse_terms(&self.content_filter_terms);
+                                        if let Err(e) = content_filter::validate_terms(&terms) {
+                                            ui.colored_label(egui::Color32::RED, e.to_string());
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    // 主选区快速提问
+                                    ui.label("快速提问:");
+                                    ui.vertical(|ui| {
+                                        if ui.add(TextEdit::singleline(&mut self.quick_ask_prompt)
+                                            .hint_text(quick_ask::DEFAULT_PROMPT)
+                                            .desired_width(ui.available_width() - 60.0)).changed() {
+                                            config_changed = true;
+                                        }
+                                        ui.label(RichText::new("Ctrl+Shift+E 对选中的文字提问（Linux 主选区），只在 dream 窗口获得焦点时有效")
+                                            .small()
+                                            .color(egui::Color32::GRAY));
+                                        ui.label(RichText::new(format!("要在其他程序中使用，请在桌面环境的快捷键设置中绑定命令: {}", *quick_ask::COMMAND))
+                                            .small()
+                                            .color(egui::Color32::GRAY));
+                                    });
+                                    ui.end_row();
+
+                                    // 回复语言
+                                    ui.label("回复语言:");
+                                    if ui.checkbox(&mut self.match_reply_language, "始终使用与我的消息相同的语言回复")
+                                        .on_hover_text("在请求中附加隐藏指令，不修改系统提示词")
+                                        .changed()
+                                    {
+                                        config_changed = true;
+                                    }
+                                    ui.end_row();
+
+                                    // 自动生成的对话标题
+                                    ui.label("对话标题:");
+                                    ui.vertical(|ui| {
+                                        if ui.checkbox(&mut self.title_config.enabled, "用模型生成标题")
+                                            .on_hover_text("关闭后根据第一条消息在本地生成标题，不发送请求")
+                                            .changed()
+                                        {
+                                            config_changed = true;
+                                        }
+                                        ui.horizontal(|ui| {
+                                            ui.label("模型:");
+                                            if ui.add_enabled(self.title_config.enabled, TextEdit::singleline(&mut self.title_config.model)
+                                                .hint_text("留空使用对话的模型")
+                                                .desired_width(180.0)).changed() {
+                                                config_changed = true;
                                             }
-                                            if ui.small_button("加").clicked() && !self.new_model_input.is_empty() {
-                                                if !self.available_models.contains(&self.new_model_input) {
-                                                    self.available_models.push(self.new_model_input.clone());
-                                                    self.new_model_input.clear();
+                                        });
+                                        egui::ComboBox::from_id_salt("title_language")
+                                            .selected_text(self.title_config.language.label())
+                                            .show_ui(ui, |ui| {
+                                                for language in TitleLanguage::ALL {
+                                                    if ui.selectable_value(&mut self.title_config.language, language, language.label()).changed() {
+                                                        config_changed = true;
+                                                    }
+                                                }
+                                            });
+                                        ui.collapsing("标题提示词", |ui| {
+                                            for (language, unit) in [(TitleLanguage::Chinese, " 字"), (TitleLanguage::Japanese, " 字"), (TitleLanguage::English, " 词")] {
+                                                let locale = self.title_config.locale_mut(language);
+                                                ui.horizontal(|ui| {
+                                                    ui.label(language.label());
+                                                    ui.label("长度上限:");
+                                                    if ui.add(egui::DragValue::new(&mut locale.max_length).range(1..=50).suffix(unit)).changed() {
+                                                        config_changed = true;
+                                                    }
+                                                });
+                                                if ui.add(TextEdit::multiline(&mut locale.prompt)
+                                                    .desired_rows(2)
+                                                    .desired_width(ui.available_width() - 60.0)).changed() {
                                                     config_changed = true;
                                                 }
                                             }
+                                            ui.label(RichText::new("{max} 会替换为长度上限").small().color(egui::Color32::GRAY));
                                         });
                                     });
                                     ui.end_row();
 
+                                    // 上下文裁剪
+                                    ui.label("上下文裁剪:");
+                                    ui.horizontal(|ui| {
+                                        egui::ComboBox::from_id_salt("context_strategy")
+                                            .selected_text(self.context_strategy.label())
+                                            .show_ui(ui, |ui| {
+                                                for strategy in ContextStrategy::ALL {
+                                                    if ui.selectable_value(&mut self.context_strategy, strategy, strategy.label()).changed() {
+                                                        config_changed = true;
+                                                    }
+                                                }
+                                            });
+                                        if self.context_strategy == ContextStrategy::KeepLast
+                                            && ui.add(egui::DragValue::new(&mut self.context_keep_last).range(1..=500).suffix(" 条")).changed()
+                                        {
+                                            config_changed = true;
+                                        }
+                                    }).response.on_hover_text("对话超出模型的上下文窗口时如何处理较早的消息；摘要会额外发送一次请求");
+                                    ui.end_row();
+
+                                    // 对话记忆
+                                    ui.label("对话记忆:");
+                                    if ui.checkbox(&mut self.auto_memory, "在后台把长对话中较早的消息摘要成记忆")
+                                        .on_hover_text(format!("最近 {} 条消息之前的内容以摘要代替发送，摘要会额外发送请求", memory::KEEP_RECENT))
+                                        .changed()
+                                    {
+                                        config_changed = true;
+                                    }
+                                    ui.end_row();
+
+                                    // 回复中的实体快捷操作
+                                    ui.label("快捷操作:");
+                                    if ui.checkbox(&mut self.entity_actions, "标注回复中的链接、路径、命令和代码标识符")
+                                        .on_hover_text("点击标注可以复制、打开、搜索；运行命令前需要确认")
+                                        .changed()
+                                    {
+                                        config_changed = true;
+                                    }
+                                    ui.end_row();
+
+                                    // 朗读
+                                    ui.label("朗读:");
+                                    ui.horizontal(|ui| {
+                                        if ui.add(TextEdit::singleline(&mut self.tts_model)
+                                            .desired_width(80.0)
+                                            .hint_text("tts-1")).changed() {
+                                            config_changed = true;
+                                        }
+                                        egui::ComboBox::from_id_salt("tts_voice_selector")
+                                            .selected_text(&self.tts_voice)
+                                            .show_ui(ui, |ui| {
+                                                for voice in speech::VOICES {
+                                                    if ui.selectable_value(&mut self.tts_voice, voice.to_string(), voice).changed() {
+                                                        config_changed = true;
+                                                    }
+                                                }
+                                            });
+                                    });
+                                    ui.end_row();
+
+                                    // 知识库
+                                    ui.label("知识库:");
+                                    ui.horizontal(|ui| {
+                                        if ui.add(TextEdit::singleline(&mut self.embedding_model)
+                                            .desired_width(160.0)
+                                            .hint_text("text-embedding-3-small"))
+                                            .on_hover_text("嵌入模型，更换后需要重新建立索引")
+                                            .changed()
+                                        {
+                                            config_changed = true;
+                                        }
+                                        ui.label("检索条数:");
+                                        if ui.add(egui::DragValue::new(&mut self.knowledge_top_k).range(1..=20)).changed() {
+                                            config_changed = true;
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    // 本地诊断统计
+                                    ui.label("诊断统计:");
+                                    ui.horizontal(|ui| {
+                                        if ui.checkbox(&mut self.metrics_enabled, "记录请求和帧耗时")
+                                            .on_hover_text("数据只保存在本机的 metrics.json 中，不会上传")
+                                            .changed()
+                                        {
+                                            self.metrics.set_enabled(self.metrics_enabled);
+                                            config_changed = true;
+                                        }
+                                        if ui.button("诊断面板").clicked() {
+                                            self.show_metrics = true;
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    // 图片生成
+                                    ui.label("图片生成:");
+                                    if ui.add(TextEdit::singleline(&mut self.image_model)
+                                        .desired_width(120.0)
+                                        .hint_text("dall-e-3"))
+                                        .on_hover_text("在输入框中使用 /image 描述 生成图片")
+                                        .changed()
+                                    {
+                                        config_changed = true;
+                                    }
+                                    ui.end_row();
+
+                                    // 听写桥接
+                                    ui.label("听写桥接:");
+                                    ui.vertical(|ui| {
+                                        if ui.checkbox(&mut self.dictation_bridge_enabled, "允许外部工具向输入框写入文本").changed() {
+                                            config_changed = true;
+                                        }
+                                        if self.dictation_bridge_enabled {
+                                            let status = if self.dictation_running() { "监听中" } else { "未运行，详见日志" };
+                                            ui.label(RichText::new(format!("{}: {}", status, dictation::bridge_address()))
+                                                .small()
+                                                .color(egui::Color32::GRAY));
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    // 回复建议
+                                    ui.label("回复建议:");
+                                    ui.horizontal(|ui| {
+                                        if ui.checkbox(&mut self.smart_replies_enabled, "每次回复后建议后续问题").changed() {
+                                            config_changed = true;
+                                        }
+                                        egui::ComboBox::from_id_salt("suggestion_model_selector")
+                                            .selected_text(&self.suggestion_model)
+                                            .show_ui(ui, |ui| {
+                                                for model in &self.available_models {
+                                                    if ui.selectable_value(&mut self.suggestion_model, model.clone(), model).changed() {
+                                                        config_changed = true;
+                                                    }
+                                                }
+                                            });
+                                    });
+                                    ui.end_row();
+
+                                    // 输入补全
+                                    ui.label("输入补全:");
+                                    ui.horizontal(|ui| {
+                                        if ui.checkbox(&mut self.ghost_text_enabled, "停顿时提示续写，Tab 接受").changed() {
+                                            if let Some(id) = self.ghost_text.clear() {
+                                                self.task_registry.abort(id);
+                                            }
+                                            config_changed = true;
+                                        }
+                                        egui::ComboBox::from_id_salt("ghost_text_model_selector")
+                                            .selected_text(&self.ghost_text_model)
+                                            .show_ui(ui, |ui| {
+                                                for model in &self.available_models {
+                                                    if ui.selectable_value(&mut self.ghost_text_model, model.clone(), model).changed() {
+                                                        config_changed = true;
+                                                    }
+                                                }
+                                            });
+                                    });
+                                    ui.end_row();
+
+                                    // 推理模型（o1 / o3 等）的思考强度
+                                    ui.label("推理强度:");
+                                    ui.horizontal(|ui| {
+                                        for effort in config::ReasoningEffort::ALL {
+                                            if ui.radio(self.reasoning_effort == effort, effort.label()).clicked() {
+                                                self.reasoning_effort = effort;
+                                                config_changed = true;
+                                            }
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    // 输入框按键绑定
+                                    ui.label("按键绑定:");
+                                    ui.horizontal(|ui| {
+                                        for mode in config::KeybindingMode::ALL {
+                                            if ui.radio(self.keybinding_mode == mode, mode.label()).clicked() {
+                                                self.keybinding_mode = mode;
+                                                self.modal_state = ModalState::default();
+                                                config_changed = true;
+                                            }
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    // 发送快捷键
+                                    ui.label("发送快捷键:");
+                                    ui.horizontal(|ui| {
+                                        for send_key in config::SendKey::ALL {
+                                            if ui.radio(self.send_key == send_key, send_key.label())
+                                                .on_hover_text("其他带 Enter 的组合键（如 Shift+Enter）用于换行")
+                                                .clicked()
+                                            {
+                                                self.send_key = send_key;
+                                                config_changed = true;
+                                            }
+                                        }
+                                    });
+                                    ui.end_row();
+
                                     // 添加聊天记录清空模式设置
                                     ui.label("清空聊天模式:");
                                     ui.horizontal(|ui| {
@@ -1184,6 +9015,39 @@ impl eframe::App for ChatApp {
                                     ui.end_row();
                                 });
 
+                            ui.separator();
+                            ui.horizontal(|ui| {
+                                if ui.button("导出配置").clicked() {
+                                    self.export_config();
+                                }
+                                if ui.button("导入配置").clicked() {
+                                    self.import_config(frame);
+                                }
+                                if ui.button("导入对话包...")
+                                    .on_hover_text("导入在对话右键菜单中导出的 JSON 对话包")
+                                    .clicked()
+                                {
+                                    self.import_chat_bundle();
+                                }
+                                if ui.button("导入其他客户端的对话...")
+                                    .on_hover_text("支持 Chatbox、NextChat 和 LM Studio 导出的 JSON 文件")
+                                    .clicked()
+                                {
+                                    self.pick_chat_import();
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                for section in [
+                                    config::ConfigSection::Api,
+                                    config::ConfigSection::Chat,
+                                    config::ConfigSection::All,
+                                ] {
+                                    if ui.button(format!("重置{}", section.label())).clicked() {
+                                        self.pending_config_reset = Some(section);
+                                    }
+                                }
+                            });
+
                             if config_changed {
                                 debug!("配置已更改正在保存");
                                 if let Err(e) = self.save_config(frame) {
@@ -1208,16 +9072,41 @@ impl eframe::App for ChatApp {
                         ui.vertical(|ui| {
                             // 图片上传按钮和文件名显示
                             ui.horizontal(|ui| {
-                                if ui.small_button("\u{f0c6}").clicked() {
+                                self.display_model_switcher(ui);
+                                self.display_capabilities(ui, frame);
+                                let vision = self.current_capabilities().is_none_or(|caps| caps.vision);
+                                if ui.add_enabled(vision, egui::Button::new("\u{f0c6}").small())
+                                    .on_disabled_hover_text("当前模型不支持图片输入")
+                                    .clicked()
+                                {
                                     if let Some(path) = FileDialog::new()
                                         .add_filter("图片", &["png", "jpg", "jpeg"])
                                         .pick_file()
                                     {
                                         self.selected_image = Some(path.clone());
-                                        let runtime_handle = self.runtime_handle.clone();
-                                        self.processing_image = Some(runtime_handle.spawn(async move {
-                                            utils::copy_to_cache(&path).await
-                                        }));
+                                        let cache_dir = self.image_cache_dir();
+                                        self.processing_image = Some(self.task_registry.spawn(
+                                            &self.runtime_handle,
+                                            "处理图片",
+                                            None,
+                                            async move { utils::copy_to_cache(&path, &cache_dir).await },
+                                        ));
+                                    }
+                                }
+
+                                // 附加补丁文件，回复时进入代码审查模式
+                                if ui.small_button("\u{f1c9}").on_hover_text("附加代码差异 / 补丁文件").clicked() {
+                                    if let Some(path) = FileDialog::new()
+                                        .add_filter("补丁", &["diff", "patch", "txt"])
+                                        .pick_file()
+                                    {
+                                        match std::fs::read_to_string(&path) {
+                                            Ok(content) => {
+                                                let name = path.file_name().map_or_else(|| "补丁".to_string(), |name| name.to_string_lossy().to_string());
+                                                self.text_attachments.push(TextAttachment { name, content });
+                                            }
+                                            Err(e) => error!("读取补丁文件失败: {}", e),
+                                        }
                                     }
                                 }
 
@@ -1236,6 +9125,275 @@ impl eframe::App for ChatApp {
                                 if should_clear_image {
                                     self.selected_image = None;
                                 }
+
+                                // 待发送的文本附件
+                                let mut attachment_to_remove = None;
+                                for (index, attachment) in self.text_attachments.iter().enumerate() {
+                                    let label = format!("\u{f15c} {} ({} 行)", attachment.name, attachment.line_count());
+                                    if attachment_chip(ui, &label) {
+                                        attachment_to_remove = Some(index);
+                                    }
+                                }
+                                if let Some(index) = attachment_to_remove {
+                                    self.text_attachments.remove(index);
+                                }
+
+                                // 智能粘贴的撤销入口；输入框中已找不到包装后的内容时自动消失
+                                if self
+                                    .code_paste_undo
+                                    .as_ref()
+                                    .is_some_and(|undo| !self.input_text.contains(&undo.fenced))
+                                {
+                                    self.code_paste_undo = None;
+                                }
+                                if let Some(undo) = self.code_paste_undo.clone() {
+                                    let label = match undo.language {
+                                        Some(language) => format!("\u{f121} 已包装为 {} 代码块", language),
+                                        None => "\u{f121} 已包装为代码块".to_string(),
+                                    };
+                                    ui.label(RichText::new(label).small());
+                                    if ui.small_button("撤销").on_hover_text("恢复为粘贴的原文").clicked() {
+                                        self.input_text = self.input_text.replacen(&undo.fenced, &undo.original, 1);
+                                        self.code_paste_undo = None;
+                                        self.input_focus = true;
+                                    }
+                                    if ui.small_button("\u{f00d}").clicked() {
+                                        self.code_paste_undo = None;
+                                    }
+                                }
+
+                                // Markdown 预览切换
+                                if ui.selectable_label(self.show_input_preview, "\u{f06e}")
+                                    .on_hover_text("预览 Markdown")
+                                    .clicked()
+                                {
+                                    self.show_input_preview = !self.show_input_preview;
+                                    self.input_focus = !self.show_input_preview;
+                                }
+
+                                // 代码输入模式切换与语言选择
+                                if ui.selectable_label(self.code_input_mode, "\u{f121}")
+                                    .on_hover_text("代码输入模式")
+                                    .clicked()
+                                {
+                                    self.code_input_mode = !self.code_input_mode;
+                                    self.input_focus = true;
+                                }
+                                // 对比模式开关与模型选择
+                                if ui.selectable_label(self.compare_models.is_some(), "\u{f24e}")
+                                    .on_hover_text("对比模式：同一条消息同时发送给多个模型（仅发送文字）")
+                                    .clicked()
+                                {
+                                    self.compare_models = match self.compare_models {
+                                        Some(_) => None,
+                                        None => Some(vec![self.effective_config(self.chat_list.current_chat_id.as_deref()).model_name]),
+                                    };
+                                }
+                                if self.compare_models.is_some() {
+                                    self.display_compare_models(ui);
+                                }
+                                self.display_group_members(ui);
+                                self.display_journal_calendar(ui);
+                                if self.current_chat_is_incognito() {
+                                    ui.label(RichText::new("\u{f21b} 无痕").small().color(egui::Color32::LIGHT_BLUE))
+                                        .on_hover_text("这个对话不会写入对话记录和费用统计，切换到其他对话后立即清除");
+                                }
+                                self.display_chat_memory(ui);
+                                if self.code_input_mode {
+                                    egui::ComboBox::from_id_salt("code_language_selector")
+                                        .selected_text(&self.code_language)
+                                        .width(60.0)
+                                        .show_ui(ui, |ui| {
+                                            for language in CODE_LANGUAGES {
+                                                ui.selectable_value(&mut self.code_language, language.to_string(), *language);
+                                            }
+                                        });
+                                }
+
+                                // 模态编辑模式提示
+                                if let Some(label) = self.modal_state.mode_label(self.keybinding_mode) {
+                                    ui.label(RichText::new(label).monospace().color(egui::Color32::GRAY));
+                                }
+
+                                // emoji 选择器
+                                ui.menu_button("\u{f118}", |ui| {
+                                    ui.add(TextEdit::singleline(&mut self.emoji_search)
+                                        .desired_width(220.0)
+                                        .hint_text("\u{f002} 搜索 emoji..."));
+                                    ScrollArea::vertical().max_height(180.0).show(ui, |ui| {
+                                        egui::Grid::new("emoji_picker_grid").show(ui, |ui| {
+                                            for (index, (code, symbol)) in emoji::search(&self.emoji_search).into_iter().enumerate() {
+                                                if index > 0 && index % 8 == 0 {
+                                                    ui.end_row();
+                                                }
+                                                if ui.button(RichText::new(symbol).size(18.0))
+                                                    .on_hover_text(format!(":{}:", code))
+                                                    .clicked()
+                                                {
+                                                    self.input_text.push_str(symbol);
+                                                    self.move_input_cursor_to_end = true;
+                                                    self.input_focus = true;
+                                                    ui.close_menu();
+                                                }
+                                            }
+                                        });
+                                    });
+                                });
+
+                                // 将回复同步输出到外部文件
+                                let current_tee = self.current_tee();
+                                let tee_icon = if current_tee.is_some() {
+                                    RichText::new("\u{f0c7}").color(egui::Color32::LIGHT_GREEN)
+                                } else {
+                                    RichText::new("\u{f0c7}")
+                                };
+                                ui.menu_button(tee_icon, |ui| {
+                                    match current_tee {
+                                        Some(mut tee) => {
+                                            ui.label(format!("输出到: {}", tee.path));
+                                            if ui.checkbox(&mut tee.live, "实时写入流式输出").changed() {
+                                                self.set_current_tee(Some(tee));
+                                            }
+                                            if ui.button("停止输出").clicked() {
+                                                self.set_current_tee(None);
+                                                ui.close_menu();
+                                            }
+                                        }
+                                        None => {
+                                            if ui.button("输出回复到文件...").clicked() {
+                                                if let Some(path) = FileDialog::new()
+                                                    .add_filter("Markdown", &["md"])
+                                                    .add_filter("日志", &["log", "txt"])
+                                                    .set_file_name("dream-output.md")
+                                                    .save_file()
+                                                {
+                                                    self.set_current_tee(Some(TeeTarget {
+                                                        path: path.to_string_lossy().to_string(),
+                                                        live: false,
+                                                    }));
+                                                }
+                                                ui.close_menu();
+                                            }
+                                        }
+                                    }
+                                }).response.on_hover_text("输出到文件");
+
+                                // 本对话的 Webhook，留空时使用全局设置
+                                let mut chat_webhook = self.current_webhook().unwrap_or_default();
+                                let webhook_icon = if !chat_webhook.is_empty() {
+                                    RichText::new("\u{f0e8}").color(egui::Color32::LIGHT_GREEN)
+                                } else {
+                                    RichText::new("\u{f0e8}")
+                                };
+                                ui.menu_button(webhook_icon, |ui| {
+                                    ui.label("回复完成后发送 JSON 到:");
+                                    let response = ui.add(
+                                        TextEdit::singleline(&mut chat_webhook)
+                                            .hint_text(if self.webhook_url.is_empty() {
+                                                "https://..."
+                                            } else {
+                                                self.webhook_url.as_str()
+                                            })
+                                            .desired_width(280.0),
+                                    );
+                                    if response.changed() {
+                                        self.set_current_webhook(Some(chat_webhook.clone()));
+                                    }
+                                    if response.lost_focus() {
+                                        if let Err(e) = self.save_chat_list() {
+                                            error!("保存聊天列表失败: {}", e);
+                                        }
+                                    }
+                                    if !chat_webhook.is_empty() && !webhook::is_valid_url(&chat_webhook) {
+                                        ui.colored_label(egui::Color32::YELLOW, "地址需要以 http:// 或 https:// 开头");
+                                    }
+                                    ui.label(
+                                        RichText::new("包含对话 ID、最后一条用户消息、最终回复和用量；留空时使用设置中的全局地址")
+                                            .small()
+                                            .weak(),
+                                    );
+                                }).response.on_hover_text("Webhook");
+
+                                // 本对话应用的回复后处理步骤
+                                if self.post_processors.iter().any(|processor| processor.enabled) {
+                                    let mut disabled = self.current_disabled_post_processors();
+                                    let post_process_icon = if disabled.is_empty() {
+                                        RichText::new("\u{f0d0}")
+                                    } else {
+                                        RichText::new("\u{f0d0}").color(egui::Color32::YELLOW)
+                                    };
+                                    ui.menu_button(post_process_icon, |ui| {
+                                        ui.label("本对话应用的回复后处理:");
+                                        let mut changed = false;
+                                        for processor in self.post_processors.iter().filter(|processor| processor.enabled) {
+                                            let name = processor.display_name().to_string();
+                                            let mut enabled = !disabled.contains(&name);
+                                            if ui.checkbox(&mut enabled, &name).changed() {
+                                                if enabled {
+                                                    disabled.retain(|n| n != &name);
+                                                } else {
+                                                    disabled.push(name);
+                                                }
+                                                changed = true;
+                                            }
+                                        }
+                                        if changed {
+                                            self.set_current_disabled_post_processors(disabled);
+                                            if let Err(e) = self.save_chat_list() {
+                                                error!("保存聊天列表失败: {}", e);
+                                            }
+                                        }
+                                    }).response.on_hover_text("回复后处理");
+                                }
+
+                                // :shortcode: 自动补全建议
+                                if let Some(code) = emoji::pending_shortcode(&self.input_text) {
+                                    let suggestions: Vec<_> = emoji::search(code).into_iter().take(6).collect();
+                                    if !suggestions.is_empty() {
+                                        ui.separator();
+                                        if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Tab)) {
+                                            emoji::complete_pending(&mut self.input_text, suggestions[0].1);
+                                            self.move_input_cursor_to_end = true;
+                                            self.input_focus = true;
+                                        } else {
+                                            for (code, symbol) in suggestions {
+                                                if ui.small_button(format!("{} :{}:", symbol, code)).clicked() {
+                                                    emoji::complete_pending(&mut self.input_text, symbol);
+                                                    self.move_input_cursor_to_end = true;
+                                                    self.input_focus = true;
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
+                                // 关联了文件夹的对话中补全文件路径
+                                if path_complete::pending_path(&self.input_text).is_some() {
+                                    let input = self.input_text.clone();
+                                    let suggestions: Vec<String> = match (path_complete::pending_path(&input), self.workspace_paths()) {
+                                        (Some(pending), Some(paths)) => path_complete::search(paths, pending, 6)
+                                            .into_iter()
+                                            .map(str::to_string)
+                                            .collect(),
+                                        _ => Vec::new(),
+                                    };
+                                    if !suggestions.is_empty() {
+                                        ui.separator();
+                                        if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Tab)) {
+                                            path_complete::complete_pending(&mut self.input_text, &suggestions[0]);
+                                            self.move_input_cursor_to_end = true;
+                                            self.input_focus = true;
+                                        } else {
+                                            for path in suggestions {
+                                                if ui.small_button(RichText::new(&path).monospace()).clicked() {
+                                                    path_complete::complete_pending(&mut self.input_text, &path);
+                                                    self.move_input_cursor_to_end = true;
+                                                    self.input_focus = true;
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
+                                self.display_token_count(ui);
                             });
                             
                             // 使用计算的高度，并减去底部空白 40 像素
@@ -1243,12 +9401,181 @@ impl eframe::App for ChatApp {
                                 .auto_shrink([false; 2])
                                 .min_scrolled_height(available_height - 40.0) // 减去顶部和底部的空间
                                 .show(ui, |ui| {
-                                    let text_edit = TextEdit::multiline(&mut self.input_text)
-                                        .desired_rows(((available_height - 40.0) / 20.0) as usize)
-                                        .desired_width(ui.available_width())
-                                        .frame(false);
+                                    // 预览模式下渲染草稿，不显示编辑框
+                                    if self.show_input_preview {
+                                        let draft = self.input_text.clone();
+                                        if draft.trim().is_empty() {
+                                            ui.label(RichText::new("（暂无内容）").italics().color(egui::Color32::GRAY));
+                                        } else {
+                                            self.render_markdown(ui, &draft);
+                                        }
+                                        return;
+                                    }
+
+                                    // 内容过大时只读显示，避免每帧排版整段文字
+                                    if !is_large_paste(&self.input_text) {
+                                        self.edit_large_input = false;
+                                    } else if !self.edit_large_input {
+                                        self.display_large_input(ui, available_height - 40.0);
+                                        return;
+                                    }
+
+                                    // Tab 接受补全建议（光标需在末尾）
+                                    let input_id = egui::Id::new("composer_input");
+                                    let cursor_at_end = TextEdit::load_state(ui.ctx(), input_id)
+                                        .and_then(|state| state.cursor.char_range())
+                                        .is_none_or(|range| range.primary.index == self.input_text.chars().count());
+                                    if self.ghost_text_enabled
+                                        && cursor_at_end
+                                        && ui.ctx().memory(|m| m.has_focus(input_id))
+                                        && self.ghost_text.suffix_for(&self.input_text).is_some()
+                                        && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Tab))
+                                        && self.ghost_text.accept(&mut self.input_text)
+                                    {
+                                        self.move_input_cursor_to_end = true;
+                                    }
+
+                                    // Tab 展开输入末尾的缩写
+                                    if snippets::has_trailing_trigger(&self.input_text, &self.snippets)
+                                        && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Tab))
+                                    {
+                                        snippets::expand_trailing(&mut self.input_text, &self.snippets);
+                                        self.move_input_cursor_to_end = true;
+                                    }
+
+                                    // 拦截大段粘贴，交由用户决定是否作为附件
+                                    if ui.ctx().memory(|m| m.has_focus(input_id)) {
+                                        let large_paste = ui.input_mut(|i| {
+                                            let index = i.events.iter().position(|event| {
+                                                matches!(event, egui::Event::Paste(text) if is_large_paste(text))
+                                            })?;
+                                            match i.events.remove(index) {
+                                                egui::Event::Paste(text) => Some(text),
+                                                _ => None,
+                                            }
+                                        });
+                                        if let Some(text) = large_paste {
+                                            debug!("检测到大段粘贴: {} 字节", text.len());
+                                            self.pending_large_paste = Some(text);
+                                        }
+                                    }
+
+                                    // 像代码的粘贴内容自动包进代码块，已在代码块中时不处理
+                                    if self.smart_code_paste && !self.code_input_mode && ui.ctx().memory(|m| m.has_focus(input_id)) {
+                                        let cursor = TextEdit::load_state(ui.ctx(), input_id)
+                                            .and_then(|state| state.cursor.char_range())
+                                            .map_or(self.input_text.chars().count(), |range| range.primary.index);
+                                        if !code_paste::inside_fence(&self.input_text, cursor) {
+                                            let prefix_newline = cursor > 0
+                                                && self.input_text.chars().nth(cursor - 1).is_some_and(|c| c != '\n');
+                                            let undo = ui.input_mut(|i| {
+                                                i.events.iter_mut().find_map(|event| match event {
+                                                    egui::Event::Paste(text) if code_paste::looks_like_code(text) => {
+                                                        let language = code_paste::detect_language(text);
+                                                        let fenced = code_paste::fence(text, language, prefix_newline);
+                                                        let original = std::mem::replace(text, fenced.clone());
+                                                        Some(CodePasteUndo { fenced, original, language })
+                                                    }
+                                                    _ => None,
+                                                })
+                                            });
+                                            if undo.is_some() {
+                                                self.code_paste_undo = undo;
+                                            }
+                                        }
+                                    }
+
+                                    // Vim / Emacs 按键绑定
+                                    if ui.ctx().memory(|m| m.has_focus(input_id)) {
+                                        let cursor = TextEdit::load_state(ui.ctx(), input_id)
+                                            .and_then(|state| state.cursor.char_range())
+                                            .map_or(self.input_text.chars().count(), |range| range.primary.index);
+                                        let edited = ui.input_mut(|i| {
+                                            keybindings::handle_events(
+                                                self.keybinding_mode,
+                                                &mut self.modal_state,
+                                                &self.input_text,
+                                                cursor,
+                                                &mut i.events,
+                                            )
+                                        });
+                                        if let Some((text, cursor)) = edited {
+                                            self.input_text = text;
+                                            let mut state = TextEdit::load_state(ui.ctx(), input_id).unwrap_or_default();
+                                            let cursor = egui::text::CCursor::new(cursor);
+                                            state.cursor.set_char_range(Some(egui::text::CCursorRange::one(cursor)));
+                                            state.store(ui.ctx(), input_id);
+                                        }
+                                    }
+
+                                    // 发送快捷键：输入法组合期间的 Enter 用于确认候选词，不发送
+                                    let send_pressed = ui.ctx().memory(|m| m.has_focus(input_id))
+                                        && ui.input_mut(|i| {
+                                            !self.ime_state.update(&i.events)
+                                                && keybindings::take_send_key(self.send_key, &mut i.events)
+                                        });
+
+                                    let text_edit_output = composer_edit(
+                                        ui,
+                                        &mut self.input_text,
+                                        input_id,
+                                        ((available_height - 40.0) / 20.0) as usize,
+                                        self.code_input_mode.then_some(self.code_language.as_str()),
+                                    );
+                                    let text_edit_response = text_edit_output.response.clone();
+                                    let content_height = text_edit_output.galley.size().y;
+                                    if content_height != self.input_content_height {
+                                        self.input_content_height = content_height;
+                                        ui.ctx().request_repaint();
+                                    }
+
+                                    // 输入补全：停顿后请求建议，并以灰色文字显示在光标后
+                                    if self.ghost_text_enabled {
+                                        if text_edit_response.changed() {
+                                            if let Some(id) = self.ghost_text.on_edit(&self.input_text) {
+                                                self.task_registry.abort(id);
+                                            }
+                                        }
+                                        let cursor_at_end = text_edit_output
+                                            .cursor_range
+                                            .is_none_or(|range| range.primary.ccursor.index == self.input_text.chars().count());
+                                        if text_edit_response.has_focus() && cursor_at_end {
+                                            if self.ghost_text.should_request(&self.input_text) {
+                                                self.request_ghost_text();
+                                            }
+                                            if let Some(delay) = self.ghost_text.next_check(&self.input_text) {
+                                                ui.ctx().request_repaint_after(delay);
+                                            }
+                                            if let Some(rest) = self.ghost_text.suffix_for(&self.input_text) {
+                                                ghost_suffix(ui, &text_edit_output, rest, self.code_input_mode);
+                                            }
+                                        }
+                                    }
+
+                                    // 输入完整的 :shortcode: 后自动替换为 emoji
+                                    if text_edit_response.changed()
+                                        && emoji::expand_trailing_shortcode(&mut self.input_text)
+                                    {
+                                        self.move_input_cursor_to_end = true;
+                                    }
+
+                                    // 输入空格时展开缩写
+                                    if text_edit_response.changed()
+                                        && self.input_text.ends_with(' ')
+                                        && snippets::expand_trailing(&mut self.input_text, &self.snippets)
+                                    {
+                                        self.move_input_cursor_to_end = true;
+                                    }
 
-                                    let text_edit_response = ui.add(text_edit);
+                                    // 程序修改输入内容后，将光标移到末尾
+                                    if self.move_input_cursor_to_end {
+                                        self.move_input_cursor_to_end = false;
+                                        if let Some(mut state) = TextEdit::load_state(ui.ctx(), text_edit_response.id) {
+                                            let end = egui::text::CCursor::new(self.input_text.chars().count());
+                                            state.cursor.set_char_range(Some(egui::text::CCursorRange::one(end)));
+                                            state.store(ui.ctx(), text_edit_response.id);
+                                        }
+                                    }
 
                                     // 如果需要聚焦且输入框还没有焦点
                                     if self.input_focus && !text_edit_response.has_focus() {
@@ -1259,10 +9586,11 @@ impl eframe::App for ChatApp {
                                         self.input_focus = false;
                                     }
 
-                                    // 检查 Enter 键送
-                                    if (ui.input(|i| i.key_pressed(egui::Key::Enter) && !i.modifiers.shift)
-                                        && text_edit_response.has_focus())
-                                        && (!self.input_text.is_empty() || self.selected_image.is_some())
+                                    // 按下发送快捷键时发送
+                                    if send_pressed
+                                        && (!self.input_text.is_empty()
+                                            || self.selected_image.is_some()
+                                            || !self.text_attachments.is_empty())
                                     {
                                         self.send_message();
                                         self.input_focus = true;
@@ -1276,7 +9604,7 @@ impl eframe::App for ChatApp {
                         // 在角色聊天中显示清空按钮
                         let should_clear = if let Some(current_id) = &self.chat_list.current_chat_id {
                             if let Some(chat) = self.chat_list.chats.iter().find(|c| &c.id == current_id) {
-                                chat.name.starts_with("\u{f544}")
+                                chat.is_role()
                             } else {
                                 false
                             }
@@ -1284,177 +9612,251 @@ impl eframe::App for ChatApp {
                             false
                         };
 
-                        if should_clear {
-                            if ui.button("\u{f51a}").clicked() {
-                                if let Some(id) = self.chat_list.current_chat_id.clone() {
-                                    self.clear_chat(&id);
-                                }
+                        if should_clear && ui.button("\u{f51a}").clicked() {
+                            if let Some(id) = self.chat_list.current_chat_id.clone() {
+                                self.clear_chat(&id);
                             }
                         }
                     });
                 });
             });
-            // 处理消息接收器 - 每帧最多处理一条消息
-            if let Some(receiver) = &mut self.receiver {
-                if let Ok(response) = receiver.try_recv() {  // 获取一条消息
-                    match response.as_str() {
-                        "__CLEAR_ERRORS__" => {
-                            // 清空最后一条消息如果它是错误提示
-                            if let Some(last_msg) = self.chat_history.0.last() {
-                                if last_msg.content.starts_with("遇到") {
-                                    self.chat_history.0.pop();
-                                }
+            // 处理消息接收器 - 一帧内取完所有已到达的片段，避免输出较快时界面落后于数据流
+            while let Some(event) = self.receiver.as_mut().and_then(|receiver| receiver.try_recv().ok()) {
+                match event {
+                    UiEvent::ClearNotices => {
+                        // 重试成功后移除之前的重试提示
+                        self.pop_notices();
+                    }
+                    UiEvent::Notice(text) => self.push_event(MessageKind::Notice, &text),
+                    UiEvent::Error(text) => {
+                        if !self.inspector.is_empty() {
+                            self.failed_request = Some(self.inspector.clone());
+                        }
+                        self.push_event(MessageKind::Error, &text);
+                    }
+                    UiEvent::Usage(usage) => {
+                        if let Some(last_msg) = self.chat_history.0.last_mut().filter(|msg| msg.is_reply()) {
+                            last_msg.usage = Some(usage);
+                        }
+                    }
+                    UiEvent::Reasoning(text) => self.handle_reasoning(&text),
+                    UiEvent::Inspect(event) => self.inspector.record(event),
+                    UiEvent::Stalled(seconds) => self.stream_stalled = Some(seconds),
+                    UiEvent::Resumed => self.stream_stalled = None,
+                    UiEvent::Retrieved(contexts) => {
+                        if let Some(manifest) = self.pending_manifest.as_mut() {
+                            manifest.insert_retrieved(&contexts);
+                        }
+                    }
+                    UiEvent::ImageResolved(path) => {
+                        if let Some(last_msg) = self.chat_history.0.last_mut() {
+                            last_msg.image_path = Some(path);
+                        }
+                    }
+                    UiEvent::Done => {
+                        debug!("流式响应完成");
+                        self.is_loading = false; // 清除加载状态
+                        self.stream_stalled = None;
+                        self.loading_dots.clear();
+                        if let Some(manifest) = self.pending_manifest.take() {
+                            if let Some(last_msg) = self.chat_history.0.last_mut().filter(|msg| msg.is_reply()) {
+                                last_msg.request_manifest = Some(manifest);
                             }
                         }
-                        s if s.starts_with("__UPDATE_MESSAGE_IMAGE__:") => {
-                            if let Some(path) = s.strip_prefix("__UPDATE_MESSAGE_IMAGE__:") {
-                                if let Some(last_msg) = self.chat_history.0.last_mut() {
-                                    last_msg.image_path = Some(path.to_string());
+                        self.post_process_reply();
+                        let reply_model = self
+                            .effective_config(self.chat_list.current_chat_id.as_deref())
+                            .model_name;
+                        self.tee_finished_reply(&reply_model);
+                        self.record_reply_cost(&reply_model);
+                        self.fire_webhook(&reply_model);
+                        if self.auto_extract_tasks {
+                            if let Some(current_id) = self.chat_list.current_chat_id.clone() {
+                                // 先同步消息，再基于最新对话提取任务
+                                if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == current_id) {
+                                    chat.messages = self.chat_history.0.clone();
                                 }
+                                self.extract_tasks(&current_id);
                             }
                         }
-                        s if s.starts_with("__TITLE_UPDATE__") => {
-                            debug!("收到标题更新消息: {}", s);
-                            if let Some(remaining) = s.strip_prefix("__TITLE_UPDATE__") {
-                                let parts: Vec<&str> = remaining.splitn(2, ':').collect();
-                                if parts.len() == 2 {
-                                    let chat_id = parts[0];
-                                    let title = parts[1];
-                                    debug!("正在更新标题 - chat_id: {}, title: {}", chat_id, title);
-                                    if let Some(chat) = self.chat_list.chats
-                                        .iter_mut()
-                                        .find(|c| c.id == chat_id)
-                                    {
-                                        debug!("找到对应的聊天，更新标题");
-                                        chat.name = title.to_string();
-                                        chat.has_been_renamed = true;
-                                        chat.messages = self.chat_history.0.clone();  // 同消息历史
-
-                                        // 保存更新后的聊天列表
-                                        if let Err(e) = self.save_chat_list() {
-                                            error!("保存聊天列表失败: {}", e);
-                                        }
-                                    }
-                                }
+                        if self.smart_replies_enabled {
+                            if let Some(current_id) = self.chat_list.current_chat_id.clone() {
+                                self.suggest_replies(&current_id);
                             }
                         }
-                        "__STREAM_DONE__" => {
-                            debug!("流式响应完成");
-                            self.is_loading = false; // 清除加载状态
-                            self.loading_dots.clear();
-                            if let Some(current_id) = &self.chat_list.current_chat_id {
-                                if let Some(chat) = self.chat_list.chats
-                                    .iter_mut()
-                                    .find(|c| &c.id == current_id)
-                                {
+                        if self.auto_memory {
+                            if let Some(current_id) = self.chat_list.current_chat_id.clone() {
+                                if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == current_id) {
                                     chat.messages = self.chat_history.0.clone();
-
-                                    // 在这里生成标题
-                                    if !chat.has_been_renamed {
-                                        debug!("开始生成标题");
-                                        // 获取用户输入和完整的助手回
-                                        let user_input = chat.messages.iter()
-                                            .find(|msg| msg.role == "user")
-                                            .map(|msg| msg.content.clone())
-                                            .unwrap_or_default();
-
-                                        let assistant_response = chat.messages.iter()
-                                            .find(|msg| msg.role == "assistant")
-                                            .map(|msg| msg.content.clone())
-                                            .unwrap_or_default();
-
-                                        let title_payload = json!({
-                                            "model": self.model_name.clone(),
-                                            "messages": vec![
-                                                json!({
-                                                    "role": "system",
-                                                    "content": "你善于总结标题，标题不超过10个字，不要包含有任何解释和符号。"
-                                                }),
-                                                json!({
-                                                    "role": "user",
-                                                    "content": user_input
-                                                }),
-                                                json!({
-                                                    "role": "assistant",
-                                                    "content": assistant_response
-                                                }),
-                                                json!({
-                                                    "role": "user",
-                                                    "content": "总结我们对话的标题，标题不超过10个字，不要包含有任何解释和符号。"
-                                                }),
-                                            ],
-                                            "temperature": 0.7,
-                                            "max_tokens": 60
-                                        });
-
-                                        // 发送标题生成请求
-                                        let runtime_handle = self.runtime_handle.clone();
-                                        let api_endpoint = self.api_endpoint.clone();
-                                        let api_key = self.api_key.clone();
-                                        let chat_id = current_id.clone();
-                                        let client = self.client.clone();
-
-                                        // 创建新的通道用于标题更新
-                                        let (tx, rx) = mpsc::unbounded_channel();
-
-                                        runtime_handle.spawn(async move {
-                                            debug!("发送标题生成请求: {}", title_payload);
-                                            match client
-                                                .post(&api_endpoint)
-                                                .header("Authorization", format!("Bearer {}", api_key))
-                                                .header("Content-Type", "application/json")
-                                                .json(&title_payload)
-                                                .send()
-                                                .await
-                                            {
-                                                Ok(response) => {
-                                                    debug!("收到标题生成响应: {:?}", response.status());
-                                                    match response.json::<JsonValue>().await {
-                                                        Ok(json) => {
-                                                            debug!("标题生成响应JSON: {:?}", json);
-                                                            if let Some(title) = json["choices"][0]["message"]["content"]
-                                                                .as_str()
-                                                                .map(|s| s.trim().to_string())
-                                                            {
-                                                                debug!("成功生成标题: {}", title);
-                                                                let title_message = format!("__TITLE_UPDATE__{}:{}", chat_id, title);
-                                                                debug!("发送标题更新消息: {}", title_message);
-                                                                if let Err(e) = tx.send(title_message) {
-                                                                    error!("发送标题更新消息失败: {}", e);
-                                                                }
-                                                            } else {
-                                                                error!("无法从响应中提取标题");
-                                                            }
-                                                        }
-                                                        Err(e) => {
-                                                            error!("解析标题生成响应失败: {}", e);
-                                                        }
-                                                    }
-                                                }
-                                                Err(e) => {
-                                                    error!("标题生成请求失败: {}", e);
-                                                }
-                                            }
-                                        });
-
-                                        // 设置新的接器
-                                        self.receiver = Some(rx);
-                                    }
-
-                                    if let Err(e) = self.save_chat_list() {
-                                        error!("保存聊天列表失败: {}", e);
-                                    }
                                 }
+                                self.schedule_memory_update(&current_id);
                             }
                         }
-                        response => {
-                            self.handle_response(response.to_string());
+                        if let Some(current_id) = self.chat_list.current_chat_id.clone() {
+                            if let Some(chat) = self.chat_list.chats.iter_mut().find(|c| c.id == current_id) {
+                                chat.messages = self.chat_history.0.clone();
+                                if let Err(e) = self.save_chat_list() {
+                                    error!("保存聊天列表失败: {}", e);
+                                }
+                            }
+                            self.generate_title(&current_id);
                         }
                     }
+                    UiEvent::Delta(text) => {
+                        self.handle_response(text);
+                    }
                 }
             }
         });
 
+        if self.show_template_import {
+            self.display_template_import(ctx, frame);
+        }
+        self.display_share_link(ctx);
+
+        self.display_encrypted_export(ctx);
+        self.display_replay_setup(ctx);
+        self.display_replay_progress(ctx);
+        self.display_comparison(ctx);
+        self.display_group_setup(ctx);
+        self.display_changelog(ctx);
+        self.display_backups(ctx);
+        self.display_lock_setup(ctx, frame);
+        self.display_chat_import(ctx);
+        self.display_metrics(ctx);
+        self.display_inspector(ctx);
+        self.display_notifications(ctx);
+
+        // 批量删除确认窗口
+        if self.confirm_batch_delete {
+            egui::Window::new("确认删除")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+                .show(ctx, |ui| {
+                    ui.label(format!("确定要删除选中的 {} 个对话吗？此操作无法撤销。", self.selected_chat_ids.len()));
+                    ui.add_space(8.0);
+                    ui.horizontal(|ui| {
+                        if ui.button("删除").clicked() {
+                            self.confirm_batch_delete = false;
+                            self.start_batch_job(BatchAction::Delete);
+                        }
+                        if ui.button("取消").clicked() {
+                            self.confirm_batch_delete = false;
+                        }
+                    });
+                });
+        }
+
+        // 批量操作进度窗口
+        self.process_batch_job();
+        if let Some(job) = &self.batch_job {
+            let done = job.total - job.pending.len();
+            egui::Window::new("批量操作")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+                .show(ctx, |ui| {
+                    ui.label(format!("正在{}: {}/{}", job.action.label(), done, job.total));
+                    ui.add(egui::ProgressBar::new(done as f32 / job.total.max(1) as f32).show_percentage());
+                    if job.failed > 0 {
+                        ui.label(RichText::new(format!("{} 个失败，详见日志", job.failed)).color(egui::Color32::RED));
+                    }
+                });
+            ctx.request_repaint();
+        }
+
+        // 后台任务调试视图
+        if self.show_task_debug {
+            let mut open = self.show_task_debug;
+            egui::Window::new("后台任务")
+                .open(&mut open)
+                .default_width(360.0)
+                .show(ctx, |ui| {
+                    let mut task_to_abort = None;
+                    if self.task_registry.running().is_empty() {
+                        ui.label(RichText::new("没有正在运行的任务").italics().color(egui::Color32::GRAY));
+                    }
+                    egui::Grid::new("task_debug_grid").striped(true).show(ui, |ui| {
+                        for task in self.task_registry.running() {
+                            ui.label(format!("#{}", task.id));
+                            ui.label(&task.name);
+                            ui.label(task.chat_id.as_deref().map_or("-".to_string(), |id| id.chars().take(8).collect()));
+                            ui.label(format!("{:.1}s", task.elapsed().as_secs_f32()));
+                            if ui.small_button("取消").clicked() {
+                                task_to_abort = Some(task.id);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                    if let Some(id) = task_to_abort {
+                        self.task_registry.abort(id);
+                    }
+                    if ui.button("全部取消").clicked() {
+                        self.task_registry.abort_all();
+                        self.is_loading = false;
+                        self.loading_dots.clear();
+                    }
+                });
+            self.show_task_debug = open;
+            ctx.request_repaint_after(std::time::Duration::from_millis(500));
+        }
+
+        // 大段粘贴确认窗口
+        if let Some(text) = self.pending_large_paste.clone() {
+            egui::Window::new("粘贴内容较大")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+                .show(ctx, |ui| {
+                    ui.label(format!(
+                        "粘贴的内容共 {} 行、{} 个字符，直接插入可能导致输入框卡顿。",
+                        text.lines().count(),
+                        text.chars().count()
+                    ));
+                    ui.add_space(8.0);
+                    ui.horizontal(|ui| {
+                        if ui.button("作为附件").clicked() {
+                            let name = format!("粘贴内容-{}.txt", chrono::Local::now().format("%H%M%S"));
+                            self.text_attachments.push(TextAttachment { name, content: text.clone() });
+                            self.pending_large_paste = None;
+                            self.input_focus = true;
+                        }
+                        if ui.button("直接插入").clicked() {
+                            self.input_text.push_str(&text);
+                            self.move_input_cursor_to_end = true;
+                            self.pending_large_paste = None;
+                            self.input_focus = true;
+                        }
+                        if ui.button("取消").clicked() {
+                            self.pending_large_paste = None;
+                            self.input_focus = true;
+                        }
+                    });
+                });
+        }
+
+        // 重置配置确认窗口
+        if let Some(section) = self.pending_config_reset {
+            egui::Window::new("确认重置")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+                .show(ctx, |ui| {
+                    ui.label(format!("确定要将{}恢复为默认值吗？", section.label()));
+                    ui.add_space(8.0);
+                    ui.horizontal(|ui| {
+                        if ui.button("确定").clicked() {
+                            self.reset_config(section, frame);
+                            self.pending_config_reset = None;
+                        }
+                        if ui.button("取消").clicked() {
+                            self.pending_config_reset = None;
+                        }
+                    });
+                });
+        }
+
         // 添加角色创建窗口
         if self.show_role_creator {
             egui::Window::new("创建角色")
@@ -1467,10 +9869,11 @@ impl eframe::App for ChatApp {
 
                         ui.add_space(8.0);
                         ui.label("选择模型:");
+                        let model_choices = self.model_choices(self.role_profile.as_deref());
                         egui::ComboBox::from_id_salt("role_model_selector")
                             .selected_text(&self.role_model_name)
                             .show_ui(ui, |ui| {
-                                for model in &self.available_models {
+                                for model in &model_choices {
                                     ui.selectable_value(
                                         &mut self.role_model_name,
                                         model.clone(),
@@ -1479,24 +9882,81 @@ impl eframe::App for ChatApp {
                                 }
                             });
 
+                        ui.add_space(8.0);
+                        ui.label("服务商:");
+                        egui::ComboBox::from_id_salt("role_provider_selector")
+                            .selected_text(self.role_provider.map_or("跟随设置", |p| p.label()))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut self.role_provider, None, "跟随设置");
+                                for provider in ProviderKind::ALL {
+                                    ui.selectable_value(&mut self.role_provider, Some(provider), provider.label());
+                                }
+                            });
+
+                        if !self.profiles.is_empty() {
+                            ui.add_space(8.0);
+                            ui.label("配置档案:");
+                            egui::ComboBox::from_id_salt("role_profile_selector")
+                                .selected_text(self.role_profile.as_deref().unwrap_or("跟随设置"))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.role_profile, None, "跟随设置");
+                                    for profile in &self.profiles {
+                                        ui.selectable_value(&mut self.role_profile, Some(profile.name.clone()), &profile.name);
+                                    }
+                                });
+                        }
+
                         ui.add_space(8.0);
                         ui.label("系统提示词:");
                         ui.text_edit_multiline(&mut self.role_prompt_input);
 
+                        ui.add_space(8.0);
+                        ui.label("开场白（可选）:");
+                        ui.text_edit_multiline(&mut self.role_greeting_input);
+                        ui.checkbox(&mut self.role_greeting_in_context, "将开场白加入上下文");
+
                         ui.add_space(8.0);
                         ui.label("Temperature:");
                         ui.add(
                             egui::Slider::new(&mut self.role_temperature, 0.0..=2.0).step_by(0.1),
                         );
 
+                        ui.add_space(8.0);
+                        ui.label("内容过滤:");
+                        egui::ComboBox::from_id_salt("role_filter_selector")
+                            .selected_text(self.role_filter_mode.map_or("跟随设置", |m| m.label()))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut self.role_filter_mode, None, "跟随设置");
+                                for mode in FilterMode::ALL {
+                                    ui.selectable_value(&mut self.role_filter_mode, Some(mode), mode.label());
+                                }
+                            });
+                        ui.add(TextEdit::multiline(&mut self.role_filter_terms)
+                            .hint_text("追加的过滤规则，每行一条")
+                            .desired_rows(2));
+
                         ui.add_space(16.0);
-                        if ui.small_button("创建角色").clicked()
-                            && !self.role_name_input.trim().is_empty()
-                        {
-                            self.create_role();
-                        }
+                        ui.horizontal(|ui| {
+                            if ui.small_button("创建角色").clicked()
+                                && !self.role_name_input.trim().is_empty()
+                            {
+                                self.create_role();
+                            }
+                            if ui.small_button("从 URL 导入").clicked() {
+                                self.show_template_import = true;
+                            }
+                            if ui.small_button("导出模板").clicked() {
+                                self.export_template();
+                            }
+                            if ui.small_button("分享链接").clicked() {
+                                let bundle = self.current_template();
+                                self.share_bundle(&bundle);
+                            }
+                        });
                     });
                 });
         }
+
+        self.metrics.record_frame(frame_started.elapsed());
     }
 }
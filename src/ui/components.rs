@@ -0,0 +1,668 @@
+// 可复用的界面组件：消息气泡、侧边栏对话条目、设置面板中的表格行和输入框，只依赖传入的数据，不读写 ChatApp 的状态
+use crate::config;
+use crate::doh::DohProvider;
+use crate::journal;
+use crate::metrics;
+use crate::models::{Chat, Message, MessageKind, TokenUsage};
+use crate::providers::ProviderKind;
+use crate::resolver::IpPreference;
+use crate::secrets::SecretMatch;
+use eframe::egui::{self, RichText, TextEdit};
+
+// 回复中疑似密钥的提示色
+const WARNING_COLOR: egui::Color32 = egui::Color32::from_rgb(230, 160, 0);
+
+// 消息气泡的标题行：发言者和生成回复的模型，需放在横向布局中；返回是否点击了“查看上下文”
+pub fn message_header(ui: &mut egui::Ui, speaker: &str, model: Option<&str>) -> bool {
+    ui.label(RichText::new(speaker).strong().size(16.0));
+    ui.add_space(8.0);
+    let Some(model) = model else {
+        return false;
+    };
+    ui.label(RichText::new(model).small().color(egui::Color32::GRAY));
+    ui.small_button("\u{f05a}").on_hover_text("查看上下文").clicked()
+}
+
+// 错误、提示、分隔线、日期和工具调用等非对话消息；普通对话消息返回 None，由调用方渲染
+pub fn status_message(ui: &mut egui::Ui, msg: &Message) -> Option<egui::Response> {
+    let response = match msg.kind {
+        MessageKind::Chat => return None,
+        MessageKind::Error => {
+            ui.label(RichText::new(format!("\u{f071} 错误: {}", msg.content)).color(egui::Color32::RED))
+        }
+        MessageKind::Notice => {
+            ui.label(RichText::new(format!("\u{f05a} {}", msg.content)).italics().color(egui::Color32::GRAY))
+        }
+        MessageKind::Divider => ui.vertical_centered(|ui| {
+            ui.label(RichText::new("—— 以上内容不再作为上下文 ——").small().color(egui::Color32::GRAY));
+        }).response,
+        MessageKind::DaySection => ui.vertical_centered(|ui| {
+            ui.add_space(8.0);
+            let label = journal::section_date(msg).map_or_else(|| msg.content.clone(), journal::format_date);
+            ui.label(RichText::new(format!("—— {} ——", label)).strong().color(egui::Color32::GRAY));
+        }).response,
+        MessageKind::ToolCall | MessageKind::ToolResult => {
+            let title = if msg.kind == MessageKind::ToolCall { "\u{f0ad} 工具调用" } else { "\u{f0ad} 工具结果" };
+            egui::CollapsingHeader::new(title)
+                .id_salt(egui::Id::new("tool_message").with(&msg.content))
+                .default_open(false)
+                .show(ui, |ui| {
+                    ui.label(RichText::new(&msg.content).monospace());
+                })
+                .header_response
+        }
+    };
+    Some(response)
+}
+
+// 回复中疑似泄露的密钥或凭据，只显示开头几个字符
+pub fn secret_warning(ui: &mut egui::Ui, content: &str, found: &[SecretMatch]) {
+    ui.horizontal_wrapped(|ui| {
+        ui.label(RichText::new("\u{f071} 回复中可能包含密钥或凭据:").color(WARNING_COLOR));
+        for secret in found {
+            let preview: String = content[secret.range.clone()].chars().take(6).collect();
+            ui.label(RichText::new(format!("{}… ({})", preview, secret.kind))
+                .monospace()
+                .small()
+                .color(WARNING_COLOR));
+        }
+    });
+}
+
+// 回复下方的 token 用量
+pub fn usage_line(ui: &mut egui::Ui, usage: &TokenUsage) {
+    ui.label(RichText::new(format!(
+        "输入 {} · 输出 {} tokens",
+        usage.prompt_tokens, usage.completion_tokens
+    ))
+    .small()
+    .color(egui::Color32::GRAY));
+}
+
+// 侧边栏条目的点击方式
+pub enum SidebarClick {
+    Open(String),
+    Toggle(String),
+    Range(String),
+    NewFromRole(String),
+    ExportCode(String),
+    ExportHtml(String),
+    ExportBundle(String),
+    EncryptedExport(String),
+    Replay(String),
+    AttachKnowledge(String),
+    ReindexKnowledge(String),
+    DetachKnowledge(String),
+    ShareRole(String),
+    OpenSplit(String),
+    // (对话 ID, 目标文件夹)，None 表示移出文件夹
+    MoveToFolder(String, Option<String>),
+    TogglePin(String),
+    ToggleArchive(String),
+    DeleteFolder(String),
+}
+
+// 侧边栏中拖动的对话
+pub struct DraggedChat(pub String);
+
+// 渲染侧边栏中的一个对话条目
+pub fn chat_list_item(ui: &mut egui::Ui, chat: &Chat, is_current: bool, is_checked: bool, folders: &[String]) -> Option<SidebarClick> {
+    let mut click = None;
+    ui.horizontal(|ui| {
+        ui.set_min_height(24.0);
+
+        let label = if is_checked {
+            format!("\u{f14a} {}", chat.name)
+        } else {
+            chat.name.clone()
+        };
+        let response = ui.selectable_label(is_current || is_checked, RichText::new(label));
+        let pin_label = if chat.pinned { "取消置顶" } else { "置顶" };
+        if chat.pinned {
+            ui.label(RichText::new("\u{f08d}").small().color(egui::Color32::GRAY));
+        }
+        if let Some(folder) = &chat.knowledge_folder {
+            ui.label(RichText::new("\u{f02d}").small().color(egui::Color32::GRAY))
+                .on_hover_text(format!("知识库: {}", folder));
+        }
+        for tag in &chat.tags {
+            ui.label(RichText::new(format!("#{}", tag)).small().color(egui::Color32::GRAY));
+        }
+
+        if chat.is_role() {
+            response.context_menu(|ui| {
+                if ui.button(pin_label).clicked() {
+                    click = Some(SidebarClick::TogglePin(chat.id.clone()));
+                    ui.close_menu();
+                }
+                if ui.button("基于此角色新建对话").clicked() {
+                    click = Some(SidebarClick::NewFromRole(chat.id.clone()));
+                    ui.close_menu();
+                }
+                if ui.button("分享链接 / 二维码...").clicked() {
+                    click = Some(SidebarClick::ShareRole(chat.id.clone()));
+                    ui.close_menu();
+                }
+            });
+        } else {
+            response.context_menu(|ui| {
+                if ui.button(pin_label).clicked() {
+                    click = Some(SidebarClick::TogglePin(chat.id.clone()));
+                    ui.close_menu();
+                }
+                if ui.button(if chat.archived { "取消归档" } else { "归档" }).clicked() {
+                    click = Some(SidebarClick::ToggleArchive(chat.id.clone()));
+                    ui.close_menu();
+                }
+                if ui.button("导出代码块...").clicked() {
+                    click = Some(SidebarClick::ExportCode(chat.id.clone()));
+                    ui.close_menu();
+                }
+                if ui.button("导出为 HTML...").clicked() {
+                    click = Some(SidebarClick::ExportHtml(chat.id.clone()));
+                    ui.close_menu();
+                }
+                if ui.button("导出对话包 (JSON)...").clicked() {
+                    click = Some(SidebarClick::ExportBundle(chat.id.clone()));
+                    ui.close_menu();
+                }
+                if ui.button("加密导出...").clicked() {
+                    click = Some(SidebarClick::EncryptedExport(chat.id.clone()));
+                    ui.close_menu();
+                }
+                if ui.button("用其他模型重放...").clicked() {
+                    click = Some(SidebarClick::Replay(chat.id.clone()));
+                    ui.close_menu();
+                }
+                if !is_current && ui.button("在分屏中打开").clicked() {
+                    click = Some(SidebarClick::OpenSplit(chat.id.clone()));
+                    ui.close_menu();
+                }
+                if !folders.is_empty() {
+                    ui.menu_button("移动到文件夹", |ui| {
+                        for folder in folders {
+                            if chat.folder.as_ref() != Some(folder) && ui.button(folder).clicked() {
+                                click = Some(SidebarClick::MoveToFolder(chat.id.clone(), Some(folder.clone())));
+                                ui.close_menu();
+                            }
+                        }
+                        if chat.folder.is_some() {
+                            ui.separator();
+                            if ui.button("移出文件夹").clicked() {
+                                click = Some(SidebarClick::MoveToFolder(chat.id.clone(), None));
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                }
+                ui.separator();
+                if ui.button("关联知识库文件夹...").clicked() {
+                    click = Some(SidebarClick::AttachKnowledge(chat.id.clone()));
+                    ui.close_menu();
+                }
+                if chat.knowledge_folder.is_some() {
+                    if ui.button("重新建立知识库索引").clicked() {
+                        click = Some(SidebarClick::ReindexKnowledge(chat.id.clone()));
+                        ui.close_menu();
+                    }
+                    if ui.button("移除知识库").clicked() {
+                        click = Some(SidebarClick::DetachKnowledge(chat.id.clone()));
+                        ui.close_menu();
+                    }
+                }
+            });
+        }
+
+        if response.clicked() {
+            let modifiers = ui.input(|i| i.modifiers);
+            click = Some(if modifiers.command {
+                SidebarClick::Toggle(chat.id.clone())
+            } else if modifiers.shift {
+                SidebarClick::Range(chat.id.clone())
+            } else {
+                SidebarClick::Open(chat.id.clone())
+            });
+        }
+    });
+    click
+}
+
+// 设置面板中某个服务商的密钥和端点行，返回是否有修改
+pub fn provider_config_rows(
+    ui: &mut egui::Ui,
+    name: &str,
+    provider: ProviderKind,
+    config: &mut config::ProviderConfig,
+) -> bool {
+    let mut changed = false;
+    ui.label(format!("{} Key:", name));
+    changed |= ui.add(TextEdit::singleline(&mut config.api_key)
+        .password(true)
+        .desired_width(ui.available_width() - 60.0)).changed();
+    ui.end_row();
+
+    ui.label(format!("{} 端点:", name));
+    changed |= ui.add(TextEdit::singleline(&mut config.endpoint)
+        .hint_text(provider.default_endpoint())
+        .desired_width(ui.available_width() - 60.0)).changed();
+    ui.end_row();
+    changed
+}
+
+// 设置面板中的 DNS、IP 协议和绑定地址行，返回是否有修改
+pub fn network_config_rows(ui: &mut egui::Ui, config: &mut config::NetworkConfig) -> bool {
+    let mut changed = false;
+    ui.label("DNS:");
+    changed |= ui.checkbox(&mut config.doh_enabled, "使用 DNS-over-HTTPS 解析").changed();
+    ui.end_row();
+
+    if config.doh_enabled {
+        ui.label("DoH 服务:");
+        egui::ComboBox::from_id_salt("doh_provider_selector")
+            .selected_text(config.doh_provider.label())
+            .show_ui(ui, |ui| {
+                for provider in DohProvider::ALL {
+                    changed |= ui.selectable_value(&mut config.doh_provider, provider, provider.label()).changed();
+                }
+            });
+        ui.end_row();
+
+        if config.doh_provider == DohProvider::Custom {
+            ui.label("DoH 地址:");
+            changed |= ui.add(TextEdit::singleline(&mut config.doh_url)
+                .hint_text("https://dns.example.com/dns-query")
+                .desired_width(ui.available_width() - 60.0)).changed();
+            ui.end_row();
+        }
+    }
+
+    ui.label("IP 协议:");
+    egui::ComboBox::from_id_salt("ip_preference_selector")
+        .selected_text(config.ip_preference.label())
+        .show_ui(ui, |ui| {
+            for preference in IpPreference::ALL {
+                changed |= ui.selectable_value(&mut config.ip_preference, preference, preference.label()).changed();
+            }
+        });
+    ui.end_row();
+
+    ui.label("绑定地址:");
+    changed |= ui.add(TextEdit::singleline(&mut config.bind)
+        .hint_text(if cfg!(target_os = "linux") { "本地 IP 或网卡名，留空自动" } else { "本地 IP，留空自动" })
+        .desired_width(ui.available_width() - 60.0)).changed();
+    ui.end_row();
+    changed
+}
+
+// 设置面板中的代理设置行，返回是否有修改
+pub fn proxy_config_rows(ui: &mut egui::Ui, config: &mut config::ProxyConfig) -> bool {
+    let mut changed = false;
+    ui.label("代理:");
+    egui::ComboBox::from_id_salt("proxy_mode_selector")
+        .selected_text(config.mode.label())
+        .show_ui(ui, |ui| {
+            for mode in config::ProxyMode::ALL {
+                changed |= ui.selectable_value(&mut config.mode, mode, mode.label()).changed();
+            }
+        });
+    ui.end_row();
+
+    if config.mode == config::ProxyMode::Manual {
+        ui.label("代理地址:");
+        changed |= ui.add(TextEdit::singleline(&mut config.url)
+            .hint_text("http://127.0.0.1:7890")
+            .desired_width(ui.available_width() - 60.0)).changed();
+        ui.end_row();
+
+        ui.label("代理用户名:");
+        changed |= ui.add(TextEdit::singleline(&mut config.username)
+            .hint_text("可选")
+            .desired_width(ui.available_width() - 60.0)).changed();
+        ui.end_row();
+
+        ui.label("代理密码:");
+        changed |= ui.add(TextEdit::singleline(&mut config.password)
+            .password(true)
+            .desired_width(ui.available_width() - 60.0)).changed();
+        ui.end_row();
+    }
+    changed
+}
+
+// 设置面板中的故障切换行，返回是否有修改
+pub fn failover_config_rows(ui: &mut egui::Ui, config: &mut config::FailoverConfig) -> bool {
+    let mut changed = false;
+    ui.label("故障切换:");
+    changed |= ui.checkbox(&mut config.enabled, "主服务商连续失败时临时使用备用服务商").changed();
+    ui.end_row();
+
+    if config.enabled {
+        ui.label("备用服务商:");
+        egui::ComboBox::from_id_salt("failover_provider_selector")
+            .selected_text(config.provider.label())
+            .show_ui(ui, |ui| {
+                for provider in ProviderKind::ALL {
+                    changed |= ui.selectable_value(&mut config.provider, provider, provider.label()).changed();
+                }
+            });
+        ui.end_row();
+
+        ui.label("备用模型:");
+        changed |= ui.add(TextEdit::singleline(&mut config.model)
+            .hint_text("备用服务商使用的模型")
+            .desired_width(ui.available_width() - 60.0)).changed();
+        ui.end_row();
+    }
+    changed
+}
+
+// 请求检查器中的请求头或响应头列表
+pub fn header_grid(ui: &mut egui::Ui, id: &str, headers: &[(String, String)]) {
+    egui::Grid::new(id).num_columns(2).striped(true).show(ui, |ui| {
+        for (name, value) in headers {
+            ui.label(RichText::new(name).monospace());
+            ui.label(RichText::new(value).monospace());
+            ui.end_row();
+        }
+    });
+}
+
+// 诊断面板中的直方图，每个桶一行
+pub fn histogram_rows(ui: &mut egui::Ui, histogram: &metrics::Histogram, bounds: &[u64]) {
+    let max = histogram.counts.iter().copied().max().unwrap_or(0).max(1);
+    egui::Grid::new(ui.next_auto_id()).num_columns(2).show(ui, |ui| {
+        for index in 0..bounds.len() {
+            let count = histogram.counts.get(index).copied().unwrap_or(0);
+            ui.label(metrics::bucket_label(bounds, index));
+            ui.add(egui::ProgressBar::new(count as f32 / max as f32)
+                .desired_width(200.0)
+                .text(count.to_string()));
+            ui.end_row();
+        }
+    });
+}
+
+// 设置面板中的 API 配置档案列表，返回是否有修改
+pub fn profile_rows(ui: &mut egui::Ui, profiles: &mut Vec<config::ApiProfile>) -> bool {
+    let mut changed = false;
+    ui.label("配置档案:");
+    ui.vertical(|ui| {
+        let mut profile_to_remove = None;
+        for (index, profile) in profiles.iter_mut().enumerate() {
+            ui.push_id(index, |ui| {
+                ui.group(|ui| {
+                    ui.horizontal(|ui| {
+                        changed |= ui.add(TextEdit::singleline(&mut profile.name)
+                            .desired_width(100.0)
+                            .hint_text("名称")).changed();
+                        egui::ComboBox::from_id_salt("profile_provider")
+                            .selected_text(profile.provider.label())
+                            .show_ui(ui, |ui| {
+                                for provider in ProviderKind::ALL {
+                                    changed |= ui.selectable_value(&mut profile.provider, provider, provider.label()).changed();
+                                }
+                            });
+                        if ui.small_button("\u{f1f8}").clicked() {
+                            profile_to_remove = Some(index);
+                        }
+                    });
+                    changed |= ui.add(TextEdit::singleline(&mut profile.endpoint)
+                        .hint_text(profile.provider.default_endpoint())
+                        .desired_width(ui.available_width())).changed();
+                    changed |= ui.add(TextEdit::singleline(&mut profile.api_key)
+                        .password(true)
+                        .hint_text("API Key")
+                        .desired_width(ui.available_width())).changed();
+
+                    // 输入过程中保留原始文本，避免逗号和空格被立即规整掉
+                    let models_id = ui.id().with("models");
+                    let mut models_text = ui
+                        .data_mut(|data| data.get_temp::<String>(models_id))
+                        .unwrap_or_else(|| profile.models.join(", "));
+                    if ui.add(TextEdit::singleline(&mut models_text)
+                        .hint_text("常用模型，用逗号分隔")
+                        .desired_width(ui.available_width())).changed() {
+                        profile.models = models_text
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|model| !model.is_empty())
+                            .map(str::to_string)
+                            .collect();
+                        changed = true;
+                    }
+                    ui.data_mut(|data| data.insert_temp(models_id, models_text));
+                });
+            });
+        }
+        if let Some(index) = profile_to_remove {
+            profiles.remove(index);
+            changed = true;
+        }
+        if ui.small_button("添加配置档案").clicked() {
+            profiles.push(config::ApiProfile {
+                name: format!("配置 {}", profiles.len() + 1),
+                ..Default::default()
+            });
+            changed = true;
+        }
+    });
+    ui.end_row();
+    changed
+}
+
+// 设置面板中 Azure OpenAI 的部署信息行，返回是否有修改
+pub fn azure_config_rows(ui: &mut egui::Ui, config: &mut config::AzureConfig) -> bool {
+    let mut changed = false;
+    ui.label("Azure Key:");
+    changed |= ui.add(TextEdit::singleline(&mut config.api_key)
+        .password(true)
+        .desired_width(ui.available_width() - 60.0)).changed();
+    ui.end_row();
+
+    ui.label("Azure 资源名称:");
+    changed |= ui.add(TextEdit::singleline(&mut config.resource_name)
+        .hint_text("my-resource")
+        .desired_width(ui.available_width() - 60.0)).changed();
+    ui.end_row();
+
+    ui.label("Azure 部署 ID:");
+    changed |= ui.add(TextEdit::singleline(&mut config.deployment_id)
+        .hint_text("留空则使用模型名")
+        .desired_width(ui.available_width() - 60.0)).changed();
+    ui.end_row();
+
+    ui.label("Azure API 版本:");
+    changed |= ui.add(TextEdit::singleline(&mut config.api_version)
+        .hint_text("2024-06-01")
+        .desired_width(ui.available_width() - 60.0)).changed();
+    ui.end_row();
+    changed
+}
+
+// 输入框上方的附件条目，返回是否点击了移除
+pub fn attachment_chip(ui: &mut egui::Ui, label: &str) -> bool {
+    ui.label(label);
+    ui.small_button("\u{f00d}").clicked()
+}
+
+// 输入框本体；传入代码语言时按代码编辑器显示并高亮
+pub fn composer_edit(
+    ui: &mut egui::Ui,
+    text: &mut String,
+    id: egui::Id,
+    rows: usize,
+    code_language: Option<&str>,
+) -> egui::text_edit::TextEditOutput {
+    let theme = egui_extras::syntax_highlighting::CodeTheme::from_memory(ui.ctx(), ui.style());
+    let mut code_layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+        let mut layout_job = egui_extras::syntax_highlighting::highlight(
+            ui.ctx(),
+            ui.style(),
+            &theme,
+            text,
+            code_language.unwrap_or_default(),
+        );
+        layout_job.wrap.max_width = wrap_width;
+        ui.fonts(|f| f.layout_job(layout_job))
+    };
+
+    let mut text_edit = TextEdit::multiline(text)
+        .id(id)
+        .desired_rows(rows)
+        .desired_width(ui.available_width())
+        .frame(false);
+    if code_language.is_some() {
+        text_edit = text_edit.code_editor().layouter(&mut code_layouter);
+    }
+    text_edit.show(ui)
+}
+
+// 只读显示大段文字，只排版可见的行
+pub fn large_text_preview(ui: &mut egui::Ui, text: &str, max_height: f32) {
+    let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+    egui::ScrollArea::both()
+        .id_salt("large_text_preview")
+        .max_height(max_height)
+        .auto_shrink([false, true])
+        .show_rows(ui, row_height, text.lines().count(), |ui, row_range| {
+            for line in text.lines().skip(row_range.start).take(row_range.len()) {
+                ui.label(RichText::new(line).monospace());
+            }
+        });
+}
+
+// 在输入框光标后用灰色文字显示补全建议
+pub fn ghost_suffix(ui: &egui::Ui, output: &egui::text_edit::TextEditOutput, rest: &str, monospace: bool) {
+    let galley = &output.galley;
+    let end = galley.pos_from_cursor(&galley.end());
+    let font_id = if monospace {
+        egui::TextStyle::Monospace.resolve(ui.style())
+    } else {
+        egui::TextStyle::Body.resolve(ui.style())
+    };
+    ui.painter().with_clip_rect(output.text_clip_rect).text(
+        output.galley_pos + end.right_top().to_vec2(),
+        egui::Align2::LEFT_TOP,
+        rest,
+        font_id,
+        egui::Color32::GRAY,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fonts;
+    use crate::secrets;
+    use egui_kittest::kittest::Queryable;
+    use egui_kittest::Harness;
+
+    // 使用程序内置字体和浅色主题渲染，与实际界面一致
+    fn harness<'a, State>(
+        size: egui::Vec2,
+        state: State,
+        app: impl FnMut(&mut egui::Ui, &mut State) + 'a,
+    ) -> Harness<'a, State> {
+        let mut harness = Harness::builder().with_size(size).build_ui_state(app, state);
+        harness.ctx.set_fonts(fonts::bundled_fonts());
+        harness.ctx.set_theme(egui::Theme::Light);
+        harness.run();
+        harness
+    }
+
+    #[test]
+    fn message_bubble() {
+        let mut reply = Message::new_assistant("在 `Cargo.toml` 中加入依赖即可。".to_string());
+        reply.usage = Some(TokenUsage { prompt_tokens: 128, completion_tokens: 42 });
+        let leaked = "测试用的密钥 sk-abcdefghijklmnopqrstuvwxyz123456";
+        let events = [
+            Message::new_event(MessageKind::Notice, "网络错误，正在重试 (1/3)".to_string()),
+            Message::new_event(MessageKind::Error, "请求超时".to_string()),
+            Message::new_event(MessageKind::Divider, String::new()),
+        ];
+        let harness = harness(egui::vec2(480.0, 260.0), false, |ui, viewed| {
+            ui.horizontal(|ui| {
+                message_header(ui, "You:", None);
+            });
+            ui.label("怎么给项目加一个依赖？");
+            ui.separator();
+            ui.horizontal(|ui| {
+                *viewed |= message_header(ui, "AI:", Some("gpt-4o-mini"));
+            });
+            ui.label(&reply.content);
+            secret_warning(ui, leaked, &secrets::find_secrets(leaked));
+            usage_line(ui, reply.usage.as_ref().unwrap());
+            for event in &events {
+                assert!(status_message(ui, event).is_some());
+            }
+            assert!(status_message(ui, &reply).is_none());
+        });
+        harness.wgpu_snapshot("message_bubble");
+    }
+
+    #[test]
+    fn sidebar_item() {
+        let mut chat = Chat::new("周报草稿".to_string());
+        chat.pinned = true;
+        chat.tags = vec!["工作".to_string()];
+        let mut harness = harness(egui::vec2(280.0, 40.0), None, |ui, click| {
+            if let Some(result) = chat_list_item(ui, &chat, true, false, &[]) {
+                *click = Some(result);
+            }
+        });
+        harness.wgpu_snapshot("sidebar_item");
+
+        harness.get_by_label("周报草稿").click();
+        harness.run();
+        assert!(matches!(harness.state(), Some(SidebarClick::Open(_))));
+    }
+
+    #[test]
+    fn settings_grid() {
+        let configs = (
+            config::ProxyConfig {
+                mode: config::ProxyMode::Manual,
+                url: "http://127.0.0.1:7890".to_string(),
+                ..Default::default()
+            },
+            config::FailoverConfig {
+                enabled: true,
+                model: "gpt-4o-mini".to_string(),
+                ..Default::default()
+            },
+        );
+        let harness = harness(egui::vec2(480.0, 200.0), configs, |ui, (proxy, failover)| {
+            egui::Grid::new("settings_grid").num_columns(2).show(ui, |ui| {
+                proxy_config_rows(ui, proxy);
+                failover_config_rows(ui, failover);
+            });
+        });
+        harness.wgpu_snapshot("settings_grid");
+    }
+
+    #[test]
+    fn composer() {
+        let drafts = (
+            "帮我看看这段代码".to_string(),
+            "fn main() {\n    println!(\"hello\");\n}".to_string(),
+        );
+        let harness = harness(egui::vec2(480.0, 200.0), drafts, |ui, (text, code)| {
+            ui.horizontal(|ui| {
+                attachment_chip(ui, "\u{f15c} notes.txt (12 行)");
+            });
+            composer_edit(ui, text, egui::Id::new("composer_text"), 2, None);
+            ui.separator();
+            composer_edit(ui, code, egui::Id::new("composer_code"), 3, Some("rs"));
+        });
+        harness.wgpu_snapshot("composer");
+    }
+
+    #[test]
+    fn large_input_preview() {
+        let log: String = (1..=5000).map(|line| format!("[INFO] 第 {} 行日志\n", line)).collect();
+        let harness = harness(egui::vec2(480.0, 160.0), (), |ui, _| {
+            large_text_preview(ui, &log, 140.0);
+        });
+        harness.wgpu_snapshot("large_input_preview");
+    }
+}
@@ -1,13 +1,14 @@
+use crate::diagnostics;
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use chrono::Local;
 use env_logger::Builder;
-use image::GenericImageView;
 use log::{debug, error, LevelFilter};
 use std::io;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::time::Instant;
 use tokio::fs;
+use tokio::io::AsyncWriteExt;
 use tokio::task;
 use uuid::Uuid;
 
@@ -47,16 +48,15 @@ impl std::error::Error for ImageError {
     }
 }
 
-pub async fn ensure_cache_dir() -> io::Result<PathBuf> {
-    let cache_dir = PathBuf::from(".cache/images");
-    fs::create_dir_all(&cache_dir).await?;
-    Ok(cache_dir)
+pub async fn ensure_cache_dir(cache_dir: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(cache_dir).await?;
+    Ok(cache_dir.to_path_buf())
 }
 
-pub async fn copy_to_cache(source_path: &Path) -> Result<PathBuf, ImageError> {
+pub async fn copy_to_cache(source_path: &Path, cache_dir: &Path) -> Result<PathBuf, ImageError> {
     let start = Instant::now();
 
-    let cache_dir = ensure_cache_dir().await?;
+    let cache_dir = ensure_cache_dir(cache_dir).await?;
     let file_name = format!("{}.jpg", Uuid::new_v4());
     let cache_path = cache_dir.join(&file_name);
 
@@ -126,26 +126,77 @@ pub async fn get_image_base64(path: &Path) -> io::Result<String> {
     Ok(encoded)
 }
 
+// 在文件名后追加后缀，如 chat_list.json -> chat_list.json.bak
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+pub fn backup_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".bak")
+}
+
+// 先写入临时文件并落盘，再重命名替换原文件，原文件保留为 .bak；写入中途崩溃不会损坏原文件
+pub async fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let temp = with_suffix(path, ".tmp");
+    let mut file = fs::File::create(&temp).await?;
+    file.write_all(contents.as_ref()).await?;
+    file.sync_all().await?;
+    drop(file);
+    if fs::try_exists(path).await.unwrap_or(false) {
+        fs::copy(path, backup_path(path)).await?;
+    }
+    fs::rename(&temp, path).await
+}
+
+// 读取并解析文件，文件损坏时改用 .bak 备份；文件不存在时返回 None，两者都无法解析时返回原文件的错误
+pub async fn load_with_backup<T, E: std::fmt::Display>(
+    path: &Path,
+    parse: impl Fn(&str) -> Result<T, E>,
+) -> Option<Result<T, E>> {
+    let content = fs::read_to_string(path).await.ok()?;
+    let error = match parse(&content) {
+        Ok(value) => return Some(Ok(value)),
+        Err(e) => e,
+    };
+    error!("{} 已损坏，尝试读取备份: {}", path.display(), error);
+    let backup = backup_path(path);
+    if let Ok(content) = fs::read_to_string(&backup).await {
+        match parse(&content) {
+            Ok(value) => {
+                debug!("已从备份恢复: {}", backup.display());
+                return Some(Ok(value));
+            }
+            Err(e) => error!("备份同样无法解析: {}", e),
+        }
+    }
+    Some(Err(error))
+}
+
 pub fn setup_logger() {
     Builder::from_default_env()
         .format(|buf, record| {
-            writeln!(
-                buf,
+            let line = format!(
                 "{} [{}] - {}",
                 Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
                 record.level(),
                 record.args()
-            )
+            );
+            writeln!(buf, "{}", line)?;
+            // 同时保留最近的日志，用于生成诊断包
+            diagnostics::record_log(line);
+            Ok(())
         })
         .filter_level(LevelFilter::Debug)
         .init();
 }
 
-pub async fn remove_cached_image(path: &str) -> std::io::Result<()> {
+pub async fn remove_cached_image(path: &str, cache_dir: &Path) -> std::io::Result<()> {
     debug!("尝试删除缓存图片: {}", path);
 
     // 获取缓存目录的绝对路径
-    let cache_dir = ensure_cache_dir().await?;
+    let cache_dir = ensure_cache_dir(cache_dir).await?;
     let cache_dir = cache_dir.canonicalize()?;
     debug!("缓存目录: {:?}", cache_dir);
 
@@ -183,3 +234,120 @@ pub async fn remove_cached_image(path: &str) -> std::io::Result<()> {
         Ok(())
     }
 }
+
+// 在系统 shell 中运行命令行：Windows 上为 cmd /C，其他系统为 sh -c；超时被丢弃时结束进程
+pub fn shell_command(command: &str) -> tokio::process::Command {
+    #[cfg(windows)]
+    let mut process = {
+        let mut process = tokio::process::Command::new("cmd");
+        process.args(["/C", command]);
+        process
+    };
+    #[cfg(not(windows))]
+    let mut process = {
+        let mut process = tokio::process::Command::new("sh");
+        process.args(["-c", command]);
+        process
+    };
+    process.kill_on_drop(true);
+    process
+}
+
+// 增量 UTF-8 解码：网络分包或管道读取可能把一个多字节字符拆在两段数据中，
+// 末尾不完整的字节留到下一段再解码，中间的非法字节替换为 U+FFFD
+#[derive(Default)]
+pub struct Utf8Decoder {
+    pending: Vec<u8>,
+}
+
+impl Utf8Decoder {
+    pub fn push(&mut self, bytes: &[u8]) -> String {
+        self.pending.extend_from_slice(bytes);
+        let mut text = String::new();
+        let mut decoded = 0;
+        while decoded < self.pending.len() {
+            match std::str::from_utf8(&self.pending[decoded..]) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    decoded = self.pending.len();
+                }
+                Err(e) => {
+                    let valid_end = decoded + e.valid_up_to();
+                    text.push_str(&String::from_utf8_lossy(&self.pending[decoded..valid_end]));
+                    match e.error_len() {
+                        // 非法字节替换后继续解码后面的内容
+                        Some(len) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            decoded = valid_end + len;
+                        }
+                        // 末尾是不完整的字符，等待下一段
+                        None => {
+                            decoded = valid_end;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        self.pending.drain(..decoded);
+        text
+    }
+
+    // 数据结束时输出剩余的字节，不完整的字符替换为 U+FFFD
+    pub fn finish(&mut self) -> String {
+        let text = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending.clear();
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Utf8Decoder;
+
+    #[test]
+    fn decodes_cjk_split_across_chunks() {
+        let bytes = "你好，世界".as_bytes();
+        for split in 0..=bytes.len() {
+            let mut decoder = Utf8Decoder::default();
+            let mut text = decoder.push(&bytes[..split]);
+            text.push_str(&decoder.push(&bytes[split..]));
+            text.push_str(&decoder.finish());
+            assert_eq!(text, "你好，世界", "split at {}", split);
+        }
+    }
+
+    #[test]
+    fn decodes_one_byte_at_a_time() {
+        let mut decoder = Utf8Decoder::default();
+        let mut text = String::new();
+        for byte in "中文🙂abc".as_bytes() {
+            text.push_str(&decoder.push(&[*byte]));
+        }
+        assert_eq!(text, "中文🙂abc");
+        assert_eq!(decoder.finish(), "");
+    }
+
+    #[test]
+    fn holds_incomplete_tail() {
+        let mut decoder = Utf8Decoder::default();
+        // “中” 为 E4 B8 AD
+        assert_eq!(decoder.push(b"a\xE4\xB8"), "a");
+        assert_eq!(decoder.push(b"\xAD"), "中");
+    }
+
+    #[test]
+    fn invalid_byte_before_incomplete_tail() {
+        let mut decoder = Utf8Decoder::default();
+        assert_eq!(decoder.push(b"a\xFFb\xE4\xB8"), "a\u{FFFD}b");
+        assert_eq!(decoder.push(b"\xAD"), "中");
+    }
+
+    #[test]
+    fn finish_flushes_incomplete_character() {
+        let mut decoder = Utf8Decoder::default();
+        assert_eq!(decoder.push(b"x\xE4\xB8"), "x");
+        assert_eq!(decoder.finish(), "\u{FFFD}");
+        assert_eq!(decoder.finish(), "");
+    }
+}
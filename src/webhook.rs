@@ -0,0 +1,54 @@
+// 回复完成后的 Webhook：把对话 ID、最后一条用户消息、最终回复和用量以 JSON 发送到指定地址，便于接入自动化流程
+use crate::models::TokenUsage;
+use chrono::Utc;
+use reqwest::Client;
+use serde_json::{json, Value as JsonValue};
+use std::time::Duration;
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(15);
+
+pub struct Completion<'a> {
+    pub chat_id: &'a str,
+    pub chat_name: &'a str,
+    pub model: &'a str,
+    pub user_message: &'a str,
+    pub answer: &'a str,
+    pub usage: Option<TokenUsage>,
+}
+
+pub fn is_valid_url(url: &str) -> bool {
+    let url = url.trim();
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+pub fn payload(completion: &Completion) -> JsonValue {
+    json!({
+        "event": "reply.completed",
+        "chat_id": completion.chat_id,
+        "chat_name": completion.chat_name,
+        "model": completion.model,
+        "user_message": completion.user_message,
+        "answer": completion.answer,
+        "usage": completion.usage.map(|usage| json!({
+            "prompt_tokens": usage.prompt_tokens,
+            "completion_tokens": usage.completion_tokens,
+            "total_tokens": usage.prompt_tokens + usage.completion_tokens,
+        })),
+        "completed_at": Utc::now().to_rfc3339(),
+    })
+}
+
+pub async fn send(client: &Client, url: &str, payload: &JsonValue) -> Result<(), String> {
+    let response = client
+        .post(url.trim())
+        .timeout(WEBHOOK_TIMEOUT)
+        .json(payload)
+        .send()
+        .await
+        .map_err(|e| format!("请求失败: {}", e))?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("服务器返回 {}", status));
+    }
+    Ok(())
+}